@@ -22,8 +22,10 @@ public class SurfmanInstrumentedTest {
     private static native void testDeviceAccessors();
     private static native void testDeviceCreation();
     private static native void testGenericSurfaceCreation();
+    private static native void testGenericSurfacesCannotBePresented();
     private static native void testGL();
     private static native void testNewlyCreatedContextsAreNotCurrent();
+    private static native void testSurfacePixelReadback();
     private static native void testSurfaceTextureBlitFramebuffer();
     private static native void testSurfaceTextureRightSideUp();
 
@@ -69,6 +71,11 @@ public class SurfmanInstrumentedTest {
         testGenericSurfaceCreation();
     }
 
+    @Test
+    public void genericSurfacesCannotBePresented() {
+        testGenericSurfacesCannotBePresented();
+    }
+
     @Test
     public void gl() {
         testGL();
@@ -79,6 +86,11 @@ public class SurfmanInstrumentedTest {
         testNewlyCreatedContextsAreNotCurrent();
     }
 
+    @Test
+    public void surfacePixelReadback() {
+        testSurfacePixelReadback();
+    }
+
     @Test
     public void surfaceTextureBlitFramebuffer() {
         testSurfaceTextureBlitFramebuffer();
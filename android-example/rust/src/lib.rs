@@ -112,6 +112,14 @@ pub unsafe extern "system" fn
     tests::test_generic_surface_creation();
 }
 
+#[no_mangle]
+pub unsafe extern "system" fn
+        Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testGenericSurfacesCannotBePresented(
+            _env: JNIEnv,
+            _class: JClass) {
+    tests::test_generic_surfaces_cannot_be_presented();
+}
+
 #[no_mangle]
 pub unsafe extern "system" fn
         Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testGL(
@@ -128,6 +136,14 @@ pub unsafe extern "system" fn
     tests::test_newly_created_contexts_are_not_current();
 }
 
+#[no_mangle]
+pub unsafe extern "system" fn
+        Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testSurfacePixelReadback(
+            _env: JNIEnv,
+            _class: JClass) {
+    tests::test_surface_pixel_readback();
+}
+
 #[no_mangle]
 pub unsafe extern "system" fn
         Java_org_mozilla_surfmanthreadsexample_SurfmanInstrumentedTest_testSurfaceTextureBlitFramebuffer(
@@ -24,7 +24,10 @@ fn main() {
         angle: { all(windows, feature = "sm-angle") },
         angle_builtin: { all(windows, feature = "sm-angle-builtin") },
         angle_default: { all(windows, feature = "sm-angle-default") },
+        gbm: { all(linux, feature = "sm-gbm") },
+        glx: { all(linux, feature = "sm-glx") },
         no_wgl: { all(windows, feature = "sm-no-wgl") },
+        osmesa: { all(linux, feature = "sm-osmesa") },
         wayland_default: { all(linux, feature = "sm-wayland-default") },
         x11: { all(linux, feature = "sm-x11") },
     }
@@ -49,7 +52,11 @@ fn main() {
         registry.write_bindings(StructGenerator, &mut file).unwrap();
     } else {
         let mut file = File::create(&dest.join("gl_bindings.rs")).unwrap();
-        let registry = Registry::new(Api::Gl, (3, 3), Profile::Core, Fallbacks::All, []);
+        let registry = Registry::new(Api::Gl,
+                                     (3, 3),
+                                     Profile::Core,
+                                     Fallbacks::All,
+                                     ["GL_KHR_debug"]);
         registry.write_bindings(StructGenerator, &mut file).unwrap();
     }
 }
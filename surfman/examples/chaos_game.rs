@@ -4,7 +4,7 @@
 
 use euclid::default::Point2D;
 use rand::{self, Rng};
-use surfman::{SurfaceAccess, SurfaceType};
+use surfman::{SurfaceAccess, SurfaceColorspace, SurfaceType};
 use winit::dpi::PhysicalSize;
 use winit::{DeviceEvent, Event, EventsLoop, KeyboardInput, VirtualKeyCode};
 use winit::{WindowBuilder, WindowEvent};
@@ -50,7 +50,10 @@ fn main() {
 
     let native_widget = connection.create_native_widget_from_winit_window(&window).unwrap();
 
-    let surface_type = SurfaceType::Widget { native_widget };
+    let surface_type = SurfaceType::Widget {
+        native_widget,
+        colorspace: SurfaceColorspace::Default,
+    };
     let mut surface = device.create_surface(SurfaceAccess::GPUCPU, surface_type).unwrap();
 
     let mut rng = rand::thread_rng();
@@ -6,7 +6,7 @@
 use crate::common::{Buffer, FilesystemResourceLoader, Program, Shader, ShaderKind, ck};
 
 use clap::{App, Arg};
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
 use gl::types::{GLchar, GLenum, GLint, GLuint, GLvoid};
 use gl;
 use png::{BitDepth, ColorType, Encoder};
@@ -15,7 +15,8 @@ use std::mem;
 use std::path::Path;
 use std::slice;
 use surfman::{Connection, ContextAttributeFlags, ContextAttributes, GLApi, GLVersion};
-use surfman::{SurfaceAccess, SurfaceType};
+use surfman::{ChannelOrder, ReadbackFormat, SurfaceAccess, SurfaceDescriptor};
+use surfman::SurfaceType;
 
 mod common;
 
@@ -72,19 +73,19 @@ fn main() {
     let context_attributes = ContextAttributes {
         version: GLVersion::new(3, 3),
         flags: ContextAttributeFlags::empty(),
+        samples: 0,
     };
     let context_descriptor = device.create_context_descriptor(&context_attributes).unwrap();
     let mut context = device.create_context(&context_descriptor).unwrap();
     let surface = device.create_surface(&context, SurfaceAccess::GPUOnly, SurfaceType::Generic {
         size: Size2D::new(FRAMEBUFFER_WIDTH, FRAMEBUFFER_HEIGHT),
+        descriptor: SurfaceDescriptor::default(),
     }).unwrap();
     device.bind_surface_to_context(&mut context, surface).unwrap();
 
     gl::load_with(|symbol_name| device.get_proc_address(&context, symbol_name));
     device.make_context_current(&context).unwrap();
 
-    let mut pixels: Vec<u8> =
-        vec![0; FRAMEBUFFER_WIDTH as usize * FRAMEBUFFER_HEIGHT as usize * 4];
     let tri_vertex_array = TriVertexArray::new(device.gl_api(),
                                                device.surface_gl_texture_target());
 
@@ -95,17 +96,19 @@ fn main() {
         gl::BindVertexArray(tri_vertex_array.object); ck();
         gl::UseProgram(tri_vertex_array.tri_program.program.object); ck();
         gl::DrawArrays(gl::TRIANGLES, 0, 3); ck();
-        gl::Flush(); ck();
-
-        gl::ReadPixels(0,
-                       0,
-                       FRAMEBUFFER_WIDTH,
-                       FRAMEBUFFER_HEIGHT,
-                       gl::RGBA,
-                       gl::UNSIGNED_BYTE,
-                       pixels.as_mut_ptr() as *mut GLvoid); ck();
     }
 
+    // Read the rendered image back, top row first, as PNG expects.
+    let mut surface = device.unbind_surface_from_context(&mut context).unwrap().unwrap();
+    let readback_format = ReadbackFormat { channel_order: ChannelOrder::RGBA, flip_y: true };
+    let readback = device.read_surface_pixels(&context,
+                                              &surface,
+                                              Rect::from_size(Size2D::new(FRAMEBUFFER_WIDTH,
+                                                                          FRAMEBUFFER_HEIGHT)),
+                                              readback_format).unwrap();
+    let pixels = device.finish_pixel_readback(&context, readback).unwrap();
+
+    device.destroy_surface(&mut context, &mut surface).unwrap();
     device.destroy_context(&mut context).unwrap();
 
     let mut encoder = Encoder::new(output_file,
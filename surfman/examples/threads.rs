@@ -9,7 +9,8 @@ use gl::types::{GLchar, GLenum, GLint, GLuint, GLvoid};
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::thread;
 use surfman::{Adapter, Connection, Context, ContextDescriptor, Device, GLApi, Surface};
-use surfman::{SurfaceAccess, SurfaceTexture, SurfaceType, declare_surfman};
+use surfman::{SurfaceAccess, SurfaceColorspace, SurfaceDescriptor, SurfaceTexture, SurfaceType};
+use surfman::declare_surfman;
 
 #[cfg(not(target_os = "android"))]
 use self::common::FilesystemResourceLoader;
@@ -103,10 +104,14 @@ fn main() {
     let context_attributes = ContextAttributes {
         version: GLVersion::new(3, 0),
         flags: ContextAttributeFlags::ALPHA,
+        samples: 0,
     };
     let context_descriptor = device.create_context_descriptor(&context_attributes).unwrap();
 
-    let surface_type = SurfaceType::Widget { native_widget };
+    let surface_type = SurfaceType::Widget {
+        native_widget,
+        colorspace: SurfaceColorspace::Default,
+    };
     let mut context = device.create_context(&context_descriptor).unwrap();
     let surface = device.create_surface(&context, SurfaceAccess::GPUOnly, surface_type).unwrap();
     device.bind_surface_to_context(&mut context, surface).unwrap();
@@ -329,7 +334,7 @@ fn worker_thread(connection: Connection,
                  worker_from_main_receiver: Receiver<Surface>) {
     // Open the device, create a context, and make it current.
     let size = Size2D::new(SUBSCREEN_WIDTH, SUBSCREEN_HEIGHT);
-    let surface_type = SurfaceType::Generic { size };
+    let surface_type = SurfaceType::Generic { size, descriptor: SurfaceDescriptor::default() };
     let mut device = connection.create_device(&adapter).unwrap();
     let mut context = device.create_context(&context_descriptor).unwrap();
     let surface = device.create_surface(&context, SurfaceAccess::GPUOnly, surface_type).unwrap();
@@ -357,7 +362,7 @@ fn worker_thread(connection: Connection,
     let mut theta_z = INITIAL_ROTATION_Z;
 
     // Send an initial surface back to the main thread.
-    let surface_type = SurfaceType::Generic { size };
+    let surface_type = SurfaceType::Generic { size, descriptor: SurfaceDescriptor::default() };
     let surface = Some(device.create_surface(&context, SurfaceAccess::GPUOnly, surface_type)
                              .unwrap());
     worker_to_main_sender.send(Frame {
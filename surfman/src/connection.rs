@@ -48,6 +48,9 @@ pub trait Connection: Sized {
     /// Returns the "best" adapter on this system, preferring software adapters.
     fn create_software_adapter(&self) -> Result<Self::Adapter, Error>;
 
+    /// Returns every adapter that can be used with this connection.
+    fn adapters(&self) -> Result<Vec<Self::Adapter>, Error>;
+
     /// Opens a device.
     fn create_device(&self, adapter: &Self::Adapter) -> Result<Self::Device, Error>;
 
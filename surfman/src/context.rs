@@ -4,13 +4,19 @@
 
 #![allow(unused_imports)]
 
+use crate::info::{self, GLApi};
 use crate::Gl;
-use crate::gl::types::GLuint;
+use crate::gl::types::{GLchar, GLenum, GLint, GLsizei, GLuint};
 use crate::gl;
 use crate::info::GLVersion;
 
+use log::Level;
+
 use std::ffi::CStr;
-use std::os::raw::c_char;
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::slice;
 use std::sync::Mutex;
 
 /// A unique ID among all currently-allocated contexts.
@@ -45,9 +51,35 @@ bitflags! {
         /// The OpenGL compatibility profile will be used. If this is not present, the core profile
         /// is used.
         const COMPATIBILITY_PROFILE = 0x08;
+        /// Out-of-bounds buffer and texture accesses will be well-defined rather than crashing
+        /// or returning other contexts' data, as WebGL requires.
+        const ROBUST_ACCESS         = 0x10;
+        /// The driver will report GPU resets to this context, via
+        /// `Device::context_reset_status()`, and this context will lose its state when one
+        /// occurs. Without this flag, resets go unreported.
+        const LOSE_CONTEXT_ON_RESET = 0x20;
+        /// The context will be a debug context. Messages from the driver's `KHR_debug` output
+        /// are forwarded to the `log` crate, and the OpenGL objects that `surfman` creates are
+        /// labeled so that they can be told apart in those messages and in GL debuggers.
+        const DEBUG                 = 0x40;
     }
 }
 
+/// Whether a context has been lost because of a GPU reset, as reported by
+/// `Device::context_reset_status()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContextResetStatus {
+    /// The context has not been reset.
+    NoError,
+    /// The context was reset, and it caused the reset itself (for example, by running a shader
+    /// that never terminated).
+    Guilty,
+    /// The context was reset because of something that another context did.
+    Innocent,
+    /// The context was reset, but the cause is unknown.
+    Unknown,
+}
+
 /// Attributes that control aspects of a context and/or surfaces created from that context.
 ///
 /// Similar to: https://www.khronos.org/registry/webgl/specs/latest/1.0/#WEBGLCONTEXTATTRIBUTES
@@ -60,12 +92,27 @@ pub struct ContextAttributes {
     pub version: GLVersion,
     /// Various flags.
     pub flags: ContextAttributeFlags,
+    /// The number of samples per pixel for multisample antialiasing of generic surfaces created
+    /// for this context, like WebGL's `antialias` attribute. 0 or 1 disables multisampling.
+    /// 
+    /// Rendering to a multisampled surface goes to multisampled renderbuffers, which are resolved
+    /// into the surface's texture when the surface is unbound from its context. Counts above the
+    /// implementation's `GL_MAX_SAMPLES` are clamped to it.
+    ///
+    /// Multisampling is currently only supported by the EGL backends on Linux: surfaceless, X11,
+    /// Wayland and GBM. On every other backend, `create_context_descriptor()` returns an
+    /// `Unimplemented` error if this is greater than 1.
+    pub samples: u32,
 }
 
 impl ContextAttributes {
     #[allow(dead_code)]
     pub(crate) fn zeroed() -> ContextAttributes {
-        ContextAttributes { version: GLVersion::new(0, 0), flags: ContextAttributeFlags::empty() }
+        ContextAttributes {
+            version: GLVersion::new(0, 0),
+            flags: ContextAttributeFlags::empty(),
+            samples: 0,
+        }
     }
 }
 
@@ -77,29 +124,225 @@ pub(crate) fn current_context_uses_compatibility_profile(_gl: &Gl) -> bool {
 #[cfg(not(target_os = "android"))]
 #[allow(dead_code)]
 pub(crate) fn current_context_uses_compatibility_profile(gl: &Gl) -> bool {
+    // OpenGL ES has no profiles.
+    let (api, version) = info::current_api_and_version(gl);
+    if api == GLApi::GLES {
+        return false;
+    }
+
     unsafe {
-        // First, try `GL_CONTEXT_PROFILE_MASK`.
-        let mut context_profile_mask = 0;
-        gl.GetIntegerv(gl::CONTEXT_PROFILE_MASK, &mut context_profile_mask);
-        if gl.GetError() == gl::NO_ERROR &&
-                (context_profile_mask & gl::CONTEXT_COMPATIBILITY_PROFILE_BIT as i32) != 0 {
-            return true;
+        // First, try `GL_CONTEXT_PROFILE_MASK`, which arrived in OpenGL 3.2.
+        if version.is_at_least(3, 2) {
+            let mut context_profile_mask = 0;
+            gl.GetIntegerv(gl::CONTEXT_PROFILE_MASK, &mut context_profile_mask);
+            if (context_profile_mask & gl::CONTEXT_COMPATIBILITY_PROFILE_BIT as i32) != 0 {
+                return true;
+            }
         }
+    }
 
-        // Second, look for the `GL_ARB_compatibility` extension.
-        let mut num_extensions = 0;
-        gl.GetIntegerv(gl::NUM_EXTENSIONS, &mut num_extensions);
-        if gl.GetError() == gl::NO_ERROR {
-            for extension_index in 0..(num_extensions as GLuint) {
-                let extension = gl.GetStringi(gl::EXTENSIONS, extension_index) as *const c_char;
-                let extension = CStr::from_ptr(extension);
-                if extension.to_str() == Ok("GL_ARB_compatibility") {
-                    return true;
-                }
-            }
+    // Second, look for the `GL_ARB_compatibility` extension.
+    info::current_context_extensions(gl).iter().any(|extension| {
+        extension == "GL_ARB_compatibility"
+    })
+}
+
+// Returns whether the current context has robust buffer access and whether it loses its state on
+// a GPU reset, in that order.
+//
+// Each query is only made if the context's version or extensions say it exists, so that the
+// caller's pending GL errors are neither added to nor cleared.
+#[allow(dead_code)]
+pub(crate) fn current_context_robustness(gl: &Gl) -> (bool, bool) {
+    const CONTEXT_FLAGS: GLenum = 0x821e;
+    const CONTEXT_FLAG_ROBUST_ACCESS_BIT: GLint = 0x4;
+    const LOSE_CONTEXT_ON_RESET: GLint = 0x8252;
+    const RESET_NOTIFICATION_STRATEGY: GLenum = 0x8256;
+    const CONTEXT_ROBUST_ACCESS: GLenum = 0x90f3;
+
+    let (api, version) = info::current_api_and_version(gl);
+    let extensions = info::current_context_extensions(gl);
+    let has_extension = |name| extensions.iter().any(|extension| extension == name);
+    let (has_context_flags, has_robust_access, has_reset_notification_strategy) = match api {
+        GLApi::GL => {
+            let has_robustness = version.is_at_least(4, 5) ||
+                has_extension("GL_ARB_robustness") || has_extension("GL_KHR_robustness");
+            (version.is_at_least(3, 0), false, has_robustness)
+        }
+        GLApi::GLES => {
+            let has_robustness = version.is_at_least(3, 2) ||
+                has_extension("GL_EXT_robustness") || has_extension("GL_KHR_robustness");
+            (version.is_at_least(3, 2), has_robustness, has_robustness)
+        }
+    };
+
+    unsafe {
+        let (mut context_flags, mut robust_access, mut reset_notification_strategy) = (0, 0, 0);
+        if has_context_flags {
+            gl.GetIntegerv(CONTEXT_FLAGS, &mut context_flags);
+        }
+        if has_robust_access {
+            gl.GetIntegerv(CONTEXT_ROBUST_ACCESS, &mut robust_access);
+        }
+        if has_reset_notification_strategy {
+            gl.GetIntegerv(RESET_NOTIFICATION_STRATEGY, &mut reset_notification_strategy);
         }
 
-        false
+        ((context_flags & CONTEXT_FLAG_ROBUST_ACCESS_BIT) != 0 || robust_access != 0,
+         reset_notification_strategy == LOSE_CONTEXT_ON_RESET)
+    }
+}
+
+// Returns whether the current context was created as a debug context.
+#[allow(dead_code)]
+pub(crate) fn current_context_is_debug(gl: &Gl) -> bool {
+    const CONTEXT_FLAGS: GLenum = 0x821e;
+    const CONTEXT_FLAG_DEBUG_BIT: GLint = 0x2;
+
+    // `GL_CONTEXT_FLAGS` arrived in OpenGL 3.0 and OpenGL ES 3.2. Don't query it on older
+    // contexts, which would leave an error behind for the caller.
+    let (api, version) = info::current_api_and_version(gl);
+    let has_context_flags = match api {
+        GLApi::GL => version.is_at_least(3, 0),
+        GLApi::GLES => version.is_at_least(3, 2),
+    };
+    if !has_context_flags {
+        return false;
+    }
+
+    unsafe {
+        let mut context_flags = 0;
+        gl.GetIntegerv(CONTEXT_FLAGS, &mut context_flags);
+        (context_flags & CONTEXT_FLAG_DEBUG_BIT) != 0
     }
 }
 
+// Returns whether the current context has been reset, via `glGetGraphicsResetStatus()` or one of
+// its extension variants. Contexts that support none of them are never reported as reset.
+#[allow(dead_code)]
+pub(crate) unsafe fn current_context_reset_status<F>(get_proc_address: F) -> ContextResetStatus
+                                                     where F: Fn(&str) -> *const c_void {
+    const GUILTY_CONTEXT_RESET: GLenum = 0x8253;
+    const INNOCENT_CONTEXT_RESET: GLenum = 0x8254;
+    const UNKNOWN_CONTEXT_RESET: GLenum = 0x8255;
+
+    let get_graphics_reset_status = ["glGetGraphicsResetStatus",
+                                     "glGetGraphicsResetStatusKHR",
+                                     "glGetGraphicsResetStatusARB",
+                                     "glGetGraphicsResetStatusEXT"].iter().map(|symbol_name| {
+        get_proc_address(symbol_name)
+    }).find(|function| !function.is_null());
+    let get_graphics_reset_status: extern "system" fn() -> GLenum =
+        match get_graphics_reset_status {
+            Some(function) => mem::transmute(function),
+            None => return ContextResetStatus::NoError,
+        };
+
+    match get_graphics_reset_status() {
+        GUILTY_CONTEXT_RESET => ContextResetStatus::Guilty,
+        INNOCENT_CONTEXT_RESET => ContextResetStatus::Innocent,
+        UNKNOWN_CONTEXT_RESET => ContextResetStatus::Unknown,
+        _ => ContextResetStatus::NoError,
+    }
+}
+
+// Routes the `KHR_debug` messages of the current context to the `log` crate. Output is made
+// synchronous, so that a breakpoint in the logger lands on the offending GL call. Contexts without
+// `KHR_debug` are left alone.
+#[allow(dead_code)]
+pub(crate) unsafe fn install_debug_message_callback<F>(get_proc_address: F)
+                                                       where F: Fn(&str) -> *const c_void {
+    const DEBUG_OUTPUT_SYNCHRONOUS: GLenum = 0x8242;
+    const DEBUG_OUTPUT: GLenum = 0x92e0;
+
+    let debug_message_callback = ["glDebugMessageCallback",
+                                  "glDebugMessageCallbackKHR",
+                                  "glDebugMessageCallbackARB"].iter().map(|symbol_name| {
+        get_proc_address(symbol_name)
+    }).find(|function| !function.is_null());
+    let debug_message_callback: extern "system" fn(DebugMessageCallback, *const c_void) =
+        match debug_message_callback {
+            Some(function) => mem::transmute(function),
+            None => {
+                warn!("surfman: debug context requested, but `KHR_debug` is unavailable");
+                return;
+            }
+        };
+    let enable = get_proc_address("glEnable");
+    if enable.is_null() {
+        return;
+    }
+    let enable: extern "system" fn(GLenum) = mem::transmute(enable);
+
+    // Debug contexts enable `GL_DEBUG_OUTPUT` by default, but other contexts may still report
+    // messages if asked to.
+    enable(DEBUG_OUTPUT);
+    enable(DEBUG_OUTPUT_SYNCHRONOUS);
+    debug_message_callback(log_debug_message, ptr::null());
+}
+
+type DebugMessageCallback = extern "system" fn(GLenum,
+                                               GLenum,
+                                               GLuint,
+                                               GLenum,
+                                               GLsizei,
+                                               *const GLchar,
+                                               *mut c_void);
+
+extern "system" fn log_debug_message(source: GLenum,
+                                     message_type: GLenum,
+                                     id: GLuint,
+                                     severity: GLenum,
+                                     length: GLsizei,
+                                     message: *const GLchar,
+                                     _: *mut c_void) {
+    const DEBUG_SOURCE_API: GLenum = 0x8246;
+    const DEBUG_SOURCE_WINDOW_SYSTEM: GLenum = 0x8247;
+    const DEBUG_SOURCE_SHADER_COMPILER: GLenum = 0x8248;
+    const DEBUG_SOURCE_THIRD_PARTY: GLenum = 0x8249;
+    const DEBUG_SOURCE_APPLICATION: GLenum = 0x824a;
+    const DEBUG_TYPE_ERROR: GLenum = 0x824c;
+    const DEBUG_TYPE_DEPRECATED_BEHAVIOR: GLenum = 0x824d;
+    const DEBUG_TYPE_UNDEFINED_BEHAVIOR: GLenum = 0x824e;
+    const DEBUG_TYPE_PORTABILITY: GLenum = 0x824f;
+    const DEBUG_TYPE_PERFORMANCE: GLenum = 0x8250;
+    const DEBUG_SEVERITY_HIGH: GLenum = 0x9146;
+    const DEBUG_SEVERITY_MEDIUM: GLenum = 0x9147;
+    const DEBUG_SEVERITY_LOW: GLenum = 0x9148;
+
+    let level = match severity {
+        DEBUG_SEVERITY_HIGH => Level::Error,
+        DEBUG_SEVERITY_MEDIUM => Level::Warn,
+        DEBUG_SEVERITY_LOW => Level::Info,
+        _ => Level::Debug,
+    };
+    if !log_enabled!(target: "surfman::gl", level) {
+        return;
+    }
+
+    let source = match source {
+        DEBUG_SOURCE_API => "API",
+        DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        DEBUG_SOURCE_THIRD_PARTY => "third party",
+        DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    };
+    let message_type = match message_type {
+        DEBUG_TYPE_ERROR => "error",
+        DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        DEBUG_TYPE_PORTABILITY => "portability",
+        DEBUG_TYPE_PERFORMANCE => "performance",
+        _ => "other",
+    };
+    let message = unsafe {
+        if length < 0 {
+            CStr::from_ptr(message).to_string_lossy()
+        } else {
+            String::from_utf8_lossy(slice::from_raw_parts(message as *const u8, length as usize))
+        }
+    };
+
+    log!(target: "surfman::gl", level, "GL {} {} {}: {}", source, message_type, id, message);
+}
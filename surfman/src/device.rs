@@ -2,12 +2,17 @@
 //
 //! The abstract interface that all devices conform to.
 
-use crate::{ContextAttributes, ContextID, Error, GLApi, SurfaceAccess, SurfaceInfo, SurfaceType};
+use crate::{ContextAttributes, ContextID, ContextInfo, ContextResetStatus, Error, GLApi};
+use crate::{PixelReadback, ReadbackFormat};
+use crate::{SurfaceAccess, SurfaceColorspace, SurfaceInfo, SurfaceType};
 use crate::gl::types::{GLenum, GLuint};
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
 use super::connection::Connection as ConnectionInterface;
 
 use std::os::raw::c_void;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::time::Duration;
 
 /// A thread-local handle to a device.
 ///
@@ -19,6 +24,10 @@ pub trait Device: Sized where Self::Connection: ConnectionInterface {
     type Context;
     /// The context descriptor type associated with this device.
     type ContextDescriptor;
+    /// The fence type associated with this device.
+    type Fence;
+    /// The frame callback type associated with this device.
+    type FrameCallback;
     /// The native context type associated with this device.
     type NativeContext;
     /// The surface type associated with this device.
@@ -82,6 +91,19 @@ pub trait Device: Sized where Self::Connection: ConnectionInterface {
     fn context_descriptor_attributes(&self, context_descriptor: &Self::ContextDescriptor)
                                      -> ContextAttributes;
 
+    /// Returns detailed information about the context, as reported by the driver: the renderer,
+    /// the vendor, the version that was actually created, and the supported extensions.
+    fn context_info(&self, context: &Self::Context) -> Result<ContextInfo, Error>;
+
+    /// Returns whether the context has been lost because of a GPU reset.
+    ///
+    /// Resets are only reported to contexts created with the `LOSE_CONTEXT_ON_RESET` flag. Once
+    /// this returns anything other than `NoError`, the context and everything in it are gone for
+    /// good, and rendering commands will be ignored. To recover, destroy all surfaces and surface
+    /// textures created with the context and then the context itself, create a new context from
+    /// the same context descriptor, and recreate your surfaces and OpenGL objects in it.
+    fn context_reset_status(&self, context: &Self::Context) -> Result<ContextResetStatus, Error>;
+
     /// Fetches the address of an OpenGL function associated with this context.
     /// 
     /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
@@ -125,6 +147,45 @@ pub trait Device: Sized where Self::Connection: ConnectionInterface {
     /// Returns the native context associated with the given context.
     fn native_context(&self, context: &Self::Context) -> Self::NativeContext;
 
+    /// Inserts a fence into the command stream of the given context.
+    ///
+    /// The fence is signaled once the GPU has finished all commands issued on the context before
+    /// this call. Fences are currently only supported by the EGL backends on Linux; elsewhere,
+    /// this returns an `Unimplemented` error.
+    fn create_fence(&self, context: &Self::Context) -> Result<Self::Fence, Error>;
+
+    /// Imports a sync file, such as one received from another process, as a fence in the command
+    /// stream of the given context.
+    ///
+    /// On success, the fence takes ownership of the file descriptor. On failure, the caller
+    /// retains ownership of it.
+    #[cfg(unix)]
+    fn create_fence_from_fd(&self, context: &Self::Context, fd: RawFd)
+                            -> Result<Self::Fence, Error>;
+
+    /// Blocks the calling thread until the fence is signaled.
+    ///
+    /// If `timeout` is `None`, this waits indefinitely. Returns true if the fence was signaled or
+    /// false if the timeout elapsed first.
+    fn wait_fence(&self, fence: &Self::Fence, timeout: Option<Duration>) -> Result<bool, Error>;
+
+    /// Makes the GPU wait for the fence to be signaled before executing any further commands
+    /// issued on the given context, without blocking the calling thread where possible.
+    fn server_wait_fence(&self, context: &Self::Context, fence: &Self::Fence)
+                         -> Result<(), Error>;
+
+    /// Returns a sync file that is signaled along with the fence, suitable for passing to other
+    /// processes or APIs such as Vulkan or KMS.
+    ///
+    /// The caller takes ownership of the returned file descriptor.
+    #[cfg(unix)]
+    fn export_fence_fd(&self, fence: &Self::Fence) -> Result<RawFd, Error>;
+
+    /// Destroys a fence.
+    ///
+    /// Fences must be explicitly destroyed with this function, or a panic will occur.
+    fn destroy_fence(&self, fence: &mut Self::Fence);
+
     // surface.rs
 
     /// Creates either a generic or a widget surface, depending on the supplied surface type.
@@ -188,9 +249,90 @@ pub trait Device: Sized where Self::Connection: ConnectionInterface {
     fn present_surface(&self, context: &Self::Context, surface: &mut Self::Surface)
                        -> Result<(), Error>;
 
-    /// Resizes a widget surface.
-    fn resize_surface(&self, context: &Self::Context, surface: &mut Self::Surface, size: Size2D<i32>)
-                       -> Result<(), Error>;
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// have changed since the last time it was presented.
+    ///
+    /// The regions are in device pixels, with the origin at the bottom left as in OpenGL. The
+    /// windowing system may use them to recomposite less of the screen, which saves power. If the
+    /// platform doesn't support damage regions, or `damage` is empty, the whole surface is
+    /// presented as with `present_surface()`.
+    fn present_surface_with_damage(&self,
+                                   context: &Self::Context,
+                                   surface: &mut Self::Surface,
+                                   damage: &[Rect<i32>])
+                                   -> Result<(), Error>;
+
+    /// Returns the number of frames ago that the contents of the back buffer of a widget surface
+    /// were presented, so that renderers can redraw only what changed since then.
+    ///
+    /// An age of 0 means that the contents of the back buffer are undefined and everything must
+    /// be redrawn, which is always the case on platforms that can't report buffer ages. Calling
+    /// this method on a generic surface returns a `NoWidgetAttached` error.
+    fn surface_buffer_age(&self, context: &Self::Context, surface: &Self::Surface)
+                          -> Result<u32, Error>;
+
+    /// Sets the number of vertical blanks that presenting a widget surface waits for.
+    /// 
+    /// An interval of 0 presents immediately, which may tear; 1 synchronizes with every vertical
+    /// blank. Negative intervals request adaptive vsync, which presents immediately only when a
+    /// frame misses its vertical blank; backends that can't provide it return an
+    /// `UnsupportedOnThisPlatform` error. The driver may clamp the interval to the range it
+    /// supports; use `swap_interval()` to find out the interval actually in effect. Calling this
+    /// method on a generic surface returns a `NoWidgetAttached` error.
+    fn set_swap_interval(&self, context: &Self::Context, surface: &Self::Surface, interval: i32)
+                         -> Result<(), Error>;
+
+    /// Returns the swap interval that is in effect for a widget surface.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    fn swap_interval(&self, context: &Self::Context, surface: &Self::Surface)
+                     -> Result<i32, Error>;
+
+    /// Asks the compositor to signal a frame callback when it's a good time to draw the next frame
+    /// of a widget surface.
+    ///
+    /// The callback covers the next `present_surface()` call. Frame callbacks are currently only
+    /// supported by the Wayland backend; elsewhere, this returns an `UnsupportedOnThisPlatform`
+    /// error. Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    fn request_frame_callback(&self, surface: &Self::Surface)
+                              -> Result<Self::FrameCallback, Error>;
+
+    /// Dispatches any frame callback events that have arrived, without blocking, and returns true
+    /// if the given frame callback has been signaled.
+    fn poll_frame_callback(&self, callback: &Self::FrameCallback) -> Result<bool, Error>;
+
+    /// Blocks the calling thread until the given frame callback is signaled or the timeout
+    /// elapses.
+    ///
+    /// If `timeout` is `None`, this waits indefinitely, which may be forever if the window is
+    /// hidden. Returns true if the frame callback was signaled.
+    fn wait_for_frame_callback(&self,
+                               callback: &Self::FrameCallback,
+                               timeout: Option<Duration>)
+                               -> Result<bool, Error>;
+
+    /// Destroys a frame callback.
+    ///
+    /// Frame callbacks must be explicitly destroyed with this function, or a panic will occur.
+    fn destroy_frame_callback(&self, callback: &mut Self::FrameCallback);
+
+    /// Resizes a surface.
+    /// 
+    /// Generic surfaces are reallocated at the new size. If `preserve_contents` is true, the pixels
+    /// in the region common to the old and new sizes, anchored at the bottom left, are kept, and
+    /// the rest of the surface is cleared to transparent black; otherwise, the whole surface is
+    /// cleared. The surface's ID and framebuffer object may change, so `surface_info()` must be
+    /// called again afterward.
+    /// 
+    /// Widget surfaces take on the current size of their native widget. On platforms where the
+    /// application sizes a widget's buffers itself, such as Wayland, the buffers are resized to
+    /// `size` first. Either way, `preserve_contents` has no effect on widget surfaces.
+    fn resize_surface(&self,
+                      context: &Self::Context,
+                      surface: &mut Self::Surface,
+                      size: Size2D<i32>,
+                      preserve_contents: bool)
+                      -> Result<(), Error>;
 
     /// Returns various information about the surface, including the framebuffer object needed to
     /// render to this surface.
@@ -200,8 +342,65 @@ pub trait Device: Sized where Self::Connection: ConnectionInterface {
     /// 0, the default framebuffer, depending on platform.
     fn surface_info(&self, surface: &Self::Surface) -> SurfaceInfo;
 
+    /// Returns the colorspaces that widget surfaces created on this device may request via
+    /// `SurfaceType::Widget`.
+    /// 
+    /// The list always begins with `SurfaceColorspace::Default` if widget surfaces are supported
+    /// at all, and is empty on backends that don't support widget surfaces.
+    fn supported_colorspaces(&self) -> Vec<SurfaceColorspace>;
+
     /// Returns the OpenGL texture object containing the contents of this surface.
     /// 
     /// It is only legal to read from, not write to, this texture object.
     fn surface_texture_object(&self, surface_texture: &Self::SurfaceTexture) -> GLuint;
+
+    /// Returns the OpenGL texture object containing the depth (and, if present, stencil) buffer
+    /// of the surface wrapped by this surface texture.
+    /// 
+    /// This is only available if the surface was created with
+    /// `SurfaceDescriptor::sampleable_depth` and has a depth buffer; otherwise, `None` is
+    /// returned. It is only legal to read from, not write to, this texture object.
+    fn surface_texture_depth_object(&self, surface_texture: &Self::SurfaceTexture)
+                                    -> Option<GLuint>;
+
+    /// Names the OpenGL objects behind a surface after the given label, so that `KHR_debug`
+    /// messages and GL debuggers can refer to them (for example, as "my label FBO").
+    /// 
+    /// Surfaces of contexts created with `ContextAttributeFlags::DEBUG` are already labeled after
+    /// their IDs. This does nothing on platforms without `KHR_debug`, or for surfaces that aren't
+    /// backed by OpenGL objects. The supplied context must be the context the surface is
+    /// associated with, or an `IncompatibleSurface` error is returned.
+    fn set_surface_label(&self, context: &Self::Context, surface: &Self::Surface, label: &str)
+                         -> Result<(), Error>;
+
+    /// Starts copying a region of a surface into CPU memory, without waiting for pending
+    /// rendering to finish.
+    ///
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned. The region is in device pixels, with the origin
+    /// at the bottom left as in OpenGL, and must lie within the surface, or an `InvalidRegion`
+    /// error is returned. For widget surfaces, this reads from the back buffer.
+    fn read_surface_pixels(&self,
+                           context: &Self::Context,
+                           surface: &Self::Surface,
+                           rect: Rect<i32>,
+                           format: ReadbackFormat)
+                           -> Result<PixelReadback, Error>;
+
+    /// Returns true if the pixels of a readback can be retrieved without blocking.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned.
+    fn poll_pixel_readback(&self, context: &Self::Context, readback: &PixelReadback)
+                           -> Result<bool, Error>;
+
+    /// Waits for a readback to complete and returns its pixels, in the format it was started
+    /// with.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned. If the pixels can't be copied into CPU memory, a
+    /// `PixelReadbackFailed` error is returned; the readback that comes back with it is finished
+    /// and can be dropped.
+    fn finish_pixel_readback(&self, context: &Self::Context, readback: PixelReadback)
+                             -> Result<Vec<u8>, (Error, PixelReadback)>;
 }
@@ -43,6 +43,8 @@ pub enum Error {
     SurfaceAlreadyBound,
     /// No suitable adapter could be found.
     NoAdapterFound,
+    /// A software adapter was requested, but the system has none.
+    NoSoftwareAdapters,
     /// The device couldn't be opened.
     DeviceOpenFailed,
     /// The system couldn't create a surface.
@@ -92,6 +94,34 @@ pub enum Error {
     IncompatibleNativeContext,
     /// The native device does not match the supplied connection.
     IncompatibleNativeDevice,
+    /// The fence is from a different backend than the device.
+    IncompatibleFence,
+    /// The frame callback is from a different backend than the device.
+    IncompatibleFrameCallback,
+    /// The requested region does not lie entirely within the surface.
+    InvalidRegion,
+    /// The pixels of a readback couldn't be copied into CPU memory.
+    PixelReadbackFailed,
+    /// The system couldn't create a fence.
+    FenceCreationFailed(WindowingApiError),
+    /// Waiting on a fence failed.
+    FenceWaitFailed(WindowingApiError),
+    /// The system couldn't export a fence as a native sync object.
+    FenceExportFailed(WindowingApiError),
+    /// The system couldn't export a surface for use by another API or process.
+    SurfaceExportFailed(WindowingApiError),
+    /// The requested backend is unknown, or the connection type doesn't provide it.
+    UnknownBackend,
+    /// The driver reported an OpenGL version string that couldn't be parsed.
+    UnrecognizedGLVersion,
+    /// The backend or the driver can't create surfaces with the requested format.
+    UnsupportedSurfaceFormat,
+    /// The system couldn't set the swap interval of a widget surface.
+    SwapIntervalFailed(WindowingApiError),
+    /// The connection to the display server was lost.
+    ConnectionLost,
+    /// The surface is still in use by another context.
+    SurfaceInUse,
 }
 
 /// Abstraction of the errors that EGL, CGL, GLX, CGL, etc. return.
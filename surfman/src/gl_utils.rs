@@ -5,6 +5,7 @@
 use crate::Gl;
 use crate::gl::types::{GLenum, GLint, GLuint};
 use crate::gl;
+use crate::renderbuffers::Renderbuffers;
 
 #[allow(dead_code)]
 pub(crate) fn create_and_bind_framebuffer(gl: &Gl, texture_target: GLenum, texture_object: GLuint)
@@ -37,6 +38,39 @@ pub(crate) fn unbind_framebuffer_if_necessary(gl: &Gl, framebuffer_object: GLuin
     }
 }
 
+// Clears the color buffer of a framebuffer, such as that of a newly-created surface, leaving the
+// framebuffer bindings, clear color, color mask, and scissor test as they were.
+#[allow(dead_code)]
+pub(crate) fn clear_framebuffer(gl: &Gl, framebuffer_object: GLuint, color: &[f32; 4]) {
+    unsafe {
+        let (mut old_draw_framebuffer, mut old_read_framebuffer) = (0, 0);
+        gl.GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut old_draw_framebuffer);
+        gl.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut old_read_framebuffer);
+        let mut old_clear_color = [0.0; 4];
+        gl.GetFloatv(gl::COLOR_CLEAR_VALUE, old_clear_color.as_mut_ptr());
+        let mut old_color_mask = [gl::FALSE; 4];
+        gl.GetBooleanv(gl::COLOR_WRITEMASK, old_color_mask.as_mut_ptr());
+        let scissor_test_was_enabled = gl.IsEnabled(gl::SCISSOR_TEST) != gl::FALSE;
+
+        gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+        gl.ClearColor(color[0], color[1], color[2], color[3]);
+        gl.ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+        gl.Disable(gl::SCISSOR_TEST);
+        gl.Clear(gl::COLOR_BUFFER_BIT);
+
+        if scissor_test_was_enabled {
+            gl.Enable(gl::SCISSOR_TEST);
+        }
+        gl.ColorMask(old_color_mask[0], old_color_mask[1], old_color_mask[2], old_color_mask[3]);
+        gl.ClearColor(old_clear_color[0],
+                      old_clear_color[1],
+                      old_clear_color[2],
+                      old_clear_color[3]);
+        gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, old_draw_framebuffer as GLuint);
+        gl.BindFramebuffer(gl::READ_FRAMEBUFFER, old_read_framebuffer as GLuint);
+    }
+}
+
 #[allow(dead_code)]
 pub(crate) fn destroy_framebuffer(gl: &Gl, framebuffer_object: GLuint) {
     unbind_framebuffer_if_necessary(gl, framebuffer_object);
@@ -44,3 +78,31 @@ pub(crate) fn destroy_framebuffer(gl: &Gl, framebuffer_object: GLuint) {
         gl.DeleteFramebuffers(1, &framebuffer_object);
     }
 }
+
+// Names an OpenGL object, so that `KHR_debug` messages and GL debuggers can refer to it. This does
+// nothing if the implementation doesn't support `KHR_debug`.
+#[cfg(not(target_os = "android"))]
+pub(crate) fn set_object_label(gl: &Gl, identifier: GLenum, name: GLuint, label: &str) {
+    if name == 0 || !gl.ObjectLabel.is_loaded() {
+        return;
+    }
+    unsafe {
+        gl.ObjectLabel(identifier, name, label.len() as _, label.as_ptr() as *const _);
+    }
+}
+
+// The OpenGL ES bindings only have the `KHR`-suffixed entry points, which we don't load.
+#[cfg(target_os = "android")]
+pub(crate) fn set_object_label(_: &Gl, _: GLenum, _: GLuint, _: &str) {}
+
+// Names the FBO, color texture, and renderbuffers that back a surface after the given label.
+#[allow(dead_code)]
+pub(crate) fn set_surface_objects_label(gl: &Gl,
+                                        label: &str,
+                                        framebuffer_object: GLuint,
+                                        texture_object: GLuint,
+                                        renderbuffers: &Renderbuffers) {
+    set_object_label(gl, gl::FRAMEBUFFER, framebuffer_object, &format!("{} FBO", label));
+    set_object_label(gl, gl::TEXTURE, texture_object, &format!("{} texture", label));
+    renderbuffers.set_label(gl, label);
+}
@@ -60,6 +60,11 @@ impl ConnectionInterface for Connection {
         Connection::create_software_adapter(self)
     }
 
+    #[inline]
+    fn adapters(&self) -> Result<Vec<Adapter>, Error> {
+        Connection::adapters(self)
+    }
+
     #[inline]
     fn create_device(&self, adapter: &Adapter) -> Result<Device, Error> {
         Connection::create_device(self, adapter)
@@ -6,20 +6,27 @@
 use crate::connection::Connection as ConnectionInterface;
 use crate::device::Device as DeviceInterface;
 use crate::gl::types::{GLenum, GLuint};
-use crate::{ContextAttributes, ContextID, Error, GLApi, SurfaceAccess, SurfaceInfo, SurfaceType};
-use euclid::default::Size2D;
+use crate::{ContextAttributes, ContextID, ContextInfo, ContextResetStatus, Error, GLApi};
+use crate::{PixelReadback, ReadbackFormat};
+use crate::{SurfaceAccess, SurfaceColorspace, SurfaceInfo, SurfaceType};
+use euclid::default::{Rect, Size2D};
 use super::super::connection::Connection;
-use super::super::context::{Context, ContextDescriptor, NativeContext};
+use super::super::context::{Context, ContextDescriptor, Fence, NativeContext};
 use super::super::device::{Adapter, Device};
-use super::super::surface::{NativeWidget, Surface, SurfaceTexture};
+use super::super::surface::{FrameCallback, NativeWidget, Surface, SurfaceTexture};
 
 use std::os::raw::c_void;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::time::Duration;
 
 #[deny(unconditional_recursion)]
 impl DeviceInterface for Device {
     type Connection = Connection;
     type Context = Context;
     type ContextDescriptor = ContextDescriptor;
+    type Fence = Fence;
+    type FrameCallback = FrameCallback;
     type NativeContext = NativeContext;
     type Surface = Surface;
     type SurfaceTexture = SurfaceTexture;
@@ -93,6 +100,16 @@ impl DeviceInterface for Device {
         Device::context_descriptor_attributes(self, context_descriptor)
     }
 
+    #[inline]
+    fn context_info(&self, context: &Context) -> Result<ContextInfo, Error> {
+        Device::context_info(self, context)
+    }
+
+    #[inline]
+    fn context_reset_status(&self, context: &Context) -> Result<ContextResetStatus, Error> {
+        Device::context_reset_status(self, context)
+    }
+
     #[inline]
     fn get_proc_address(&self, context: &Self::Context, symbol_name: &str) -> *const c_void {
         Device::get_proc_address(self, context, symbol_name)
@@ -125,6 +142,40 @@ impl DeviceInterface for Device {
         Device::native_context(self, context)
     }
 
+    #[inline]
+    fn create_fence(&self, context: &Self::Context) -> Result<Self::Fence, Error> {
+        Device::create_fence(self, context)
+    }
+
+    #[cfg(unix)]
+    #[inline]
+    fn create_fence_from_fd(&self, context: &Self::Context, fd: RawFd)
+                            -> Result<Self::Fence, Error> {
+        Device::create_fence_from_fd(self, context, fd)
+    }
+
+    #[inline]
+    fn wait_fence(&self, fence: &Self::Fence, timeout: Option<Duration>) -> Result<bool, Error> {
+        Device::wait_fence(self, fence, timeout)
+    }
+
+    #[inline]
+    fn server_wait_fence(&self, context: &Self::Context, fence: &Self::Fence)
+                         -> Result<(), Error> {
+        Device::server_wait_fence(self, context, fence)
+    }
+
+    #[cfg(unix)]
+    #[inline]
+    fn export_fence_fd(&self, fence: &Self::Fence) -> Result<RawFd, Error> {
+        Device::export_fence_fd(self, fence)
+    }
+
+    #[inline]
+    fn destroy_fence(&self, fence: &mut Self::Fence) {
+        Device::destroy_fence(self, fence)
+    }
+
     // surface.rs
 
     #[inline]
@@ -168,9 +219,64 @@ impl DeviceInterface for Device {
     }
 
     #[inline]
-    fn resize_surface(&self, context: &Context, surface: &mut Surface, size: Size2D<i32>)
-                       -> Result<(), Error> {
-        Device::resize_surface(self, context, surface, size)
+    fn present_surface_with_damage(&self,
+                                   context: &Self::Context,
+                                   surface: &mut Self::Surface,
+                                   damage: &[Rect<i32>])
+                                   -> Result<(), Error> {
+        Device::present_surface_with_damage(self, context, surface, damage)
+    }
+
+    #[inline]
+    fn surface_buffer_age(&self, context: &Self::Context, surface: &Self::Surface)
+                          -> Result<u32, Error> {
+        Device::surface_buffer_age(self, context, surface)
+    }
+
+    #[inline]
+    fn set_swap_interval(&self, context: &Self::Context, surface: &Self::Surface, interval: i32)
+                         -> Result<(), Error> {
+        Device::set_swap_interval(self, context, surface, interval)
+    }
+
+    #[inline]
+    fn swap_interval(&self, context: &Self::Context, surface: &Self::Surface)
+                     -> Result<i32, Error> {
+        Device::swap_interval(self, context, surface)
+    }
+
+    #[inline]
+    fn request_frame_callback(&self, surface: &Self::Surface)
+                              -> Result<Self::FrameCallback, Error> {
+        Device::request_frame_callback(self, surface)
+    }
+
+    #[inline]
+    fn poll_frame_callback(&self, callback: &Self::FrameCallback) -> Result<bool, Error> {
+        Device::poll_frame_callback(self, callback)
+    }
+
+    #[inline]
+    fn wait_for_frame_callback(&self,
+                               callback: &Self::FrameCallback,
+                               timeout: Option<Duration>)
+                               -> Result<bool, Error> {
+        Device::wait_for_frame_callback(self, callback, timeout)
+    }
+
+    #[inline]
+    fn destroy_frame_callback(&self, callback: &mut Self::FrameCallback) {
+        Device::destroy_frame_callback(self, callback)
+    }
+
+    #[inline]
+    fn resize_surface(&self,
+                      context: &Context,
+                      surface: &mut Surface,
+                      size: Size2D<i32>,
+                      preserve_contents: bool)
+                      -> Result<(), Error> {
+        Device::resize_surface(self, context, surface, size, preserve_contents)
     }
 
     #[inline]
@@ -178,8 +284,47 @@ impl DeviceInterface for Device {
         Device::surface_info(self, surface)
     }
 
+    #[inline]
+    fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        Device::supported_colorspaces(self)
+    }
+
     #[inline]
     fn surface_texture_object(&self, surface_texture: &Self::SurfaceTexture) -> GLuint {
         Device::surface_texture_object(self, surface_texture)
     }
+
+    #[inline]
+    fn surface_texture_depth_object(&self, surface_texture: &Self::SurfaceTexture)
+                                    -> Option<GLuint> {
+        Device::surface_texture_depth_object(self, surface_texture)
+    }
+
+    #[inline]
+    fn set_surface_label(&self, context: &Self::Context, surface: &Self::Surface, label: &str)
+                         -> Result<(), Error> {
+        Device::set_surface_label(self, context, surface, label)
+    }
+
+    #[inline]
+    fn read_surface_pixels(&self,
+                           context: &Self::Context,
+                           surface: &Self::Surface,
+                           rect: Rect<i32>,
+                           format: ReadbackFormat)
+                           -> Result<PixelReadback, Error> {
+        Device::read_surface_pixels(self, context, surface, rect, format)
+    }
+
+    #[inline]
+    fn poll_pixel_readback(&self, context: &Self::Context, readback: &PixelReadback)
+                           -> Result<bool, Error> {
+        Device::poll_pixel_readback(self, context, readback)
+    }
+
+    #[inline]
+    fn finish_pixel_readback(&self, context: &Self::Context, readback: PixelReadback)
+                             -> Result<Vec<u8>, (Error, PixelReadback)> {
+        Device::finish_pixel_readback(self, context, readback)
+    }
 }
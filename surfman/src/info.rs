@@ -2,8 +2,10 @@
 //
 //! OpenGL information.
 
-use crate::Gl;
+use crate::context;
+use crate::gl::types::{GLenum, GLuint};
 use crate::gl;
+use crate::{Error, Gl};
 
 use std::ffi::CStr;
 use std::os::raw::c_char;
@@ -36,26 +38,163 @@ impl GLVersion {
         GLVersion { major, minor }
     }
 
+    /// Parses an OpenGL or OpenGL ES version string, as returned by `glGetString(GL_VERSION)`.
+    ///
+    /// This accepts strings such as `4.6 (Core Profile) Mesa 23.0.4`, `4.6.0 NVIDIA 535.54`, and
+    /// `OpenGL ES 3.2 Mesa 23.0.4`. Anything else results in an `UnrecognizedGLVersion` error.
+    pub fn parse(version_string: &str) -> Result<GLVersion, Error> {
+        // Skip the `OpenGL ES` or `OpenGL ES-CM` prefix, if present.
+        let version_start = version_string.find(|c: char| c.is_ascii_digit())
+                                          .ok_or(Error::UnrecognizedGLVersion)?;
+        let mut version_string_iter = version_string[version_start..].split(|c| {
+            c == '.' || c == ' '
+        });
+        let mut next_number = || -> Result<u8, Error> {
+            version_string_iter.next()
+                               .and_then(|number| number.parse().ok())
+                               .ok_or(Error::UnrecognizedGLVersion)
+        };
+        let major = next_number()?;
+        let minor = next_number()?;
+        Ok(GLVersion { major, minor })
+    }
+
+    // Returns the version of the current context.
+    //
+    // If the driver reports a version that can't be parsed, this returns 0.0 instead of failing.
     #[allow(dead_code)]
     pub(crate) fn current(gl: &Gl) -> GLVersion {
+        current_api_and_version(gl).1
+    }
+
+    // Returns true if this version is at least `major.minor`.
+    #[allow(dead_code)]
+    pub(crate) fn is_at_least(self, major: u8, minor: u8) -> bool {
+        (self.major, self.minor) >= (major, minor)
+    }
+}
+
+// Returns the API and version of the current context. Unlike most queries, this can't generate a
+// GL error, so it's safe to use to decide which other queries the context supports.
+//
+// If the driver reports a version that can't be parsed, the version is 0.0.
+#[allow(dead_code)]
+pub(crate) fn current_api_and_version(gl: &Gl) -> (GLApi, GLVersion) {
+    let version_string = unsafe { get_string(gl, gl::VERSION) }.unwrap_or_default();
+    let api = if version_string.starts_with("OpenGL ES") { GLApi::GLES } else { GLApi::GL };
+    match GLVersion::parse(&version_string) {
+        Ok(version) => (api, version),
+        Err(_) => {
+            warn!("Couldn't parse the OpenGL version string {:?}!", version_string);
+            (api, GLVersion::new(0, 0))
+        }
+    }
+}
+
+// Returns the extensions of the current context, without generating GL errors.
+#[allow(dead_code)]
+pub(crate) fn current_context_extensions(gl: &Gl) -> Vec<String> {
+    unsafe { get_extensions(gl) }
+}
+
+/// The OpenGL profile of a context.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GLProfile {
+    /// The core profile, without deprecated functionality.
+    Core,
+    /// The compatibility profile, which keeps deprecated functionality.
+    Compatibility,
+}
+
+/// Detailed information about a context, as reported by the driver.
+///
+/// This is mostly useful for logging and bug reports. Use `Device::context_info()` to query it.
+#[derive(Clone, Debug)]
+pub struct ContextInfo {
+    /// The company responsible for the OpenGL implementation (`GL_VENDOR`).
+    pub vendor: String,
+    /// The name of the renderer (`GL_RENDERER`), which typically names the GPU.
+    pub renderer: String,
+    /// The full version string (`GL_VERSION`), which typically includes the driver version.
+    pub version_string: String,
+    /// The OpenGL or OpenGL ES version that the context actually provides, which may be newer
+    /// than the one that was requested.
+    pub version: GLVersion,
+    /// Whether this is an OpenGL or an OpenGL ES context.
+    pub api: GLApi,
+    /// The profile of the context, or `None` for OpenGL ES contexts, which have no profiles.
+    pub profile: Option<GLProfile>,
+    /// The OpenGL extensions that the context supports.
+    pub extensions: Vec<String>,
+    /// The EGL client extensions. This is empty on backends that don't use EGL.
+    pub egl_client_extensions: Vec<String>,
+    /// The extensions of the EGL display that the context belongs to. This is empty on backends
+    /// that don't use EGL.
+    pub egl_display_extensions: Vec<String>,
+}
+
+impl ContextInfo {
+    // Queries the current context. Backends that use EGL fill in the EGL extensions afterward.
+    #[allow(dead_code)]
+    pub(crate) fn current(gl: &Gl) -> Result<ContextInfo, Error> {
         unsafe {
-            let version_string = gl.GetString(gl::VERSION) as *const c_char;
-            let version_string = CStr::from_ptr(version_string).to_string_lossy()
-                                                               .trim_start_matches("OpenGL ES")
-                                                               .trim_start()
-                                                               .to_owned();
-            let mut version_string_iter = version_string.split(|c| c == '.' || c == ' ');
-            let major_version: u8 =
-                version_string_iter.next()
-                                   .expect("Where's the major GL version?")
-                                   .parse()
-                                   .expect("Couldn't parse the major GL version!");
-            let minor_version: u8 =
-                version_string_iter.next()
-                                   .expect("Where's the minor GL version?")
-                                   .parse()
-                                   .expect("Couldn't parse the minor GL version!");
-            GLVersion { major: major_version, minor: minor_version }
+            let vendor = get_string(gl, gl::VENDOR).ok_or(Error::NoCurrentContext)?;
+            let renderer = get_string(gl, gl::RENDERER).ok_or(Error::NoCurrentContext)?;
+            let version_string = get_string(gl, gl::VERSION).ok_or(Error::NoCurrentContext)?;
+            let version = GLVersion::parse(&version_string)?;
+
+            let (api, profile) = if version_string.starts_with("OpenGL ES") {
+                (GLApi::GLES, None)
+            } else if version.major < 3 || (version.major == 3 && version.minor < 2) {
+                // Profiles were introduced in OpenGL 3.2. Earlier contexts have everything.
+                (GLApi::GL, Some(GLProfile::Compatibility))
+            } else if context::current_context_uses_compatibility_profile(gl) {
+                (GLApi::GL, Some(GLProfile::Compatibility))
+            } else {
+                (GLApi::GL, Some(GLProfile::Core))
+            };
+
+            Ok(ContextInfo {
+                vendor,
+                renderer,
+                version_string,
+                version,
+                api,
+                profile,
+                extensions: get_extensions(gl),
+                egl_client_extensions: vec![],
+                egl_display_extensions: vec![],
+            })
         }
     }
 }
+
+unsafe fn get_string(gl: &Gl, name: GLenum) -> Option<String> {
+    let string = gl.GetString(name) as *const c_char;
+    if string.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(string).to_string_lossy().into_owned())
+    }
+}
+
+unsafe fn get_extensions(gl: &Gl) -> Vec<String> {
+    // Core profile contexts only support querying extensions one at a time. Check the version
+    // rather than trying it, so that older contexts aren't left with an error to report.
+    if current_api_and_version(gl).1.is_at_least(3, 0) && gl.GetStringi.is_loaded() {
+        let mut extension_count = 0;
+        gl.GetIntegerv(gl::NUM_EXTENSIONS, &mut extension_count);
+        return (0..(extension_count as GLuint)).filter_map(|extension_index| {
+            let extension = gl.GetStringi(gl::EXTENSIONS, extension_index) as *const c_char;
+            if extension.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(extension).to_string_lossy().into_owned())
+            }
+        }).collect();
+    }
+
+    get_string(gl, gl::EXTENSIONS).map(|extensions| {
+        extensions.split_whitespace().map(|extension| extension.to_owned()).collect()
+    }).unwrap_or_default()
+}
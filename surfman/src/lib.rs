@@ -28,6 +28,9 @@ pub use platform::default::context::{Context, ContextDescriptor, NativeContext};
 pub use platform::default::device::{Adapter, Device, NativeDevice};
 pub use platform::default::surface::{NativeWidget, Surface, SurfaceTexture};
 
+#[cfg(linux)]
+pub use platform::unix::Backend;
+
 // TODO(pcwalton): Fill this in with other OS's.
 #[cfg(target_os = "macos")]
 pub use platform::system::connection::Connection as SystemConnection;
@@ -44,12 +47,20 @@ pub use crate::error::{Error, WindowingApiError};
 
 mod context;
 pub use crate::context::{ContextAttributes, ContextAttributeFlags, ContextID};
+pub use crate::context::ContextResetStatus;
 
 mod info;
-pub use crate::info::{GLApi, GLVersion};
+pub use crate::info::{ContextInfo, GLApi, GLProfile, GLVersion};
 
 mod surface;
-pub use crate::surface::{SurfaceAccess, SurfaceID, SurfaceInfo, SurfaceType, SystemSurfaceInfo};
+pub use crate::surface::{SurfaceAccess, SurfaceDescriptor, SurfaceFormat, SurfaceID, SurfaceInfo};
+pub use crate::surface::{SurfaceColorspace, SurfaceType, SurfaceUsageFlags, SystemSurfaceInfo};
+
+mod readback;
+pub use crate::readback::{ChannelOrder, PixelReadback, ReadbackFormat};
+
+mod swap_chain;
+pub use crate::swap_chain::{SwapChain, SwapChainBuffering};
 
 pub mod macros;
 
@@ -9,6 +9,7 @@ use crate::gl::types::{GLenum, GLuint};
 use crate::gl;
 use crate::gl_utils;
 use crate::platform::generic::egl::device::EGL_FUNCTIONS;
+use crate::platform::generic::egl::error::ToWindowingApiError;
 use crate::platform::generic::egl::ffi::EGLImageKHR;
 use crate::platform::generic::egl::ffi::EGL_EXTENSION_FUNCTIONS;
 use crate::platform::generic::egl::ffi::EGL_IMAGE_PRESERVED_KHR;
@@ -16,21 +17,27 @@ use crate::platform::generic::egl::ffi::EGL_NATIVE_BUFFER_ANDROID;
 use crate::platform::generic::egl::ffi::EGL_NO_IMAGE_KHR;
 use crate::platform::generic;
 use crate::renderbuffers::Renderbuffers;
-use crate::{Error, SurfaceAccess, SurfaceID, SurfaceInfo, SurfaceType, WindowingApiError};
+use crate::{Error, PixelReadback, ReadbackFormat, SurfaceAccess, SurfaceDescriptor};
+use crate::{SurfaceColorspace, SurfaceFormat, SurfaceID, SurfaceInfo, SurfaceType};
+use crate::WindowingApiError;
 use super::context::{Context, GL_FUNCTIONS};
 use super::device::Device;
-use super::ffi::{AHARDWAREBUFFER_FORMAT_R8G8B8A8_UNORM, AHARDWAREBUFFER_USAGE_CPU_READ_NEVER};
+use super::ffi::{AHARDWAREBUFFER_FORMAT_R10G10B10A2_UNORM, AHARDWAREBUFFER_FORMAT_R8_UNORM};
+use super::ffi::{AHARDWAREBUFFER_FORMAT_R16G16B16A16_FLOAT, AHARDWAREBUFFER_FORMAT_R8G8B8A8_UNORM};
+use super::ffi::AHARDWAREBUFFER_USAGE_CPU_READ_NEVER;
 use super::ffi::{AHARDWAREBUFFER_USAGE_CPU_WRITE_NEVER, AHARDWAREBUFFER_USAGE_GPU_FRAMEBUFFER};
 use super::ffi::{AHARDWAREBUFFER_USAGE_GPU_SAMPLED_IMAGE, AHardwareBuffer, AHardwareBuffer_Desc};
 use super::ffi::{AHardwareBuffer_allocate, AHardwareBuffer_release, ANativeWindow};
 use super::ffi::{ANativeWindow_getHeight, ANativeWindow_getWidth};
 
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
+use std::cell::Cell;
 use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
 use std::os::raw::c_void;
 use std::ptr;
 use std::thread;
+use std::time::Duration;
 
 pub use crate::platform::generic::egl::context::ContextDescriptor;
 
@@ -55,6 +62,7 @@ const SURFACE_GL_TEXTURE_TARGET: GLenum = gl::TEXTURE_2D;
 pub struct Surface {
     pub(crate) context_id: ContextID,
     pub(crate) size: Size2D<i32>,
+    pub(crate) format: SurfaceFormat,
     pub(crate) objects: SurfaceObjects,
     pub(crate) destroyed: bool,
 }
@@ -85,6 +93,7 @@ pub(crate) enum SurfaceObjects {
     },
     Window {
         egl_surface: EGLSurface,
+        swap_interval: Cell<i32>,
     },
 }
 
@@ -115,35 +124,65 @@ pub struct NativeWidget {
     pub(crate) native_window: *mut ANativeWindow,
 }
 
+/// A request to be told when the compositor wants the next frame of a widget surface.
+///
+/// Frame callbacks aren't supported on this backend, so this type can't be constructed.
+#[derive(Debug)]
+pub enum FrameCallback {}
+
 impl Device {
     /// Creates either a generic or a widget surface, depending on the supplied surface type.
     /// 
     /// Only the given context may ever render to the surface, but generic surfaces can be wrapped
     /// up in a `SurfaceTexture` for reading by other contexts.
+    /// 
+    /// Generic surfaces are backed by hardware buffers, which support the `RGBA8`, `RGB10_A2`,
+    /// `RGBA16F`, and `R8` formats. Other formats result in an `UnsupportedSurfaceFormat` error.
     pub fn create_surface(&mut self,
                           context: &Context,
                           _: SurfaceAccess,
                           surface_type: SurfaceType<NativeWidget>)
                           -> Result<Surface, Error> {
         match surface_type {
-            SurfaceType::Generic { size } => self.create_generic_surface(context, &size),
-            SurfaceType::Widget { native_widget } => {
+            SurfaceType::Generic { size, descriptor } => {
+                self.create_generic_surface(context, &size, &descriptor)
+            }
+            SurfaceType::Widget { native_widget, colorspace } => {
                 unsafe {
-                    self.create_window_surface(context, native_widget.native_window)
+                    self.create_window_surface(context, native_widget.native_window, colorspace)
                 }
             }
         }
     }
 
-    fn create_generic_surface(&mut self, context: &Context, size: &Size2D<i32>)
+    fn create_generic_surface(&mut self,
+                              context: &Context,
+                              size: &Size2D<i32>,
+                              descriptor: &SurfaceDescriptor)
                               -> Result<Surface, Error> {
+        // FIXME: Support sampleable depth buffers.
+        if descriptor.sampleable_depth {
+            return Err(Error::Unimplemented);
+        }
+
+        let hardware_buffer_format = match descriptor.format {
+            SurfaceFormat::RGBA8 => AHARDWAREBUFFER_FORMAT_R8G8B8A8_UNORM,
+            SurfaceFormat::RGB10_A2 => AHARDWAREBUFFER_FORMAT_R10G10B10A2_UNORM,
+            SurfaceFormat::RGBA16F => AHARDWAREBUFFER_FORMAT_R16G16B16A16_FLOAT,
+            SurfaceFormat::R8 => AHARDWAREBUFFER_FORMAT_R8_UNORM,
+            SurfaceFormat::BGRA8 |
+            SurfaceFormat::RGBA32F |
+            SurfaceFormat::RG8 |
+            SurfaceFormat::SRGB8_A8 => return Err(Error::UnsupportedSurfaceFormat),
+        };
+
         let _guard = self.temporarily_make_context_current(context)?;
 
         GL_FUNCTIONS.with(|gl| {
             unsafe {
                 // Create a native hardware buffer.
                 let hardware_buffer_desc = AHardwareBuffer_Desc {
-                    format: AHARDWAREBUFFER_FORMAT_R8G8B8A8_UNORM,
+                    format: hardware_buffer_format,
                     height: size.height as u32,
                     width: size.width as u32,
                     layers: 1,
@@ -158,6 +197,10 @@ impl Device {
                 let mut hardware_buffer = ptr::null_mut();
                 let result = AHardwareBuffer_allocate(&hardware_buffer_desc, &mut hardware_buffer);
                 if result != 0 {
+                    // Devices may not support every format that the API defines.
+                    if descriptor.format != SurfaceFormat::RGBA8 {
+                        return Err(Error::UnsupportedSurfaceFormat);
+                    }
                     return Err(Error::SurfaceCreationFailed(WindowingApiError::Failed));
                 }
 
@@ -177,14 +220,20 @@ impl Device {
                 // Bind renderbuffers as appropriate.
                 let context_descriptor = self.context_descriptor(context);
                 let context_attributes = self.context_descriptor_attributes(&context_descriptor);
-                let renderbuffers = Renderbuffers::new(gl, size, &context_attributes);
+                let framebuffer_attributes = descriptor.framebuffer_attributes(&context_attributes);
+                let renderbuffers = Renderbuffers::new(gl, size, &framebuffer_attributes);
                 renderbuffers.bind_to_current_framebuffer(gl);
 
                 debug_assert_eq!(gl.CheckFramebufferStatus(gl::FRAMEBUFFER),
                                  gl::FRAMEBUFFER_COMPLETE);
 
+                if let Some(ref clear_color) = descriptor.clear_color {
+                    gl_utils::clear_framebuffer(gl, framebuffer_object, clear_color);
+                }
+
                 Ok(Surface {
                     size: *size,
+                    format: descriptor.format,
                     context_id: context.id,
                     objects: SurfaceObjects::HardwareBuffer {
                         hardware_buffer,
@@ -201,7 +250,8 @@ impl Device {
 
     unsafe fn create_window_surface(&mut self,
                                     context: &Context,
-                                    native_window: *mut ANativeWindow)
+                                    native_window: *mut ANativeWindow,
+                                    colorspace: SurfaceColorspace)
                                     -> Result<Surface, Error> {
         let width = ANativeWindow_getWidth(native_window);
         let height = ANativeWindow_getHeight(native_window);
@@ -209,17 +259,36 @@ impl Device {
         let context_descriptor = self.context_descriptor(context);
         let egl_config = self.context_descriptor_to_egl_config(&context_descriptor);
 
+        let mut window_surface_attributes = vec![];
+        let colorspace_attribute =
+            generic::egl::surface::colorspace_attribute(self.egl_display, colorspace)?;
+        if let Some(value) = colorspace_attribute {
+            window_surface_attributes.extend_from_slice(&[
+                egl::GL_COLORSPACE as EGLint, value as EGLint,
+            ]);
+        }
+        window_surface_attributes.push(egl::NONE as EGLint);
+
         EGL_FUNCTIONS.with(|egl| {
             let egl_surface = egl.CreateWindowSurface(self.egl_display,
                                                       egl_config,
                                                       native_window as *const c_void,
-                                                      ptr::null());
-            assert_ne!(egl_surface, egl::NO_SURFACE);
+                                                      window_surface_attributes.as_ptr());
+            if egl_surface == egl::NO_SURFACE {
+                let windowing_api_error = egl.GetError().to_windowing_api_error();
+                return Err(Error::SurfaceCreationFailed(windowing_api_error));
+            }
 
+            let swap_interval =
+                generic::egl::surface::default_swap_interval(self.egl_display, egl_surface);
             Ok(Surface {
                 context_id: context.id,
                 size: Size2D::new(width, height),
-                objects: SurfaceObjects::Window { egl_surface },
+                format: SurfaceFormat::RGBA8,
+                objects: SurfaceObjects::Window {
+                    egl_surface,
+                    swap_interval: Cell::new(swap_interval),
+                },
                 destroyed: false,
             })
         })
@@ -278,7 +347,7 @@ impl Device {
         EGL_FUNCTIONS.with(|egl| {
             unsafe {
                 match surface.objects {
-                    SurfaceObjects::Window { egl_surface } => {
+                    SurfaceObjects::Window { egl_surface, .. } => {
                         egl.SwapBuffers(self.egl_display, egl_surface);
                         Ok(())
                     }
@@ -288,10 +357,146 @@ impl Device {
         })
     }
 
-    /// Resizes a widget surface.
-    pub fn resize_surface(&self, _context: &Context, surface: &mut Surface, size: Size2D<i32>) -> Result<(), Error> {
-        surface.size = size;
-        Ok(())
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// have changed since the last time it was presented.
+    /// 
+    /// The regions are in device pixels, with the origin at the bottom left as in OpenGL. If the
+    /// display doesn't support `EGL_KHR_swap_buffers_with_damage`, or `damage` is empty, the
+    /// whole surface is presented.
+    pub fn present_surface_with_damage(&self,
+                                       context: &Context,
+                                       surface: &mut Surface,
+                                       damage: &[Rect<i32>])
+                                       -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        match surface.objects {
+            SurfaceObjects::Window { egl_surface, .. } => unsafe {
+                generic::egl::surface::swap_buffers_with_damage(self.egl_display,
+                                                                egl_surface,
+                                                                damage)
+            },
+            SurfaceObjects::HardwareBuffer { .. } => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    /// Returns the number of frames ago that the contents of the back buffer of a widget surface
+    /// were presented, or 0 if they are undefined or the display doesn't support
+    /// `EGL_EXT_buffer_age`.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn surface_buffer_age(&self, context: &Context, surface: &Surface) -> Result<u32, Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        match surface.objects {
+            SurfaceObjects::Window { egl_surface, .. } => unsafe {
+                generic::egl::surface::query_buffer_age(self.egl_display,
+                                                        egl_surface,
+                                                        context.egl_context)
+            },
+            SurfaceObjects::HardwareBuffer { .. } => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    /// Sets the number of vertical blanks that presenting a widget surface waits for.
+    /// 
+    /// EGL clamps the interval to the range supported by the surface's config; use
+    /// `swap_interval()` to find out the interval actually in effect. EGL has no adaptive vsync,
+    /// so negative intervals return an `UnsupportedOnThisPlatform` error. Calling this method on
+    /// a generic surface returns a `NoWidgetAttached` error.
+    pub fn set_swap_interval(&self, context: &Context, surface: &Surface, interval: i32)
+                             -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        match surface.objects {
+            SurfaceObjects::Window { egl_surface, ref swap_interval } => unsafe {
+                let effective_interval =
+                    generic::egl::surface::set_swap_interval(self.egl_display,
+                                                             egl_surface,
+                                                             context.egl_context,
+                                                             interval)?;
+                swap_interval.set(effective_interval);
+                Ok(())
+            },
+            SurfaceObjects::HardwareBuffer { .. } => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    /// Returns the swap interval that is in effect for a widget surface.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn swap_interval(&self, _: &Context, surface: &Surface) -> Result<i32, Error> {
+        match surface.objects {
+            SurfaceObjects::Window { ref swap_interval, .. } => Ok(swap_interval.get()),
+            SurfaceObjects::HardwareBuffer { .. } => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    /// Asks the compositor to signal a frame callback when it's a good time to draw the next frame
+    /// of a widget surface.
+    ///
+    /// Frame callbacks aren't supported on this backend, so this always returns an
+    /// `UnsupportedOnThisPlatform` error.
+    #[inline]
+    pub fn request_frame_callback(&self, _: &Surface) -> Result<FrameCallback, Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
+    /// Dispatches any frame callback events that have arrived, without blocking, and returns true
+    /// if the given frame callback has been signaled.
+    #[inline]
+    pub fn poll_frame_callback(&self, callback: &FrameCallback) -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Blocks the calling thread until the given frame callback is signaled or the timeout
+    /// elapses.
+    #[inline]
+    pub fn wait_for_frame_callback(&self, callback: &FrameCallback, _: Option<Duration>)
+                                   -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Destroys a frame callback.
+    #[inline]
+    pub fn destroy_frame_callback(&self, callback: &mut FrameCallback) {
+        match *callback {}
+    }
+
+    /// Resizes a surface.
+    /// 
+    /// Widget surfaces take on the current size of their native window, and ignore `size` and
+    /// `preserve_contents`. Resizing generic surfaces isn't supported yet on this backend, and
+    /// returns an `Unimplemented` error.
+    /// 
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn resize_surface(&self,
+                          context: &Context,
+                          surface: &mut Surface,
+                          _: Size2D<i32>,
+                          _: bool)
+                          -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        match surface.objects {
+            SurfaceObjects::Window { egl_surface, .. } => {
+                surface.size = unsafe {
+                    generic::egl::surface::query_surface_size(self.egl_display, egl_surface)
+                };
+                Ok(())
+            }
+            // FIXME: Reallocate the hardware buffer.
+            SurfaceObjects::HardwareBuffer { .. } => Err(Error::Unimplemented),
+        }
     }
 
     #[allow(non_snake_case)]
@@ -362,7 +567,7 @@ impl Device {
                         *hardware_buffer = ptr::null_mut();
                     });
                 }
-                SurfaceObjects::Window { ref mut egl_surface } => {
+                SurfaceObjects::Window { ref mut egl_surface, .. } => {
                     EGL_FUNCTIONS.with(|egl| {
                         egl.DestroySurface(self.egl_display, *egl_surface);
                         *egl_surface = egl::NO_SURFACE;
@@ -434,9 +639,16 @@ impl Device {
                 SurfaceObjects::HardwareBuffer { framebuffer_object, .. } => framebuffer_object,
                 SurfaceObjects::Window { .. } => 0,
             },
+            format: surface.format,
         }
     }
 
+    /// Returns the colorspaces that widget surfaces may request, depending on the EGL extensions
+    /// that the display supports.
+    pub fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        generic::egl::surface::supported_colorspaces(self.egl_display)
+    }
+
     /// Returns the OpenGL texture object containing the contents of this surface.
     /// 
     /// It is only legal to read from, not write to, this texture object.
@@ -444,6 +656,76 @@ impl Device {
     pub fn surface_texture_object(&self, surface_texture: &SurfaceTexture) -> GLuint {
         surface_texture.texture_object
     }
+
+    /// Returns the OpenGL texture object containing the depth buffer of the surface wrapped by
+    /// this surface texture.
+    /// 
+    /// Sampleable depth buffers aren't supported on this backend, so this always returns `None`.
+    #[inline]
+    pub fn surface_texture_depth_object(&self, _: &SurfaceTexture) -> Option<GLuint> {
+        None
+    }
+
+    /// Names the OpenGL objects behind a surface after the given label, so that `KHR_debug`
+    /// messages and GL debuggers can refer to them.
+    /// 
+    /// Widget surfaces have no such objects, so this does nothing for them. The supplied context
+    /// must be the context the surface is associated with, or an `IncompatibleSurface` error is
+    /// returned.
+    pub fn set_surface_label(&self, context: &Context, surface: &Surface, label: &str)
+                             -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| {
+            match surface.objects {
+                SurfaceObjects::HardwareBuffer {
+                    framebuffer_object,
+                    texture_object,
+                    ref renderbuffers,
+                    ..
+                } => {
+                    gl_utils::set_surface_objects_label(gl,
+                                                        label,
+                                                        framebuffer_object,
+                                                        texture_object,
+                                                        renderbuffers);
+                }
+                SurfaceObjects::Window { .. } => {}
+            }
+        });
+        Ok(())
+    }
+
+    /// Starts copying a region of a surface into CPU memory, without waiting for pending
+    /// rendering to finish.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    #[inline]
+    pub fn read_surface_pixels(&self,
+                               _: &Context,
+                               _: &Surface,
+                               _: Rect<i32>,
+                               _: ReadbackFormat)
+                               -> Result<PixelReadback, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Returns true if the pixels of a readback can be retrieved without blocking.
+    #[inline]
+    pub fn poll_pixel_readback(&self, _: &Context, _: &PixelReadback) -> Result<bool, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Waits for a readback to complete and returns its pixels, in the format it was started
+    /// with.
+    #[inline]
+    pub fn finish_pixel_readback(&self, _: &Context, readback: PixelReadback)
+                                 -> Result<Vec<u8>, (Error, PixelReadback)> {
+        Err((Error::Unimplemented, readback))
+    }
 }
 
 impl NativeWidget {
@@ -458,7 +740,7 @@ impl Surface {
     fn id(&self) -> SurfaceID {
         match self.objects {
             SurfaceObjects::HardwareBuffer { egl_image, .. } => SurfaceID(egl_image as usize),
-            SurfaceObjects::Window { egl_surface } => SurfaceID(egl_surface as usize),
+            SurfaceObjects::Window { egl_surface, .. } => SurfaceID(egl_surface as usize),
         }
     }
 }
@@ -6,12 +6,17 @@ use crate::context::{self, CREATE_CONTEXT_MUTEX};
 use crate::egl::types::{EGLConfig, EGLContext, EGLDisplay, EGLSurface, EGLint};
 use crate::egl;
 use crate::surface::Framebuffer;
-use crate::{ContextAttributeFlags, ContextAttributes, ContextID, Error, GLApi, GLVersion};
-use crate::{Gl, SurfaceInfo};
-use super::device::EGL_FUNCTIONS;
+use crate::{ContextAttributeFlags, ContextAttributes, ContextID, ContextInfo};
+use crate::{ContextResetStatus, Error, GLApi, GLVersion, Gl, SurfaceInfo, WindowingApiError};
+use super::device::{self, EGL_FUNCTIONS};
 use super::error::ToWindowingApiError;
-use super::ffi::{EGL_CONTEXT_MINOR_VERSION_KHR, EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT};
+use super::ffi::{EGL_CONTEXT_FLAGS_KHR, EGL_CONTEXT_MINOR_VERSION_KHR};
+use super::ffi::{EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT, EGL_CONTEXT_OPENGL_DEBUG_BIT_KHR};
 use super::ffi::EGL_CONTEXT_OPENGL_PROFILE_MASK;
+use super::ffi::EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT;
+use super::ffi::EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_KHR;
+use super::ffi::{EGL_CONTEXT_OPENGL_ROBUST_ACCESS_BIT_KHR, EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT};
+use super::ffi::{EGL_LOSE_CONTEXT_ON_RESET_EXT, EGL_LOSE_CONTEXT_ON_RESET_KHR};
 use super::surface::{EGLBackedSurface, ExternalEGLSurfaces};
 
 use std::ffi::CString;
@@ -29,6 +34,11 @@ pub(crate) struct EGLBackedContext {
     pub(crate) id: ContextID,
     framebuffer: Framebuffer<EGLBackedSurface, ExternalEGLSurfaces>,
     context_is_owned: bool,
+    // Set by `simulate_loss()`, so that context loss can be tested without a real GPU reset.
+    loss_simulated: bool,
+    // EGL has no notion of surface multisampling, so remember the sample count this context was
+    // created with for its generic surfaces.
+    samples: u32,
 }
 
 /// Wrapper for a native `EGLContext`.
@@ -50,6 +60,10 @@ pub struct ContextDescriptor {
     pub(crate) egl_config_id: EGLint,
     pub(crate) gl_version: GLVersion,
     pub(crate) compatibility_profile: bool,
+    pub(crate) robust_access: bool,
+    pub(crate) lose_context_on_reset: bool,
+    pub(crate) debug: bool,
+    pub(crate) samples: u32,
 }
 
 #[must_use]
@@ -100,6 +114,8 @@ impl EGLBackedContext {
             id: *next_context_id,
             framebuffer: Framebuffer::None,
             context_is_owned: true,
+            loss_simulated: false,
+            samples: descriptor.samples,
         };
         next_context_id.0 += 1;
         Ok(context)
@@ -115,11 +131,20 @@ impl EGLBackedContext {
                 read: native_context.egl_read_surface,
             }),
             context_is_owned: false,
+            loss_simulated: false,
+            samples: 0,
         };
         next_context_id.0 += 1;
         context
     }
 
+    pub(crate) unsafe fn descriptor(&self, gl: &Gl, egl_display: EGLDisplay)
+                                    -> ContextDescriptor {
+        let mut descriptor = ContextDescriptor::from_egl_context(gl, egl_display, self.egl_context);
+        descriptor.samples = self.samples;
+        descriptor
+    }
+
     pub(crate) unsafe fn destroy(&mut self, egl_display: EGLDisplay) {
         EGL_FUNCTIONS.with(|egl| {
             egl.MakeCurrent(egl_display, egl::NO_SURFACE, egl::NO_SURFACE, egl::NO_CONTEXT);
@@ -167,6 +192,29 @@ impl EGLBackedContext {
         })
     }
 
+    // Returns whether this context has been reset, making it current temporarily if necessary.
+    pub(crate) unsafe fn reset_status(&self, egl_display: EGLDisplay)
+                                      -> Result<ContextResetStatus, Error> {
+        if self.loss_simulated {
+            return Ok(ContextResetStatus::Guilty);
+        }
+
+        let _guard = CurrentContextGuard::new();
+        match self.make_current(egl_display) {
+            Ok(()) => Ok(context::current_context_reset_status(get_proc_address)),
+            // EGL may refuse to make a lost context current at all.
+            Err(Error::MakeCurrentFailed(WindowingApiError::ContextLost)) => {
+                Ok(ContextResetStatus::Unknown)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn simulate_loss(&mut self) {
+        self.loss_simulated = true;
+    }
+
     #[inline]
     pub(crate) fn is_current(&self) -> bool {
         unsafe {
@@ -200,7 +248,8 @@ impl EGLBackedContext {
                                         -> Result<Option<EGLBackedSurface>, Error> {
         match self.framebuffer {
             Framebuffer::None => return Ok(None),
-            Framebuffer::Surface(_) => {}
+            // Leave the surface's texture with the final contents of any multisampled rendering.
+            Framebuffer::Surface(ref surface) => surface.resolve(gl, egl_display)?,
             Framebuffer::External(_) => return Err(Error::ExternalRenderTarget),
         }
 
@@ -258,6 +307,9 @@ impl ContextDescriptor {
         let stencil_size = if flags.contains(ContextAttributeFlags::STENCIL) { 8  } else { 0 };
 
         let compatibility_profile = flags.contains(ContextAttributeFlags::COMPATIBILITY_PROFILE);
+        let robust_access = flags.contains(ContextAttributeFlags::ROBUST_ACCESS);
+        let lose_context_on_reset = flags.contains(ContextAttributeFlags::LOSE_CONTEXT_ON_RESET);
+        let debug = flags.contains(ContextAttributeFlags::DEBUG);
 
         // Mesa doesn't support the OpenGL compatibility profile post version 3.0. Take that into
         // account.
@@ -267,6 +319,15 @@ impl ContextDescriptor {
             return Err(Error::UnsupportedGLProfile);
         }
 
+        if (robust_access || lose_context_on_reset) &&
+                !device::display_supports_extension(egl_display,
+                                                    "EGL_EXT_create_context_robustness") {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+        if debug && !device::display_supports_extension(egl_display, "EGL_KHR_create_context") {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+
         // Create required config attributes.
         //
         // We check these separately because `eglChooseConfig` on its own might give us 32-bit
@@ -335,6 +396,10 @@ impl ContextDescriptor {
                 egl_config_id,
                 gl_version,
                 compatibility_profile,
+                robust_access,
+                lose_context_on_reset,
+                debug,
+                samples: attributes.samples,
             })
         })
     }
@@ -351,7 +416,19 @@ impl ContextDescriptor {
             let gl_version = GLVersion::current(gl);
             let compatibility_profile = context::current_context_uses_compatibility_profile(gl);
 
-            ContextDescriptor { egl_config_id, gl_version, compatibility_profile }
+            let (robust_access, lose_context_on_reset) = context::current_context_robustness(gl);
+            let debug = context::current_context_is_debug(gl);
+
+            ContextDescriptor {
+                egl_config_id,
+                gl_version,
+                compatibility_profile,
+                robust_access,
+                lose_context_on_reset,
+                debug,
+                // Wrapped contexts have no surfman surfaces of their own.
+                samples: 0,
+            }
         })
     }
 
@@ -391,9 +468,17 @@ impl ContextDescriptor {
 
         attribute_flags.set(ContextAttributeFlags::COMPATIBILITY_PROFILE,
                             self.compatibility_profile);
+        attribute_flags.set(ContextAttributeFlags::ROBUST_ACCESS, self.robust_access);
+        attribute_flags.set(ContextAttributeFlags::LOSE_CONTEXT_ON_RESET,
+                            self.lose_context_on_reset);
+        attribute_flags.set(ContextAttributeFlags::DEBUG, self.debug);
 
         // Create appropriate context attributes.
-        ContextAttributes { flags: attribute_flags, version: self.gl_version }
+        ContextAttributes {
+            flags: attribute_flags,
+            version: self.gl_version,
+            samples: self.samples,
+        }
     }
 }
 
@@ -440,6 +525,51 @@ pub(crate) unsafe fn create_context(egl_display: EGLDisplay,
         ]);
     }
 
+    // Debug contexts, as well as robust desktop OpenGL contexts, are requested with the
+    // `EGL_CONTEXT_FLAGS_KHR` bitfield from `EGL_KHR_create_context`.
+    let mut context_flags = 0;
+    if descriptor.debug {
+        context_flags |= EGL_CONTEXT_OPENGL_DEBUG_BIT_KHR;
+    }
+
+    // `EGL_EXT_create_context_robustness` only applies to OpenGL ES. Desktop OpenGL contexts use
+    // the equivalent attributes from `EGL_KHR_create_context` instead.
+    if descriptor.robust_access || descriptor.lose_context_on_reset {
+        match gl_api {
+            GLApi::GLES => {
+                if descriptor.robust_access {
+                    egl_context_attributes.extend(&[
+                        EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT as EGLint, egl::TRUE as EGLint,
+                    ]);
+                }
+                if descriptor.lose_context_on_reset {
+                    egl_context_attributes.extend(&[
+                        EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT as EGLint,
+                        EGL_LOSE_CONTEXT_ON_RESET_EXT as EGLint,
+                    ]);
+                }
+            }
+            GLApi::GL => {
+                if !device::display_supports_extension(egl_display, "EGL_KHR_create_context") {
+                    return Err(Error::RequiredExtensionUnavailable);
+                }
+                if descriptor.robust_access {
+                    context_flags |= EGL_CONTEXT_OPENGL_ROBUST_ACCESS_BIT_KHR;
+                }
+                if descriptor.lose_context_on_reset {
+                    egl_context_attributes.extend(&[
+                        EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_KHR as EGLint,
+                        EGL_LOSE_CONTEXT_ON_RESET_KHR as EGLint,
+                    ]);
+                }
+            }
+        }
+    }
+
+    if context_flags != 0 {
+        egl_context_attributes.extend(&[EGL_CONTEXT_FLAGS_KHR as EGLint, context_flags]);
+    }
+
     // Include some extra zeroes to work around broken implementations.
     //
     // FIXME(pcwalton): Which implementations are those? (This is copied from Gecko.)
@@ -459,6 +589,22 @@ pub(crate) unsafe fn create_context(egl_display: EGLDisplay,
             return Err(Error::ContextCreationFailed(err));
         }
 
+        // The debug message callback is per-context state, so install it while we can make the
+        // context current on our own terms.
+        if descriptor.debug {
+            let _guard = CurrentContextGuard::new();
+            let nothing_was_current = egl.GetCurrentContext() == egl::NO_CONTEXT;
+            if egl.MakeCurrent(egl_display, egl::NO_SURFACE, egl::NO_SURFACE, egl_context) ==
+                    egl::FALSE {
+                warn!("surfman: couldn't make the debug context current to install its logger");
+            } else {
+                context::install_debug_message_callback(get_proc_address);
+                if nothing_was_current {
+                    egl.MakeCurrent(egl_display, egl::NO_SURFACE, egl::NO_SURFACE, egl::NO_CONTEXT);
+                }
+            }
+        }
+
         Ok(egl_context)
     })
 }
@@ -477,6 +623,15 @@ pub(crate) unsafe fn make_no_context_current(egl_display: EGLDisplay) -> Result<
     })
 }
 
+// Queries information about the current context, which must belong to the given display.
+pub(crate) unsafe fn current_context_info(gl: &Gl, egl_display: EGLDisplay)
+                                          -> Result<ContextInfo, Error> {
+    let mut info = ContextInfo::current(gl)?;
+    info.egl_client_extensions = device::display_extensions(egl::NO_DISPLAY);
+    info.egl_display_extensions = device::display_extensions(egl_display);
+    Ok(info)
+}
+
 pub(crate) unsafe fn get_config_attr(egl_display: EGLDisplay, egl_config: EGLConfig, attr: EGLint)
                                      -> EGLint {
     EGL_FUNCTIONS.with(|egl| {
@@ -3,8 +3,10 @@
 //! Functionality common to backends using EGL displays.
 
 use crate::egl::Egl;
+use crate::egl::types::EGLDisplay;
+use crate::egl;
 
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::mem;
 use std::os::raw::{c_char, c_void};
 
@@ -71,3 +73,25 @@ pub(crate) unsafe fn lookup_egl_extension(name: &'static [u8]) -> *mut c_void {
         mem::transmute(egl.GetProcAddress(&name[0] as *const u8 as *const c_char))
     })
 }
+
+// Returns true if the display advertises the given extension.
+pub(crate) unsafe fn display_supports_extension(egl_display: EGLDisplay, name: &str) -> bool {
+    display_extensions(egl_display).iter().any(|ext| ext == name)
+}
+
+// Returns the extensions of the given display, or the client extensions if the display is
+// `EGL_NO_DISPLAY`.
+pub(crate) unsafe fn display_extensions(egl_display: EGLDisplay) -> Vec<String> {
+    EGL_FUNCTIONS.with(|egl| {
+        let extensions = egl.QueryString(egl_display, egl::EXTENSIONS as _);
+        if extensions.is_null() {
+            // Querying client extensions fails if `EGL_EXT_client_extensions` is unsupported.
+            egl.GetError();
+            return vec![];
+        }
+        CStr::from_ptr(extensions).to_string_lossy()
+                                  .split_whitespace()
+                                  .map(|ext| ext.to_owned())
+                                  .collect()
+    })
+}
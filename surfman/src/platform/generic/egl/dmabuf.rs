@@ -0,0 +1,276 @@
+// surfman/surfman/src/platform/generic/egl/dmabuf.rs
+//
+//! Sharing surfaces with other APIs and processes as Linux DMA buffers.
+
+use crate::egl::types::{EGLDisplay, EGLenum, EGLint};
+use crate::egl;
+use crate::{Error, SurfaceFormat, WindowingApiError};
+use super::device::{self, EGL_FUNCTIONS};
+use super::error::ToWindowingApiError;
+use super::ffi::{EGL_DMA_BUF_PLANE0_FD_EXT, EGL_DMA_BUF_PLANE0_MODIFIER_HI_EXT};
+use super::ffi::{EGL_DMA_BUF_PLANE0_MODIFIER_LO_EXT, EGL_DMA_BUF_PLANE0_OFFSET_EXT};
+use super::ffi::{EGL_DMA_BUF_PLANE0_PITCH_EXT, EGL_DMA_BUF_PLANE1_FD_EXT};
+use super::ffi::{EGL_DMA_BUF_PLANE1_MODIFIER_HI_EXT, EGL_DMA_BUF_PLANE1_MODIFIER_LO_EXT};
+use super::ffi::{EGL_DMA_BUF_PLANE1_OFFSET_EXT, EGL_DMA_BUF_PLANE1_PITCH_EXT};
+use super::ffi::{EGL_DMA_BUF_PLANE2_FD_EXT, EGL_DMA_BUF_PLANE2_MODIFIER_HI_EXT};
+use super::ffi::{EGL_DMA_BUF_PLANE2_MODIFIER_LO_EXT, EGL_DMA_BUF_PLANE2_OFFSET_EXT};
+use super::ffi::{EGL_DMA_BUF_PLANE2_PITCH_EXT, EGL_DMA_BUF_PLANE3_FD_EXT};
+use super::ffi::{EGL_DMA_BUF_PLANE3_MODIFIER_HI_EXT, EGL_DMA_BUF_PLANE3_MODIFIER_LO_EXT};
+use super::ffi::{EGL_DMA_BUF_PLANE3_OFFSET_EXT, EGL_DMA_BUF_PLANE3_PITCH_EXT};
+use super::ffi::{EGL_EXTENSION_FUNCTIONS, EGL_LINUX_DMA_BUF_EXT, EGL_LINUX_DRM_FOURCC_EXT};
+use super::ffi::{EGL_NO_IMAGE_KHR, EGLImageKHR, EGLuint64KHR};
+
+use euclid::default::Size2D;
+use std::os::unix::io::RawFd;
+use std::ptr;
+
+const MAX_PLANES: usize = 4;
+
+// The file descriptor, offset, pitch, modifier low bits, and modifier high bits attributes for
+// each plane.
+static PLANE_ATTRIBUTES: [[EGLenum; 5]; MAX_PLANES] = [
+    [
+        EGL_DMA_BUF_PLANE0_FD_EXT,
+        EGL_DMA_BUF_PLANE0_OFFSET_EXT,
+        EGL_DMA_BUF_PLANE0_PITCH_EXT,
+        EGL_DMA_BUF_PLANE0_MODIFIER_LO_EXT,
+        EGL_DMA_BUF_PLANE0_MODIFIER_HI_EXT,
+    ],
+    [
+        EGL_DMA_BUF_PLANE1_FD_EXT,
+        EGL_DMA_BUF_PLANE1_OFFSET_EXT,
+        EGL_DMA_BUF_PLANE1_PITCH_EXT,
+        EGL_DMA_BUF_PLANE1_MODIFIER_LO_EXT,
+        EGL_DMA_BUF_PLANE1_MODIFIER_HI_EXT,
+    ],
+    [
+        EGL_DMA_BUF_PLANE2_FD_EXT,
+        EGL_DMA_BUF_PLANE2_OFFSET_EXT,
+        EGL_DMA_BUF_PLANE2_PITCH_EXT,
+        EGL_DMA_BUF_PLANE2_MODIFIER_LO_EXT,
+        EGL_DMA_BUF_PLANE2_MODIFIER_HI_EXT,
+    ],
+    [
+        EGL_DMA_BUF_PLANE3_FD_EXT,
+        EGL_DMA_BUF_PLANE3_OFFSET_EXT,
+        EGL_DMA_BUF_PLANE3_PITCH_EXT,
+        EGL_DMA_BUF_PLANE3_MODIFIER_LO_EXT,
+        EGL_DMA_BUF_PLANE3_MODIFIER_HI_EXT,
+    ],
+];
+
+/// The format modifier indicating that the tiling layout of a DMA buffer is implied by the
+/// driver rather than described explicitly.
+pub const DRM_FORMAT_MOD_INVALID: u64 = 0x00ff_ffff_ffff_ffff;
+
+/// One plane of a DMA buffer.
+#[derive(Clone, Copy, Debug)]
+pub struct DmabufPlane {
+    /// The file descriptor referring to the memory backing this plane.
+    pub fd: RawFd,
+    /// The offset of this plane from the start of the buffer, in bytes.
+    pub offset: u32,
+    /// The number of bytes per row of this plane.
+    pub stride: u32,
+}
+
+/// Describes the layout of an image stored in a Linux DMA buffer, so that it can be shared with
+/// other APIs such as Vulkan, V4L2, or a Wayland compositor without copying.
+///
+/// This is a plain description of the buffer; it does not own the file descriptors in its planes.
+/// Whoever created the descriptor is responsible for closing them.
+#[derive(Clone, Debug)]
+pub struct DmabufDescriptor {
+    /// The size of the image, in pixels.
+    pub size: Size2D<i32>,
+    /// The DRM fourcc code of the pixel format, as found in `drm_fourcc.h`.
+    pub fourcc: u32,
+    /// The DRM format modifier describing the tiling layout of the planes, or
+    /// `DRM_FORMAT_MOD_INVALID` if the layout is implicit.
+    pub modifier: u64,
+    /// The planes of the image, in order.
+    pub planes: Vec<DmabufPlane>,
+}
+
+// Exports the memory behind an EGL image via `EGL_MESA_image_dma_buf_export`. The caller owns
+// the file descriptors in the returned descriptor.
+pub(crate) unsafe fn export_egl_image(egl_display: EGLDisplay,
+                                      egl_image: EGLImageKHR,
+                                      size: &Size2D<i32>)
+                                      -> Result<DmabufDescriptor, Error> {
+    let (query, export) = match (EGL_EXTENSION_FUNCTIONS.ExportDMABUFImageQueryMESA,
+                                 EGL_EXTENSION_FUNCTIONS.ExportDMABUFImageMESA) {
+        (Some(query), Some(export)) if
+                device::display_supports_extension(egl_display,
+                                                   "EGL_MESA_image_dma_buf_export") => {
+            (query, export)
+        }
+        _ => return Err(Error::RequiredExtensionUnavailable),
+    };
+
+    let (mut fourcc, mut plane_count) = (0, 0);
+    if query(egl_display, egl_image, &mut fourcc, &mut plane_count, ptr::null_mut()) ==
+            egl::FALSE {
+        return Err(export_error());
+    }
+    let plane_count = plane_count as usize;
+    if plane_count == 0 || plane_count > MAX_PLANES {
+        return Err(Error::SurfaceExportFailed(WindowingApiError::Failed));
+    }
+
+    let mut modifiers: [EGLuint64KHR; MAX_PLANES] = [DRM_FORMAT_MOD_INVALID; MAX_PLANES];
+    let mut queried_plane_count = 0;
+    if query(egl_display,
+             egl_image,
+             &mut fourcc,
+             &mut queried_plane_count,
+             modifiers.as_mut_ptr()) == egl::FALSE {
+        return Err(export_error());
+    }
+
+    let mut fds: [EGLint; MAX_PLANES] = [-1; MAX_PLANES];
+    let mut strides: [EGLint; MAX_PLANES] = [0; MAX_PLANES];
+    let mut offsets: [EGLint; MAX_PLANES] = [0; MAX_PLANES];
+    if export(egl_display,
+              egl_image,
+              fds.as_mut_ptr(),
+              strides.as_mut_ptr(),
+              offsets.as_mut_ptr()) == egl::FALSE {
+        return Err(export_error());
+    }
+
+    // The descriptor has a single modifier for all planes, so reject images whose planes differ.
+    if modifiers[1..plane_count].iter().any(|&modifier| modifier != modifiers[0]) {
+        close_exported_fds(&fds[0..plane_count]);
+        return Err(Error::SurfaceExportFailed(WindowingApiError::BadMatch));
+    }
+
+    // Drivers may return the same file descriptor for several planes that share a buffer. Give
+    // each plane its own, so that the caller can close them independently.
+    let mut planes: Vec<DmabufPlane> = Vec::with_capacity(plane_count);
+    for plane_index in 0..plane_count {
+        let mut fd = fds[plane_index];
+        if fds[0..plane_index].contains(&fd) {
+            fd = libc::dup(fd);
+            if fd < 0 {
+                close_exported_fds(&fds[0..plane_count]);
+                for plane in &planes {
+                    if !fds[0..plane_count].contains(&plane.fd) {
+                        libc::close(plane.fd);
+                    }
+                }
+                return Err(Error::SurfaceExportFailed(WindowingApiError::BadAlloc));
+            }
+        }
+        planes.push(DmabufPlane {
+            fd,
+            offset: offsets[plane_index] as u32,
+            stride: strides[plane_index] as u32,
+        });
+    }
+
+    Ok(DmabufDescriptor {
+        size: *size,
+        fourcc: fourcc as u32,
+        modifier: modifiers[0],
+        planes,
+    })
+}
+
+// Closes the file descriptors that `eglExportDMABUFImageMESA()` returned, once each.
+unsafe fn close_exported_fds(fds: &[EGLint]) {
+    for (fd_index, &fd) in fds.iter().enumerate() {
+        if !fds[0..fd_index].contains(&fd) {
+            libc::close(fd);
+        }
+    }
+}
+
+// Wraps a DMA buffer in an EGL image via `EGL_EXT_image_dma_buf_import`. EGL doesn't take
+// ownership of the file descriptors in the descriptor.
+pub(crate) unsafe fn import_egl_image(egl_display: EGLDisplay, descriptor: &DmabufDescriptor)
+                                      -> Result<EGLImageKHR, Error> {
+    if !device::display_supports_extension(egl_display, "EGL_EXT_image_dma_buf_import") {
+        return Err(Error::RequiredExtensionUnavailable);
+    }
+    let explicit_modifier = descriptor.modifier != DRM_FORMAT_MOD_INVALID;
+    if explicit_modifier &&
+            !device::display_supports_extension(egl_display,
+                                                "EGL_EXT_image_dma_buf_import_modifiers") {
+        return Err(Error::RequiredExtensionUnavailable);
+    }
+    if descriptor.planes.is_empty() || descriptor.planes.len() > MAX_PLANES {
+        return Err(Error::SurfaceImportFailed(WindowingApiError::BadParameter));
+    }
+
+    let mut attributes = vec![
+        egl::WIDTH as EGLint,               descriptor.size.width,
+        egl::HEIGHT as EGLint,              descriptor.size.height,
+        EGL_LINUX_DRM_FOURCC_EXT as EGLint, descriptor.fourcc as EGLint,
+    ];
+    for (plane, plane_attributes) in descriptor.planes.iter().zip(PLANE_ATTRIBUTES.iter()) {
+        attributes.extend_from_slice(&[
+            plane_attributes[0] as EGLint, plane.fd,
+            plane_attributes[1] as EGLint, plane.offset as EGLint,
+            plane_attributes[2] as EGLint, plane.stride as EGLint,
+        ]);
+        if explicit_modifier {
+            attributes.extend_from_slice(&[
+                plane_attributes[3] as EGLint, descriptor.modifier as u32 as EGLint,
+                plane_attributes[4] as EGLint, (descriptor.modifier >> 32) as u32 as EGLint,
+            ]);
+        }
+    }
+    attributes.extend_from_slice(&[egl::NONE as EGLint, 0]);
+
+    let egl_image = (EGL_EXTENSION_FUNCTIONS.CreateImageKHR)(egl_display,
+                                                             egl::NO_CONTEXT,
+                                                             EGL_LINUX_DMA_BUF_EXT,
+                                                             ptr::null_mut(),
+                                                             attributes.as_ptr());
+    if egl_image == EGL_NO_IMAGE_KHR {
+        let windowing_api_error = EGL_FUNCTIONS.with(|egl| egl.GetError());
+        return Err(Error::SurfaceImportFailed(windowing_api_error.to_windowing_api_error()));
+    }
+    Ok(egl_image)
+}
+
+// Returns the surface format that a DMA buffer of the given DRM fourcc code is rendered and
+// sampled as.
+//
+// Surfaces are always bound to `GL_TEXTURE_2D`, which can't sample multi-planar or subsampled
+// images, so YUV formats such as NV12 and YUYV (which need `GL_TEXTURE_EXTERNAL_OES`) are rejected
+// with an `UnsupportedSurfaceFormat` error, as is anything else without a surface equivalent.
+// That includes formats whose alpha bits are padding, such as XR24: every surface format with
+// color channels has alpha, and sampling it would return whatever the padding happens to hold.
+pub(crate) fn surface_format(fourcc: u32) -> Result<SurfaceFormat, Error> {
+    match &fourcc.to_le_bytes() {
+        b"AB24" => Ok(SurfaceFormat::RGBA8),
+        b"AR24" => Ok(SurfaceFormat::BGRA8),
+        b"AB30" => Ok(SurfaceFormat::RGB10_A2),
+        b"AB4H" => Ok(SurfaceFormat::RGBA16F),
+        b"R8  " => Ok(SurfaceFormat::R8),
+        b"GR88" => Ok(SurfaceFormat::RG8),
+        _ => Err(Error::UnsupportedSurfaceFormat),
+    }
+}
+
+// Returns the DRM fourcc code of the DMA buffer format that lays out pixels in memory the same way
+// as the given surface format, if there is one. This is the inverse of `surface_format()`.
+pub(crate) fn fourcc(format: SurfaceFormat) -> Option<u32> {
+    let code = match format {
+        SurfaceFormat::RGBA8 => b"AB24",
+        SurfaceFormat::BGRA8 => b"AR24",
+        SurfaceFormat::RGB10_A2 => b"AB30",
+        SurfaceFormat::RGBA16F => b"AB4H",
+        SurfaceFormat::R8 => b"R8  ",
+        SurfaceFormat::RG8 => b"GR88",
+        SurfaceFormat::RGBA32F | SurfaceFormat::SRGB8_A8 => return None,
+    };
+    Some(u32::from_le_bytes(*code))
+}
+
+fn export_error() -> Error {
+    let windowing_api_error = EGL_FUNCTIONS.with(|egl| unsafe { egl.GetError() });
+    Error::SurfaceExportFailed(windowing_api_error.to_windowing_api_error())
+}
@@ -0,0 +1,177 @@
+// surfman/surfman/src/platform/generic/egl/fence.rs
+//
+//! GPU fences built on `EGL_KHR_fence_sync`, common to the EGL backends.
+
+use crate::Gl;
+use crate::egl::types::{EGLDisplay, EGLenum, EGLint};
+use crate::egl;
+use crate::{Error, WindowingApiError};
+use super::device::{self, EGL_FUNCTIONS};
+use super::error::ToWindowingApiError;
+use super::ffi::{EGL_CONDITION_SATISFIED_KHR, EGL_EXTENSION_FUNCTIONS, EGL_FOREVER_KHR};
+use super::ffi::{EGL_NO_NATIVE_FENCE_FD_ANDROID, EGL_NO_SYNC_KHR, EGL_SYNC_FENCE_KHR};
+use super::ffi::{EGL_SYNC_FLUSH_COMMANDS_BIT_KHR, EGL_SYNC_NATIVE_FENCE_ANDROID};
+use super::ffi::{EGL_SYNC_NATIVE_FENCE_FD_ANDROID, EGL_TIMEOUT_EXPIRED_KHR, EGLSyncKHR};
+use super::ffi::EGLTimeKHR;
+
+use std::fmt::{self, Debug, Formatter};
+use std::os::unix::io::RawFd;
+use std::thread;
+use std::time::Duration;
+
+/// A point in a context's command stream that the CPU or another context can wait on.
+///
+/// Fences are signaled once the GPU has finished all commands issued before the fence was
+/// created. Unlike contexts, fences may be sent between threads, and can be waited on by any
+/// context created from the same connection.
+///
+/// Fences must be destroyed with `destroy_fence()`, or a panic will occur.
+pub struct Fence {
+    pub(crate) egl_sync: EGLSyncKHR,
+    // Whether this fence is backed by a sync file and can therefore be exported.
+    pub(crate) native: bool,
+}
+
+unsafe impl Send for Fence {}
+
+impl Debug for Fence {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Fence({:?})", self.egl_sync)
+    }
+}
+
+impl Drop for Fence {
+    fn drop(&mut self) {
+        if self.egl_sync != EGL_NO_SYNC_KHR && !thread::panicking() {
+            panic!("Should have destroyed the fence first with `destroy_fence()`!")
+        }
+    }
+}
+
+impl Fence {
+    // Inserts a fence into the command stream of the current context.
+    //
+    // Sync-file-backed fences are preferred when the display supports them, so that the fence
+    // can later be exported.
+    pub(crate) unsafe fn new(gl: &Gl, egl_display: EGLDisplay) -> Result<Fence, Error> {
+        let native = device::display_supports_extension(egl_display,
+                                                        "EGL_ANDROID_native_fence_sync");
+        let sync_type = if native { EGL_SYNC_NATIVE_FENCE_ANDROID } else { EGL_SYNC_FENCE_KHR };
+        let attributes = [egl::NONE as EGLint];
+        Fence::create(gl, egl_display, sync_type, &attributes, native)
+    }
+
+    // Wraps a sync file in a fence in the command stream of the current context. On success,
+    // EGL takes ownership of the file descriptor.
+    pub(crate) unsafe fn from_fd(gl: &Gl, egl_display: EGLDisplay, fd: RawFd)
+                                 -> Result<Fence, Error> {
+        if !device::display_supports_extension(egl_display, "EGL_ANDROID_native_fence_sync") {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+
+        let attributes = [
+            EGL_SYNC_NATIVE_FENCE_FD_ANDROID as EGLint, fd,
+            egl::NONE as EGLint,                        0,
+        ];
+        Fence::create(gl, egl_display, EGL_SYNC_NATIVE_FENCE_ANDROID, &attributes, true)
+    }
+
+    unsafe fn create(gl: &Gl,
+                     egl_display: EGLDisplay,
+                     sync_type: EGLenum,
+                     attributes: &[EGLint],
+                     native: bool)
+                     -> Result<Fence, Error> {
+        let create_sync = match EGL_EXTENSION_FUNCTIONS.CreateSyncKHR {
+            Some(create_sync) if device::display_supports_extension(egl_display,
+                                                                    "EGL_KHR_fence_sync") => {
+                create_sync
+            }
+            _ => return Err(Error::RequiredExtensionUnavailable),
+        };
+
+        let egl_sync = create_sync(egl_display, sync_type, attributes.as_ptr());
+        if egl_sync == EGL_NO_SYNC_KHR {
+            let windowing_api_error = EGL_FUNCTIONS.with(|egl| egl.GetError());
+            return Err(Error::FenceCreationFailed(windowing_api_error.to_windowing_api_error()));
+        }
+
+        // Submit the fence, so that other threads can wait on it. This is also what gives native
+        // fences their sync file.
+        gl.Flush();
+
+        Ok(Fence { egl_sync, native })
+    }
+
+    // Blocks the calling thread until the fence is signaled or the timeout elapses. Returns true
+    // if the fence was signaled.
+    pub(crate) unsafe fn wait(&self, egl_display: EGLDisplay, timeout: Option<Duration>)
+                              -> Result<bool, Error> {
+        let client_wait_sync = match EGL_EXTENSION_FUNCTIONS.ClientWaitSyncKHR {
+            Some(client_wait_sync) => client_wait_sync,
+            None => return Err(Error::RequiredExtensionUnavailable),
+        };
+
+        let timeout = match timeout {
+            None => EGL_FOREVER_KHR,
+            Some(timeout) => timeout.as_nanos().min(EGL_FOREVER_KHR as u128 - 1) as EGLTimeKHR,
+        };
+
+        match client_wait_sync(egl_display,
+                               self.egl_sync,
+                               EGL_SYNC_FLUSH_COMMANDS_BIT_KHR,
+                               timeout) as EGLenum {
+            EGL_CONDITION_SATISFIED_KHR => Ok(true),
+            EGL_TIMEOUT_EXPIRED_KHR => Ok(false),
+            _ => {
+                let windowing_api_error = EGL_FUNCTIONS.with(|egl| egl.GetError());
+                Err(Error::FenceWaitFailed(windowing_api_error.to_windowing_api_error()))
+            }
+        }
+    }
+
+    // Makes the current context wait on the GPU for the fence to be signaled before executing
+    // any further commands. Falls back to a CPU wait if `EGL_KHR_wait_sync` is unavailable.
+    pub(crate) unsafe fn server_wait(&self, egl_display: EGLDisplay) -> Result<(), Error> {
+        let wait_sync = match EGL_EXTENSION_FUNCTIONS.WaitSyncKHR {
+            Some(wait_sync) if device::display_supports_extension(egl_display,
+                                                                  "EGL_KHR_wait_sync") => {
+                wait_sync
+            }
+            _ => return self.wait(egl_display, None).map(|_| ()),
+        };
+
+        if wait_sync(egl_display, self.egl_sync, 0) == egl::FALSE as EGLint {
+            let windowing_api_error = EGL_FUNCTIONS.with(|egl| egl.GetError());
+            return Err(Error::FenceWaitFailed(windowing_api_error.to_windowing_api_error()));
+        }
+        Ok(())
+    }
+
+    // Returns a new sync file descriptor that is signaled along with this fence. The caller
+    // owns the returned file descriptor.
+    pub(crate) unsafe fn export_fd(&self, egl_display: EGLDisplay) -> Result<RawFd, Error> {
+        let dup_native_fence_fd = match EGL_EXTENSION_FUNCTIONS.DupNativeFenceFDANDROID {
+            Some(dup_native_fence_fd) if self.native => dup_native_fence_fd,
+            _ => return Err(Error::RequiredExtensionUnavailable),
+        };
+
+        let fd = dup_native_fence_fd(egl_display, self.egl_sync);
+        if fd == EGL_NO_NATIVE_FENCE_FD_ANDROID {
+            return Err(Error::FenceExportFailed(WindowingApiError::Failed));
+        }
+        Ok(fd)
+    }
+
+    pub(crate) unsafe fn destroy(&mut self, egl_display: EGLDisplay) {
+        if self.egl_sync == EGL_NO_SYNC_KHR {
+            return;
+        }
+
+        // We can only have a fence if creating one succeeded, which means this function exists.
+        let destroy_sync = EGL_EXTENSION_FUNCTIONS.DestroySyncKHR.unwrap();
+        let result = destroy_sync(egl_display, self.egl_sync);
+        assert_ne!(result, egl::FALSE);
+        self.egl_sync = EGL_NO_SYNC_KHR;
+    }
+}
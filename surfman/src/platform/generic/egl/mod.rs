@@ -6,6 +6,12 @@
 
 pub(crate) mod context;
 pub(crate) mod device;
+#[cfg(linux)]
+pub(crate) mod dmabuf;
 pub(crate) mod error;
+#[cfg(linux)]
+pub(crate) mod fence;
 pub(crate) mod ffi;
+#[cfg(linux)]
+pub(crate) mod shared_surface;
 pub(crate) mod surface;
@@ -0,0 +1,371 @@
+// surfman/surfman/src/platform/generic/egl/shared_surface.rs
+//
+//! Handles that allow surfaces to be shared with other processes over Unix domain sockets.
+
+use crate::{Error, WindowingApiError};
+use super::dmabuf::{DmabufDescriptor, DmabufPlane};
+
+use euclid::default::Size2D;
+use std::ffi::CStr;
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::raw::c_void;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::ptr;
+
+const MAX_FDS: usize = 4;
+
+// The kind, width, height, fourcc, modifier, plane count, and offset and stride of each plane.
+const HEADER_SIZE: usize = 4 * 4 + 8 + 4 + MAX_FDS * 8;
+
+const KIND_DMABUF: u32 = 0;
+const KIND_MEMORY: u32 = 1;
+
+// The seals that make a memory file immutable. Received memory files must carry all of them.
+const MEMORY_SEALS: libc::c_int = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE |
+    libc::F_SEAL_SEAL;
+
+/// A handle to the contents of a surface that can be sent to another process over a Unix domain
+/// socket and turned back into a surface there with `import_shared_surface()`.
+///
+/// If the driver supports exporting dmabufs, the handle refers to the surface's memory directly,
+/// so the receiving process sees the surface without any copies. Otherwise, the handle holds a
+/// snapshot of the surface's pixels in a sealed memory file, taken when the handle was created.
+///
+/// The handle owns the file descriptors it refers to and closes them when dropped.
+pub struct SharedSurfaceHandle {
+    size: Size2D<i32>,
+    storage: SharedSurfaceStorage,
+}
+
+enum SharedSurfaceStorage {
+    Dmabuf(DmabufDescriptor),
+    // A memory file containing RGBA pixels, ordered bottom to top, with no padding between rows.
+    Memory(RawFd),
+}
+
+impl Debug for SharedSurfaceHandle {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.storage {
+            SharedSurfaceStorage::Dmabuf(ref descriptor) => {
+                write!(f, "SharedSurfaceHandle(Dmabuf, {:?})", descriptor)
+            }
+            SharedSurfaceStorage::Memory(fd) => {
+                write!(f, "SharedSurfaceHandle(Memory({}), {:?})", fd, self.size)
+            }
+        }
+    }
+}
+
+impl Drop for SharedSurfaceHandle {
+    fn drop(&mut self) {
+        for fd in self.fds() {
+            unsafe {
+                libc::close(fd);
+            }
+        }
+    }
+}
+
+impl SharedSurfaceHandle {
+    // Takes ownership of the file descriptors in the descriptor.
+    pub(crate) fn from_dmabuf(descriptor: DmabufDescriptor) -> SharedSurfaceHandle {
+        SharedSurfaceHandle {
+            size: descriptor.size,
+            storage: SharedSurfaceStorage::Dmabuf(descriptor),
+        }
+    }
+
+    // Copies pixels, in OpenGL's bottom-to-top RGBA layout, into a new sealed memory file.
+    pub(crate) fn from_pixels(size: &Size2D<i32>, pixels: &[u8])
+                              -> Result<SharedSurfaceHandle, Error> {
+        debug_assert_eq!(pixels.len(), size.width as usize * size.height as usize * 4);
+        unsafe {
+            let name = CStr::from_bytes_with_nul_unchecked(b"surfman-shared-surface\0");
+            let flags = libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING;
+            let fd = libc::memfd_create(name.as_ptr(), flags);
+            if fd < 0 {
+                return Err(Error::SurfaceExportFailed(WindowingApiError::BadAlloc));
+            }
+            let handle = SharedSurfaceHandle {
+                size: *size,
+                storage: SharedSurfaceStorage::Memory(fd),
+            };
+
+            let mut offset = 0;
+            while offset < pixels.len() {
+                let result = libc::write(fd,
+                                         pixels[offset..].as_ptr() as *const c_void,
+                                         pixels.len() - offset);
+                if result <= 0 {
+                    return Err(Error::SurfaceExportFailed(WindowingApiError::Failed));
+                }
+                offset += result as usize;
+            }
+
+            // Seal the file, so that the receiving process can trust its contents not to change
+            // underneath it.
+            if libc::fcntl(fd, libc::F_ADD_SEALS, MEMORY_SEALS) < 0 {
+                return Err(Error::SurfaceExportFailed(WindowingApiError::Failed));
+            }
+
+            Ok(handle)
+        }
+    }
+
+    /// Returns the size of the shared surface, in device pixels.
+    #[inline]
+    pub fn size(&self) -> Size2D<i32> {
+        self.size
+    }
+
+    /// Returns true if this handle refers to the surface's memory directly rather than to a copy
+    /// of its contents.
+    #[inline]
+    pub fn is_zero_copy(&self) -> bool {
+        match self.storage {
+            SharedSurfaceStorage::Dmabuf(_) => true,
+            SharedSurfaceStorage::Memory(_) => false,
+        }
+    }
+
+    /// Sends this handle over a Unix domain socket, passing its file descriptors with
+    /// `SCM_RIGHTS`.
+    ///
+    /// This handle retains ownership of its file descriptors; the receiving process gets
+    /// duplicates of them.
+    pub fn send(&self, socket: &UnixStream) -> io::Result<()> {
+        let header = self.header();
+        let fds = self.fds();
+
+        unsafe {
+            let fds_size = mem::size_of::<RawFd>() * fds.len();
+            let mut control = vec![0u8; libc::CMSG_SPACE(fds_size as u32) as usize];
+            let mut iov = libc::iovec {
+                iov_base: header.as_ptr() as *mut c_void,
+                iov_len: header.len(),
+            };
+            let mut message: libc::msghdr = mem::zeroed();
+            message.msg_iov = &mut iov;
+            message.msg_iovlen = 1;
+            message.msg_control = control.as_mut_ptr() as *mut c_void;
+            message.msg_controllen = control.len() as _;
+
+            let control_message = libc::CMSG_FIRSTHDR(&message);
+            (*control_message).cmsg_level = libc::SOL_SOCKET;
+            (*control_message).cmsg_type = libc::SCM_RIGHTS;
+            (*control_message).cmsg_len = libc::CMSG_LEN(fds_size as u32) as _;
+            ptr::copy_nonoverlapping(fds.as_ptr(),
+                                     libc::CMSG_DATA(control_message) as *mut RawFd,
+                                     fds.len());
+
+            let sent = libc::sendmsg(socket.as_raw_fd(), &message, libc::MSG_NOSIGNAL);
+            if sent < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // The file descriptors went along with the first byte. Send whatever didn't fit.
+            (&*socket).write_all(&header[(sent as usize)..])
+        }
+    }
+
+    /// Receives a handle sent with `send()` from a Unix domain socket.
+    pub fn receive(socket: &UnixStream) -> io::Result<SharedSurfaceHandle> {
+        let mut header = [0; HEADER_SIZE];
+
+        let mut fds = vec![];
+        let received = unsafe {
+            let fds_size = mem::size_of::<RawFd>() * MAX_FDS;
+            let mut control = vec![0u8; libc::CMSG_SPACE(fds_size as u32) as usize];
+            let mut iov = libc::iovec {
+                iov_base: header.as_mut_ptr() as *mut c_void,
+                iov_len: header.len(),
+            };
+            let mut message: libc::msghdr = mem::zeroed();
+            message.msg_iov = &mut iov;
+            message.msg_iovlen = 1;
+            message.msg_control = control.as_mut_ptr() as *mut c_void;
+            message.msg_controllen = control.len() as _;
+
+            let received = libc::recvmsg(socket.as_raw_fd(),
+                                         &mut message,
+                                         libc::MSG_CMSG_CLOEXEC);
+            if received < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut control_message = libc::CMSG_FIRSTHDR(&message);
+            while !control_message.is_null() {
+                if (*control_message).cmsg_level == libc::SOL_SOCKET &&
+                        (*control_message).cmsg_type == libc::SCM_RIGHTS {
+                    let data = libc::CMSG_DATA(control_message) as *const RawFd;
+                    let data_size = (*control_message).cmsg_len as usize -
+                        (data as usize - control_message as usize);
+                    for fd_index in 0..(data_size / mem::size_of::<RawFd>()) {
+                        fds.push(*data.add(fd_index));
+                    }
+                }
+                control_message = libc::CMSG_NXTHDR(&message, control_message);
+            }
+
+            received as usize
+        };
+
+        let result = if received == 0 {
+            Err(io::Error::from(io::ErrorKind::UnexpectedEof))
+        } else {
+            (&*socket).read_exact(&mut header[received..]).and_then(|_| {
+                SharedSurfaceHandle::from_header(&header, &fds)
+            })
+        };
+
+        if result.is_err() {
+            for &fd in &fds {
+                unsafe {
+                    libc::close(fd);
+                }
+            }
+        }
+        result
+    }
+
+    // Returns the dmabuf that this handle refers to, if any.
+    pub(crate) fn dmabuf(&self) -> Option<&DmabufDescriptor> {
+        match self.storage {
+            SharedSurfaceStorage::Dmabuf(ref descriptor) => Some(descriptor),
+            SharedSurfaceStorage::Memory(_) => None,
+        }
+    }
+
+    // Reads the pixels out of a memory-backed handle, in OpenGL's bottom-to-top RGBA layout.
+    pub(crate) fn read_pixels(&self) -> Result<Vec<u8>, Error> {
+        let fd = match self.storage {
+            SharedSurfaceStorage::Memory(fd) => fd,
+            SharedSurfaceStorage::Dmabuf(_) => unreachable!(),
+        };
+
+        let byte_length = match memory_byte_length(&self.size) {
+            Some(byte_length) => byte_length,
+            None => return Err(Error::SurfaceImportFailed(WindowingApiError::BadParameter)),
+        };
+        let mut pixels = vec![0; byte_length];
+        let mut offset = 0;
+        while offset < byte_length {
+            let result = unsafe {
+                libc::pread(fd,
+                            pixels[offset..].as_mut_ptr() as *mut c_void,
+                            byte_length - offset,
+                            offset as libc::off_t)
+            };
+            if result <= 0 {
+                return Err(Error::SurfaceImportFailed(WindowingApiError::BadAccess));
+            }
+            offset += result as usize;
+        }
+        Ok(pixels)
+    }
+
+    fn fds(&self) -> Vec<RawFd> {
+        match self.storage {
+            SharedSurfaceStorage::Dmabuf(ref descriptor) => {
+                descriptor.planes.iter().map(|plane| plane.fd).collect()
+            }
+            SharedSurfaceStorage::Memory(fd) => vec![fd],
+        }
+    }
+
+    fn header(&self) -> [u8; HEADER_SIZE] {
+        let mut header = [0; HEADER_SIZE];
+        let (kind, fourcc, modifier, planes) = match self.storage {
+            SharedSurfaceStorage::Dmabuf(ref descriptor) => {
+                (KIND_DMABUF, descriptor.fourcc, descriptor.modifier, &descriptor.planes[..])
+            }
+            SharedSurfaceStorage::Memory(_) => (KIND_MEMORY, 0, 0, &[][..]),
+        };
+
+        header[0..4].copy_from_slice(&kind.to_ne_bytes());
+        header[4..8].copy_from_slice(&self.size.width.to_ne_bytes());
+        header[8..12].copy_from_slice(&self.size.height.to_ne_bytes());
+        header[12..16].copy_from_slice(&fourcc.to_ne_bytes());
+        header[16..24].copy_from_slice(&modifier.to_ne_bytes());
+        header[24..28].copy_from_slice(&(planes.len() as u32).to_ne_bytes());
+        for (plane_index, plane) in planes.iter().enumerate() {
+            let start = 28 + plane_index * 8;
+            header[start..(start + 4)].copy_from_slice(&plane.offset.to_ne_bytes());
+            header[(start + 4)..(start + 8)].copy_from_slice(&plane.stride.to_ne_bytes());
+        }
+        header
+    }
+
+    // Takes ownership of the file descriptors on success.
+    fn from_header(header: &[u8; HEADER_SIZE], fds: &[RawFd])
+                   -> io::Result<SharedSurfaceHandle> {
+        let read_u32 = |start: usize| {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&header[start..(start + 4)]);
+            u32::from_ne_bytes(bytes)
+        };
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid shared surface");
+
+        let size = Size2D::new(read_u32(4) as i32, read_u32(8) as i32);
+        if size.width <= 0 || size.height <= 0 {
+            return Err(invalid());
+        }
+
+        match read_u32(0) {
+            KIND_DMABUF => {
+                let plane_count = read_u32(24) as usize;
+                if plane_count == 0 || plane_count > MAX_FDS || plane_count != fds.len() {
+                    return Err(invalid());
+                }
+                let mut modifier = [0; 8];
+                modifier.copy_from_slice(&header[16..24]);
+                let planes = fds.iter().enumerate().map(|(plane_index, &fd)| {
+                    DmabufPlane {
+                        fd,
+                        offset: read_u32(28 + plane_index * 8),
+                        stride: read_u32(28 + plane_index * 8 + 4),
+                    }
+                }).collect();
+                Ok(SharedSurfaceHandle::from_dmabuf(DmabufDescriptor {
+                    size,
+                    fourcc: read_u32(12),
+                    modifier: u64::from_ne_bytes(modifier),
+                    planes,
+                }))
+            }
+            KIND_MEMORY if fds.len() == 1 => {
+                // Only trust the file if the sender can no longer change it, and make sure it
+                // holds exactly the pixels that the header claims.
+                let fd = fds[0];
+                let byte_length = memory_byte_length(&size).ok_or_else(invalid)?;
+                unsafe {
+                    let seals = libc::fcntl(fd, libc::F_GET_SEALS);
+                    if seals < 0 || seals & MEMORY_SEALS != MEMORY_SEALS {
+                        return Err(invalid());
+                    }
+                    let mut stat: libc::stat = mem::zeroed();
+                    if libc::fstat(fd, &mut stat) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    if stat.st_size < 0 || stat.st_size as u64 != byte_length as u64 {
+                        return Err(invalid());
+                    }
+                }
+                Ok(SharedSurfaceHandle { size, storage: SharedSurfaceStorage::Memory(fd) })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+// Returns the number of bytes of RGBA pixels in a memory-backed handle of the given size, or
+// `None` if the size is empty or the length would overflow.
+fn memory_byte_length(size: &Size2D<i32>) -> Option<usize> {
+    if size.width <= 0 || size.height <= 0 {
+        return None;
+    }
+    (size.width as usize).checked_mul(size.height as usize)?.checked_mul(4)
+}
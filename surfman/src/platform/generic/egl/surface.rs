@@ -2,25 +2,35 @@
 //
 //! Functionality common to backends using EGL surfaces.
 
-use crate::egl::types::{EGLAttrib, EGLConfig, EGLContext, EGLDisplay, EGLSurface, EGLint};
+use crate::egl::types::{EGLAttrib, EGLConfig, EGLContext, EGLDisplay, EGLSurface, EGLenum};
+use crate::egl::types::EGLint;
 use crate::egl;
 use crate::Gl;
-use crate::gl::types::{GLint, GLuint};
+use crate::gl::types::{GLenum, GLint, GLuint};
 use crate::gl;
 use crate::gl_utils;
 use crate::platform::generic::egl::error::ToWindowingApiError;
 use crate::platform::generic::egl::ffi::EGLClientBuffer;
 use crate::platform::generic::egl::ffi::EGLImageKHR;
+use crate::platform::generic::egl::ffi::EGL_BUFFER_AGE_EXT;
 use crate::platform::generic::egl::ffi::EGL_EXTENSION_FUNCTIONS;
+use crate::platform::generic::egl::ffi::EGL_GL_COLORSPACE_BT2020_PQ_EXT;
+use crate::platform::generic::egl::ffi::EGL_GL_COLORSPACE_DISPLAY_P3_EXT;
+use crate::platform::generic::egl::ffi::EGL_GL_COLORSPACE_SCRGB_EXT;
 use crate::platform::generic::egl::ffi::EGL_GL_TEXTURE_2D_KHR;
 use crate::platform::generic::egl::ffi::EGL_IMAGE_PRESERVED_KHR;
 use crate::platform::generic::egl::ffi::EGL_NO_IMAGE_KHR;
-use crate::renderbuffers::Renderbuffers;
-use crate::{ContextAttributes, ContextID, Error, SurfaceID, SurfaceInfo};
-use super::context::CurrentContextGuard;
-use super::device::EGL_FUNCTIONS;
+use crate::renderbuffers::{self, MultisampleFramebuffer, Renderbuffers};
+use crate::{ContextAttributeFlags, ContextAttributes, ContextID, Error, PixelReadback};
+use crate::{ReadbackFormat, SurfaceAccess, SurfaceDescriptor, SurfaceFormat, SurfaceID};
+use crate::{SurfaceColorspace, SurfaceInfo};
+use super::context::{self, CurrentContextGuard};
+use super::device::{self, EGL_FUNCTIONS};
+#[cfg(linux)]
+use super::dmabuf::{self, DmabufDescriptor};
 
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
+use std::cell::Cell;
 use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
 use std::mem;
@@ -36,7 +46,10 @@ pub(crate) struct ExternalEGLSurfaces {
 
 pub struct EGLBackedSurface {
     pub(crate) context_id: ContextID,
+    pub(crate) egl_context: EGLContext,
     pub(crate) size: Size2D<i32>,
+    pub(crate) access: SurfaceAccess,
+    pub(crate) format: SurfaceFormat,
     pub(crate) objects: EGLSurfaceObjects,
     pub(crate) destroyed: bool,
 }
@@ -56,16 +69,36 @@ pub(crate) enum EGLSurfaceObjects {
         framebuffer_object: GLuint,
         texture_object: GLuint,
         renderbuffers: Renderbuffers,
+        multisample_framebuffer: Option<MultisampleFramebuffer>,
+        depth_image: Option<EGLDepthImage>,
+        // The attributes that the renderbuffers were created with, for reallocating them.
+        framebuffer_attributes: ContextAttributes,
     },
     Window {
         native_window: *const c_void,
         egl_surface: EGLSurface,
+        swap_interval: Cell<i32>,
     },
 }
 
+// A depth texture, or a combined depth/stencil texture, that a generic surface renders to in
+// place of private renderbuffers, along with the EGL image that lets other contexts sample it.
+pub(crate) struct EGLDepthImage {
+    egl_image: EGLImageKHR,
+    texture_object: GLuint,
+    attachment: GLenum,
+}
+
+pub(crate) struct EGLSurfaceDataGuard<'a> {
+    surface: &'a mut EGLBackedSurface,
+    egl_display: EGLDisplay,
+    pixels: Vec<u8>,
+}
+
 pub(crate) struct EGLSurfaceTexture {
     pub(crate) surface: EGLBackedSurface,
     pub(crate) texture_object: GLuint,
+    pub(crate) depth_texture_object: Option<GLuint>,
     pub(crate) phantom: PhantomData<*const ()>,
 }
 
@@ -80,71 +113,407 @@ impl EGLBackedSurface {
                               egl_display: EGLDisplay,
                               egl_context: EGLContext,
                               context_id: ContextID,
+                              access: SurfaceAccess,
                               context_attributes: &ContextAttributes,
-                              size: &Size2D<i32>)
-                              -> EGLBackedSurface {
-        let egl_image_attribs = [
-            EGL_IMAGE_PRESERVED_KHR as EGLint,  egl::FALSE as EGLint,
-            egl::NONE as EGLint,                0,
-        ];
-
+                              size: &Size2D<i32>,
+                              descriptor: &SurfaceDescriptor)
+                              -> Result<EGLBackedSurface, Error> {
         unsafe {
-            // Create our texture.
-            let mut texture_object = 0;
-            gl.GenTextures(1, &mut texture_object);
-            // Save the current texture binding
-            let mut old_texture_object = 0;
-            gl.GetIntegerv(gl::TEXTURE_BINDING_2D, &mut old_texture_object);
-            gl.BindTexture(gl::TEXTURE_2D, texture_object);
-            // Unbind PIXEL_UNPACK_BUFFER, because if it is bound,
-            // it can cause errors in glTexImage2D.
-            // TODO: should this be inside a check for GL 2.0?
-            let mut unpack_buffer = 0;
-            gl.GetIntegerv(gl::PIXEL_UNPACK_BUFFER_BINDING, &mut unpack_buffer);
-            if unpack_buffer != 0 { gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0); }
-            gl.TexImage2D(gl::TEXTURE_2D,
-                          0,
-                          gl::RGBA as GLint,
-                          size.width,
-                          size.height,
-                          0,
-                          gl::RGBA,
-                          gl::UNSIGNED_BYTE,
-                          ptr::null());
-            // Restore the old bindings
-            gl.BindTexture(gl::TEXTURE_2D, old_texture_object as _);
-            if unpack_buffer != 0 { gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, unpack_buffer as _); }
-
-            // Create our image.
-            let egl_client_buffer = texture_object as usize as EGLClientBuffer;
-            let egl_image =
-                (EGL_EXTENSION_FUNCTIONS.CreateImageKHR)(egl_display,
+            // Create our texture and image.
+            let (texture_object, egl_image) =
+                create_texture_image(gl,
+                                     egl_display,
+                                     egl_context,
+                                     size,
+                                     descriptor.format.gl_texture_formats())?;
+
+            EGLBackedSurface::new_generic_from_texture_image(gl,
+                                                             egl_display,
+                                                             egl_image,
+                                                             texture_object,
+                                                             egl_context,
+                                                             context_id,
+                                                             access,
+                                                             context_attributes,
+                                                             size,
+                                                             descriptor)
+        }
+    }
+
+    // Like `new_generic()`, but renders to an existing EGL image of the given size and format,
+    // such as one backed by a buffer that another API allocated. The surface takes ownership of
+    // the image, and destroys it on failure. The context must be current.
+    pub(crate) unsafe fn new_generic_from_egl_image(gl: &Gl,
+                                                    egl_display: EGLDisplay,
+                                                    egl_image: EGLImageKHR,
+                                                    egl_context: EGLContext,
+                                                    context_id: ContextID,
+                                                    access: SurfaceAccess,
+                                                    context_attributes: &ContextAttributes,
+                                                    size: &Size2D<i32>,
+                                                    descriptor: &SurfaceDescriptor)
+                                                    -> Result<EGLBackedSurface, Error> {
+        let texture_object = bind_egl_image_to_gl_texture(gl, egl_image);
+        EGLBackedSurface::new_generic_from_texture_image(gl,
+                                                         egl_display,
+                                                         egl_image,
+                                                         texture_object,
                                                          egl_context,
-                                                         EGL_GL_TEXTURE_2D_KHR,
-                                                         egl_client_buffer,
-                                                         egl_image_attribs.as_ptr());
-
-            // Create the framebuffer, and bind the texture to it.
-            let framebuffer_object = gl_utils::create_and_bind_framebuffer(gl,
-                                                                           gl::TEXTURE_2D,
-                                                                           texture_object);
-
-            // Bind renderbuffers as appropriate.
-            let renderbuffers = Renderbuffers::new(gl, size, context_attributes);
-            renderbuffers.bind_to_current_framebuffer(gl);
-
-            debug_assert_eq!(gl.CheckFramebufferStatus(gl::FRAMEBUFFER), gl::FRAMEBUFFER_COMPLETE);
-
-            EGLBackedSurface {
-                context_id,
-                size: *size,
-                objects: EGLSurfaceObjects::TextureImage {
-                    egl_image,
-                    framebuffer_object,
-                    texture_object,
-                    renderbuffers,
-                },
-                destroyed: false,
+                                                         context_id,
+                                                         access,
+                                                         context_attributes,
+                                                         size,
+                                                         descriptor)
+    }
+
+    // Finishes creating a generic surface around its texture and image, adding the depth texture,
+    // renderbuffers, and framebuffers that the descriptor asks for. On failure, the texture and
+    // image are destroyed. The context must be current.
+    unsafe fn new_generic_from_texture_image(gl: &Gl,
+                                             egl_display: EGLDisplay,
+                                             egl_image: EGLImageKHR,
+                                             texture_object: GLuint,
+                                             egl_context: EGLContext,
+                                             context_id: ContextID,
+                                             access: SurfaceAccess,
+                                             context_attributes: &ContextAttributes,
+                                             size: &Size2D<i32>,
+                                             descriptor: &SurfaceDescriptor)
+                                             -> Result<EGLBackedSurface, Error> {
+        let mut framebuffer_attributes = descriptor.framebuffer_attributes(context_attributes);
+        let sampleable_depth = descriptor.sampleable_depth &&
+            framebuffer_attributes.flags.contains(ContextAttributeFlags::DEPTH);
+        // FIXME: Resolve multisampled depth into the depth texture.
+        if sampleable_depth && framebuffer_attributes.samples > 1 {
+            (EGL_EXTENSION_FUNCTIONS.DestroyImageKHR)(egl_display, egl_image);
+            gl.DeleteTextures(1, &texture_object);
+            return Err(Error::Unimplemented);
+        }
+
+        // Create the depth texture and image, if requested. These take the place of the depth
+        // and stencil renderbuffers.
+        let mut depth_image = None;
+        if sampleable_depth {
+            let stencil = framebuffer_attributes.flags.contains(ContextAttributeFlags::STENCIL);
+            match EGLDepthImage::new(gl, egl_display, egl_context, size, stencil) {
+                Ok(image) => depth_image = Some(image),
+                Err(err) => {
+                    (EGL_EXTENSION_FUNCTIONS.DestroyImageKHR)(egl_display, egl_image);
+                    gl.DeleteTextures(1, &texture_object);
+                    return Err(err);
+                }
+            }
+            framebuffer_attributes.flags.remove(ContextAttributeFlags::DEPTH |
+                                                ContextAttributeFlags::STENCIL);
+        }
+
+        let mut surface = match EGLBackedSurface::from_texture_image(gl,
+                                                                     egl_image,
+                                                                     texture_object,
+                                                                     egl_context,
+                                                                     context_id,
+                                                                     access,
+                                                                     &framebuffer_attributes,
+                                                                     size,
+                                                                     descriptor.format) {
+            Ok(surface) => surface,
+            Err(err) => {
+                if let Some(ref mut depth_image) = depth_image {
+                    depth_image.destroy(gl, egl_display);
+                }
+                (EGL_EXTENSION_FUNCTIONS.DestroyImageKHR)(egl_display, egl_image);
+                gl.DeleteTextures(1, &texture_object);
+                return Err(err);
+            }
+        };
+
+        if let Some(depth_image) = depth_image {
+            surface.attach_depth_image(gl, depth_image);
+        }
+        surface.set_default_label(gl, context_attributes);
+
+        if let Some(ref clear_color) = descriptor.clear_color {
+            surface.clear(gl, clear_color);
+        }
+        Ok(surface)
+    }
+
+    // Wraps an existing EGL image, such as one imported from another API, in a surface. The
+    // surface takes ownership of the image, and destroys it if the image can't be rendered to.
+    // The context must be current.
+    pub(crate) unsafe fn new_from_egl_image(gl: &Gl,
+                                            egl_display: EGLDisplay,
+                                            egl_image: EGLImageKHR,
+                                            egl_context: EGLContext,
+                                            context_id: ContextID,
+                                            access: SurfaceAccess,
+                                            context_attributes: &ContextAttributes,
+                                            size: &Size2D<i32>,
+                                            format: SurfaceFormat)
+                                            -> Result<EGLBackedSurface, Error> {
+        let texture_object = bind_egl_image_to_gl_texture(gl, egl_image);
+        let surface = EGLBackedSurface::from_texture_image(gl,
+                                                           egl_image,
+                                                           texture_object,
+                                                           egl_context,
+                                                           context_id,
+                                                           access,
+                                                           context_attributes,
+                                                           size,
+                                                           format).map_err(|err| {
+            (EGL_EXTENSION_FUNCTIONS.DestroyImageKHR)(egl_display, egl_image);
+            gl.DeleteTextures(1, &texture_object);
+            err
+        })?;
+        surface.set_default_label(gl, context_attributes);
+        Ok(surface)
+    }
+
+    // Creates the framebuffer objects that render to an EGL image and its texture. When
+    // multisampling, rendering goes to a multisampled framebuffer instead, which `resolve()`
+    // copies into the texture.
+    //
+    // If the driver can't render to the texture, an `UnsupportedSurfaceFormat` error is returned,
+    // and the caller remains responsible for the image and the texture.
+    unsafe fn from_texture_image(gl: &Gl,
+                                 egl_image: EGLImageKHR,
+                                 texture_object: GLuint,
+                                 egl_context: EGLContext,
+                                 context_id: ContextID,
+                                 access: SurfaceAccess,
+                                 context_attributes: &ContextAttributes,
+                                 size: &Size2D<i32>,
+                                 format: SurfaceFormat)
+                                 -> Result<EGLBackedSurface, Error> {
+        // Create the framebuffer, and bind the texture to it.
+        let framebuffer_object = gl_utils::create_and_bind_framebuffer(gl,
+                                                                       gl::TEXTURE_2D,
+                                                                       texture_object);
+        if gl.CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+            gl_utils::destroy_framebuffer(gl, framebuffer_object);
+            return Err(Error::UnsupportedSurfaceFormat);
+        }
+
+        // Create the multisampled framebuffer, if necessary. This leaves it bound, so that the
+        // renderbuffers below are attached to whichever framebuffer is rendered to.
+        let multisample_framebuffer = MultisampleFramebuffer::new(gl,
+                                                                  size,
+                                                                  context_attributes,
+                                                                  format);
+
+        // Bind renderbuffers as appropriate.
+        let renderbuffers = Renderbuffers::new(gl, size, context_attributes);
+        renderbuffers.bind_to_current_framebuffer(gl);
+
+        debug_assert_eq!(gl.CheckFramebufferStatus(gl::FRAMEBUFFER), gl::FRAMEBUFFER_COMPLETE);
+
+        Ok(EGLBackedSurface {
+            context_id,
+            egl_context,
+            size: *size,
+            access,
+            format,
+            objects: EGLSurfaceObjects::TextureImage {
+                egl_image,
+                framebuffer_object,
+                texture_object,
+                renderbuffers,
+                multisample_framebuffer,
+                depth_image: None,
+                framebuffer_attributes: *context_attributes,
+            },
+            destroyed: false,
+        })
+    }
+
+    // Attaches a depth texture to the framebuffer of a generic surface that was created without
+    // depth or stencil renderbuffers. The context must be current.
+    unsafe fn attach_depth_image(&mut self, gl: &Gl, new_depth_image: EGLDepthImage) {
+        if let EGLSurfaceObjects::TextureImage {
+            framebuffer_object,
+            ref mut depth_image,
+            ..
+        } = self.objects {
+            gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+            gl.FramebufferTexture2D(gl::FRAMEBUFFER,
+                                    new_depth_image.attachment,
+                                    gl::TEXTURE_2D,
+                                    new_depth_image.texture_object,
+                                    0);
+            debug_assert_eq!(gl.CheckFramebufferStatus(gl::FRAMEBUFFER),
+                             gl::FRAMEBUFFER_COMPLETE);
+            *depth_image = Some(new_depth_image);
+        }
+    }
+
+    // Reallocates the storage of a generic surface at the given size, with the same format and
+    // framebuffer attributes. If `preserve_contents` is true, the region common to the old and
+    // new sizes is copied over; everything else is cleared to transparent black. The surface's
+    // own context is made current for the duration.
+    pub(crate) fn resize(&mut self,
+                         gl: &Gl,
+                         egl_display: EGLDisplay,
+                         size: &Size2D<i32>,
+                         preserve_contents: bool)
+                         -> Result<(), Error> {
+        if let EGLSurfaceObjects::Window { .. } = self.objects {
+            return Err(Error::WidgetAttached);
+        }
+
+        unsafe {
+            let _guard = self.make_own_context_current(egl_display)?;
+
+            let (texture_object, egl_image) =
+                create_texture_image(gl,
+                                     egl_display,
+                                     self.egl_context,
+                                     size,
+                                     self.format.gl_texture_formats())?;
+            self.replace_texture_image(gl,
+                                       egl_display,
+                                       egl_image,
+                                       texture_object,
+                                       size,
+                                       preserve_contents)
+        }
+    }
+
+    // Like `resize()`, but moves the surface to an existing EGL image of the given size, such as
+    // one backed by a buffer that another API allocated. The surface takes ownership of the image,
+    // and destroys it on failure.
+    pub(crate) unsafe fn resize_to_egl_image(&mut self,
+                                             gl: &Gl,
+                                             egl_display: EGLDisplay,
+                                             egl_image: EGLImageKHR,
+                                             size: &Size2D<i32>,
+                                             preserve_contents: bool)
+                                             -> Result<(), Error> {
+        let _guard = match self.make_own_context_current(egl_display) {
+            Ok(guard) => guard,
+            Err(err) => {
+                (EGL_EXTENSION_FUNCTIONS.DestroyImageKHR)(egl_display, egl_image);
+                return Err(err);
+            }
+        };
+
+        let texture_object = bind_egl_image_to_gl_texture(gl, egl_image);
+        self.replace_texture_image(gl,
+                                   egl_display,
+                                   egl_image,
+                                   texture_object,
+                                   size,
+                                   preserve_contents)
+    }
+
+    // Replaces the texture and image of a generic surface with new ones of the given size, and
+    // reallocates its depth texture, renderbuffers, and framebuffers to match. On failure, the new
+    // texture and image are destroyed and the surface is left as it was. The surface's own
+    // context must be current.
+    unsafe fn replace_texture_image(&mut self,
+                                    gl: &Gl,
+                                    egl_display: EGLDisplay,
+                                    egl_image: EGLImageKHR,
+                                    texture_object: GLuint,
+                                    size: &Size2D<i32>,
+                                    preserve_contents: bool)
+                                    -> Result<(), Error> {
+        let (framebuffer_attributes, depth_image_has_stencil) = match self.objects {
+            EGLSurfaceObjects::TextureImage { framebuffer_attributes, ref depth_image, .. } => {
+                let depth_image_has_stencil = depth_image.as_ref().map(|depth_image| {
+                    depth_image.attachment == gl::DEPTH_STENCIL_ATTACHMENT
+                });
+                (framebuffer_attributes, depth_image_has_stencil)
+            }
+            EGLSurfaceObjects::Window { .. } => {
+                (EGL_EXTENSION_FUNCTIONS.DestroyImageKHR)(egl_display, egl_image);
+                gl.DeleteTextures(1, &texture_object);
+                return Err(Error::WidgetAttached);
+            }
+        };
+
+        let mut depth_image = None;
+        if let Some(stencil) = depth_image_has_stencil {
+            match EGLDepthImage::new(gl, egl_display, self.egl_context, size, stencil) {
+                Ok(image) => depth_image = Some(image),
+                Err(err) => {
+                    (EGL_EXTENSION_FUNCTIONS.DestroyImageKHR)(egl_display, egl_image);
+                    gl.DeleteTextures(1, &texture_object);
+                    return Err(err);
+                }
+            }
+        }
+
+        let new_surface = EGLBackedSurface::from_texture_image(gl,
+                                                               egl_image,
+                                                               texture_object,
+                                                               self.egl_context,
+                                                               self.context_id,
+                                                               self.access,
+                                                               &framebuffer_attributes,
+                                                               size,
+                                                               self.format);
+        let mut new_surface = match new_surface {
+            Ok(surface) => surface,
+            Err(err) => {
+                if let Some(ref mut depth_image) = depth_image {
+                    depth_image.destroy(gl, egl_display);
+                }
+                (EGL_EXTENSION_FUNCTIONS.DestroyImageKHR)(egl_display, egl_image);
+                gl.DeleteTextures(1, &texture_object);
+                return Err(err);
+            }
+        };
+
+        if let Some(depth_image) = depth_image {
+            new_surface.attach_depth_image(gl, depth_image);
+        }
+        new_surface.set_default_label(gl, &framebuffer_attributes);
+
+        new_surface.clear(gl, &[0.0; 4]);
+        if preserve_contents {
+            new_surface.copy_common_region(gl, self);
+        }
+
+        let mut old_surface = mem::replace(self, new_surface);
+        old_surface.destroy(gl, egl_display, self.context_id)?;
+        Ok(())
+    }
+
+    // Copies the pixels in the region common to another generic surface and this one, anchored at
+    // the bottom left, including those of the multisampled framebuffers, if any. Both surfaces
+    // must have been created with the same framebuffer attributes. The context must be current.
+    unsafe fn copy_common_region(&self, gl: &Gl, source: &EGLBackedSurface) {
+        let size = self.size.min(source.size);
+        if let (&EGLSurfaceObjects::TextureImage {
+            framebuffer_object: source_framebuffer_object,
+            multisample_framebuffer: ref source_multisample_framebuffer,
+            ..
+        }, &EGLSurfaceObjects::TextureImage {
+            framebuffer_object: dest_framebuffer_object,
+            multisample_framebuffer: ref dest_multisample_framebuffer,
+            ..
+        }) = (&source.objects, &self.objects) {
+            renderbuffers::blit_color(gl,
+                                      source_framebuffer_object,
+                                      dest_framebuffer_object,
+                                      &size);
+            if let (Some(source_multisample_framebuffer), Some(dest_multisample_framebuffer)) =
+                    (source_multisample_framebuffer, dest_multisample_framebuffer) {
+                renderbuffers::blit_color(gl,
+                                          source_multisample_framebuffer.framebuffer_object,
+                                          dest_multisample_framebuffer.framebuffer_object,
+                                          &size);
+            }
+        }
+    }
+
+    // Updates the size of a widget surface to the size of its native window, as EGL reports it.
+    pub(crate) fn update_window_size(&mut self, egl_display: EGLDisplay) -> Result<(), Error> {
+        match self.objects {
+            EGLSurfaceObjects::TextureImage { .. } => Err(Error::NoWidgetAttached),
+            EGLSurfaceObjects::Window { egl_surface, .. } => {
+                self.size = unsafe { query_surface_size(egl_display, egl_surface) };
+                Ok(())
             }
         }
     }
@@ -152,24 +521,45 @@ impl EGLBackedSurface {
     pub(crate) fn new_window(egl_display: EGLDisplay,
                              egl_config: EGLConfig,
                              native_window: *mut c_void,
+                             egl_context: EGLContext,
                              context_id: ContextID,
-                             size: &Size2D<i32>)
-                             -> EGLBackedSurface {
+                             access: SurfaceAccess,
+                             size: &Size2D<i32>,
+                             colorspace: SurfaceColorspace)
+                             -> Result<EGLBackedSurface, Error> {
         EGL_FUNCTIONS.with(|egl| {
             unsafe {
-                let window_surface_attribs = [egl::NONE as EGLAttrib];
+                let mut window_surface_attribs = vec![];
+                if let Some(value) = colorspace_attribute(egl_display, colorspace)? {
+                    window_surface_attribs.extend_from_slice(&[
+                        egl::GL_COLORSPACE as EGLAttrib, value as EGLAttrib,
+                    ]);
+                }
+                window_surface_attribs.push(egl::NONE as EGLAttrib);
+
                 let egl_surface = egl.CreatePlatformWindowSurface(egl_display,
                                                                   egl_config,
                                                                   native_window,
                                                                   window_surface_attribs.as_ptr());
-                assert_ne!(egl_surface, egl::NO_SURFACE);
+                if egl_surface == egl::NO_SURFACE {
+                    let windowing_api_error = egl.GetError().to_windowing_api_error();
+                    return Err(Error::SurfaceCreationFailed(windowing_api_error));
+                }
 
-                EGLBackedSurface {
+                let swap_interval = Cell::new(default_swap_interval(egl_display, egl_surface));
+                Ok(EGLBackedSurface {
                     context_id,
+                    egl_context,
                     size: *size,
-                    objects: EGLSurfaceObjects::Window { native_window, egl_surface },
+                    access,
+                    format: SurfaceFormat::RGBA8,
+                    objects: EGLSurfaceObjects::Window {
+                        native_window,
+                        egl_surface,
+                        swap_interval,
+                    },
                     destroyed: false,
-                }
+                })
             }
         })
     }
@@ -177,12 +567,23 @@ impl EGLBackedSurface {
     pub(crate) fn to_surface_texture(self, gl: &Gl)
                                      -> Result<EGLSurfaceTexture, (Error, EGLBackedSurface)> {
         unsafe {
-            let egl_image = match self.objects {
-                EGLSurfaceObjects::TextureImage { egl_image, .. } => egl_image,
+            let (egl_image, depth_egl_image) = match self.objects {
+                EGLSurfaceObjects::TextureImage { egl_image, ref depth_image, .. } => {
+                    (egl_image, depth_image.as_ref().map(|depth_image| depth_image.egl_image))
+                }
                 EGLSurfaceObjects::Window { .. } => return Err((Error::WidgetAttached, self)),
             };
             let texture_object = bind_egl_image_to_gl_texture(gl, egl_image);
-            Ok(EGLSurfaceTexture { surface: self, texture_object, phantom: PhantomData })
+            // Depth textures can't be filtered linearly in OpenGL ES without a comparison mode.
+            let depth_texture_object = depth_egl_image.map(|depth_egl_image| {
+                bind_egl_image_to_gl_texture_with_filter(gl, depth_egl_image, gl::NEAREST)
+            });
+            Ok(EGLSurfaceTexture {
+                surface: self,
+                texture_object,
+                depth_texture_object,
+                phantom: PhantomData,
+            })
         }
     }
 
@@ -199,11 +600,20 @@ impl EGLBackedSurface {
                     ref mut framebuffer_object,
                     ref mut texture_object,
                     ref mut renderbuffers,
+                    ref mut multisample_framebuffer,
+                    ref mut depth_image,
+                    ..
                 } => {
                     gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
                     gl.DeleteFramebuffers(1, framebuffer_object);
                     *framebuffer_object = 0;
                     renderbuffers.destroy(gl);
+                    if let Some(ref mut multisample_framebuffer) = *multisample_framebuffer {
+                        multisample_framebuffer.destroy(gl);
+                    }
+                    if let Some(ref mut depth_image) = *depth_image {
+                        depth_image.destroy(gl, egl_display);
+                    }
 
                     let result = (EGL_EXTENSION_FUNCTIONS.DestroyImageKHR)(egl_display,
                                                                            *egl_image);
@@ -216,7 +626,7 @@ impl EGLBackedSurface {
                     self.destroyed = true;
                     Ok(None)
                 }
-                EGLSurfaceObjects::Window { ref mut egl_surface, ref mut native_window } => {
+                EGLSurfaceObjects::Window { ref mut egl_surface, ref mut native_window, .. } => {
                     EGL_FUNCTIONS.with(|egl| {
                         egl.DestroySurface(egl_display, *egl_surface);
                         *egl_surface = egl::NO_SURFACE;
@@ -229,29 +639,77 @@ impl EGLBackedSurface {
         }
     }
 
-    // TODO(pcwalton): Damage regions.
     pub(crate) fn present(&self, egl_display: EGLDisplay, egl_context: EGLContext)
                           -> Result<(), Error> {
-        unsafe {
-            match self.objects {
-                EGLSurfaceObjects::Window { egl_surface, .. } => {
+        self.present_with_damage(egl_display, egl_context, &[])
+    }
+
+    // Swaps a window surface, telling the compositor which regions changed if possible.
+    pub(crate) fn present_with_damage(&self,
+                                      egl_display: EGLDisplay,
+                                      egl_context: EGLContext,
+                                      damage: &[Rect<i32>])
+                                      -> Result<(), Error> {
+        match self.objects {
+            EGLSurfaceObjects::Window { egl_surface, .. } => {
+                unsafe {
                     // The surface must be bound to the current context in EGL 1.4. Temporarily
                     // make this surface current to enforce this.
                     let _guard = CurrentContextGuard::new();
-
                     EGL_FUNCTIONS.with(|egl| {
                         egl.MakeCurrent(egl_display, egl_surface, egl_surface, egl_context);
+                    });
 
-                        let ok = egl.SwapBuffers(egl_display, egl_surface);
-                        if ok != egl::FALSE {
-                            Ok(())
-                        } else {
-                            Err(Error::PresentFailed(egl.GetError().to_windowing_api_error()))
-                        }
-                    })
+                    swap_buffers_with_damage(egl_display, egl_surface, damage)
                 }
-                EGLSurfaceObjects::TextureImage { .. } => Err(Error::NoWidgetAttached),
             }
+            EGLSurfaceObjects::TextureImage { .. } => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    pub(crate) fn buffer_age(&self, egl_display: EGLDisplay, egl_context: EGLContext)
+                             -> Result<u32, Error> {
+        match self.objects {
+            EGLSurfaceObjects::Window { egl_surface, .. } => {
+                unsafe { query_buffer_age(egl_display, egl_surface, egl_context) }
+            }
+            EGLSurfaceObjects::TextureImage { .. } => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    pub(crate) fn set_swap_interval(&self,
+                                    egl_display: EGLDisplay,
+                                    egl_context: EGLContext,
+                                    interval: i32)
+                                    -> Result<(), Error> {
+        match self.objects {
+            EGLSurfaceObjects::Window { egl_surface, ref swap_interval, .. } => {
+                unsafe {
+                    let effective_interval =
+                        set_swap_interval(egl_display, egl_surface, egl_context, interval)?;
+                    swap_interval.set(effective_interval);
+                    Ok(())
+                }
+            }
+            EGLSurfaceObjects::TextureImage { .. } => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    pub(crate) fn swap_interval(&self) -> Result<i32, Error> {
+        match self.objects {
+            EGLSurfaceObjects::Window { ref swap_interval, .. } => Ok(swap_interval.get()),
+            EGLSurfaceObjects::TextureImage { .. } => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    // The surface must not be bound to a context, so that any rendering to it has been flushed.
+    #[cfg(linux)]
+    pub(crate) fn export_dmabuf(&self, egl_display: EGLDisplay) -> Result<DmabufDescriptor, Error> {
+        match self.objects {
+            EGLSurfaceObjects::TextureImage { egl_image, .. } => {
+                unsafe { dmabuf::export_egl_image(egl_display, egl_image, &self.size) }
+            }
+            EGLSurfaceObjects::Window { .. } => Err(Error::WidgetAttached),
         }
     }
 
@@ -261,9 +719,67 @@ impl EGLBackedSurface {
             id: self.id(),
             context_id: self.context_id,
             framebuffer_object: match self.objects {
+                EGLSurfaceObjects::TextureImage {
+                    multisample_framebuffer: Some(ref multisample_framebuffer),
+                    ..
+                } => multisample_framebuffer.framebuffer_object,
                 EGLSurfaceObjects::TextureImage { framebuffer_object, .. } => framebuffer_object,
                 EGLSurfaceObjects::Window { .. } => 0,
             },
+            format: self.format,
+        }
+    }
+
+    // Names the OpenGL objects behind this surface. The context must be current. Widget surfaces
+    // have no such objects, so this does nothing for them.
+    pub(crate) fn set_label(&self, gl: &Gl, label: &str) {
+        match self.objects {
+            EGLSurfaceObjects::TextureImage {
+                framebuffer_object,
+                texture_object,
+                ref renderbuffers,
+                ref multisample_framebuffer,
+                ref depth_image,
+                ..
+            } => {
+                gl_utils::set_surface_objects_label(gl,
+                                                    label,
+                                                    framebuffer_object,
+                                                    texture_object,
+                                                    renderbuffers);
+                if let Some(ref multisample_framebuffer) = *multisample_framebuffer {
+                    multisample_framebuffer.set_label(gl, label);
+                }
+                if let Some(ref depth_image) = *depth_image {
+                    gl_utils::set_object_label(gl,
+                                               gl::TEXTURE,
+                                               depth_image.texture_object,
+                                               &format!("{} depth texture", label));
+                }
+            }
+            EGLSurfaceObjects::Window { .. } => {}
+        }
+    }
+
+    // Clears the color buffers of a generic surface, including its multisampled framebuffer, if
+    // any. The context must be current.
+    unsafe fn clear(&self, gl: &Gl, color: &[f32; 4]) {
+        if let EGLSurfaceObjects::TextureImage {
+            framebuffer_object,
+            ref multisample_framebuffer,
+            ..
+        } = self.objects {
+            gl_utils::clear_framebuffer(gl, framebuffer_object, color);
+            if let Some(ref multisample_framebuffer) = *multisample_framebuffer {
+                gl_utils::clear_framebuffer(gl, multisample_framebuffer.framebuffer_object, color);
+            }
+        }
+    }
+
+    // Surfaces of debug contexts are labeled after their ID until the user picks a better name.
+    fn set_default_label(&self, gl: &Gl, context_attributes: &ContextAttributes) {
+        if context_attributes.flags.contains(ContextAttributeFlags::DEBUG) {
+            self.set_label(gl, &format!("surfman surface {:#x}", self.id().0));
         }
     }
 
@@ -292,8 +808,17 @@ impl EGLBackedSurface {
                 egl.MakeCurrent(egl_display, egl::NO_SURFACE, egl::NO_SURFACE, egl_context);
 
                 match self.objects {
-                    EGLSurfaceObjects::TextureImage { framebuffer_object, .. } => {
+                    EGLSurfaceObjects::TextureImage {
+                        framebuffer_object,
+                        ref multisample_framebuffer,
+                        ..
+                    } => {
                         gl_utils::unbind_framebuffer_if_necessary(gl, framebuffer_object);
+                        if let Some(ref multisample_framebuffer) = *multisample_framebuffer {
+                            gl_utils::unbind_framebuffer_if_necessary(
+                                gl,
+                                multisample_framebuffer.framebuffer_object);
+                        }
                     }
                     EGLSurfaceObjects::Window { .. } => {}
                 }
@@ -301,6 +826,189 @@ impl EGLBackedSurface {
         }
     }
 
+    // Copies the contents of the multisampled framebuffer, if any, into the surface's texture.
+    // The surface's own context is made current for the duration.
+    pub(crate) fn resolve(&self, gl: &Gl, egl_display: EGLDisplay) -> Result<(), Error> {
+        if let EGLSurfaceObjects::TextureImage {
+            framebuffer_object,
+            multisample_framebuffer: Some(ref multisample_framebuffer),
+            ..
+        } = self.objects {
+            unsafe {
+                let _guard = self.make_own_context_current(egl_display)?;
+                multisample_framebuffer.resolve(gl, framebuffer_object, &self.size);
+            }
+        }
+        Ok(())
+    }
+
+    // There is no way to map the memory behind an EGL image on this path, so the pixels are read
+    // back when the surface is locked and uploaded again when it is unlocked. They are transferred
+    // in the surface's own format, so that nothing is lost on the way.
+    //
+    // The surface's own context is made current for the duration, so the surface must be locked
+    // on the thread that its context belongs to.
+    pub(crate) fn lock_data(&mut self, gl: &Gl, egl_display: EGLDisplay)
+                            -> Result<EGLSurfaceDataGuard<'_>, Error> {
+        if !self.access.cpu_access_allowed() {
+            return Err(Error::SurfaceDataInaccessible);
+        }
+
+        let pixels = self.read_all_pixels_in_format(gl, egl_display, self.format)?;
+        Ok(EGLSurfaceDataGuard { surface: self, egl_display, pixels })
+    }
+
+    // Synchronously reads back the entire contents of a generic surface, in OpenGL's
+    // bottom-to-top RGBA layout. The surface's own context is made current for the duration.
+    pub(crate) fn read_all_pixels(&self, gl: &Gl, egl_display: EGLDisplay)
+                                  -> Result<Vec<u8>, Error> {
+        self.read_all_pixels_in_format(gl, egl_display, SurfaceFormat::RGBA8)
+    }
+
+    // Synchronously reads back the entire contents of a generic surface, in OpenGL's
+    // bottom-to-top layout, converting the pixels to the given format.
+    fn read_all_pixels_in_format(&self, gl: &Gl, egl_display: EGLDisplay, format: SurfaceFormat)
+                                 -> Result<Vec<u8>, Error> {
+        let framebuffer_object = match self.objects {
+            EGLSurfaceObjects::TextureImage { framebuffer_object, .. } => framebuffer_object,
+            EGLSurfaceObjects::Window { .. } => return Err(Error::WidgetAttached),
+        };
+
+        let (_, gl_format, gl_type) = format.gl_texture_formats();
+        let stride = self.size.width as usize * format.bytes_per_pixel();
+        let mut pixels = vec![0; stride * self.size.height as usize];
+
+        unsafe {
+            let _guard = self.make_own_context_current(egl_display)?;
+
+            let (mut old_framebuffer, mut old_pack_buffer, mut old_pack_alignment) = (0, 0, 0);
+            gl.GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut old_framebuffer);
+            gl.GetIntegerv(gl::PIXEL_PACK_BUFFER_BINDING, &mut old_pack_buffer);
+            gl.GetIntegerv(gl::PACK_ALIGNMENT, &mut old_pack_alignment);
+
+            gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+            gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl.ReadPixels(0,
+                          0,
+                          self.size.width,
+                          self.size.height,
+                          gl_format,
+                          gl_type,
+                          pixels.as_mut_ptr() as *mut c_void);
+
+            gl.PixelStorei(gl::PACK_ALIGNMENT, old_pack_alignment);
+            gl.BindBuffer(gl::PIXEL_PACK_BUFFER, old_pack_buffer as GLuint);
+            gl.BindFramebuffer(gl::FRAMEBUFFER, old_framebuffer as GLuint);
+        }
+
+        Ok(pixels)
+    }
+
+    pub(crate) fn read_pixels(&self,
+                              gl: &Gl,
+                              egl_display: EGLDisplay,
+                              rect: &Rect<i32>,
+                              format: ReadbackFormat)
+                              -> Result<PixelReadback, Error> {
+        unsafe {
+            let _guard = self.make_own_context_current(egl_display)?;
+            let framebuffer_object = match self.objects {
+                EGLSurfaceObjects::TextureImage { framebuffer_object, .. } => framebuffer_object,
+                EGLSurfaceObjects::Window { .. } => 0,
+            };
+            PixelReadback::start(gl,
+                                 self.context_id,
+                                 framebuffer_object,
+                                 &self.size,
+                                 rect,
+                                 format)
+        }
+    }
+
+    // Replaces the entire contents of a generic surface with the given pixels, in OpenGL's
+    // bottom-to-top RGBA layout. The surface's own context is made current for the duration.
+    pub(crate) fn upload_pixels(&self, gl: &Gl, egl_display: EGLDisplay, pixels: &[u8])
+                                -> Result<(), Error> {
+        self.upload_pixels_in_format(gl, egl_display, pixels, SurfaceFormat::RGBA8)
+    }
+
+    // Replaces the entire contents of a generic surface with the given pixels, in OpenGL's
+    // bottom-to-top layout and the given format.
+    fn upload_pixels_in_format(&self,
+                               gl: &Gl,
+                               egl_display: EGLDisplay,
+                               pixels: &[u8],
+                               format: SurfaceFormat)
+                               -> Result<(), Error> {
+        let (texture_object, framebuffer_object, multisample_framebuffer) = match self.objects {
+            EGLSurfaceObjects::TextureImage {
+                texture_object,
+                framebuffer_object,
+                ref multisample_framebuffer,
+                ..
+            } => (texture_object, framebuffer_object, multisample_framebuffer),
+            EGLSurfaceObjects::Window { .. } => return Err(Error::WidgetAttached),
+        };
+        let (_, gl_format, gl_type) = format.gl_texture_formats();
+        debug_assert_eq!(pixels.len(),
+                         self.size.width as usize * self.size.height as usize *
+                         format.bytes_per_pixel());
+
+        unsafe {
+            let _guard = self.make_own_context_current(egl_display)?;
+
+            let (mut old_texture_object, mut old_unpack_buffer, mut old_unpack_alignment) =
+                (0, 0, 0);
+            gl.GetIntegerv(gl::TEXTURE_BINDING_2D, &mut old_texture_object);
+            gl.GetIntegerv(gl::PIXEL_UNPACK_BUFFER_BINDING, &mut old_unpack_buffer);
+            gl.GetIntegerv(gl::UNPACK_ALIGNMENT, &mut old_unpack_alignment);
+
+            gl.BindTexture(gl::TEXTURE_2D, texture_object);
+            gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl.TexSubImage2D(gl::TEXTURE_2D,
+                             0,
+                             0,
+                             0,
+                             self.size.width,
+                             self.size.height,
+                             gl_format,
+                             gl_type,
+                             pixels.as_ptr() as *const c_void);
+            if let Some(ref multisample_framebuffer) = *multisample_framebuffer {
+                multisample_framebuffer.unresolve(gl, framebuffer_object, &self.size);
+            }
+
+            // Other contexts may sample the surface as soon as this returns, and nothing orders
+            // their commands after ours, so wait for the upload to complete.
+            gl.Finish();
+
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, old_unpack_alignment);
+            gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, old_unpack_buffer as GLuint);
+            gl.BindTexture(gl::TEXTURE_2D, old_texture_object as GLuint);
+        }
+        Ok(())
+    }
+
+    // Makes the surface's context current. Window surfaces are made current along with it, so
+    // that the default framebuffer refers to their back buffer.
+    unsafe fn make_own_context_current(&self, egl_display: EGLDisplay)
+                                       -> Result<CurrentContextGuard, Error> {
+        let guard = CurrentContextGuard::new();
+        let egl_surface = match self.objects {
+            EGLSurfaceObjects::TextureImage { .. } => egl::NO_SURFACE,
+            EGLSurfaceObjects::Window { egl_surface, .. } => egl_surface,
+        };
+        EGL_FUNCTIONS.with(|egl| {
+            let ok = egl.MakeCurrent(egl_display, egl_surface, egl_surface, self.egl_context);
+            if ok == egl::FALSE {
+                return Err(Error::MakeCurrentFailed(egl.GetError().to_windowing_api_error()));
+            }
+            Ok(guard)
+        })
+    }
+
     pub(crate) fn egl_surfaces(&self) -> ExternalEGLSurfaces {
         match self.objects {
             EGLSurfaceObjects::Window { egl_surface, .. } => {
@@ -311,16 +1019,75 @@ impl EGLBackedSurface {
     }
 }
 
+impl<'a> EGLSurfaceDataGuard<'a> {
+    #[inline]
+    pub(crate) fn stride(&self) -> usize {
+        self.surface.size.width as usize * self.surface.format.bytes_per_pixel()
+    }
+
+    #[inline]
+    pub(crate) fn data(&mut self) -> &mut [u8] {
+        &mut self.pixels
+    }
+
+    // Uploads the CPU copy of the pixels back to the surface. This happens when the guard is
+    // dropped, so there is nobody to return an error to.
+    pub(crate) fn unlock(&mut self, gl: &Gl) {
+        let format = self.surface.format;
+        if let Err(err) = self.surface.upload_pixels_in_format(gl,
+                                                               self.egl_display,
+                                                               &self.pixels,
+                                                               format) {
+            error!("surfman: couldn't write locked surface data back to the surface: {:?}", err);
+        }
+    }
+}
+
 impl EGLSurfaceTexture {
     pub(crate) fn destroy(mut self, gl: &Gl) -> EGLBackedSurface {
         unsafe {
             gl.DeleteTextures(1, &self.texture_object);
             self.texture_object = 0;
+            if let Some(depth_texture_object) = self.depth_texture_object.take() {
+                gl.DeleteTextures(1, &depth_texture_object);
+            }
             self.surface
         }
     }
 }
 
+impl EGLDepthImage {
+    // Creates a depth texture for a surface. If `stencil` is true, the texture holds the stencil
+    // buffer too. The context must be current.
+    unsafe fn new(gl: &Gl,
+                  egl_display: EGLDisplay,
+                  egl_context: EGLContext,
+                  size: &Size2D<i32>,
+                  stencil: bool)
+                  -> Result<EGLDepthImage, Error> {
+        let (texture_formats, attachment) =
+            if stencil {
+                ((gl::DEPTH24_STENCIL8, gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8),
+                 gl::DEPTH_STENCIL_ATTACHMENT)
+            } else {
+                ((gl::DEPTH_COMPONENT24, gl::DEPTH_COMPONENT, gl::UNSIGNED_INT),
+                 gl::DEPTH_ATTACHMENT)
+            };
+        let (texture_object, egl_image) =
+            create_texture_image(gl, egl_display, egl_context, size, texture_formats)?;
+        Ok(EGLDepthImage { egl_image, texture_object, attachment })
+    }
+
+    unsafe fn destroy(&mut self, gl: &Gl, egl_display: EGLDisplay) {
+        let result = (EGL_EXTENSION_FUNCTIONS.DestroyImageKHR)(egl_display, self.egl_image);
+        assert_ne!(result, egl::FALSE);
+        self.egl_image = EGL_NO_IMAGE_KHR;
+
+        gl.DeleteTextures(1, &self.texture_object);
+        self.texture_object = 0;
+    }
+}
+
 impl Default for ExternalEGLSurfaces {
     #[inline]
     fn default() -> ExternalEGLSurfaces {
@@ -328,6 +1095,201 @@ impl Default for ExternalEGLSurfaces {
     }
 }
 
+// The colorspaces that can be requested for window surfaces, each with the EGL extensions that
+// must be present to request it and the value of the `EGL_GL_COLORSPACE` attribute.
+static COLORSPACES: [(SurfaceColorspace, &[&str], EGLenum); 4] = [
+    (SurfaceColorspace::SRGB, &["EGL_KHR_gl_colorspace"], egl::GL_COLORSPACE_SRGB),
+    (
+        SurfaceColorspace::DisplayP3,
+        &["EGL_KHR_gl_colorspace", "EGL_EXT_gl_colorspace_display_p3"],
+        EGL_GL_COLORSPACE_DISPLAY_P3_EXT,
+    ),
+    (
+        SurfaceColorspace::BT2020PQ,
+        &["EGL_KHR_gl_colorspace", "EGL_EXT_gl_colorspace_bt2020_pq"],
+        EGL_GL_COLORSPACE_BT2020_PQ_EXT,
+    ),
+    (
+        SurfaceColorspace::SCRGB,
+        &["EGL_KHR_gl_colorspace", "EGL_EXT_gl_colorspace_scrgb"],
+        EGL_GL_COLORSPACE_SCRGB_EXT,
+    ),
+];
+
+// Returns the current size of a window surface. Some platforms, such as X11, only notice that the
+// native window was resized when this is queried.
+pub(crate) unsafe fn query_surface_size(egl_display: EGLDisplay, egl_surface: EGLSurface)
+                                        -> Size2D<i32> {
+    EGL_FUNCTIONS.with(|egl| {
+        let (mut width, mut height) = (0, 0);
+        egl.QuerySurface(egl_display, egl_surface, egl::WIDTH as EGLint, &mut width);
+        egl.QuerySurface(egl_display, egl_surface, egl::HEIGHT as EGLint, &mut height);
+        Size2D::new(width, height)
+    })
+}
+
+// Swaps a window surface, telling the compositor that only the given regions changed if the
+// display supports `EGL_KHR_swap_buffers_with_damage` or its EXT predecessor. Otherwise, or if no
+// regions are given, the whole surface is swapped. The surface must be current.
+pub(crate) unsafe fn swap_buffers_with_damage(egl_display: EGLDisplay,
+                                              egl_surface: EGLSurface,
+                                              damage: &[Rect<i32>])
+                                              -> Result<(), Error> {
+    // The KHR and EXT flavors of the extension have identical signatures.
+    let mut swap_buffers_with_damage = None;
+    if device::display_supports_extension(egl_display, "EGL_KHR_swap_buffers_with_damage") {
+        swap_buffers_with_damage = EGL_EXTENSION_FUNCTIONS.SwapBuffersWithDamageKHR;
+    }
+    if swap_buffers_with_damage.is_none() &&
+            device::display_supports_extension(egl_display, "EGL_EXT_swap_buffers_with_damage") {
+        swap_buffers_with_damage = EGL_EXTENSION_FUNCTIONS.SwapBuffersWithDamageEXT;
+    }
+
+    EGL_FUNCTIONS.with(|egl| {
+        let ok = match swap_buffers_with_damage {
+            Some(swap_buffers_with_damage) if !damage.is_empty() => {
+                let mut rects = Vec::with_capacity(damage.len() * 4);
+                for rect in damage {
+                    rects.extend_from_slice(&[
+                        rect.origin.x,
+                        rect.origin.y,
+                        rect.size.width,
+                        rect.size.height,
+                    ]);
+                }
+                swap_buffers_with_damage(egl_display,
+                                         egl_surface,
+                                         rects.as_ptr(),
+                                         damage.len() as EGLint)
+            }
+            _ => egl.SwapBuffers(egl_display, egl_surface),
+        };
+        if ok != egl::FALSE {
+            Ok(())
+        } else {
+            Err(Error::PresentFailed(egl.GetError().to_windowing_api_error()))
+        }
+    })
+}
+
+// Returns the number of frames ago that the back buffer of a window surface was last presented,
+// or 0 if its contents are undefined, via `EGL_EXT_buffer_age`. Displays without that extension
+// always report 0.
+//
+// The age can only be queried for the surface that is current on the calling thread, so the
+// surface is made current with the given context for the duration.
+pub(crate) unsafe fn query_buffer_age(egl_display: EGLDisplay,
+                                      egl_surface: EGLSurface,
+                                      egl_context: EGLContext)
+                                      -> Result<u32, Error> {
+    if !device::display_supports_extension(egl_display, "EGL_EXT_buffer_age") {
+        return Ok(0);
+    }
+
+    let _guard = CurrentContextGuard::new();
+    EGL_FUNCTIONS.with(|egl| {
+        if egl.MakeCurrent(egl_display, egl_surface, egl_surface, egl_context) == egl::FALSE {
+            let windowing_api_error = egl.GetError().to_windowing_api_error();
+            return Err(Error::MakeCurrentFailed(windowing_api_error));
+        }
+
+        let mut age = 0;
+        if egl.QuerySurface(egl_display, egl_surface, EGL_BUFFER_AGE_EXT as EGLint, &mut age) ==
+                egl::FALSE {
+            return Ok(0);
+        }
+        Ok(age as u32)
+    })
+}
+
+// Sets the number of vertical blanks that presenting a window surface waits for, and returns the
+// interval that is actually in effect: EGL silently clamps the requested interval to the range
+// that the surface's config supports. EGL has no notion of adaptive vsync, so negative intervals
+// are rejected with `UnsupportedOnThisPlatform`.
+//
+// The swap interval applies to the surface that is current on the calling thread, so the surface
+// is made current with the given context for the duration.
+pub(crate) unsafe fn set_swap_interval(egl_display: EGLDisplay,
+                                       egl_surface: EGLSurface,
+                                       egl_context: EGLContext,
+                                       interval: i32)
+                                       -> Result<i32, Error> {
+    if interval < 0 {
+        return Err(Error::UnsupportedOnThisPlatform);
+    }
+
+    let _guard = CurrentContextGuard::new();
+    EGL_FUNCTIONS.with(|egl| {
+        if egl.MakeCurrent(egl_display, egl_surface, egl_surface, egl_context) == egl::FALSE {
+            let windowing_api_error = egl.GetError().to_windowing_api_error();
+            return Err(Error::MakeCurrentFailed(windowing_api_error));
+        }
+
+        if egl.SwapInterval(egl_display, interval) == egl::FALSE {
+            let windowing_api_error = egl.GetError().to_windowing_api_error();
+            return Err(Error::SwapIntervalFailed(windowing_api_error));
+        }
+
+        let (min_interval, max_interval) = swap_interval_range(egl_display, egl_surface);
+        Ok(interval.max(min_interval).min(max_interval))
+    })
+}
+
+// Returns the swap interval that a newly-created window surface has: the EGL default of 1, clamped
+// to the range that the surface's config supports.
+pub(crate) unsafe fn default_swap_interval(egl_display: EGLDisplay, egl_surface: EGLSurface)
+                                           -> i32 {
+    let (min_interval, max_interval) = swap_interval_range(egl_display, egl_surface);
+    1.max(min_interval).min(max_interval)
+}
+
+unsafe fn swap_interval_range(egl_display: EGLDisplay, egl_surface: EGLSurface)
+                              -> (EGLint, EGLint) {
+    let mut egl_config_id = 0;
+    EGL_FUNCTIONS.with(|egl| {
+        let result = egl.QuerySurface(egl_display,
+                                      egl_surface,
+                                      egl::CONFIG_ID as EGLint,
+                                      &mut egl_config_id);
+        assert_ne!(result, egl::FALSE);
+    });
+
+    let egl_config = context::egl_config_from_id(egl_display, egl_config_id);
+    let min_interval =
+        context::get_config_attr(egl_display, egl_config, egl::MIN_SWAP_INTERVAL as EGLint);
+    let max_interval =
+        context::get_config_attr(egl_display, egl_config, egl::MAX_SWAP_INTERVAL as EGLint);
+    (min_interval, max_interval)
+}
+
+// Returns the value of the `EGL_GL_COLORSPACE` attribute that selects the given colorspace for a
+// window surface, or `None` if the attribute should be left out so the default is used.
+pub(crate) fn colorspace_attribute(egl_display: EGLDisplay, colorspace: SurfaceColorspace)
+                                   -> Result<Option<EGLenum>, Error> {
+    if colorspace == SurfaceColorspace::Default {
+        return Ok(None);
+    }
+    let (_, extensions, value) = COLORSPACES.iter()
+                                            .find(|entry| entry.0 == colorspace)
+                                            .unwrap();
+    unsafe {
+        if extensions.iter().all(|name| device::display_supports_extension(egl_display, name)) {
+            Ok(Some(*value))
+        } else {
+            Err(Error::RequiredExtensionUnavailable)
+        }
+    }
+}
+
+// Returns the colorspaces that window surfaces on the given display can be created with.
+pub(crate) fn supported_colorspaces(egl_display: EGLDisplay) -> Vec<SurfaceColorspace> {
+    let mut colorspaces = vec![SurfaceColorspace::Default];
+    colorspaces.extend(COLORSPACES.iter().map(|entry| entry.0).filter(|&colorspace| {
+        colorspace_attribute(egl_display, colorspace).is_ok()
+    }));
+    colorspaces
+}
+
 #[allow(dead_code)]
 pub(crate) unsafe fn create_pbuffer_surface(egl_display: EGLDisplay,
                                             egl_config: EGLConfig,
@@ -349,8 +1311,79 @@ pub(crate) unsafe fn create_pbuffer_surface(egl_display: EGLDisplay,
     })
 }
 
+// Allocates a texture with the given internal format, format, and type, and wraps it in an EGL
+// image. If the driver can't allocate the texture, an `UnsupportedSurfaceFormat` error is
+// returned. The context must be current.
+unsafe fn create_texture_image(gl: &Gl,
+                               egl_display: EGLDisplay,
+                               egl_context: EGLContext,
+                               size: &Size2D<i32>,
+                               texture_formats: (GLenum, GLenum, GLenum))
+                               -> Result<(GLuint, EGLImageKHR), Error> {
+    let egl_image_attribs = [
+        EGL_IMAGE_PRESERVED_KHR as EGLint,  egl::FALSE as EGLint,
+        egl::NONE as EGLint,                0,
+    ];
+
+    // Create our texture.
+    let mut texture_object = 0;
+    gl.GenTextures(1, &mut texture_object);
+    // Save the current texture binding
+    let mut old_texture_object = 0;
+    gl.GetIntegerv(gl::TEXTURE_BINDING_2D, &mut old_texture_object);
+    gl.BindTexture(gl::TEXTURE_2D, texture_object);
+    // Unbind PIXEL_UNPACK_BUFFER, because if it is bound,
+    // it can cause errors in glTexImage2D.
+    // TODO: should this be inside a check for GL 2.0?
+    let mut unpack_buffer = 0;
+    gl.GetIntegerv(gl::PIXEL_UNPACK_BUFFER_BINDING, &mut unpack_buffer);
+    if unpack_buffer != 0 { gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0); }
+    // Drain stale errors, so that we can tell whether the driver supports the format.
+    while gl.GetError() != gl::NO_ERROR {}
+    let (internal_format, format, type_) = texture_formats;
+    gl.TexImage2D(gl::TEXTURE_2D,
+                  0,
+                  internal_format as GLint,
+                  size.width,
+                  size.height,
+                  0,
+                  format,
+                  type_,
+                  ptr::null());
+    let texture_error = gl.GetError();
+    // Restore the old bindings
+    gl.BindTexture(gl::TEXTURE_2D, old_texture_object as _);
+    if unpack_buffer != 0 { gl.BindBuffer(gl::PIXEL_UNPACK_BUFFER, unpack_buffer as _); }
+    if texture_error != gl::NO_ERROR {
+        gl.DeleteTextures(1, &texture_object);
+        return Err(Error::UnsupportedSurfaceFormat);
+    }
+
+    // Create our image.
+    let egl_client_buffer = texture_object as usize as EGLClientBuffer;
+    let egl_image = (EGL_EXTENSION_FUNCTIONS.CreateImageKHR)(egl_display,
+                                                             egl_context,
+                                                             EGL_GL_TEXTURE_2D_KHR,
+                                                             egl_client_buffer,
+                                                             egl_image_attribs.as_ptr());
+    if egl_image == EGL_NO_IMAGE_KHR {
+        gl.DeleteTextures(1, &texture_object);
+        let windowing_api_error = EGL_FUNCTIONS.with(|egl| egl.GetError());
+        return Err(Error::SurfaceCreationFailed(windowing_api_error.to_windowing_api_error()));
+    }
+
+    Ok((texture_object, egl_image))
+}
+
 #[allow(dead_code)]
 pub(crate) unsafe fn bind_egl_image_to_gl_texture(gl: &Gl, egl_image: EGLImageKHR) -> GLuint {
+    bind_egl_image_to_gl_texture_with_filter(gl, egl_image, gl::LINEAR)
+}
+
+unsafe fn bind_egl_image_to_gl_texture_with_filter(gl: &Gl,
+                                                   egl_image: EGLImageKHR,
+                                                   filter: GLenum)
+                                                   -> GLuint {
     let mut texture = 0;
     gl.GenTextures(1, &mut texture);
     debug_assert_ne!(texture, 0);
@@ -361,8 +1394,8 @@ pub(crate) unsafe fn bind_egl_image_to_gl_texture(gl: &Gl, egl_image: EGLImageKH
     // FIXME(pcwalton): Should this be `GL_TEXTURE_EXTERNAL_OES`?
     gl.BindTexture(gl::TEXTURE_2D, texture);
     (EGL_EXTENSION_FUNCTIONS.ImageTargetTexture2DOES)(gl::TEXTURE_2D, egl_image);
-    gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
-    gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+    gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as GLint);
+    gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as GLint);
     gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
     gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
     gl.BindTexture(gl::TEXTURE_2D, texture_binding as GLuint);
@@ -0,0 +1,301 @@
+// surfman/surfman/src/platform/generic/egl/tests.rs
+//
+//! Unit tests for functionality that only the Linux EGL backends provide.
+
+#![allow(missing_docs)]
+
+use crate::gl;
+use crate::{ContextAttributeFlags, ContextAttributes, Error, GLVersion, Gl, SurfaceAccess};
+use crate::{SurfaceDescriptor, SurfaceFormat, SurfaceType};
+use super::connection::Connection;
+use super::context::Context;
+use super::device::Device;
+use super::surface::{DRM_FORMAT_MOD_INVALID, DmabufDescriptor, DmabufPlane};
+use super::surface::{SharedSurfaceHandle, Surface};
+
+use euclid::default::Size2D;
+use std::io::ErrorKind;
+use std::os::unix::net::UnixStream;
+
+const SURFACE_SIZE: i32 = 64;
+
+struct Environment {
+    device: Device,
+    context: Context,
+    gl: Gl,
+}
+
+impl Environment {
+    fn new() -> Option<Environment> {
+        let connection = Connection::new().unwrap();
+        let adapter = connection.create_low_power_adapter().unwrap();
+        let mut device = match connection.create_device(&adapter) {
+            Ok(device) => device,
+            Err(Error::RequiredExtensionUnavailable) => {
+                // Can't run these tests on this hardware.
+                return None;
+            }
+            Err(err) => panic!("Failed to create device: {:?}", err),
+        };
+        let context_descriptor = device.create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(3, 0),
+            flags: ContextAttributeFlags::empty(),
+            samples: 0,
+        }).unwrap();
+        let context = device.create_context(&context_descriptor).unwrap();
+        device.make_context_current(&context).unwrap();
+        let gl = Gl::load_with(|symbol| device.get_proc_address(&context, symbol));
+        Some(Environment { device, context, gl })
+    }
+
+    // Renders a solid color to a new generic surface and returns it unbound.
+    fn make_filled_surface(&mut self, color: [u8; 4]) -> Surface {
+        let surface_type = SurfaceType::Generic {
+            size: Size2D::new(SURFACE_SIZE, SURFACE_SIZE),
+            descriptor: SurfaceDescriptor::default(),
+        };
+        let surface = self.device
+                          .create_surface(&self.context, SurfaceAccess::GPUOnly, surface_type)
+                          .unwrap();
+        self.device.bind_surface_to_context(&mut self.context, surface).unwrap();
+        self.device.make_context_current(&self.context).unwrap();
+        unsafe {
+            self.bind_context_framebuffer();
+            self.gl.ClearColor(color[0] as f32 / 255.0,
+                               color[1] as f32 / 255.0,
+                               color[2] as f32 / 255.0,
+                               color[3] as f32 / 255.0);
+            self.gl.Clear(gl::COLOR_BUFFER_BIT);
+        }
+        self.device.unbind_surface_from_context(&mut self.context).unwrap().unwrap()
+    }
+
+    // Binds a surface to the context and returns the color of its first pixel.
+    fn first_pixel(&mut self, surface: Surface) -> (Surface, [u8; 4]) {
+        self.device.bind_surface_to_context(&mut self.context, surface).unwrap();
+        self.device.make_context_current(&self.context).unwrap();
+        let mut pixel = [0; 4];
+        unsafe {
+            self.bind_context_framebuffer();
+            self.gl.ReadPixels(0, 0, 1, 1, gl::RGBA, gl::UNSIGNED_BYTE, pixel.as_mut_ptr() as _);
+            assert_eq!(self.gl.GetError(), gl::NO_ERROR);
+        }
+        (self.device.unbind_surface_from_context(&mut self.context).unwrap().unwrap(), pixel)
+    }
+
+    unsafe fn bind_context_framebuffer(&self) {
+        let framebuffer_object = self.device
+                                     .context_surface_info(&self.context)
+                                     .unwrap()
+                                     .unwrap()
+                                     .framebuffer_object;
+        self.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+        self.gl.Viewport(0, 0, SURFACE_SIZE, SURFACE_SIZE);
+    }
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_context_descriptor_keeps_gl_errors() {
+    let mut env = match Environment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    // Fetching the descriptor queries the context's flags. That mustn't clear the application's
+    // pending errors.
+    unsafe {
+        env.gl.Enable(0xffff);
+    }
+    let context_descriptor = env.device.context_descriptor(&env.context);
+    drop(env.device.context_descriptor_attributes(&context_descriptor));
+    env.device.make_context_current(&env.context).unwrap();
+    unsafe {
+        assert_eq!(env.gl.GetError(), gl::INVALID_ENUM);
+        assert_eq!(env.gl.GetError(), gl::NO_ERROR);
+    }
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+fn close_dmabuf(descriptor: &DmabufDescriptor) {
+    for plane in &descriptor.planes {
+        unsafe {
+            libc::close(plane.fd);
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_dmabuf_export_import_round_trip() {
+    let mut env = match Environment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let mut surface = env.make_filled_surface([0, 255, 0, 255]);
+    let descriptor = match env.device.export_surface_dmabuf(&surface) {
+        Ok(descriptor) => descriptor,
+        Err(Error::RequiredExtensionUnavailable) => {
+            env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+            env.device.destroy_context(&mut env.context).unwrap();
+            return;
+        }
+        Err(err) => panic!("Failed to export surface: {:?}", err),
+    };
+    assert_eq!(descriptor.size, Size2D::new(SURFACE_SIZE, SURFACE_SIZE));
+    assert!(!descriptor.planes.is_empty());
+
+    let import_result = env.device.create_surface_from_dmabuf(&env.context, descriptor.clone());
+    close_dmabuf(&descriptor);
+    let imported_surface = match import_result {
+        Ok(imported_surface) => imported_surface,
+        Err(Error::RequiredExtensionUnavailable) => {
+            env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+            env.device.destroy_context(&mut env.context).unwrap();
+            return;
+        }
+        Err(err) => panic!("Failed to import surface: {:?}", err),
+    };
+
+    // The imported surface shares memory with the exported one, so it must have its contents.
+    let surface_info = env.device.surface_info(&imported_surface);
+    assert_eq!(surface_info.size, Size2D::new(SURFACE_SIZE, SURFACE_SIZE));
+    assert_eq!(surface_info.format, SurfaceFormat::RGBA8);
+    let (mut imported_surface, pixel) = env.first_pixel(imported_surface);
+    assert_eq!(pixel, [0, 255, 0, 255]);
+
+    env.device.destroy_surface(&mut env.context, &mut imported_surface).unwrap();
+    env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_dmabuf_import_rejects_yuv_formats() {
+    let mut env = match Environment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    // The format is checked before the buffer is touched, so the file descriptors are never used.
+    for fourcc in &[b"NV12", b"YUYV"] {
+        let descriptor = DmabufDescriptor {
+            size: Size2D::new(SURFACE_SIZE, SURFACE_SIZE),
+            fourcc: u32::from_le_bytes(**fourcc),
+            modifier: DRM_FORMAT_MOD_INVALID,
+            planes: vec![DmabufPlane { fd: -1, offset: 0, stride: SURFACE_SIZE as u32 * 2 }],
+        };
+        match env.device.create_surface_from_dmabuf(&env.context, descriptor) {
+            Err(Error::UnsupportedSurfaceFormat) => {}
+            Err(err) => panic!("Expected `UnsupportedSurfaceFormat` but got {:?}", err),
+            Ok(_) => panic!("YUV buffers can't be sampled as `GL_TEXTURE_2D`!"),
+        }
+    }
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_dmabuf_import_rejects_formats_without_alpha() {
+    let mut env = match Environment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    for fourcc in &[b"XB24", b"XR24", b"XB30", b"XB4H"] {
+        let descriptor = DmabufDescriptor {
+            size: Size2D::new(SURFACE_SIZE, SURFACE_SIZE),
+            fourcc: u32::from_le_bytes(**fourcc),
+            modifier: DRM_FORMAT_MOD_INVALID,
+            planes: vec![DmabufPlane { fd: -1, offset: 0, stride: SURFACE_SIZE as u32 * 8 }],
+        };
+        match env.device.create_surface_from_dmabuf(&env.context, descriptor) {
+            Err(Error::UnsupportedSurfaceFormat) => {}
+            Err(err) => panic!("Expected `UnsupportedSurfaceFormat` but got {:?}", err),
+            Ok(_) => panic!("Buffers without alpha can't be sampled as opaque!"),
+        }
+    }
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+// Sends a shared surface handle across a socket pair, as another process would receive it.
+fn send_and_receive(handle: &SharedSurfaceHandle) -> SharedSurfaceHandle {
+    let (sender, receiver) = UnixStream::pair().unwrap();
+    handle.send(&sender).unwrap();
+    SharedSurfaceHandle::receive(&receiver).unwrap()
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_shared_surface_memory_round_trip() {
+    let mut env = match Environment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let size = Size2D::new(SURFACE_SIZE, SURFACE_SIZE);
+    let pixels = [0, 0, 255, 255].iter().cloned().cycle();
+    let pixels: Vec<u8> = pixels.take(SURFACE_SIZE as usize * SURFACE_SIZE as usize * 4).collect();
+    let handle = SharedSurfaceHandle::from_pixels(&size, &pixels).unwrap();
+    let received_handle = send_and_receive(&handle);
+    drop(handle);
+    assert!(!received_handle.is_zero_copy());
+    assert_eq!(received_handle.size(), size);
+
+    let surface = env.device.import_shared_surface(&env.context, received_handle).unwrap();
+    let (mut surface, pixel) = env.first_pixel(surface);
+    assert_eq!(pixel, [0, 0, 255, 255]);
+
+    env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_shared_surface_dmabuf_round_trip() {
+    let mut env = match Environment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let mut surface = env.make_filled_surface([255, 0, 0, 255]);
+    let handle = match env.device.export_surface_dmabuf(&surface) {
+        Ok(descriptor) => SharedSurfaceHandle::from_dmabuf(descriptor),
+        Err(Error::RequiredExtensionUnavailable) => {
+            env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+            env.device.destroy_context(&mut env.context).unwrap();
+            return;
+        }
+        Err(err) => panic!("Failed to export surface: {:?}", err),
+    };
+    let received_handle = send_and_receive(&handle);
+    drop(handle);
+    assert!(received_handle.is_zero_copy());
+    assert_eq!(received_handle.size(), Size2D::new(SURFACE_SIZE, SURFACE_SIZE));
+
+    let imported_surface = match env.device.import_shared_surface(&env.context, received_handle) {
+        Ok(imported_surface) => imported_surface,
+        Err(Error::RequiredExtensionUnavailable) => {
+            env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+            env.device.destroy_context(&mut env.context).unwrap();
+            return;
+        }
+        Err(err) => panic!("Failed to import shared surface: {:?}", err),
+    };
+    let (mut imported_surface, pixel) = env.first_pixel(imported_surface);
+    assert_eq!(pixel, [255, 0, 0, 255]);
+
+    env.device.destroy_surface(&mut env.context, &mut imported_surface).unwrap();
+    env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_shared_surface_rejects_empty_size() {
+    let handle = SharedSurfaceHandle::from_pixels(&Size2D::new(0, 0), &[]).unwrap();
+    let (sender, receiver) = UnixStream::pair().unwrap();
+    handle.send(&sender).unwrap();
+    match SharedSurfaceHandle::receive(&receiver) {
+        Err(ref err) if err.kind() == ErrorKind::InvalidData => {}
+        Err(err) => panic!("Expected an `InvalidData` error but got {:?}", err),
+        Ok(handle) => panic!("An empty shared surface should be rejected, got {:?}", handle),
+    }
+}
@@ -11,11 +11,40 @@ use super::surface::NativeWidget;
 
 use euclid::default::Size2D;
 
+use std::env;
+use std::fmt::Debug;
 use std::os::raw::c_void;
+use std::str::FromStr;
 
 #[cfg(feature = "sm-winit")]
 use winit::Window;
 
+// A comma-separated list of backend names which, if set, overrides the order in which
+// `Connection::new_from_env()` tries backends.
+const BACKEND_ENV_VAR: &str = "SURFMAN_BACKEND";
+
+/// A connection type that belongs to a named backend, so that `multi::Connection` can choose
+/// between backends at runtime.
+///
+/// `multi::Connection` implements this trait itself, by asking first its default and then its
+/// alternate connection type. This is what lets backends be chosen by name through any number of
+/// nested `multi::Connection`s.
+pub trait BackendConnection: Sized {
+    /// The type that names backends.
+    type Backend: Copy + Debug + PartialEq + FromStr<Err = Error>;
+
+    /// Connects to the default display of the given backend.
+    ///
+    /// Returns `None` if this connection type doesn't provide the backend.
+    fn new_with_backend(backend: Self::Backend) -> Option<Result<Self, Error>>;
+
+    /// Returns the backends that this connection type provides, in the order that they are tried.
+    fn backends() -> Vec<Self::Backend>;
+
+    /// Returns the backend that this connection belongs to.
+    fn backend(&self) -> Self::Backend;
+}
+
 /// A connection to the display server.
 pub enum Connection<Def, Alt> where Def: DeviceInterface,
                                     Alt: DeviceInterface,
@@ -52,12 +81,94 @@ pub enum NativeConnection<Def, Alt> where Def: DeviceInterface,
     Alternate(<Alt::Connection as ConnectionInterface>::NativeConnection),
 }
 
+impl<Def, Alt, B> Connection<Def, Alt>
+                  where Def: DeviceInterface,
+                        Alt: DeviceInterface,
+                        Def::Connection: ConnectionInterface<Device = Def> +
+                                         BackendConnection<Backend = B>,
+                        Alt::Connection: ConnectionInterface<Device = Alt> +
+                                         BackendConnection<Backend = B>,
+                        B: Copy + Debug + PartialEq + FromStr<Err = Error> {
+    /// Connects to the backends named by the `SURFMAN_BACKEND` environment variable, or to the
+    /// default display as `new()` does if it isn't set.
+    ///
+    /// The variable is a comma-separated list of backend names, which are tried in that order. If
+    /// any of them isn't provided by this connection type, an `UnknownBackend` error is returned.
+    pub fn new_from_env() -> Result<Connection<Def, Alt>, Error> {
+        let names = match env::var_os(BACKEND_ENV_VAR) {
+            None => return Connection::new(),
+            Some(names) => names,
+        };
+        let names = names.to_str().ok_or(Error::UnknownBackend)?;
+        let preference = names.split(',')
+                              .map(|name| name.trim().parse())
+                              .collect::<Result<Vec<B>, Error>>()?;
+
+        // Unlike `new_with_preference()`, don't silently skip backends that aren't compiled in,
+        // since the user asked for them explicitly.
+        let backends = Connection::<Def, Alt>::backends();
+        if preference.iter().any(|backend| !backends.contains(backend)) {
+            return Err(Error::UnknownBackend);
+        }
+        Connection::new_with_preference(&preference)
+    }
+
+    /// Connects to the default display of the first backend in `preference` that can be opened.
+    ///
+    /// Backends that this connection type doesn't provide are skipped. If none of the backends
+    /// can be opened, the error from the last one tried is returned.
+    pub fn new_with_preference(preference: &[B]) -> Result<Connection<Def, Alt>, Error> {
+        let mut last_error = Error::UnknownBackend;
+        for &backend in preference {
+            match Connection::new_with_backend(backend) {
+                Some(Ok(connection)) => return Ok(connection),
+                Some(Err(error)) => last_error = error,
+                None => {}
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Connects to the default display of the given backend.
+    ///
+    /// Returns `None` if this connection type doesn't provide the backend.
+    #[inline]
+    pub fn new_with_backend(backend: B) -> Option<Result<Connection<Def, Alt>, Error>> {
+        match <Def::Connection>::new_with_backend(backend) {
+            Some(result) => Some(result.map(Connection::Default)),
+            None => {
+                <Alt::Connection>::new_with_backend(backend).map(|result| {
+                    result.map(Connection::Alternate)
+                })
+            }
+        }
+    }
+
+    /// Returns the backends that this connection type provides, in the order that `new()` tries
+    /// them.
+    pub fn backends() -> Vec<B> {
+        let mut backends = <Def::Connection>::backends();
+        backends.extend(<Alt::Connection>::backends());
+        backends
+    }
+
+    /// Returns the backend that this connection belongs to.
+    pub fn backend(&self) -> B {
+        match *self {
+            Connection::Default(ref connection) => connection.backend(),
+            Connection::Alternate(ref connection) => connection.backend(),
+        }
+    }
+}
+
 impl<Def, Alt> Connection<Def, Alt>
                where Def: DeviceInterface,
                      Alt: DeviceInterface,
                      Def::Connection: ConnectionInterface<Device = Def>,
                      Alt::Connection: ConnectionInterface<Device = Alt> {
     /// Connects to the default display.
+    ///
+    /// The default connection is tried first and, if that fails, the alternate one.
     #[inline]
     pub fn new() -> Result<Connection<Def, Alt>, Error> {
         match <Def::Connection>::new() {
@@ -136,6 +247,18 @@ impl<Def, Alt> Connection<Def, Alt>
         }
     }
 
+    /// Returns every adapter that can be used with this connection.
+    pub fn adapters(&self) -> Result<Vec<Adapter<Def, Alt>>, Error> {
+        match *self {
+            Connection::Default(ref connection) => {
+                Ok(connection.adapters()?.into_iter().map(Adapter::Default).collect())
+            }
+            Connection::Alternate(ref connection) => {
+                Ok(connection.adapters()?.into_iter().map(Adapter::Alternate).collect())
+            }
+        }
+    }
+
     /// Opens the hardware device corresponding to the given adapter.
     /// 
     /// Device handles are local to a single thread.
@@ -233,11 +356,37 @@ impl<Def, Alt> Connection<Def, Alt>
     }
 }
 
+impl<Def, Alt, B> BackendConnection for Connection<Def, Alt>
+                  where Def: DeviceInterface,
+                        Alt: DeviceInterface,
+                        Def::Connection: ConnectionInterface<Device = Def> +
+                                         BackendConnection<Backend = B>,
+                        Alt::Connection: ConnectionInterface<Device = Alt> +
+                                         BackendConnection<Backend = B>,
+                        B: Copy + Debug + PartialEq + FromStr<Err = Error> {
+    type Backend = B;
+
+    #[inline]
+    fn new_with_backend(backend: B) -> Option<Result<Connection<Def, Alt>, Error>> {
+        Connection::new_with_backend(backend)
+    }
+
+    #[inline]
+    fn backends() -> Vec<B> {
+        Connection::<Def, Alt>::backends()
+    }
+
+    #[inline]
+    fn backend(&self) -> B {
+        Connection::backend(self)
+    }
+}
+
 impl<Def, Alt> ConnectionInterface for Connection<Def, Alt>
-                                   where Def: DeviceInterface,
-                                         Alt: DeviceInterface,
-                                         Def::Connection: ConnectionInterface<Device = Def>,
-                                         Alt::Connection: ConnectionInterface<Device = Alt> {
+               where Def: DeviceInterface,
+                     Alt: DeviceInterface,
+                     Def::Connection: ConnectionInterface<Device = Def>,
+                     Alt::Connection: ConnectionInterface<Device = Alt> {
     type Adapter = Adapter<Def, Alt>;
     type Device = Device<Def, Alt>;
     type NativeConnection = NativeConnection<Def, Alt>;
@@ -279,6 +428,11 @@ impl<Def, Alt> ConnectionInterface for Connection<Def, Alt>
         Connection::create_software_adapter(self)
     }
 
+    #[inline]
+    fn adapters(&self) -> Result<Vec<Adapter<Def, Alt>>, Error> {
+        Connection::adapters(self)
+    }
+
     #[inline]
     fn create_device(&self, adapter: &Adapter<Def, Alt>) -> Result<Device<Def, Alt>, Error> {
         Connection::create_device(self, adapter)
@@ -2,12 +2,15 @@
 //
 //! A context abstraction that allows the choice of backends dynamically.
 
-use crate::{ContextAttributes, ContextID, Error, SurfaceInfo};
+use crate::{ContextAttributes, ContextID, ContextInfo, ContextResetStatus, Error, SurfaceInfo};
 use crate::device::Device as DeviceInterface;
 use super::device::Device;
 use super::surface::Surface;
 
 use std::os::raw::c_void;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::time::Duration;
 
 /// Represents an OpenGL rendering context.
 /// 
@@ -43,6 +46,16 @@ pub enum ContextDescriptor<Def, Alt> where Def: DeviceInterface, Alt: DeviceInte
     Alternate(Alt::ContextDescriptor),
 }
 
+/// A point in a context's command stream that the CPU or another context can wait on.
+///
+/// Fences must be destroyed with `destroy_fence()`, or a panic will occur.
+pub enum Fence<Def, Alt> where Def: DeviceInterface, Alt: DeviceInterface {
+    /// The default fence type.
+    Default(Def::Fence),
+    /// The alternate fence type.
+    Alternate(Alt::Fence),
+}
+
 /// Wraps a platform-specific native context.
 pub enum NativeContext<Def, Alt> where Def: DeviceInterface, Alt: DeviceInterface {
     /// The default context type.
@@ -259,6 +272,36 @@ impl<Def, Alt> Device<Def, Alt> where Def: DeviceInterface, Alt: DeviceInterface
         }
     }
 
+    /// Returns detailed information about the context, as reported by the driver: the renderer,
+    /// the vendor, the version that was actually created, and the supported extensions.
+    pub fn context_info(&self, context: &Context<Def, Alt>) -> Result<ContextInfo, Error> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                device.context_info(context)
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                device.context_info(context)
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Returns whether the context has been lost because of a GPU reset.
+    ///
+    /// See `Device::context_reset_status()` in the `Device` trait for how to recover from a reset.
+    pub fn context_reset_status(&self, context: &Context<Def, Alt>)
+                                -> Result<ContextResetStatus, Error> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                device.context_reset_status(context)
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                device.context_reset_status(context)
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
     /// Fetches the address of an OpenGL function associated with this context.
     /// 
     /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
@@ -310,4 +353,109 @@ impl<Def, Alt> Device<Def, Alt> where Def: DeviceInterface, Alt: DeviceInterface
             _ => Err(Error::IncompatibleContext),
         }
     }
+
+    /// Inserts a fence into the command stream of the given context.
+    ///
+    /// The fence is signaled once the GPU has finished all commands issued on the context before
+    /// this call.
+    pub fn create_fence(&self, context: &Context<Def, Alt>) -> Result<Fence<Def, Alt>, Error> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                device.create_fence(context).map(Fence::Default)
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                device.create_fence(context).map(Fence::Alternate)
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Imports a sync file, such as one received from another process, as a fence in the command
+    /// stream of the given context.
+    ///
+    /// On success, the fence takes ownership of the file descriptor. On failure, the caller
+    /// retains ownership of it.
+    #[cfg(unix)]
+    pub fn create_fence_from_fd(&self, context: &Context<Def, Alt>, fd: RawFd)
+                                -> Result<Fence<Def, Alt>, Error> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                device.create_fence_from_fd(context, fd).map(Fence::Default)
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                device.create_fence_from_fd(context, fd).map(Fence::Alternate)
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Blocks the calling thread until the fence is signaled.
+    ///
+    /// If `timeout` is `None`, this waits indefinitely. Returns true if the fence was signaled or
+    /// false if the timeout elapsed first.
+    pub fn wait_fence(&self, fence: &Fence<Def, Alt>, timeout: Option<Duration>)
+                      -> Result<bool, Error> {
+        match (self, fence) {
+            (&Device::Default(ref device), &Fence::Default(ref fence)) => {
+                device.wait_fence(fence, timeout)
+            }
+            (&Device::Alternate(ref device), &Fence::Alternate(ref fence)) => {
+                device.wait_fence(fence, timeout)
+            }
+            _ => Err(Error::IncompatibleFence),
+        }
+    }
+
+    /// Makes the GPU wait for the fence to be signaled before executing any further commands
+    /// issued on the given context, without blocking the calling thread where possible.
+    pub fn server_wait_fence(&self, context: &Context<Def, Alt>, fence: &Fence<Def, Alt>)
+                             -> Result<(), Error> {
+        match (self, context, fence) {
+            (&Device::Default(ref device),
+             &Context::Default(ref context),
+             &Fence::Default(ref fence)) => {
+                device.server_wait_fence(context, fence)
+            }
+            (&Device::Alternate(ref device),
+             &Context::Alternate(ref context),
+             &Fence::Alternate(ref fence)) => {
+                device.server_wait_fence(context, fence)
+            }
+            (&Device::Default(_), &Context::Default(_), _) |
+            (&Device::Alternate(_), &Context::Alternate(_), _) => Err(Error::IncompatibleFence),
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Returns a sync file that is signaled along with the fence, suitable for passing to other
+    /// processes or APIs such as Vulkan or KMS.
+    ///
+    /// The caller takes ownership of the returned file descriptor.
+    #[cfg(unix)]
+    pub fn export_fence_fd(&self, fence: &Fence<Def, Alt>) -> Result<RawFd, Error> {
+        match (self, fence) {
+            (&Device::Default(ref device), &Fence::Default(ref fence)) => {
+                device.export_fence_fd(fence)
+            }
+            (&Device::Alternate(ref device), &Fence::Alternate(ref fence)) => {
+                device.export_fence_fd(fence)
+            }
+            _ => Err(Error::IncompatibleFence),
+        }
+    }
+
+    /// Destroys a fence.
+    ///
+    /// Fences must be explicitly destroyed with this function, or a panic will occur.
+    pub fn destroy_fence(&self, fence: &mut Fence<Def, Alt>) {
+        match (self, &mut *fence) {
+            (&Device::Default(ref device), &mut Fence::Default(ref mut fence)) => {
+                device.destroy_fence(fence)
+            }
+            (&Device::Alternate(ref device), &mut Fence::Alternate(ref mut fence)) => {
+                device.destroy_fence(fence)
+            }
+            _ => panic!("Incompatible fence!"),
+        }
+    }
 }
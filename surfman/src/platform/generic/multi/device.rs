@@ -2,17 +2,21 @@
 //
 //! A device abstraction that allows the choice of backends dynamically.
 
-use crate::{ContextID, Error, GLApi, SurfaceAccess, SurfaceInfo, SurfaceType};
+use crate::{ContextID, ContextInfo, ContextResetStatus, Error, GLApi, PixelReadback};
+use crate::{ReadbackFormat, SurfaceAccess, SurfaceColorspace, SurfaceInfo, SurfaceType};
 use crate::connection::Connection as ConnectionInterface;
 use crate::context::ContextAttributes;
 use crate::device::Device as DeviceInterface;
 use crate::gl::types::{GLenum, GLuint};
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
 use super::connection::Connection;
-use super::context::{Context, ContextDescriptor, NativeContext};
-use super::surface::{NativeWidget, Surface, SurfaceTexture};
+use super::context::{Context, ContextDescriptor, Fence, NativeContext};
+use super::surface::{FrameCallback, NativeWidget, Surface, SurfaceTexture};
 
 use std::os::raw::c_void;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::time::Duration;
 
 /// Represents a hardware display adapter that can be used for rendering (including the CPU).
 ///
@@ -101,6 +105,8 @@ impl<Def, Alt> DeviceInterface for Device<Def, Alt>
     type Connection = Connection<Def, Alt>;
     type Context = Context<Def, Alt>;
     type ContextDescriptor = ContextDescriptor<Def, Alt>;
+    type Fence = Fence<Def, Alt>;
+    type FrameCallback = FrameCallback<Def, Alt>;
     type NativeContext = NativeContext<Def, Alt>;
     type Surface = Surface<Def, Alt>;
     type SurfaceTexture = SurfaceTexture<Def, Alt>;
@@ -157,6 +163,41 @@ impl<Def, Alt> DeviceInterface for Device<Def, Alt>
         Device::native_context(self, context)
     }
 
+    #[inline]
+    fn create_fence(&self, context: &Context<Def, Alt>) -> Result<Fence<Def, Alt>, Error> {
+        Device::create_fence(self, context)
+    }
+
+    #[cfg(unix)]
+    #[inline]
+    fn create_fence_from_fd(&self, context: &Context<Def, Alt>, fd: RawFd)
+                            -> Result<Fence<Def, Alt>, Error> {
+        Device::create_fence_from_fd(self, context, fd)
+    }
+
+    #[inline]
+    fn wait_fence(&self, fence: &Fence<Def, Alt>, timeout: Option<Duration>)
+                  -> Result<bool, Error> {
+        Device::wait_fence(self, fence, timeout)
+    }
+
+    #[inline]
+    fn server_wait_fence(&self, context: &Context<Def, Alt>, fence: &Fence<Def, Alt>)
+                         -> Result<(), Error> {
+        Device::server_wait_fence(self, context, fence)
+    }
+
+    #[cfg(unix)]
+    #[inline]
+    fn export_fence_fd(&self, fence: &Fence<Def, Alt>) -> Result<RawFd, Error> {
+        Device::export_fence_fd(self, fence)
+    }
+
+    #[inline]
+    fn destroy_fence(&self, fence: &mut Fence<Def, Alt>) {
+        Device::destroy_fence(self, fence)
+    }
+
     #[inline]
     fn context_descriptor(&self, context: &Context<Def, Alt>) -> Self::ContextDescriptor {
         Device::context_descriptor(self, context)
@@ -178,6 +219,17 @@ impl<Def, Alt> DeviceInterface for Device<Def, Alt>
         Device::context_descriptor_attributes(self, context_descriptor)
     }
 
+    #[inline]
+    fn context_info(&self, context: &Context<Def, Alt>) -> Result<ContextInfo, Error> {
+        Device::context_info(self, context)
+    }
+
+    #[inline]
+    fn context_reset_status(&self, context: &Context<Def, Alt>)
+                            -> Result<ContextResetStatus, Error> {
+        Device::context_reset_status(self, context)
+    }
+
     #[inline]
     fn get_proc_address(&self, context: &Context<Def, Alt>, symbol_name: &str) -> *const c_void {
         Device::get_proc_address(self, context, symbol_name)
@@ -249,9 +301,67 @@ impl<Def, Alt> DeviceInterface for Device<Def, Alt>
     }
 
     #[inline]
-    fn resize_surface(&self, context: &Context<Def, Alt>, surface: &mut Surface<Def, Alt>, size: Size2D<i32>)
-                       -> Result<(), Error> {
-        Device::resize_surface(self, context, surface, size)
+    fn present_surface_with_damage(&self,
+                                   context: &Context<Def, Alt>,
+                                   surface: &mut Surface<Def, Alt>,
+                                   damage: &[Rect<i32>])
+                                   -> Result<(), Error> {
+        Device::present_surface_with_damage(self, context, surface, damage)
+    }
+
+    #[inline]
+    fn surface_buffer_age(&self, context: &Context<Def, Alt>, surface: &Surface<Def, Alt>)
+                          -> Result<u32, Error> {
+        Device::surface_buffer_age(self, context, surface)
+    }
+
+    #[inline]
+    fn set_swap_interval(&self,
+                         context: &Context<Def, Alt>,
+                         surface: &Surface<Def, Alt>,
+                         interval: i32)
+                         -> Result<(), Error> {
+        Device::set_swap_interval(self, context, surface, interval)
+    }
+
+    #[inline]
+    fn swap_interval(&self, context: &Context<Def, Alt>, surface: &Surface<Def, Alt>)
+                     -> Result<i32, Error> {
+        Device::swap_interval(self, context, surface)
+    }
+
+    #[inline]
+    fn request_frame_callback(&self, surface: &Surface<Def, Alt>)
+                              -> Result<FrameCallback<Def, Alt>, Error> {
+        Device::request_frame_callback(self, surface)
+    }
+
+    #[inline]
+    fn poll_frame_callback(&self, callback: &FrameCallback<Def, Alt>) -> Result<bool, Error> {
+        Device::poll_frame_callback(self, callback)
+    }
+
+    #[inline]
+    fn wait_for_frame_callback(&self,
+                               callback: &FrameCallback<Def, Alt>,
+                               timeout: Option<Duration>)
+                               -> Result<bool, Error> {
+        Device::wait_for_frame_callback(self, callback, timeout)
+    }
+
+    #[inline]
+    fn destroy_frame_callback(&self, callback: &mut FrameCallback<Def, Alt>) {
+        Device::destroy_frame_callback(self, callback)
+    }
+
+    #[inline]
+    fn resize_surface(&self,
+                      context: &Context<Def, Alt>,
+                      surface: &mut Surface<Def, Alt>,
+                      size: Size2D<i32>,
+                      preserve_contents: bool)
+                      -> Result<(), Error> {
+        Device::resize_surface(self, context, surface, size, preserve_contents)
     }
 
     #[inline]
@@ -259,8 +369,50 @@ impl<Def, Alt> DeviceInterface for Device<Def, Alt>
         Device::surface_info(self, surface)
     }
 
+    #[inline]
+    fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        Device::supported_colorspaces(self)
+    }
+
     #[inline]
     fn surface_texture_object(&self, surface_texture: &SurfaceTexture<Def, Alt>) -> GLuint {
         Device::surface_texture_object(self, surface_texture)
     }
+
+    #[inline]
+    fn surface_texture_depth_object(&self, surface_texture: &SurfaceTexture<Def, Alt>)
+                                    -> Option<GLuint> {
+        Device::surface_texture_depth_object(self, surface_texture)
+    }
+
+    #[inline]
+    fn set_surface_label(&self,
+                         context: &Context<Def, Alt>,
+                         surface: &Surface<Def, Alt>,
+                         label: &str)
+                         -> Result<(), Error> {
+        Device::set_surface_label(self, context, surface, label)
+    }
+
+    #[inline]
+    fn read_surface_pixels(&self,
+                           context: &Context<Def, Alt>,
+                           surface: &Surface<Def, Alt>,
+                           rect: Rect<i32>,
+                           format: ReadbackFormat)
+                           -> Result<PixelReadback, Error> {
+        Device::read_surface_pixels(self, context, surface, rect, format)
+    }
+
+    #[inline]
+    fn poll_pixel_readback(&self, context: &Context<Def, Alt>, readback: &PixelReadback)
+                           -> Result<bool, Error> {
+        Device::poll_pixel_readback(self, context, readback)
+    }
+
+    #[inline]
+    fn finish_pixel_readback(&self, context: &Context<Def, Alt>, readback: PixelReadback)
+                             -> Result<Vec<u8>, (Error, PixelReadback)> {
+        Device::finish_pixel_readback(self, context, readback)
+    }
 }
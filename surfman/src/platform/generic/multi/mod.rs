@@ -14,6 +14,12 @@
 //! You can "daisy chain" these types to switch between more than two backends. For example, you
 //! might use `multi::Device<wayland::Device, multi::Device<x11::Device, osmesa::Device>>` for a
 //! device that can dynamically switch between Wayland, X11, and OSMesa.
+//! 
+//! Backends in such a chain can also be chosen by name at runtime, regardless of how deeply they
+//! are nested, via `Connection::new_with_preference()` or the `SURFMAN_BACKEND` environment
+//! variable read by `Connection::new_from_env()`. This requires each backend's connection type to
+//! implement `connection::BackendConnection`; chains without it still open connections with
+//! `Connection::new()`.
 
 pub mod connection;
 pub mod context;
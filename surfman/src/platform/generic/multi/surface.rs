@@ -5,12 +5,14 @@
 use crate::connection::Connection as ConnectionInterface;
 use crate::device::Device as DeviceInterface;
 use crate::gl::types::{GLenum, GLuint};
-use crate::{Error, SurfaceAccess, SurfaceInfo, SurfaceType};
-use euclid::default::Size2D;
+use crate::{Error, PixelReadback, ReadbackFormat, SurfaceAccess, SurfaceColorspace, SurfaceInfo};
+use crate::SurfaceType;
+use euclid::default::{Rect, Size2D};
 use super::context::Context;
 use super::device::Device;
 
 use std::fmt::{self, Debug, Formatter};
+use std::time::Duration;
 
 /// Represents a hardware buffer of pixels that can be rendered to via the CPU or GPU and either
 /// displayed in a native widget or bound to a texture for reading.
@@ -35,6 +37,16 @@ pub enum Surface<Def, Alt> where Def: DeviceInterface, Alt: DeviceInterface {
     Alternate(Alt::Surface),
 }
 
+/// A request to be told when the compositor wants the next frame of a widget surface.
+///
+/// Frame callbacks must be destroyed with `destroy_frame_callback()`, or a panic will occur.
+pub enum FrameCallback<Def, Alt> where Def: DeviceInterface, Alt: DeviceInterface {
+    /// The default frame callback type.
+    Default(Def::FrameCallback),
+    /// The alternate frame callback type.
+    Alternate(Alt::FrameCallback),
+}
+
 /// Represents an OpenGL texture that wraps a surface.
 /// 
 /// Reading from the associated OpenGL texture reads from the surface. It is undefined behavior to
@@ -85,11 +97,14 @@ impl<Def, Alt> Device<Def, Alt> where Def: DeviceInterface, Alt: DeviceInterface
         match (&mut *self, context) {
             (&mut Device::Default(ref mut device), &Context::Default(ref context)) => {
                 let surface_type = match surface_type {
-                    SurfaceType::Generic { size } => SurfaceType::Generic { size },
+                    SurfaceType::Generic { size, descriptor } => {
+                        SurfaceType::Generic { size, descriptor }
+                    }
                     SurfaceType::Widget {
                         native_widget: NativeWidget::Default(native_widget),
-                    } => SurfaceType::Widget { native_widget },
-                    SurfaceType::Widget { native_widget: _ } => {
+                        colorspace,
+                    } => SurfaceType::Widget { native_widget, colorspace },
+                    SurfaceType::Widget { .. } => {
                         return Err(Error::IncompatibleNativeWidget)
                     }
                 };
@@ -98,11 +113,14 @@ impl<Def, Alt> Device<Def, Alt> where Def: DeviceInterface, Alt: DeviceInterface
             }
             (&mut Device::Alternate(ref mut device), &Context::Alternate(ref context)) => {
                 let surface_type = match surface_type {
-                    SurfaceType::Generic { size } => SurfaceType::Generic { size },
+                    SurfaceType::Generic { size, descriptor } => {
+                        SurfaceType::Generic { size, descriptor }
+                    }
                     SurfaceType::Widget {
                         native_widget: NativeWidget::Alternate(native_widget),
-                    } => SurfaceType::Widget { native_widget },
-                    SurfaceType::Widget { native_widget: _ } => {
+                        colorspace,
+                    } => SurfaceType::Widget { native_widget, colorspace },
+                    SurfaceType::Widget { .. } => {
                         return Err(Error::IncompatibleNativeWidget)
                     }
                 };
@@ -251,20 +269,198 @@ impl<Def, Alt> Device<Def, Alt> where Def: DeviceInterface, Alt: DeviceInterface
         }
     }
 
-    /// Resizes a widget surface.
-    pub fn resize_surface(&self, context: &Context<Def, Alt>, surface: &mut Surface<Def, Alt>, size: Size2D<i32>)
-                           -> Result<(), Error> {
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// have changed since the last time it was presented.
+    /// 
+    /// The regions are in device pixels, with the origin at the bottom left as in OpenGL. If the
+    /// backend doesn't support damage regions, or `damage` is empty, the whole surface is
+    /// presented.
+    pub fn present_surface_with_damage(&self,
+                                       context: &Context<Def, Alt>,
+                                       surface: &mut Surface<Def, Alt>,
+                                       damage: &[Rect<i32>])
+                                       -> Result<(), Error> {
         match (self, context) {
             (&Device::Default(ref device), &Context::Default(ref context)) => {
                 match *surface {
-                    Surface::Default(ref mut surface) => device.resize_surface(context, surface, size),
+                    Surface::Default(ref mut surface) => {
+                        device.present_surface_with_damage(context, surface, damage)
+                    }
                     _ => Err(Error::IncompatibleSurface),
                 }
             }
             (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
                 match *surface {
                     Surface::Alternate(ref mut surface) => {
-                        device.resize_surface(context, surface, size)
+                        device.present_surface_with_damage(context, surface, damage)
+                    }
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Returns the number of frames ago that the contents of the back buffer of a widget surface
+    /// were presented, or 0 if they are undefined.
+    pub fn surface_buffer_age(&self, context: &Context<Def, Alt>, surface: &Surface<Def, Alt>)
+                              -> Result<u32, Error> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                match *surface {
+                    Surface::Default(ref surface) => device.surface_buffer_age(context, surface),
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                match *surface {
+                    Surface::Alternate(ref surface) => {
+                        device.surface_buffer_age(context, surface)
+                    }
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Sets the number of vertical blanks that presenting a widget surface waits for.
+    /// 
+    /// Negative intervals request adaptive vsync. The driver may clamp the interval to the range
+    /// it supports. Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn set_swap_interval(&self,
+                             context: &Context<Def, Alt>,
+                             surface: &Surface<Def, Alt>,
+                             interval: i32)
+                             -> Result<(), Error> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                match *surface {
+                    Surface::Default(ref surface) => {
+                        device.set_swap_interval(context, surface, interval)
+                    }
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                match *surface {
+                    Surface::Alternate(ref surface) => {
+                        device.set_swap_interval(context, surface, interval)
+                    }
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Returns the swap interval that is in effect for a widget surface.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn swap_interval(&self, context: &Context<Def, Alt>, surface: &Surface<Def, Alt>)
+                         -> Result<i32, Error> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                match *surface {
+                    Surface::Default(ref surface) => device.swap_interval(context, surface),
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                match *surface {
+                    Surface::Alternate(ref surface) => device.swap_interval(context, surface),
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Asks the compositor to signal a frame callback when it's a good time to draw the next frame
+    /// of a widget surface.
+    pub fn request_frame_callback(&self, surface: &Surface<Def, Alt>)
+                                  -> Result<FrameCallback<Def, Alt>, Error> {
+        match (self, surface) {
+            (&Device::Default(ref device), &Surface::Default(ref surface)) => {
+                device.request_frame_callback(surface).map(FrameCallback::Default)
+            }
+            (&Device::Alternate(ref device), &Surface::Alternate(ref surface)) => {
+                device.request_frame_callback(surface).map(FrameCallback::Alternate)
+            }
+            _ => Err(Error::IncompatibleSurface),
+        }
+    }
+
+    /// Dispatches any frame callback events that have arrived, without blocking, and returns true
+    /// if the given frame callback has been signaled.
+    pub fn poll_frame_callback(&self, callback: &FrameCallback<Def, Alt>) -> Result<bool, Error> {
+        match (self, callback) {
+            (&Device::Default(ref device), &FrameCallback::Default(ref callback)) => {
+                device.poll_frame_callback(callback)
+            }
+            (&Device::Alternate(ref device), &FrameCallback::Alternate(ref callback)) => {
+                device.poll_frame_callback(callback)
+            }
+            _ => Err(Error::IncompatibleFrameCallback),
+        }
+    }
+
+    /// Blocks the calling thread until the given frame callback is signaled or the timeout
+    /// elapses.
+    pub fn wait_for_frame_callback(&self,
+                                   callback: &FrameCallback<Def, Alt>,
+                                   timeout: Option<Duration>)
+                                   -> Result<bool, Error> {
+        match (self, callback) {
+            (&Device::Default(ref device), &FrameCallback::Default(ref callback)) => {
+                device.wait_for_frame_callback(callback, timeout)
+            }
+            (&Device::Alternate(ref device), &FrameCallback::Alternate(ref callback)) => {
+                device.wait_for_frame_callback(callback, timeout)
+            }
+            _ => Err(Error::IncompatibleFrameCallback),
+        }
+    }
+
+    /// Destroys a frame callback.
+    ///
+    /// Frame callbacks must be explicitly destroyed with this function, or a panic will occur.
+    pub fn destroy_frame_callback(&self, callback: &mut FrameCallback<Def, Alt>) {
+        match (self, &mut *callback) {
+            (&Device::Default(ref device), &mut FrameCallback::Default(ref mut callback)) => {
+                device.destroy_frame_callback(callback)
+            }
+            (&Device::Alternate(ref device), &mut FrameCallback::Alternate(ref mut callback)) => {
+                device.destroy_frame_callback(callback)
+            }
+            _ => panic!("Incompatible frame callback!"),
+        }
+    }
+
+    /// Resizes a surface.
+    /// 
+    /// Generic surfaces are reallocated at the new size, keeping the pixels in the region common
+    /// to the old and new sizes if `preserve_contents` is true. Widget surfaces take on the
+    /// current size of their native widget. See `Device::resize_surface()` for details.
+    pub fn resize_surface(&self,
+                          context: &Context<Def, Alt>,
+                          surface: &mut Surface<Def, Alt>,
+                          size: Size2D<i32>,
+                          preserve_contents: bool)
+                          -> Result<(), Error> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                match *surface {
+                    Surface::Default(ref mut surface) => {
+                        device.resize_surface(context, surface, size, preserve_contents)
+                    }
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                match *surface {
+                    Surface::Alternate(ref mut surface) => {
+                        device.resize_surface(context, surface, size, preserve_contents)
                     }
                     _ => Err(Error::IncompatibleSurface),
                 }
@@ -302,6 +498,16 @@ impl<Def, Alt> Device<Def, Alt> where Def: DeviceInterface, Alt: DeviceInterface
         }
     }
 
+    /// Returns the colorspaces that widget surfaces created on this device may request.
+    /// 
+    /// The list is empty if this device doesn't support widget surfaces.
+    pub fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        match *self {
+            Device::Default(ref device) => device.supported_colorspaces(),
+            Device::Alternate(ref device) => device.supported_colorspaces(),
+        }
+    }
+
     /// Returns the OpenGL texture object containing the contents of this surface.
     /// 
     /// It is only legal to read from, not write to, this texture object.
@@ -316,4 +522,122 @@ impl<Def, Alt> Device<Def, Alt> where Def: DeviceInterface, Alt: DeviceInterface
             _ => panic!("Incompatible context!"),
         }
     }
+
+    /// Returns the OpenGL texture object containing the depth (and, if present, stencil) buffer
+    /// of the surface wrapped by this surface texture, if it was created with a sampleable depth
+    /// buffer.
+    /// 
+    /// It is only legal to read from, not write to, this texture object.
+    pub fn surface_texture_depth_object(&self, surface_texture: &SurfaceTexture<Def, Alt>)
+                                        -> Option<GLuint> {
+        match (self, surface_texture) {
+            (&Device::Default(ref device), SurfaceTexture::Default(ref surface_texture)) => {
+                device.surface_texture_depth_object(surface_texture)
+            }
+            (&Device::Alternate(ref device), SurfaceTexture::Alternate(ref surface_texture)) => {
+                device.surface_texture_depth_object(surface_texture)
+            }
+            _ => panic!("Incompatible context!"),
+        }
+    }
+
+    /// Names the OpenGL objects behind a surface after the given label, so that `KHR_debug`
+    /// messages and GL debuggers can refer to them.
+    /// 
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn set_surface_label(&self,
+                             context: &Context<Def, Alt>,
+                             surface: &Surface<Def, Alt>,
+                             label: &str)
+                             -> Result<(), Error> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                match *surface {
+                    Surface::Default(ref surface) => {
+                        device.set_surface_label(context, surface, label)
+                    }
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                match *surface {
+                    Surface::Alternate(ref surface) => {
+                        device.set_surface_label(context, surface, label)
+                    }
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Starts copying a region of a surface into CPU memory, without waiting for pending
+    /// rendering to finish.
+    ///
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned. The region is in device pixels, with the origin
+    /// at the bottom left as in OpenGL, and must lie within the surface, or an `InvalidRegion`
+    /// error is returned. For widget surfaces, this reads from the back buffer.
+    pub fn read_surface_pixels(&self,
+                               context: &Context<Def, Alt>,
+                               surface: &Surface<Def, Alt>,
+                               rect: Rect<i32>,
+                               format: ReadbackFormat)
+                               -> Result<PixelReadback, Error> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                match *surface {
+                    Surface::Default(ref surface) => {
+                        device.read_surface_pixels(context, surface, rect, format)
+                    }
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                match *surface {
+                    Surface::Alternate(ref surface) => {
+                        device.read_surface_pixels(context, surface, rect, format)
+                    }
+                    _ => Err(Error::IncompatibleSurface),
+                }
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Returns true if the pixels of a readback can be retrieved without blocking.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned.
+    pub fn poll_pixel_readback(&self, context: &Context<Def, Alt>, readback: &PixelReadback)
+                               -> Result<bool, Error> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                device.poll_pixel_readback(context, readback)
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                device.poll_pixel_readback(context, readback)
+            }
+            _ => Err(Error::IncompatibleContext),
+        }
+    }
+
+    /// Waits for a readback to complete and returns its pixels, in the format it was started
+    /// with.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned.
+    pub fn finish_pixel_readback(&self, context: &Context<Def, Alt>, readback: PixelReadback)
+                                 -> Result<Vec<u8>, (Error, PixelReadback)> {
+        match (self, context) {
+            (&Device::Default(ref device), &Context::Default(ref context)) => {
+                device.finish_pixel_readback(context, readback)
+            }
+            (&Device::Alternate(ref device), &Context::Alternate(ref context)) => {
+                device.finish_pixel_readback(context, readback)
+            }
+            _ => Err((Error::IncompatibleContext, readback)),
+        }
+    }
 }
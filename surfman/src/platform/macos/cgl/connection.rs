@@ -82,6 +82,14 @@ impl Connection {
         self.0.create_software_adapter().map(Adapter)
     }
 
+    /// Returns every adapter that can be used with this connection.
+    ///
+    /// This backend can't enumerate adapters yet, so this returns only the default adapter.
+    #[inline]
+    pub fn adapters(&self) -> Result<Vec<Adapter>, Error> {
+        Ok(vec![self.create_adapter()?])
+    }
+
     /// Opens the hardware device corresponding to the given adapter.
     /// 
     /// Device handles are local to a single thread.
@@ -2,10 +2,11 @@
 //
 //! Wrapper for Core OpenGL contexts.
 
-use crate::context::{CREATE_CONTEXT_MUTEX, ContextID};
+use crate::context::{CREATE_CONTEXT_MUTEX, ContextID, current_context_reset_status};
 use crate::gl_utils;
 use crate::surface::Framebuffer;
-use crate::{ContextAttributeFlags, ContextAttributes, Error, GLVersion, Gl, SurfaceInfo};
+use crate::{ContextAttributeFlags, ContextAttributes, ContextInfo, ContextResetStatus, Error};
+use crate::{GLVersion, Gl, SurfaceInfo};
 use super::device::Device;
 use super::error::ToWindowingApiError;
 use super::ffi::{CGLReleaseContext, CGLRetainContext};
@@ -23,9 +24,11 @@ use core_foundation::bundle::CFBundleRef;
 use core_foundation::string::CFString;
 use std::mem;
 use std::os::raw::c_void;
+use std::os::unix::io::RawFd;
 use std::ptr;
 use std::str::FromStr;
 use std::thread;
+use std::time::Duration;
 
 // No CGL error occurred.
 #[allow(non_upper_case_globals)]
@@ -125,6 +128,12 @@ impl Clone for ContextDescriptor {
 
 unsafe impl Send for ContextDescriptor {}
 
+/// A GPU fence.
+///
+/// Fences aren't supported on this backend yet, so this type can't be constructed.
+#[derive(Debug)]
+pub enum Fence {}
+
 impl Device {
     /// Creates a context descriptor with the given attributes.
     /// 
@@ -136,6 +145,18 @@ impl Device {
             return Err(Error::UnsupportedGLProfile);
         };
 
+        // CGL has no way to request a robust or debug context.
+        if attributes.flags.intersects(ContextAttributeFlags::ROBUST_ACCESS |
+                                       ContextAttributeFlags::LOSE_CONTEXT_ON_RESET |
+                                       ContextAttributeFlags::DEBUG) {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+
+        // FIXME: Support multisampled surfaces.
+        if attributes.samples > 1 {
+            return Err(Error::Unimplemented);
+        }
+
         let profile = if attributes.version.major >= 4 {
             kCGLOGLPVersion_GL4_Core
         } else if attributes.version.major == 3 {
@@ -376,7 +397,7 @@ impl Device {
                 attribute_flags.insert(ContextAttributeFlags::COMPATIBILITY_PROFILE);
             }
 
-            return ContextAttributes { flags: attribute_flags, version };
+            return ContextAttributes { flags: attribute_flags, version, samples: 0 };
         }
 
         unsafe fn get_pixel_format_attribute(context_descriptor: &ContextDescriptor,
@@ -392,6 +413,24 @@ impl Device {
         }
     }
 
+    /// Returns detailed information about the context, as reported by the driver: the renderer,
+    /// the vendor, the version that was actually created, and the supported extensions.
+    pub fn context_info(&self, context: &Context) -> Result<ContextInfo, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| ContextInfo::current(gl))
+    }
+
+    /// Returns whether the context has been lost because of a GPU reset.
+    /// 
+    /// Resets are only reported to contexts created with the `LOSE_CONTEXT_ON_RESET` flag. See
+    /// `Device::context_reset_status()` in the `Device` trait for how to recover from a reset.
+    pub fn context_reset_status(&self, context: &Context) -> Result<ContextResetStatus, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        unsafe {
+            Ok(current_context_reset_status(get_proc_address))
+        }
+    }
+
     /// Fetches the address of an OpenGL function associated with this context.
     /// 
     /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
@@ -434,6 +473,48 @@ impl Device {
             NativeContext(CGLRetainContext(context.cgl_context))
         }
     }
+
+    /// Inserts a fence into the command stream of the given context.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    #[inline]
+    pub fn create_fence(&self, _: &Context) -> Result<Fence, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Imports a sync file as a fence in the command stream of the given context.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    /// The caller retains ownership of the file descriptor.
+    #[inline]
+    pub fn create_fence_from_fd(&self, _: &Context, _: RawFd) -> Result<Fence, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Blocks the calling thread until the fence is signaled.
+    #[inline]
+    pub fn wait_fence(&self, fence: &Fence, _: Option<Duration>) -> Result<bool, Error> {
+        match *fence {}
+    }
+
+    /// Makes the GPU wait for the fence to be signaled before executing any further commands
+    /// issued on the given context.
+    #[inline]
+    pub fn server_wait_fence(&self, _: &Context, fence: &Fence) -> Result<(), Error> {
+        match *fence {}
+    }
+
+    /// Returns a sync file that is signaled along with the fence.
+    #[inline]
+    pub fn export_fence_fd(&self, fence: &Fence) -> Result<RawFd, Error> {
+        match *fence {}
+    }
+
+    /// Destroys a fence.
+    #[inline]
+    pub fn destroy_fence(&self, fence: &mut Fence) {
+        match *fence {}
+    }
 }
 
 fn get_proc_address(symbol_name: &str) -> *const c_void {
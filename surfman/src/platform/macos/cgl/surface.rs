@@ -6,16 +6,19 @@ use crate::context::ContextID;
 use crate::gl::types::{GLenum, GLint, GLuint};
 use crate::gl_utils;
 use crate::platform::macos::system::surface::Surface as SystemSurface;
-use crate::renderbuffers::Renderbuffers;
-use crate::{Error, SurfaceAccess, SurfaceID, SurfaceInfo, SurfaceType, WindowingApiError, gl};
+use crate::renderbuffers::{self, Renderbuffers};
+use crate::{Error, PixelReadback, ReadbackFormat, SurfaceAccess, SurfaceDescriptor};
+use crate::{SurfaceColorspace, SurfaceFormat, SurfaceID, SurfaceInfo, SurfaceType};
+use crate::{WindowingApiError, gl};
 use super::context::{Context, GL_FUNCTIONS};
 use super::device::Device;
 
 use core_foundation::base::TCFType;
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
 use io_surface::{self, IOSurface};
 use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
+use std::time::Duration;
 
 pub use crate::platform::macos::system::surface::{NativeSurface, NativeWidget};
 
@@ -40,6 +43,7 @@ const SURFACE_GL_TEXTURE_TARGET: GLenum = gl::TEXTURE_RECTANGLE;
 pub struct Surface {
     pub(crate) system_surface: SystemSurface,
     pub(crate) context_id: ContextID,
+    pub(crate) format: SurfaceFormat,
     pub(crate) framebuffer_object: GLuint,
     pub(crate) texture_object: GLuint,
     pub(crate) renderbuffers: Renderbuffers,
@@ -74,6 +78,12 @@ impl Debug for SurfaceTexture {
     }
 }
 
+/// A request to be told when the compositor wants the next frame of a widget surface.
+///
+/// Frame callbacks aren't supported on this backend, so this type can't be constructed.
+#[derive(Debug)]
+pub enum FrameCallback {}
+
 impl Device {
     /// Creates either a generic or a widget surface, depending on the supplied surface type.
     /// 
@@ -84,6 +94,10 @@ impl Device {
                           access: SurfaceAccess,
                           surface_type: SurfaceType<NativeWidget>)
                           -> Result<Surface, Error> {
+        let descriptor = match surface_type {
+            SurfaceType::Generic { descriptor, .. } => descriptor,
+            SurfaceType::Widget { .. } => SurfaceDescriptor::default(),
+        };
         let mut system_surface = self.0.create_surface(access, surface_type)?;
         self.0.set_surface_flipped(&mut system_surface, true);
 
@@ -106,9 +120,11 @@ impl Device {
                 let context_descriptor = self.context_descriptor(context);
                 let context_attributes = self.context_descriptor_attributes(&context_descriptor);
 
+                let framebuffer_attributes =
+                    descriptor.framebuffer_attributes(&context_attributes);
                 let mut renderbuffers = Renderbuffers::new(gl,
                                                            &system_surface.size,
-                                                           &context_attributes);
+                                                           &framebuffer_attributes);
                 renderbuffers.bind_to_current_framebuffer(gl);
 
                 if gl.GetError() != gl::NO_ERROR ||
@@ -132,6 +148,7 @@ impl Device {
                 Ok(Surface {
                     system_surface,
                     context_id: context.id,
+                    format: descriptor.format,
                     framebuffer_object,
                     texture_object,
                     renderbuffers,
@@ -253,6 +270,65 @@ impl Device {
         surface_texture.texture_object
     }
 
+    /// Returns the OpenGL texture object containing the depth buffer of the surface wrapped by
+    /// this surface texture.
+    /// 
+    /// Sampleable depth buffers aren't supported on this backend, so this always returns `None`.
+    #[inline]
+    pub fn surface_texture_depth_object(&self, _: &SurfaceTexture) -> Option<GLuint> {
+        None
+    }
+
+    /// Names the OpenGL objects behind a surface after the given label, so that `KHR_debug`
+    /// messages and GL debuggers can refer to them.
+    /// 
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn set_surface_label(&self, context: &Context, surface: &Surface, label: &str)
+                             -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| {
+            gl_utils::set_surface_objects_label(gl,
+                                                label,
+                                                surface.framebuffer_object,
+                                                surface.texture_object,
+                                                &surface.renderbuffers);
+        });
+        Ok(())
+    }
+
+    /// Starts copying a region of a surface into CPU memory, without waiting for pending
+    /// rendering to finish.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    #[inline]
+    pub fn read_surface_pixels(&self,
+                               _: &Context,
+                               _: &Surface,
+                               _: Rect<i32>,
+                               _: ReadbackFormat)
+                               -> Result<PixelReadback, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Returns true if the pixels of a readback can be retrieved without blocking.
+    #[inline]
+    pub fn poll_pixel_readback(&self, _: &Context, _: &PixelReadback) -> Result<bool, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Waits for a readback to complete and returns its pixels, in the format it was started
+    /// with.
+    #[inline]
+    pub fn finish_pixel_readback(&self, _: &Context, readback: PixelReadback)
+                                 -> Result<Vec<u8>, (Error, PixelReadback)> {
+        Err((Error::Unimplemented, readback))
+    }
+
     /// Returns the OpenGL texture target needed to read from this surface texture.
     /// 
     /// This will be `GL_TEXTURE_2D` or `GL_TEXTURE_RECTANGLE`, depending on platform.
@@ -285,8 +361,101 @@ impl Device {
         })
     }
 
-    /// Resizes a widget surface.
-    pub fn resize_surface(&self, context: &Context, surface: &mut Surface, size: Size2D<i32>) -> Result<(), Error> {
+    /// Displays the contents of a widget surface on screen.
+    /// 
+    /// Damage regions aren't supported on this backend, so the whole surface is always
+    /// presented.
+    #[inline]
+    pub fn present_surface_with_damage(&self,
+                                       context: &Context,
+                                       surface: &mut Surface,
+                                       _: &[Rect<i32>])
+                                       -> Result<(), Error> {
+        self.present_surface(context, surface)
+    }
+
+    /// Returns the age of the back buffer of a widget surface.
+    /// 
+    /// Buffer ages aren't available on this backend, so this returns 0, meaning that the contents
+    /// of the back buffer are undefined. Calling this method on a generic surface returns a
+    /// `NoWidgetAttached` error.
+    pub fn surface_buffer_age(&self, _: &Context, surface: &Surface) -> Result<u32, Error> {
+        match surface.system_surface.view_info {
+            Some(_) => Ok(0),
+            None => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    /// Sets the number of vertical blanks that presenting a widget surface waits for.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn set_swap_interval(&self, _: &Context, surface: &Surface, _: i32) -> Result<(), Error> {
+        match surface.system_surface.view_info {
+            // FIXME: Set `kCGLCPSwapInterval` on the context.
+            Some(_) => Err(Error::Unimplemented),
+            None => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    /// Returns the swap interval that is in effect for a widget surface.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn swap_interval(&self, _: &Context, surface: &Surface) -> Result<i32, Error> {
+        match surface.system_surface.view_info {
+            // FIXME: Query `kCGLCPSwapInterval` on the context.
+            Some(_) => Err(Error::Unimplemented),
+            None => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    /// Asks the compositor to signal a frame callback when it's a good time to draw the next frame
+    /// of a widget surface.
+    ///
+    /// Frame callbacks aren't supported on this backend, so this always returns an
+    /// `UnsupportedOnThisPlatform` error.
+    #[inline]
+    pub fn request_frame_callback(&self, _: &Surface) -> Result<FrameCallback, Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
+    /// Dispatches any frame callback events that have arrived, without blocking, and returns true
+    /// if the given frame callback has been signaled.
+    #[inline]
+    pub fn poll_frame_callback(&self, callback: &FrameCallback) -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Blocks the calling thread until the given frame callback is signaled or the timeout
+    /// elapses.
+    #[inline]
+    pub fn wait_for_frame_callback(&self, callback: &FrameCallback, _: Option<Duration>)
+                                   -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Destroys a frame callback.
+    #[inline]
+    pub fn destroy_frame_callback(&self, callback: &mut FrameCallback) {
+        match *callback {}
+    }
+
+    /// Resizes a surface, replacing its `IOSurface` with a new one of the given size.
+    /// 
+    /// For generic surfaces, if `preserve_contents` is true, the pixels in the region common to
+    /// the old and new sizes, anchored at the bottom left, are kept, and the rest of the surface
+    /// is cleared to transparent black; otherwise, the whole surface is cleared. The surface's ID
+    /// changes, so `surface_info()` must be called again afterward.
+    /// 
+    /// Widget surfaces resize their layer to `size`. `preserve_contents` has no effect on them.
+    /// 
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn resize_surface(&self,
+                          context: &Context,
+                          surface: &mut Surface,
+                          size: Size2D<i32>,
+                          preserve_contents: bool)
+                          -> Result<(), Error> {
         if context.id != surface.context_id {
             return Err(Error::IncompatibleSurface);
         }
@@ -294,6 +463,11 @@ impl Device {
         let _guard = self.temporarily_make_context_current(context);
         let _guard = self.temporarily_bind_framebuffer(surface.framebuffer_object);
 
+        // Keep the old `IOSurface` alive until its pixels have been copied over.
+        let old_size = surface.system_surface.size;
+        let old_io_surface = surface.system_surface.io_surface.clone();
+        let is_generic = surface.system_surface.view_info.is_none();
+
         self.0.resize_surface(&mut surface.system_surface, size)?;
 
         let context_descriptor = self.context_descriptor(context);
@@ -313,8 +487,25 @@ impl Device {
                 let renderbuffers = Renderbuffers::new(gl, &size, &context_attributes);
                 renderbuffers.bind_to_current_framebuffer(gl);
 
+                if is_generic {
+                    gl_utils::clear_framebuffer(gl, surface.framebuffer_object, &[0.0; 4]);
+                }
+                if is_generic && preserve_contents {
+                    let old_framebuffer_object =
+                        gl_utils::create_and_bind_framebuffer(gl,
+                                                              SURFACE_GL_TEXTURE_TARGET,
+                                                              surface.texture_object);
+                    renderbuffers::blit_color(gl,
+                                              old_framebuffer_object,
+                                              surface.framebuffer_object,
+                                              &old_size.min(size));
+                    gl_utils::destroy_framebuffer(gl, old_framebuffer_object);
+                    gl.BindFramebuffer(gl::FRAMEBUFFER, surface.framebuffer_object);
+                }
+
                 gl.DeleteTextures(1, &surface.texture_object);
                 surface.renderbuffers.destroy(gl);
+                drop(old_io_surface);
 
                 surface.texture_object = texture_object;
                 surface.renderbuffers = renderbuffers;
@@ -358,9 +549,18 @@ impl Device {
             id: system_surface_info.id,
             context_id: surface.context_id,
             framebuffer_object: surface.framebuffer_object,
+            format: surface.format,
         }
     }
 
+    /// Returns the colorspaces that widget surfaces may request.
+    /// 
+    /// Only the default colorspace is currently supported on this backend.
+    #[inline]
+    pub fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        vec![SurfaceColorspace::Default]
+    }
+
     /// Returns the native `IOSurface` corresponding to this surface.
     ///
     /// The reference count is increased on the `IOSurface` before returning.
@@ -2,7 +2,8 @@
 //
 //! Surface management for macOS.
 
-use crate::{Error, SurfaceAccess, SurfaceID, SurfaceType, SystemSurfaceInfo};
+use crate::{Error, SurfaceAccess, SurfaceColorspace, SurfaceFormat, SurfaceID, SurfaceType};
+use crate::SystemSurfaceInfo;
 use super::device::Device;
 use super::ffi::{IOSurfaceGetAllocSize, IOSurfaceGetBaseAddress, IOSurfaceGetBytesPerRow};
 use super::ffi::{IOSurfaceLock, IOSurfaceUnlock, kCVPixelFormatType_32BGRA, kIOMapDefaultCache};
@@ -106,14 +107,33 @@ pub struct SurfaceDataGuard<'a> {
 
 impl Device {
     /// Creates either a generic or a widget surface, depending on the supplied surface type.
+    /// 
+    /// Surfaces are `IOSurface`s with 8-bit BGRA pixels, so generic surfaces must be `RGBA8` or
+    /// `BGRA8`, or an `UnsupportedSurfaceFormat` error is returned.
     pub fn create_surface(&mut self,
                           access: SurfaceAccess,
                           surface_type: SurfaceType<NativeWidget>)
                           -> Result<Surface, Error> {
         unsafe {
             let size = match surface_type {
-                SurfaceType::Generic { size } => size,
-                SurfaceType::Widget { ref native_widget } => {
+                SurfaceType::Generic { size, ref descriptor } => {
+                    // FIXME: Support the other formats with the corresponding pixel formats.
+                    match descriptor.format {
+                        SurfaceFormat::RGBA8 | SurfaceFormat::BGRA8 => {}
+                        _ => return Err(Error::UnsupportedSurfaceFormat),
+                    }
+                    // FIXME: Support sampleable depth buffers.
+                    if descriptor.sampleable_depth {
+                        return Err(Error::Unimplemented);
+                    }
+                    size
+                }
+                SurfaceType::Widget { ref native_widget, colorspace } => {
+                    // FIXME: Support other colorspaces by setting the colorspace of the layer.
+                    if colorspace != SurfaceColorspace::Default {
+                        return Err(Error::UnsupportedOnThisPlatform);
+                    }
+
                     let window: id = msg_send![native_widget.view.0, window];
                     let bounds = window.convertRectToBacking(native_widget.view.0.bounds());
 
@@ -132,7 +152,12 @@ impl Device {
             let io_surface = self.create_io_surface(&size, access);
 
             let view_info = match surface_type {
-                SurfaceType::Generic { .. } => None,
+                SurfaceType::Generic { ref descriptor, .. } => {
+                    if let Some(ref clear_color) = descriptor.clear_color {
+                        clear_io_surface(&io_surface, clear_color)?;
+                    }
+                    None
+                }
                 SurfaceType::Widget { ref native_widget, .. } => {
                     Some(self.create_view_info(&size, access, native_widget))
                 }
@@ -228,10 +253,17 @@ impl Device {
         surface.present()
     }
 
-    /// Resizes a widget surface
+    /// Resizes a surface, replacing its `IOSurface` with a new one of the given size.
+    /// 
+    /// The contents of the new `IOSurface` are undefined. The layer of a widget surface is resized
+    /// to match.
     pub fn resize_surface(&self, surface: &mut Surface, size: Size2D<i32>) -> Result<(), Error> {
         let view_info = match surface.view_info {
-            None => return Err(Error::NoWidgetAttached),
+            None => {
+                surface.io_surface = self.create_io_surface(&size, surface.access);
+                surface.size = size;
+                return Ok(());
+            }
             Some(ref mut view_info) => view_info,
         };
 
@@ -369,6 +401,31 @@ impl Surface {
     }
 }
 
+// Fills every pixel of an `IOSurface` with the given RGBA color.
+fn clear_io_surface(io_surface: &IOSurface, color: &[f32; 4]) -> Result<(), Error> {
+    let mut bgra = [0; BYTES_PER_PIXEL as usize];
+    for (byte, &channel) in bgra.iter_mut().zip([color[2], color[1], color[0], color[3]].iter()) {
+        *byte = (channel.max(0.0).min(1.0) * 255.0).round() as u8;
+    }
+
+    unsafe {
+        let mut seed = 0;
+        let result = IOSurfaceLock(io_surface.as_concrete_TypeRef(), 0, &mut seed);
+        if result != KERN_SUCCESS {
+            return Err(Error::SurfaceLockFailed);
+        }
+
+        let ptr = IOSurfaceGetBaseAddress(io_surface.as_concrete_TypeRef()) as *mut u8;
+        let len = IOSurfaceGetAllocSize(io_surface.as_concrete_TypeRef());
+        for pixel in slice::from_raw_parts_mut(ptr, len).chunks_exact_mut(bgra.len()) {
+            pixel.copy_from_slice(&bgra);
+        }
+
+        IOSurfaceUnlock(io_surface.as_concrete_TypeRef(), 0, &mut seed);
+    }
+    Ok(())
+}
+
 impl<'a> SurfaceDataGuard<'a> {
     /// Returns the number of bytes per row of the surface.
     #[inline]
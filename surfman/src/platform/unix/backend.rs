@@ -0,0 +1,65 @@
+// surfman/surfman/src/platform/unix/backend.rs
+//
+//! Names for the Unix backends, used to choose between them at runtime.
+
+use crate::Error;
+
+use std::str::FromStr;
+
+/// One of the Unix backends.
+///
+/// The default Unix connection tries Wayland, then X11, GLX, surfaceless, GBM and OSMesa, skipping
+/// those whose `sm-*` features aren't enabled. Pass a list of backends to
+/// `Connection::new_with_preference()` to try them in a different order, or use
+/// `Connection::new_from_env()`, which reads the order from the `SURFMAN_BACKEND` environment
+/// variable as a comma-separated list of backend names (for example,
+/// `SURFMAN_BACKEND=surfaceless` or `SURFMAN_BACKEND=x11,surfaceless`). Naming a backend that
+/// isn't compiled in makes `Connection::new_from_env()` fail with `UnknownBackend`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// The Wayland backend.
+    Wayland,
+    /// The X11 backend.
+    X11,
+    /// The X11 backend that uses GLX instead of EGL, for drivers and remote displays that only
+    /// offer GLX.
+    GLX,
+    /// The Mesa surfaceless backend, which only supports off-screen surfaces.
+    Surfaceless,
+    /// The GBM backend, which renders on a DRM render node without any display server.
+    GBM,
+    /// The OSMesa software backend.
+    OSMesa,
+}
+
+impl Backend {
+    /// Returns the name of this backend, as accepted by `SURFMAN_BACKEND`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Wayland => "wayland",
+            Backend::X11 => "x11",
+            Backend::GLX => "glx",
+            Backend::Surfaceless => "surfaceless",
+            Backend::GBM => "gbm",
+            Backend::OSMesa => "osmesa",
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Backend, Error> {
+        [
+            Backend::Wayland,
+            Backend::X11,
+            Backend::GLX,
+            Backend::Surfaceless,
+            Backend::GBM,
+            Backend::OSMesa,
+        ].iter()
+            .cloned()
+            .find(|backend| name.eq_ignore_ascii_case(backend.name()))
+            .ok_or(Error::UnknownBackend)
+    }
+}
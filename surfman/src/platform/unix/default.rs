@@ -1,16 +1,49 @@
 // surfman/src/platform/unix/default.rs
 //
-//! The default backend for Unix, which dynamically switches between Wayland, X11 and surfaceless.
+//! The default backend for Unix, which dynamically switches between every backend that is
+//! compiled in.
+//!
+//! Backends are tried in the order Wayland, X11, GLX, surfaceless, GBM and OSMesa, where X11, GLX,
+//! GBM and OSMesa are only compiled in with their `sm-*` features.
+//! `Connection::new_with_preference()` and the `SURFMAN_BACKEND` environment variable, read by
+//! `Connection::new_from_env()`, choose a different order. Since the surfaceless backend can almost always be opened, GBM and OSMesa are
+//! in practice only used when they are asked for by name.
+
+use crate::platform::generic::multi::device::Device as MultiDevice;
+use crate::platform::unix::generic::device::Device as SurfacelessDevice;
+use crate::platform::unix::wayland::device::Device as WaylandDevice;
+#[cfg(gbm)]
+use crate::platform::unix::gbm::device::Device as GBMDevice;
+#[cfg(glx)]
+use crate::platform::unix::glx::device::Device as GLXDevice;
+#[cfg(osmesa)]
+use crate::platform::unix::osmesa::device::Device as OSMesaDevice;
+#[cfg(x11)]
+use crate::platform::unix::x11::device::Device as X11Device;
+
+// The backends that render to a display server.
+#[cfg(not(x11))]
+type HWDevice = WaylandDevice;
+#[cfg(all(x11, not(glx)))]
+type HWDevice = MultiDevice<WaylandDevice, X11Device>;
+#[cfg(glx)]
+type HWDevice = MultiDevice<WaylandDevice, MultiDevice<X11Device, GLXDevice>>;
+
+// The backends that render without a display server.
+#[cfg(not(any(gbm, osmesa)))]
+type SWDevice = SurfacelessDevice;
+#[cfg(all(gbm, not(osmesa)))]
+type SWDevice = MultiDevice<SurfacelessDevice, GBMDevice>;
+#[cfg(all(osmesa, not(gbm)))]
+type SWDevice = MultiDevice<SurfacelessDevice, OSMesaDevice>;
+#[cfg(all(gbm, osmesa))]
+type SWDevice = MultiDevice<SurfacelessDevice, MultiDevice<GBMDevice, OSMesaDevice>>;
 
 /// Wayland or X11 display server connections.
 pub mod connection {
     use crate::platform::generic::multi::connection::Connection as MultiConnection;
     use crate::platform::generic::multi::connection::NativeConnection as MultiNativeConnection;
-    use crate::platform::unix::wayland::device::Device as WaylandDevice;
-    use crate::platform::unix::x11::device::Device as X11Device;
-    use crate::platform::unix::generic::device::Device as SWDevice;
-    use crate::platform::generic::multi::device::Device as MultiDevice;
-    type HWDevice = MultiDevice<WaylandDevice, X11Device>;
+    use super::{HWDevice, SWDevice};
 
     /// Either a Wayland or an X11 display server connection.
     pub type Connection = MultiConnection<HWDevice, SWDevice>;
@@ -24,11 +57,7 @@ pub mod context {
     use crate::platform::generic::multi::context::Context as MultiContext;
     use crate::platform::generic::multi::context::ContextDescriptor as MultiContextDescriptor;
     use crate::platform::generic::multi::context::NativeContext as MultiNativeContext;
-    use crate::platform::unix::wayland::device::Device as WaylandDevice;
-    use crate::platform::unix::x11::device::Device as X11Device;
-    use crate::platform::unix::generic::device::Device as SWDevice;
-    use crate::platform::generic::multi::device::Device as MultiDevice;
-    type HWDevice = MultiDevice<WaylandDevice, X11Device>;
+    use super::{HWDevice, SWDevice};
 
     /// Represents an OpenGL rendering context.
     /// 
@@ -62,12 +91,7 @@ pub mod context {
 pub mod device {
     use crate::platform::generic::multi::device::Adapter as MultiAdapter;
     use crate::platform::generic::multi::device::NativeDevice as MultiNativeDevice;
-    use crate::platform::unix::wayland::device::Device as WaylandDevice;
-    use crate::platform::unix::x11::device::Device as X11Device;
-    use crate::platform::unix::generic::device::Device as SWDevice;
-
-    use crate::platform::generic::multi::device::Device as MultiDevice;
-    type HWDevice = MultiDevice<WaylandDevice, X11Device>;
+    use super::{HWDevice, MultiDevice, SWDevice};
 
     /// Represents a hardware display adapter that can be used for rendering (including the CPU).
     ///
@@ -86,18 +110,20 @@ pub mod device {
 
 /// Hardware buffers of pixels.
 pub mod surface {
+    use crate::platform::generic::multi::surface::FrameCallback as MultiFrameCallback;
     use crate::platform::generic::multi::surface::NativeWidget as MultiNativeWidget;
     use crate::platform::generic::multi::surface::Surface as MultiSurface;
     use crate::platform::generic::multi::surface::SurfaceTexture as MultiSurfaceTexture;
-    use crate::platform::unix::wayland::device::Device as WaylandDevice;
-    use crate::platform::unix::x11::device::Device as X11Device;
-    use crate::platform::unix::generic::device::Device as SWDevice;
-    use crate::platform::generic::multi::device::Device as MultiDevice;
-    type HWDevice = MultiDevice<WaylandDevice, X11Device>;
+    use super::{HWDevice, SWDevice};
 
     /// A wrapper for a Wayland surface or an X11 `Window`, as appropriate.
     pub type NativeWidget = MultiNativeWidget<HWDevice, SWDevice>;
 
+    /// A request to be told when the compositor wants the next frame of a widget surface.
+    ///
+    /// Only Wayland surfaces support frame callbacks.
+    pub type FrameCallback = MultiFrameCallback<HWDevice, SWDevice>;
+
     /// Represents a hardware buffer of pixels that can be rendered to via the CPU or GPU and
     /// either displayed in a native widget or bound to a texture for reading.
     /// 
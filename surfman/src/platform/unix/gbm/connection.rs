@@ -0,0 +1,522 @@
+// surfman/surfman/src/platform/unix/gbm/connection.rs
+//
+//! A connection to a DRM device node, through GBM, and the displays it can drive.
+
+use crate::Error;
+use crate::egl::types::{EGLAttrib, EGLDisplay};
+use crate::egl;
+use crate::info::GLApi;
+use crate::platform::generic::egl::device::{self, EGL_FUNCTIONS};
+use crate::platform::generic::egl::ffi::EGL_PLATFORM_GBM_KHR;
+use crate::platform::generic::multi::connection::BackendConnection;
+use crate::platform::unix::Backend;
+use super::device::{Adapter, Device, NativeDevice};
+use super::ffi::{DRM_FUNCTIONS, DRM_MODE_CONNECTED, DRM_MODE_TYPE_PREFERRED, DRMFunctions};
+use super::ffi::{GBM_FUNCTIONS, drmModeModeInfo, drm_array, gbm_device, gbm_functions};
+use super::surface::NativeWidget;
+
+use euclid::default::Size2D;
+
+use std::fmt::{self, Debug, Formatter};
+use std::fs::{File, OpenOptions};
+use std::os::raw::c_void;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(feature = "sm-winit")]
+use winit::Window;
+
+/// A connection to a DRM device node.
+///
+/// There is no display server on this backend; surfaces are allocated directly from the kernel
+/// driver of the node through GBM. Connections to primary nodes can also show widget surfaces on
+/// the displays attached to the device.
+#[derive(Clone)]
+pub struct Connection {
+    pub(crate) native_connection: Arc<NativeConnectionWrapper>,
+}
+
+/// Native connections.
+#[derive(Clone)]
+pub struct NativeConnection(Arc<NativeConnectionWrapper>);
+
+/// Native connections.
+pub struct NativeConnectionWrapper {
+    pub(crate) egl_display: EGLDisplay,
+    pub(crate) gbm_device: *mut gbm_device,
+    pub(crate) render_node: PathBuf,
+    // Keeps the node open for as long as the GBM device uses it.
+    file: File,
+}
+
+unsafe impl Send for NativeConnectionWrapper {}
+unsafe impl Sync for NativeConnectionWrapper {}
+
+impl Drop for NativeConnectionWrapper {
+    fn drop(&mut self) {
+        unsafe {
+            EGL_FUNCTIONS.with(|egl| egl.Terminate(self.egl_display));
+            (gbm_functions().device_destroy)(self.gbm_device);
+        }
+    }
+}
+
+impl Connection {
+    /// Opens the first DRM render node on the system that GBM and EGL can render with.
+    ///
+    /// Render nodes are tried in the order of their names (`/dev/dri/renderD128`, then
+    /// `/dev/dri/renderD129`, and so on), followed by the primary nodes of devices that have no
+    /// render node, such as `vkms`. To open a specific one, use `Connection::from_render_node()`
+    /// or `Connection::from_primary_node()`.
+    pub fn new() -> Result<Connection, Error> {
+        let adapters = Adapter::enumerate();
+        let render_nodes = adapters.iter().map(|adapter| adapter.render_node().to_owned());
+        let primary_nodes = Adapter::primary_nodes().into_iter().filter(|primary_node| {
+            !adapters.iter().any(|adapter| adapter.is_on_node(primary_node))
+        });
+        for node in render_nodes.chain(primary_nodes) {
+            if let Ok(connection) = Connection::from_render_node(&node) {
+                return Ok(connection);
+            }
+        }
+        Err(Error::ConnectionFailed)
+    }
+
+    /// Opens the given DRM render node, such as `/dev/dri/renderD128`.
+    ///
+    /// Requires `libgbm` and `EGL_KHR_platform_gbm`. If the node can't be opened, a
+    /// `ConnectionFailed` error is returned.
+    pub fn from_render_node<P>(render_node: P) -> Result<Connection, Error>
+                               where P: AsRef<Path> {
+        let native_connection = NativeConnectionWrapper::open(render_node.as_ref())?;
+        Ok(Connection { native_connection: Arc::new(native_connection) })
+    }
+
+    /// Opens the given DRM primary node, such as `/dev/dri/card0`.
+    ///
+    /// Unlike render nodes, primary nodes can drive displays, so widget surfaces can be created
+    /// for the connectors returned by `connectors()`. Setting modes requires being the DRM master
+    /// of the device, which usually means that no display server may be running on it.
+    #[inline]
+    pub fn from_primary_node<P>(primary_node: P) -> Result<Connection, Error>
+                                where P: AsRef<Path> {
+        Connection::from_render_node(primary_node)
+    }
+
+    /// An alias for `Connection::new()`, present for consistency with other backends.
+    #[inline]
+    pub unsafe fn from_native_connection(native_connection: NativeConnection)
+                                         -> Result<Connection, Error> {
+        Ok(Connection { native_connection: native_connection.0 })
+    }
+
+    /// Returns the underlying native connection.
+    #[inline]
+    pub fn native_connection(&self) -> NativeConnection {
+        NativeConnection(self.native_connection.clone())
+    }
+
+    /// Returns the path to the DRM node that this connection opened.
+    ///
+    /// This is a primary node if the connection was opened with `from_primary_node()`, or if the
+    /// device has no render node.
+    #[inline]
+    pub fn render_node(&self) -> &Path {
+        &self.native_connection.render_node
+    }
+
+    /// Returns the OpenGL API flavor that this connection supports (OpenGL or OpenGL ES).
+    #[inline]
+    pub fn gl_api(&self) -> GLApi {
+        GLApi::GL
+    }
+
+    /// Returns the "best" adapter on this system, preferring high-performance hardware adapters.
+    ///
+    /// This is an alias for `Connection::create_hardware_adapter()`.
+    #[inline]
+    pub fn create_adapter(&self) -> Result<Adapter, Error> {
+        self.create_hardware_adapter()
+    }
+
+    /// Returns the "best" adapter on this system, preferring high-performance hardware adapters.
+    ///
+    /// This is the last render node that belongs to a GPU, which is where PRIME setups put the
+    /// discrete GPU. If there is none, this returns the adapter of this connection.
+    #[inline]
+    pub fn create_hardware_adapter(&self) -> Result<Adapter, Error> {
+        Ok(self.select_adapter(|adapters| {
+            adapters.iter().filter(|adapter| !adapter.is_software()).last()
+        }))
+    }
+
+    /// Returns the "best" adapter on this system, preferring low-power hardware adapters.
+    ///
+    /// This is the first render node that belongs to a GPU. If there is none, this returns the
+    /// adapter of this connection.
+    #[inline]
+    pub fn create_low_power_adapter(&self) -> Result<Adapter, Error> {
+        Ok(self.select_adapter(|adapters| {
+            adapters.iter().find(|adapter| !adapter.is_software())
+        }))
+    }
+
+    /// Returns the "best" adapter on this system, preferring software adapters.
+    ///
+    /// This is the first render node of a virtual driver such as `vgem`, which Mesa renders to
+    /// on the CPU. If there is none, this returns the adapter of this connection.
+    #[inline]
+    pub fn create_software_adapter(&self) -> Result<Adapter, Error> {
+        Ok(self.select_adapter(|adapters| adapters.iter().find(|adapter| adapter.is_software())))
+    }
+
+    fn select_adapter<F>(&self, select: F) -> Adapter
+                         where F: FnOnce(&[Adapter]) -> Option<&Adapter> {
+        let adapters = Adapter::enumerate();
+        match select(&adapters) {
+            Some(adapter) => adapter.clone(),
+            None => Adapter::new(self.native_connection.render_node.clone()),
+        }
+    }
+
+    /// Returns an adapter for each DRM render node on the system, in the order of their names.
+    ///
+    /// If this connection opened a device that has no render node, its node is included at the
+    /// end. Any of these adapters may be passed to `create_device()`.
+    pub fn adapters(&self) -> Result<Vec<Adapter>, Error> {
+        let mut adapters = Adapter::enumerate();
+        let node = &self.native_connection.render_node;
+        if !adapters.iter().any(|adapter| adapter.is_on_node(node)) {
+            adapters.push(Adapter::new(node.clone()));
+        }
+        Ok(adapters)
+    }
+
+    /// Returns the display connectors of the device that this connection opened, such as HDMI or
+    /// DisplayPort outputs, along with the modes that they support.
+    ///
+    /// Only primary nodes can drive displays; on a render node, this returns an
+    /// `UnsupportedOnThisPlatform` error. If `libdrm` isn't installed, a
+    /// `RequiredExtensionUnavailable` error is returned.
+    pub fn connectors(&self) -> Result<Vec<Connector>, Error> {
+        let drm = match *DRM_FUNCTIONS {
+            Some(ref drm) => drm,
+            None => return Err(Error::RequiredExtensionUnavailable),
+        };
+
+        unsafe {
+            let fd = self.native_connection.fd();
+            let resources = (drm.mode_get_resources)(fd);
+            if resources.is_null() {
+                return Err(Error::UnsupportedOnThisPlatform);
+            }
+
+            let crtc_ids = drm_array((*resources).crtcs, (*resources).count_crtcs);
+            let connector_ids = drm_array((*resources).connectors, (*resources).count_connectors);
+            let connectors = connector_ids.iter().filter_map(|&connector_id| {
+                Connector::new(drm, fd, connector_id, crtc_ids)
+            }).collect();
+
+            (drm.mode_free_resources)(resources);
+            Ok(connectors)
+        }
+    }
+
+    /// Creates a native widget that shows widget surfaces full screen on the given connector, in
+    /// the given mode.
+    ///
+    /// The mode is usually one of `connector.modes()`. It is set when a surface created with the
+    /// widget is first presented; until then, the display keeps showing what it showed before.
+    /// If the connector has no display attached, an `InvalidNativeWidget` error is returned.
+    pub fn create_native_widget(&self, connector: &Connector, mode: &Mode)
+                                -> Result<NativeWidget, Error> {
+        if !connector.connected {
+            return Err(Error::InvalidNativeWidget);
+        }
+        match connector.crtc_ids.first() {
+            Some(&crtc_id) => {
+                Ok(NativeWidget { connector_id: connector.id, crtc_id, mode: *mode })
+            }
+            None => Err(Error::InvalidNativeWidget),
+        }
+    }
+
+    /// Opens the hardware device corresponding to the given adapter.
+    ///
+    /// Device handles are local to a single thread.
+    #[inline]
+    pub fn create_device(&self, adapter: &Adapter) -> Result<Device, Error> {
+        Device::new(self, adapter)
+    }
+
+    /// An alias for `connection.create_device()` with the adapter of the given native device.
+    #[inline]
+    pub unsafe fn create_device_from_native_device(&self, native_device: NativeDevice)
+                                                   -> Result<Device, Error> {
+        Device::new(self, &native_device.adapter)
+    }
+
+    /// Opens the display connection corresponding to the given `winit` window.
+    #[inline]
+    #[cfg(feature = "sm-winit")]
+    pub fn from_winit_window(_: &Window) -> Result<Connection, Error> {
+        Err(Error::IncompatibleNativeWidget)
+    }
+
+    /// Creates a native widget type from the given `winit` window.
+    ///
+    /// This type can be later used to create surfaces that render to the window.
+    #[inline]
+    #[cfg(feature = "sm-winit")]
+    pub fn create_native_widget_from_winit_window(&self, _: &Window)
+                                                  -> Result<NativeWidget, Error> {
+        Err(Error::IncompatibleNativeWidget)
+    }
+
+    /// Create a native widget from a raw pointer
+    ///
+    /// On this backend, `raw` must point to a `NativeWidget` created with
+    /// `create_native_widget()`, which is cloned. The size is ignored, since the widget's mode
+    /// determines it.
+    pub unsafe fn create_native_widget_from_ptr(&self, raw: *mut c_void, _size: Size2D<i32>)
+                                                -> NativeWidget {
+        (*(raw as *const NativeWidget)).clone()
+    }
+
+    /// Create a native widget type from the given `raw_window_handle::RawWindowHandle`.
+    #[cfg(feature = "sm-raw-window-handle")]
+    #[inline]
+    pub fn create_native_widget_from_rwh(&self, _: raw_window_handle::RawWindowHandle)
+                                         -> Result<NativeWidget, Error> {
+        Err(Error::IncompatibleNativeWidget)
+    }
+}
+
+impl NativeConnectionWrapper {
+    // Opens a render node and creates a GBM device and an EGL display on top of it.
+    pub(crate) fn open(render_node: &Path) -> Result<NativeConnectionWrapper, Error> {
+        let gbm = match *GBM_FUNCTIONS {
+            Some(ref gbm) => gbm,
+            None => return Err(Error::ConnectionFailed),
+        };
+        let file = match OpenOptions::new().read(true).write(true).open(render_node) {
+            Ok(file) => file,
+            Err(_) => return Err(Error::ConnectionFailed),
+        };
+
+        unsafe {
+            let gbm_device = (gbm.create_device)(file.as_raw_fd());
+            if gbm_device.is_null() {
+                return Err(Error::ConnectionFailed);
+            }
+
+            let egl_display = match open_egl_display(gbm_device) {
+                Ok(egl_display) => egl_display,
+                Err(err) => {
+                    (gbm.device_destroy)(gbm_device);
+                    return Err(err);
+                }
+            };
+
+            Ok(NativeConnectionWrapper {
+                egl_display,
+                gbm_device,
+                render_node: render_node.to_owned(),
+                file,
+            })
+        }
+    }
+
+    #[inline]
+    pub(crate) fn fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+unsafe fn open_egl_display(gbm_device: *mut gbm_device) -> Result<EGLDisplay, Error> {
+    if !device::display_supports_extension(egl::NO_DISPLAY, "EGL_KHR_platform_gbm") &&
+            !device::display_supports_extension(egl::NO_DISPLAY, "EGL_MESA_platform_gbm") {
+        return Err(Error::RequiredExtensionUnavailable);
+    }
+
+    EGL_FUNCTIONS.with(|egl| {
+        let egl_display_attributes = [egl::NONE as EGLAttrib];
+        let egl_display = egl.GetPlatformDisplay(EGL_PLATFORM_GBM_KHR,
+                                                 gbm_device as *mut c_void,
+                                                 egl_display_attributes.as_ptr());
+        if egl_display == egl::NO_DISPLAY {
+            return Err(Error::ConnectionFailed);
+        }
+
+        let (mut egl_major_version, mut egl_minor_version) = (0, 0);
+        let ok = egl.Initialize(egl_display, &mut egl_major_version, &mut egl_minor_version);
+        if ok == egl::FALSE {
+            return Err(Error::ConnectionFailed);
+        }
+
+        Ok(egl_display)
+    })
+}
+
+/// A display output of a DRM device, such as an HDMI or DisplayPort connector.
+///
+/// Connectors are enumerated with `Connection::connectors()`.
+#[derive(Clone, Debug)]
+pub struct Connector {
+    id: u32,
+    name: String,
+    connected: bool,
+    modes: Vec<Mode>,
+    // The CRTCs that can drive this connector, starting with the one that currently does.
+    crtc_ids: Vec<u32>,
+}
+
+// The names that the kernel gives to connector types, indexed by `DRM_MODE_CONNECTOR_*`.
+const CONNECTOR_TYPE_NAMES: [&str; 21] = [
+    "Unknown", "VGA", "DVI-I", "DVI-D", "DVI-A", "Composite", "SVIDEO", "LVDS", "Component",
+    "DIN", "DP", "HDMI-A", "HDMI-B", "TV", "eDP", "Virtual", "DSI", "DPI", "Writeback", "SPI",
+    "USB",
+];
+
+impl Connector {
+    unsafe fn new(drm: &DRMFunctions, fd: RawFd, connector_id: u32, all_crtc_ids: &[u32])
+                  -> Option<Connector> {
+        let connector = (drm.mode_get_connector)(fd, connector_id);
+        if connector.is_null() {
+            return None;
+        }
+
+        let type_name = CONNECTOR_TYPE_NAMES.get((*connector).connector_type as usize)
+                                            .unwrap_or(&"Unknown");
+        let name = format!("{}-{}", type_name, (*connector).connector_type_id);
+        let modes = drm_array((*connector).modes, (*connector).count_modes).iter()
+                                                                         .map(|&mode| Mode(mode))
+                                                                         .collect();
+
+        // Each encoder of the connector can be driven by some of the CRTCs, given as a mask of
+        // indices into the CRTCs of the device.
+        let (mut possible_crtcs, mut current_crtc_id) = (0, None);
+        let encoder_ids = drm_array((*connector).encoders, (*connector).count_encoders);
+        for &encoder_id in encoder_ids {
+            let encoder = (drm.mode_get_encoder)(fd, encoder_id);
+            if encoder.is_null() {
+                continue;
+            }
+            if encoder_id == (*connector).encoder_id && (*encoder).crtc_id != 0 {
+                current_crtc_id = Some((*encoder).crtc_id);
+            }
+            possible_crtcs |= (*encoder).possible_crtcs;
+            (drm.mode_free_encoder)(encoder);
+        }
+        let other_crtc_ids = all_crtc_ids.iter().enumerate().filter(|&(crtc_index, &crtc_id)| {
+            crtc_index < 32 && possible_crtcs & (1 << crtc_index) != 0 &&
+                Some(crtc_id) != current_crtc_id
+        }).map(|(_, &crtc_id)| crtc_id);
+        let crtc_ids = current_crtc_id.into_iter().chain(other_crtc_ids).collect();
+
+        let connector_info = Connector {
+            id: connector_id,
+            name,
+            connected: (*connector).connection == DRM_MODE_CONNECTED,
+            modes,
+            crtc_ids,
+        };
+        (drm.mode_free_connector)(connector);
+        Some(connector_info)
+    }
+
+    /// Returns the ID of this connector, unique to its device.
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the name of this connector, as the kernel names it (e.g. `HDMI-A-1` or
+    /// `Virtual-1`).
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns true if a display is attached to this connector.
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Returns the modes that the attached display supports, or an empty list if no display is
+    /// attached.
+    #[inline]
+    pub fn modes(&self) -> &[Mode] {
+        &self.modes
+    }
+
+    /// Returns the mode that the attached display prefers, which is usually its native
+    /// resolution, or its first mode if it doesn't say.
+    pub fn preferred_mode(&self) -> Option<Mode> {
+        self.modes.iter().find(|mode| mode.is_preferred()).or_else(|| self.modes.first()).cloned()
+    }
+}
+
+/// A display mode that a connector supports: a resolution and refresh rate, plus the timings
+/// needed to drive the display at them.
+#[derive(Clone, Copy)]
+pub struct Mode(pub(crate) drmModeModeInfo);
+
+impl Mode {
+    /// Returns the resolution of this mode, in pixels.
+    #[inline]
+    pub fn size(&self) -> Size2D<i32> {
+        Size2D::new(self.0.hdisplay as i32, self.0.vdisplay as i32)
+    }
+
+    /// Returns the refresh rate of this mode, in hertz.
+    #[inline]
+    pub fn refresh_rate(&self) -> u32 {
+        self.0.vrefresh
+    }
+
+    /// Returns the name of this mode (e.g. `1920x1080`).
+    pub fn name(&self) -> String {
+        let name: Vec<u8> = self.0.name.iter().take_while(|&&byte| byte != 0)
+                                              .map(|&byte| byte as u8)
+                                              .collect();
+        String::from_utf8_lossy(&name).into_owned()
+    }
+
+    /// Returns true if the display prefers this mode.
+    #[inline]
+    pub fn is_preferred(&self) -> bool {
+        (self.0.type_ & DRM_MODE_TYPE_PREFERRED) != 0
+    }
+}
+
+impl Debug for Mode {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "Mode({}@{}Hz)", self.name(), self.refresh_rate())
+    }
+}
+
+impl BackendConnection for Connection {
+    type Backend = Backend;
+
+    #[inline]
+    fn new_with_backend(backend: Backend) -> Option<Result<Connection, Error>> {
+        if backend == Backend::GBM {
+            Some(Connection::new())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn backends() -> Vec<Backend> {
+        vec![Backend::GBM]
+    }
+
+    #[inline]
+    fn backend(&self) -> Backend {
+        Backend::GBM
+    }
+}
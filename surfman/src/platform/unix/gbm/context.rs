@@ -0,0 +1,337 @@
+// surfman/surfman/src/platform/unix/gbm/context.rs
+//
+//! OpenGL rendering contexts on GBM.
+
+use crate::context::ContextID;
+use crate::egl::types::EGLint;
+use crate::egl;
+use crate::platform::generic::egl::context::{self, CurrentContextGuard, EGLBackedContext};
+use crate::{ContextAttributes, ContextInfo, ContextResetStatus, Error, Gl, SurfaceInfo};
+use super::device::Device;
+use super::surface::{Surface, SurfaceStorage};
+
+use std::os::raw::c_void;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+pub use crate::platform::generic::egl::context::{ContextDescriptor, NativeContext};
+pub use crate::platform::generic::egl::fence::Fence;
+
+thread_local! {
+    #[doc(hidden)]
+    pub static GL_FUNCTIONS: Gl = Gl::load_with(context::get_proc_address);
+}
+
+/// Represents an OpenGL rendering context.
+/// 
+/// A context allows you to issue rendering commands to a surface. When initially created, a
+/// context has no attached surface, so rendering commands will fail or be ignored. Typically, you
+/// attach a surface to the context before rendering.
+/// 
+/// Contexts take ownership of the surfaces attached to them. In order to mutate a surface in any
+/// way other than rendering to it (e.g. presenting it to a window, which causes a buffer swap), it
+/// must first be detached from its context. Each surface is associated with a single context upon
+/// creation and may not be rendered to from any other context. However, you can wrap a surface in
+/// a surface texture, which allows the surface to be read from another context.
+/// 
+/// OpenGL objects may not be shared across contexts directly, but surface textures effectively
+/// allow for sharing of texture data. Contexts are local to a single thread and device.
+/// 
+/// A context must be explicitly destroyed with `destroy_context()`, or a panic will occur.
+pub struct Context {
+    pub(crate) egl_backed: EGLBackedContext,
+    // The GBM storage behind the surface bound to this context, if any.
+    bound_storage: Option<SurfaceStorage>,
+}
+
+impl Device {
+    /// Creates a context descriptor with the given attributes.
+    /// 
+    /// Context descriptors are local to this device.
+    #[inline]
+    pub fn create_context_descriptor(&self, attributes: &ContextAttributes)
+                                     -> Result<ContextDescriptor, Error> {
+        unsafe {
+            ContextDescriptor::new(self.native_connection.egl_display, attributes, &[
+                egl::SURFACE_TYPE as EGLint,        egl::WINDOW_BIT as EGLint,
+                egl::RENDERABLE_TYPE as EGLint,     egl::OPENGL_BIT as EGLint,
+                egl::COLOR_BUFFER_TYPE as EGLint,   egl::RGB_BUFFER as EGLint,
+            ])
+        }
+    }
+
+    /// Creates a new OpenGL context.
+    /// 
+    /// The context initially has no surface attached. Until a surface is bound to it, rendering
+    /// commands will fail or have no effect.
+    #[inline]
+    pub fn create_context(&mut self, descriptor: &ContextDescriptor) -> Result<Context, Error> {
+        unsafe {
+            EGLBackedContext::new(self.native_connection.egl_display,
+                                  descriptor,
+                                  self.gl_api()).map(|egl_backed| {
+                Context { egl_backed, bound_storage: None }
+            })
+        }
+    }
+
+    /// Wraps an `EGLContext` in a native context and returns it.
+    ///
+    /// The context is not retained, as there is no way to do this in the EGL API. Therefore,
+    /// it is the caller's responsibility to ensure that the returned `Context` object remains
+    /// alive as long as the `EGLContext` is.
+    #[inline]
+    pub unsafe fn create_context_from_native_context(&self, native_context: NativeContext)
+                                                     -> Result<Context, Error> {
+        Ok(Context {
+            egl_backed: EGLBackedContext::from_native_context(native_context),
+            bound_storage: None,
+        })
+    }
+
+    /// Destroys a context.
+    /// 
+    /// The context must have been created on this device.
+    pub fn destroy_context(&self, context: &mut Context) -> Result<(), Error> {
+        if let Ok(Some(mut surface)) = self.unbind_surface_from_context(context) {
+            self.destroy_surface(context, &mut surface)?;
+        }
+
+        unsafe {
+            context.egl_backed.destroy(self.native_connection.egl_display);
+            Ok(())
+        }
+    }
+
+    /// Given a context, returns its underlying EGL context and attached surfaces.
+    #[inline]
+    pub fn native_context(&self, context: &Context) -> NativeContext {
+        context.egl_backed.native_context()
+    }
+
+    /// Returns the descriptor that this context was created with.
+    #[inline]
+    pub fn context_descriptor(&self, context: &Context) -> ContextDescriptor {
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                context.egl_backed.descriptor(gl, self.native_connection.egl_display)
+            }
+        })
+    }
+
+    /// Makes the context the current OpenGL context for this thread.
+    /// 
+    /// After calling this function, it is valid to use OpenGL rendering commands.
+    #[inline]
+    pub fn make_context_current(&self, context: &Context) -> Result<(), Error> {
+        unsafe {
+            context.egl_backed.make_current(self.native_connection.egl_display)
+        }
+    }
+
+    /// Removes the current OpenGL context from this thread.
+    /// 
+    /// After calling this function, OpenGL rendering commands will fail until a new context is
+    /// made current.
+    #[inline]
+    pub fn make_no_context_current(&self) -> Result<(), Error> {
+        unsafe {
+            context::make_no_context_current(self.native_connection.egl_display)
+        }
+    }
+
+    #[inline]
+    pub(crate) fn temporarily_make_context_current(&self, context: &Context)
+                                                   -> Result<CurrentContextGuard, Error> {
+        let guard = CurrentContextGuard::new();
+        self.make_context_current(context)?;
+        Ok(guard)
+    }
+
+    /// Returns the attributes that the context descriptor was created with.
+    #[inline]
+    pub fn context_descriptor_attributes(&self, context_descriptor: &ContextDescriptor)
+                                         -> ContextAttributes {
+        unsafe {
+            context_descriptor.attributes(self.native_connection.egl_display)
+        }
+    }
+
+    /// Returns detailed information about the context, as reported by the driver: the renderer,
+    /// the vendor, the version that was actually created, and the supported extensions.
+    pub fn context_info(&self, context: &Context) -> Result<ContextInfo, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                context::current_context_info(gl, self.native_connection.egl_display)
+            }
+        })
+    }
+
+    /// Returns whether the context has been lost because of a GPU reset.
+    /// 
+    /// Resets are only reported to contexts created with the `LOSE_CONTEXT_ON_RESET` flag. See
+    /// `Device::context_reset_status()` in the `Device` trait for how to recover from a reset.
+    pub fn context_reset_status(&self, context: &Context) -> Result<ContextResetStatus, Error> {
+        unsafe {
+            context.egl_backed.reset_status(self.native_connection.egl_display)
+        }
+    }
+
+    /// Makes `context_reset_status()` report that the context was reset, so that recovery from
+    /// context loss can be tested without a real GPU reset.
+    #[doc(hidden)]
+    pub fn simulate_context_loss(&self, context: &mut Context) {
+        context.egl_backed.simulate_loss()
+    }
+
+    /// Fetches the address of an OpenGL function associated with this context.
+    /// 
+    /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
+    /// with any other context.
+    /// 
+    /// This method is typically used with a function like `gl::load_with()` from the `gl` crate to
+    /// load OpenGL function pointers.
+    #[inline]
+    pub fn get_proc_address(&self, _: &Context, symbol_name: &str) -> *const c_void {
+        context::get_proc_address(symbol_name)
+    }
+
+    /// Attaches a surface to a context for rendering.
+    /// 
+    /// This function takes ownership of the surface. The surface must have been created with this
+    /// context, or an `IncompatibleSurface` error is returned.
+    /// 
+    /// If this function is called with a surface already bound, a `SurfaceAlreadyBound` error is
+    /// returned. To avoid this error, first unbind the existing surface with
+    /// `unbind_surface_from_context`.
+    /// 
+    /// If an error is returned, the surface is returned alongside it.
+    #[inline]
+    pub fn bind_surface_to_context(&self, context: &mut Context, surface: Surface)
+                                   -> Result<(), (Error, Surface)> {
+        let Surface { egl_backed, storage } = surface;
+        unsafe {
+            match context.egl_backed.bind_surface(self.native_connection.egl_display, egl_backed) {
+                Ok(()) => {
+                    context.bound_storage = Some(storage);
+                    Ok(())
+                }
+                Err((err, egl_backed)) => Err((err, Surface { egl_backed, storage })),
+            }
+        }
+    }
+
+    /// Removes and returns any attached surface from this context.
+    /// 
+    /// Any pending OpenGL commands targeting this surface will be automatically flushed, so the
+    /// surface is safe to read from immediately when this function returns.
+    pub fn unbind_surface_from_context(&self, context: &mut Context)
+                                       -> Result<Option<Surface>, Error> {
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                let egl_backed =
+                    context.egl_backed.unbind_surface(gl, self.native_connection.egl_display)?;
+                Ok(egl_backed.map(|egl_backed| {
+                    let storage = context.bound_storage
+                                         .take()
+                                         .expect("Bound surface has no GBM storage!");
+                    Surface { egl_backed, storage }
+                }))
+            }
+        })
+    }
+
+    /// Returns a unique ID representing a context.
+    /// 
+    /// This ID is unique to all currently-allocated contexts. If you destroy a context and create
+    /// a new one, the new context might have the same ID as the destroyed one.
+    #[inline]
+    pub fn context_id(&self, context: &Context) -> ContextID {
+        context.egl_backed.id
+    }
+
+    /// Returns various information about the surface attached to a context.
+    /// 
+    /// This includes, most notably, the OpenGL framebuffer object needed to render to the surface.
+    #[inline]
+    pub fn context_surface_info(&self, context: &Context) -> Result<Option<SurfaceInfo>, Error> {
+        context.egl_backed.surface_info()
+    }
+
+    /// Inserts a fence into the command stream of the given context.
+    /// 
+    /// The fence is signaled once the GPU has finished all commands issued on the context before
+    /// this call. Pending commands are flushed, so the fence may be waited on from any thread.
+    /// 
+    /// Requires `EGL_KHR_fence_sync`; if it's unavailable, a `RequiredExtensionUnavailable` error
+    /// is returned. If the display supports `EGL_ANDROID_native_fence_sync`, the fence is backed
+    /// by a sync file and may be exported with `export_fence_fd()`.
+    pub fn create_fence(&self, context: &Context) -> Result<Fence, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                Fence::new(gl, self.native_connection.egl_display)
+            }
+        })
+    }
+
+    /// Imports a sync file, such as one received from another process, as a fence in the command
+    /// stream of the given context.
+    /// 
+    /// On success, the fence takes ownership of the file descriptor. On failure, the caller
+    /// retains ownership of it. Requires `EGL_ANDROID_native_fence_sync`.
+    pub fn create_fence_from_fd(&self, context: &Context, fd: RawFd) -> Result<Fence, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                Fence::from_fd(gl, self.native_connection.egl_display, fd)
+            }
+        })
+    }
+
+    /// Blocks the calling thread until the fence is signaled.
+    /// 
+    /// If `timeout` is `None`, this waits indefinitely. Returns true if the fence was signaled or
+    /// false if the timeout elapsed first.
+    #[inline]
+    pub fn wait_fence(&self, fence: &Fence, timeout: Option<Duration>) -> Result<bool, Error> {
+        unsafe {
+            fence.wait(self.native_connection.egl_display, timeout)
+        }
+    }
+
+    /// Makes the GPU wait for the fence to be signaled before executing any further commands
+    /// issued on the given context, without blocking the calling thread.
+    /// 
+    /// If `EGL_KHR_wait_sync` is unavailable, this falls back to blocking the calling thread until
+    /// the fence is signaled.
+    pub fn server_wait_fence(&self, context: &Context, fence: &Fence) -> Result<(), Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        unsafe {
+            fence.server_wait(self.native_connection.egl_display)
+        }
+    }
+
+    /// Returns a sync file that is signaled along with the fence, suitable for passing to other
+    /// processes or APIs such as Vulkan or KMS.
+    /// 
+    /// The caller takes ownership of the returned file descriptor. If the fence isn't backed by a
+    /// sync file, a `RequiredExtensionUnavailable` error is returned.
+    #[inline]
+    pub fn export_fence_fd(&self, fence: &Fence) -> Result<RawFd, Error> {
+        unsafe {
+            fence.export_fd(self.native_connection.egl_display)
+        }
+    }
+
+    /// Destroys a fence.
+    /// 
+    /// Fences must be explicitly destroyed with this function, or a panic will occur.
+    #[inline]
+    pub fn destroy_fence(&self, fence: &mut Fence) {
+        unsafe {
+            fence.destroy(self.native_connection.egl_display)
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// surfman/surfman/src/platform/unix/gbm/device.rs
+//
+//! A wrapper around GBM devices on DRM device nodes.
+
+use crate::{Error, GLApi};
+use super::connection::{Connection, NativeConnectionWrapper};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const DRI_DIRECTORY: &str = "/dev/dri";
+const RENDER_NODE_PREFIX: &str = "renderD";
+const PRIMARY_NODE_PREFIX: &str = "card";
+
+// Kernel drivers that have no GPU behind them. Mesa renders to their buffers on the CPU, with
+// `kms_swrast`.
+const SOFTWARE_DRIVERS: [&str; 2] = ["vgem", "vkms"];
+
+/// Represents a hardware display adapter that can be used for rendering (including the CPU).
+///
+/// On GBM, an adapter is a DRM render node, or the primary node of a device that has no render
+/// node (such as `vkms`).
+///
+/// Adapters can be sent between threads. To render with an adapter, open a thread-local `Device`.
+#[derive(Clone, Debug)]
+pub struct Adapter {
+    render_node: PathBuf,
+    driver: Option<String>,
+}
+
+impl Adapter {
+    pub(crate) fn new(render_node: PathBuf) -> Adapter {
+        // The kernel driver is the name of the `driver` link in the node's sysfs directory.
+        let driver = sysfs_device(&render_node).and_then(|device| {
+            let driver_path = fs::read_link(device.join("driver")).ok()?;
+            Some(driver_path.file_name()?.to_string_lossy().into_owned())
+        });
+        Adapter { render_node, driver }
+    }
+
+    // Returns an adapter for every render node on the system, in the order of their names.
+    pub(crate) fn enumerate() -> Vec<Adapter> {
+        Adapter::render_nodes().into_iter().map(Adapter::new).collect()
+    }
+
+    // Returns the paths of all render nodes on the system, in the order of their names.
+    pub(crate) fn render_nodes() -> Vec<PathBuf> {
+        device_nodes(RENDER_NODE_PREFIX)
+    }
+
+    // Returns the paths of all primary nodes on the system, in the order of their names.
+    pub(crate) fn primary_nodes() -> Vec<PathBuf> {
+        device_nodes(PRIMARY_NODE_PREFIX)
+    }
+
+    // Returns true if this adapter is the given node, or another node of the same device (e.g.
+    // the render node that belongs to a primary node).
+    pub(crate) fn is_on_node(&self, node: &Path) -> bool {
+        if self.render_node == node {
+            return true;
+        }
+        match (sysfs_device(&self.render_node), sysfs_device(node)) {
+            (Some(this_device), Some(other_device)) => this_device == other_device,
+            _ => false,
+        }
+    }
+
+    /// Returns the path to the DRM node of this adapter.
+    ///
+    /// This is a render node (e.g. `/dev/dri/renderD128`), unless the device has none, in which
+    /// case it is the primary node (e.g. `/dev/dri/card0`).
+    #[inline]
+    pub fn render_node(&self) -> &Path {
+        &self.render_node
+    }
+
+    /// Returns the name of the kernel driver behind this adapter (e.g. `i915` or `vgem`), if
+    /// known.
+    #[inline]
+    pub fn driver(&self) -> Option<&str> {
+        self.driver.as_ref().map(|driver| &**driver)
+    }
+
+    /// Returns true if this adapter renders on the CPU.
+    #[inline]
+    pub fn is_software(&self) -> bool {
+        match self.driver {
+            Some(ref driver) => SOFTWARE_DRIVERS.contains(&&**driver),
+            None => false,
+        }
+    }
+}
+
+/// A thread-local handle to a device.
+///
+/// Devices contain most of the relevant surface management methods.
+pub struct Device {
+    pub(crate) native_connection: Arc<NativeConnectionWrapper>,
+    pub(crate) adapter: Adapter,
+}
+
+/// Wraps an adapter.
+///
+/// On GBM, devices and adapters are essentially identical types.
+#[derive(Clone)]
+pub struct NativeDevice {
+    /// The hardware adapter corresponding to this device.
+    pub adapter: Adapter,
+}
+
+impl Device {
+    #[inline]
+    pub(crate) fn new(connection: &Connection, adapter: &Adapter) -> Result<Device, Error> {
+        // Adapters on other devices than the connection's get a GBM device and display of their
+        // own. The connection's node is preferred, since only primary nodes can drive displays.
+        let native_connection = if adapter.is_on_node(&connection.native_connection.render_node) {
+            connection.native_connection.clone()
+        } else {
+            Arc::new(NativeConnectionWrapper::open(&adapter.render_node)?)
+        };
+
+        Ok(Device { native_connection, adapter: (*adapter).clone() })
+    }
+
+    /// Returns the native device corresponding to this device.
+    ///
+    /// This method is essentially an alias for the `adapter()` method on GBM, since there is no
+    /// explicit concept of a device on this backend.
+    #[inline]
+    pub fn native_device(&self) -> NativeDevice {
+        NativeDevice { adapter: self.adapter() }
+    }
+
+    /// Returns the display server connection that this device was created with.
+    #[inline]
+    pub fn connection(&self) -> Connection {
+        Connection { native_connection: self.native_connection.clone() }
+    }
+
+    /// Returns the adapter that this device was created with.
+    #[inline]
+    pub fn adapter(&self) -> Adapter {
+        self.adapter.clone()
+    }
+
+    /// Returns the OpenGL API flavor that this device supports (OpenGL or OpenGL ES).
+    #[inline]
+    pub fn gl_api(&self) -> GLApi {
+        GLApi::GL
+    }
+}
+
+// Returns the paths of the device nodes with the given prefix, in the order of their names.
+fn device_nodes(prefix: &str) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(DRI_DIRECTORY) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut device_nodes: Vec<PathBuf> = entries.filter_map(|entry| {
+        let entry = entry.ok()?;
+        if entry.file_name().to_string_lossy().starts_with(prefix) {
+            Some(entry.path())
+        } else {
+            None
+        }
+    }).collect();
+    device_nodes.sort();
+    device_nodes
+}
+
+// Returns the sysfs directory of the device behind a node, which all nodes of a device share.
+fn sysfs_device(node: &Path) -> Option<PathBuf> {
+    let node_name = node.file_name()?;
+    fs::canonicalize(Path::new("/sys/class/drm").join(node_name).join("device")).ok()
+}
@@ -0,0 +1,334 @@
+// surfman/surfman/src/platform/unix/gbm/ffi.rs
+//
+//! Bindings to `libgbm` and `libdrm`, which are loaded at runtime so that they aren't link-time
+//! dependencies.
+
+#![allow(non_camel_case_types)]
+
+use libc::{RTLD_LAZY, dlopen, dlsym};
+use std::mem;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::slice;
+
+pub(crate) enum gbm_device {}
+pub(crate) enum gbm_bo {}
+pub(crate) enum gbm_surface {}
+
+pub(crate) const GBM_BO_USE_SCANOUT: u32 = 1 << 0;
+pub(crate) const GBM_BO_USE_RENDERING: u32 = 1 << 2;
+
+pub(crate) const GBM_BO_IMPORT_FD_MODIFIER: u32 = 0x5504;
+
+pub(crate) const GBM_MAX_PLANES: usize = 4;
+
+#[repr(C)]
+pub(crate) struct gbm_import_fd_modifier_data {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) format: u32,
+    pub(crate) num_fds: u32,
+    pub(crate) fds: [c_int; GBM_MAX_PLANES],
+    pub(crate) strides: [c_int; GBM_MAX_PLANES],
+    pub(crate) offsets: [c_int; GBM_MAX_PLANES],
+    pub(crate) modifier: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) union gbm_bo_handle {
+    pub(crate) u32_: u32,
+    // Gives the union the size of the C one, which also has pointer and 64-bit members.
+    #[allow(dead_code)]
+    u64_: u64,
+}
+
+pub(crate) struct GBMFunctions {
+    pub(crate) create_device: unsafe extern "C" fn(fd: c_int) -> *mut gbm_device,
+    pub(crate) device_destroy: unsafe extern "C" fn(device: *mut gbm_device),
+    pub(crate) device_is_format_supported: unsafe extern "C" fn(device: *mut gbm_device,
+                                                                format: u32,
+                                                                usage: u32)
+                                                                -> c_int,
+    pub(crate) bo_create: unsafe extern "C" fn(device: *mut gbm_device,
+                                               width: u32,
+                                               height: u32,
+                                               format: u32,
+                                               flags: u32)
+                                               -> *mut gbm_bo,
+    pub(crate) bo_import: unsafe extern "C" fn(device: *mut gbm_device,
+                                               type_: u32,
+                                               buffer: *mut c_void,
+                                               flags: u32)
+                                               -> *mut gbm_bo,
+    pub(crate) bo_destroy: unsafe extern "C" fn(bo: *mut gbm_bo),
+    pub(crate) bo_get_fd: unsafe extern "C" fn(bo: *mut gbm_bo) -> c_int,
+    pub(crate) bo_get_width: unsafe extern "C" fn(bo: *mut gbm_bo) -> u32,
+    pub(crate) bo_get_height: unsafe extern "C" fn(bo: *mut gbm_bo) -> u32,
+    pub(crate) bo_get_format: unsafe extern "C" fn(bo: *mut gbm_bo) -> u32,
+    pub(crate) bo_get_modifier: unsafe extern "C" fn(bo: *mut gbm_bo) -> u64,
+    pub(crate) bo_get_plane_count: unsafe extern "C" fn(bo: *mut gbm_bo) -> c_int,
+    pub(crate) bo_get_offset: unsafe extern "C" fn(bo: *mut gbm_bo, plane: c_int) -> u32,
+    pub(crate) bo_get_stride_for_plane: unsafe extern "C" fn(bo: *mut gbm_bo, plane: c_int)
+                                                             -> u32,
+    pub(crate) bo_get_handle_for_plane: unsafe extern "C" fn(bo: *mut gbm_bo, plane: c_int)
+                                                             -> gbm_bo_handle,
+    pub(crate) surface_create: unsafe extern "C" fn(device: *mut gbm_device,
+                                                    width: u32,
+                                                    height: u32,
+                                                    format: u32,
+                                                    flags: u32)
+                                                    -> *mut gbm_surface,
+    pub(crate) surface_destroy: unsafe extern "C" fn(surface: *mut gbm_surface),
+    pub(crate) surface_lock_front_buffer: unsafe extern "C" fn(surface: *mut gbm_surface)
+                                                               -> *mut gbm_bo,
+    pub(crate) surface_release_buffer: unsafe extern "C" fn(surface: *mut gbm_surface,
+                                                            bo: *mut gbm_bo),
+}
+
+lazy_static! {
+    // `None` if `libgbm` isn't installed, or is too old to have the functions we need.
+    pub(crate) static ref GBM_FUNCTIONS: Option<GBMFunctions> = unsafe { GBMFunctions::load() };
+}
+
+// Returns the GBM functions, which are always loaded once a connection has been opened.
+pub(crate) fn gbm_functions() -> &'static GBMFunctions {
+    GBM_FUNCTIONS.as_ref().expect("`libgbm` isn't loaded!")
+}
+
+impl GBMFunctions {
+    unsafe fn load() -> Option<GBMFunctions> {
+        let library = dlopen(&b"libgbm.so.1\0"[0] as *const u8 as *const _, RTLD_LAZY);
+        if library.is_null() {
+            return None;
+        }
+
+        let symbol = |name: &[u8]| {
+            let symbol = dlsym(library, &name[0] as *const u8 as *const c_char);
+            if symbol.is_null() { None } else { Some(symbol) }
+        };
+
+        Some(GBMFunctions {
+            create_device: mem::transmute::<*mut c_void, _>(symbol(b"gbm_create_device\0")?),
+            device_destroy: mem::transmute::<*mut c_void, _>(symbol(b"gbm_device_destroy\0")?),
+            device_is_format_supported:
+                mem::transmute::<*mut c_void, _>(symbol(b"gbm_device_is_format_supported\0")?),
+            bo_create: mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_create\0")?),
+            bo_import: mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_import\0")?),
+            bo_destroy: mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_destroy\0")?),
+            bo_get_fd: mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_get_fd\0")?),
+            bo_get_width: mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_get_width\0")?),
+            bo_get_height: mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_get_height\0")?),
+            bo_get_format: mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_get_format\0")?),
+            bo_get_modifier: mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_get_modifier\0")?),
+            bo_get_plane_count:
+                mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_get_plane_count\0")?),
+            bo_get_offset: mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_get_offset\0")?),
+            bo_get_stride_for_plane:
+                mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_get_stride_for_plane\0")?),
+            bo_get_handle_for_plane:
+                mem::transmute::<*mut c_void, _>(symbol(b"gbm_bo_get_handle_for_plane\0")?),
+            surface_create: mem::transmute::<*mut c_void, _>(symbol(b"gbm_surface_create\0")?),
+            surface_destroy:
+                mem::transmute::<*mut c_void, _>(symbol(b"gbm_surface_destroy\0")?),
+            surface_lock_front_buffer:
+                mem::transmute::<*mut c_void, _>(symbol(b"gbm_surface_lock_front_buffer\0")?),
+            surface_release_buffer:
+                mem::transmute::<*mut c_void, _>(symbol(b"gbm_surface_release_buffer\0")?),
+        })
+    }
+}
+
+pub(crate) const DRM_DISPLAY_MODE_LEN: usize = 32;
+
+pub(crate) const DRM_MODE_CONNECTED: c_int = 1;
+pub(crate) const DRM_MODE_TYPE_PREFERRED: u32 = 1 << 3;
+pub(crate) const DRM_MODE_PAGE_FLIP_EVENT: u32 = 0x01;
+
+// The version of `drmEventContext` that has the page flip handler, but not the CRTC-aware one.
+pub(crate) const DRM_EVENT_CONTEXT_VERSION: c_int = 2;
+
+#[repr(C)]
+pub(crate) struct drmModeRes {
+    pub(crate) count_fbs: c_int,
+    pub(crate) fbs: *mut u32,
+    pub(crate) count_crtcs: c_int,
+    pub(crate) crtcs: *mut u32,
+    pub(crate) count_connectors: c_int,
+    pub(crate) connectors: *mut u32,
+    pub(crate) count_encoders: c_int,
+    pub(crate) encoders: *mut u32,
+    pub(crate) min_width: u32,
+    pub(crate) max_width: u32,
+    pub(crate) min_height: u32,
+    pub(crate) max_height: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct drmModeModeInfo {
+    pub(crate) clock: u32,
+    pub(crate) hdisplay: u16,
+    pub(crate) hsync_start: u16,
+    pub(crate) hsync_end: u16,
+    pub(crate) htotal: u16,
+    pub(crate) hskew: u16,
+    pub(crate) vdisplay: u16,
+    pub(crate) vsync_start: u16,
+    pub(crate) vsync_end: u16,
+    pub(crate) vtotal: u16,
+    pub(crate) vscan: u16,
+    pub(crate) vrefresh: u32,
+    pub(crate) flags: u32,
+    pub(crate) type_: u32,
+    pub(crate) name: [c_char; DRM_DISPLAY_MODE_LEN],
+}
+
+#[repr(C)]
+pub(crate) struct drmModeConnector {
+    pub(crate) connector_id: u32,
+    pub(crate) encoder_id: u32,
+    pub(crate) connector_type: u32,
+    pub(crate) connector_type_id: u32,
+    pub(crate) connection: c_int,
+    pub(crate) mm_width: u32,
+    pub(crate) mm_height: u32,
+    pub(crate) subpixel: c_int,
+    pub(crate) count_modes: c_int,
+    pub(crate) modes: *mut drmModeModeInfo,
+    pub(crate) count_props: c_int,
+    pub(crate) props: *mut u32,
+    pub(crate) prop_values: *mut u64,
+    pub(crate) count_encoders: c_int,
+    pub(crate) encoders: *mut u32,
+}
+
+#[repr(C)]
+pub(crate) struct drmModeEncoder {
+    pub(crate) encoder_id: u32,
+    pub(crate) encoder_type: u32,
+    pub(crate) crtc_id: u32,
+    pub(crate) possible_crtcs: u32,
+    pub(crate) possible_clones: u32,
+}
+
+#[repr(C)]
+pub(crate) struct drmModeCrtc {
+    pub(crate) crtc_id: u32,
+    pub(crate) buffer_id: u32,
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) mode_valid: c_int,
+    pub(crate) mode: drmModeModeInfo,
+    pub(crate) gamma_size: c_int,
+}
+
+pub(crate) type drmEventHandler = unsafe extern "C" fn(fd: c_int,
+                                                       sequence: c_uint,
+                                                       tv_sec: c_uint,
+                                                       tv_usec: c_uint,
+                                                       user_data: *mut c_void);
+
+#[repr(C)]
+pub(crate) struct drmEventContext {
+    pub(crate) version: c_int,
+    pub(crate) vblank_handler: Option<drmEventHandler>,
+    pub(crate) page_flip_handler: Option<drmEventHandler>,
+}
+
+pub(crate) struct DRMFunctions {
+    pub(crate) mode_get_resources: unsafe extern "C" fn(fd: c_int) -> *mut drmModeRes,
+    pub(crate) mode_free_resources: unsafe extern "C" fn(resources: *mut drmModeRes),
+    pub(crate) mode_get_connector: unsafe extern "C" fn(fd: c_int, connector_id: u32)
+                                                        -> *mut drmModeConnector,
+    pub(crate) mode_free_connector: unsafe extern "C" fn(connector: *mut drmModeConnector),
+    pub(crate) mode_get_encoder: unsafe extern "C" fn(fd: c_int, encoder_id: u32)
+                                                      -> *mut drmModeEncoder,
+    pub(crate) mode_free_encoder: unsafe extern "C" fn(encoder: *mut drmModeEncoder),
+    pub(crate) mode_get_crtc: unsafe extern "C" fn(fd: c_int, crtc_id: u32) -> *mut drmModeCrtc,
+    pub(crate) mode_free_crtc: unsafe extern "C" fn(crtc: *mut drmModeCrtc),
+    pub(crate) mode_set_crtc: unsafe extern "C" fn(fd: c_int,
+                                                   crtc_id: u32,
+                                                   buffer_id: u32,
+                                                   x: u32,
+                                                   y: u32,
+                                                   connectors: *mut u32,
+                                                   count: c_int,
+                                                   mode: *mut drmModeModeInfo)
+                                                   -> c_int,
+    pub(crate) mode_add_fb2: unsafe extern "C" fn(fd: c_int,
+                                                  width: u32,
+                                                  height: u32,
+                                                  pixel_format: u32,
+                                                  bo_handles: *const u32,
+                                                  pitches: *const u32,
+                                                  offsets: *const u32,
+                                                  buffer_id: *mut u32,
+                                                  flags: u32)
+                                                  -> c_int,
+    pub(crate) mode_rm_fb: unsafe extern "C" fn(fd: c_int, buffer_id: u32) -> c_int,
+    pub(crate) mode_page_flip: unsafe extern "C" fn(fd: c_int,
+                                                    crtc_id: u32,
+                                                    fb_id: u32,
+                                                    flags: u32,
+                                                    user_data: *mut c_void)
+                                                    -> c_int,
+    pub(crate) handle_event: unsafe extern "C" fn(fd: c_int,
+                                                  event_context: *mut drmEventContext)
+                                                  -> c_int,
+}
+
+lazy_static! {
+    // `None` if `libdrm` isn't installed. Only needed to drive displays.
+    pub(crate) static ref DRM_FUNCTIONS: Option<DRMFunctions> = unsafe { DRMFunctions::load() };
+}
+
+// Returns the DRM functions, which are always loaded once a native widget has been created.
+pub(crate) fn drm_functions() -> &'static DRMFunctions {
+    DRM_FUNCTIONS.as_ref().expect("`libdrm` isn't loaded!")
+}
+
+impl DRMFunctions {
+    unsafe fn load() -> Option<DRMFunctions> {
+        let library = dlopen(&b"libdrm.so.2\0"[0] as *const u8 as *const _, RTLD_LAZY);
+        if library.is_null() {
+            return None;
+        }
+
+        let symbol = |name: &[u8]| {
+            let symbol = dlsym(library, &name[0] as *const u8 as *const c_char);
+            if symbol.is_null() { None } else { Some(symbol) }
+        };
+
+        Some(DRMFunctions {
+            mode_get_resources:
+                mem::transmute::<*mut c_void, _>(symbol(b"drmModeGetResources\0")?),
+            mode_free_resources:
+                mem::transmute::<*mut c_void, _>(symbol(b"drmModeFreeResources\0")?),
+            mode_get_connector:
+                mem::transmute::<*mut c_void, _>(symbol(b"drmModeGetConnector\0")?),
+            mode_free_connector:
+                mem::transmute::<*mut c_void, _>(symbol(b"drmModeFreeConnector\0")?),
+            mode_get_encoder: mem::transmute::<*mut c_void, _>(symbol(b"drmModeGetEncoder\0")?),
+            mode_free_encoder:
+                mem::transmute::<*mut c_void, _>(symbol(b"drmModeFreeEncoder\0")?),
+            mode_get_crtc: mem::transmute::<*mut c_void, _>(symbol(b"drmModeGetCrtc\0")?),
+            mode_free_crtc: mem::transmute::<*mut c_void, _>(symbol(b"drmModeFreeCrtc\0")?),
+            mode_set_crtc: mem::transmute::<*mut c_void, _>(symbol(b"drmModeSetCrtc\0")?),
+            mode_add_fb2: mem::transmute::<*mut c_void, _>(symbol(b"drmModeAddFB2\0")?),
+            mode_rm_fb: mem::transmute::<*mut c_void, _>(symbol(b"drmModeRmFB\0")?),
+            mode_page_flip: mem::transmute::<*mut c_void, _>(symbol(b"drmModePageFlip\0")?),
+            handle_event: mem::transmute::<*mut c_void, _>(symbol(b"drmHandleEvent\0")?),
+        })
+    }
+}
+
+// Views an array returned by `libdrm`, which may be null if it's empty.
+pub(crate) unsafe fn drm_array<'a, T>(pointer: *const T, count: c_int) -> &'a [T] {
+    if pointer.is_null() || count <= 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(pointer, count as usize)
+    }
+}
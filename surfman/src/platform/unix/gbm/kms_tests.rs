@@ -0,0 +1,101 @@
+// surfman/surfman/src/platform/unix/gbm/kms_tests.rs
+//
+//! Unit tests for driving displays through KMS.
+//!
+//! These only run on machines with a DRM primary node that has a display attached; elsewhere they
+//! return early.
+
+#![allow(missing_docs)]
+
+use crate::{ContextAttributeFlags, ContextAttributes, Error, GLVersion, SurfaceAccess};
+use crate::{SurfaceColorspace, SurfaceType};
+use super::connection::{Connection, Connector};
+use super::device::Adapter;
+
+// Opens every primary node that can be rendered with, along with its connected connectors.
+fn connected_primary_nodes() -> Vec<(Connection, Vec<Connector>)> {
+    Adapter::primary_nodes().into_iter().filter_map(|primary_node| {
+        let connection = Connection::from_primary_node(&primary_node).ok()?;
+        let connectors = match connection.connectors() {
+            Ok(connectors) => connectors,
+            Err(Error::RequiredExtensionUnavailable) |
+            Err(Error::UnsupportedOnThisPlatform) => return None,
+            Err(err) => panic!("Failed to enumerate connectors: {:?}", err),
+        };
+        let connectors: Vec<_> = connectors.into_iter()
+                                           .filter(|connector| connector.is_connected())
+                                           .collect();
+        if connectors.is_empty() {
+            None
+        } else {
+            Some((connection, connectors))
+        }
+    }).collect()
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_kms_mode_enumeration() {
+    for (_, connectors) in connected_primary_nodes() {
+        for connector in connectors {
+            assert!(!connector.name().is_empty());
+            assert!(!connector.modes().is_empty(),
+                    "Connected connector {} has no modes!",
+                    connector.name());
+            for mode in connector.modes() {
+                assert!(mode.size().width > 0 && mode.size().height > 0, "{:?}", mode);
+                assert!(!mode.name().is_empty(), "{:?}", mode);
+            }
+
+            // The preferred mode is the one the display asks for, falling back to the first.
+            let preferred_mode = connector.preferred_mode().unwrap();
+            match connector.modes().iter().find(|mode| mode.is_preferred()) {
+                Some(mode) => assert_eq!(mode.name(), preferred_mode.name()),
+                None => assert_eq!(connector.modes()[0].name(), preferred_mode.name()),
+            }
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_kms_surface_creation() {
+    for (connection, connectors) in connected_primary_nodes() {
+        let adapter = connection.create_adapter().unwrap();
+        let mut device = match connection.create_device(&adapter) {
+            Ok(device) => device,
+            Err(Error::RequiredExtensionUnavailable) => continue,
+            Err(err) => panic!("Failed to create device: {:?}", err),
+        };
+        let context_descriptor = device.create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(3, 0),
+            flags: ContextAttributeFlags::empty(),
+            samples: 0,
+        }).unwrap();
+        let mut context = device.create_context(&context_descriptor).unwrap();
+
+        for connector in connectors {
+            let mode = connector.preferred_mode().unwrap();
+            let native_widget = connection.create_native_widget(&connector, &mode).unwrap();
+
+            // Surfaces are only created here, not presented, so this doesn't need to be the DRM
+            // master and leaves the display alone.
+            let surface_type = SurfaceType::Widget {
+                native_widget,
+                colorspace: SurfaceColorspace::Default,
+            };
+            let mut surface = match device.create_surface(&context,
+                                                          SurfaceAccess::GPUOnly,
+                                                          surface_type) {
+                Ok(surface) => surface,
+                Err(Error::SurfaceCreationFailed(_)) => {
+                    // The driver can't scan out buffers in this format.
+                    continue;
+                }
+                Err(err) => panic!("Failed to create KMS surface: {:?}", err),
+            };
+            assert_eq!(device.surface_info(&surface).size, mode.size());
+            device.destroy_surface(&mut context, &mut surface).unwrap();
+        }
+
+        device.destroy_context(&mut context).unwrap();
+    }
+}
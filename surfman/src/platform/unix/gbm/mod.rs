@@ -0,0 +1,28 @@
+// surfman/surfman/src/platform/unix/gbm/mod.rs
+//
+//! The GBM backend, which renders on a DRM render node without any display server. Generic
+//! surfaces are GBM buffer objects, so they can be shared as DMA buffers and scanned out.
+//!
+//! Connections to DRM primary nodes can also drive displays directly through KMS: native widgets
+//! are display connectors, and presenting a widget surface flips its buffer onto the screen.
+
+pub mod connection;
+pub mod context;
+pub mod device;
+pub mod surface;
+
+mod ffi;
+
+#[path = "../../../implementation/mod.rs"]
+mod implementation;
+
+#[cfg(test)]
+#[path = "../../../tests.rs"]
+mod tests;
+
+#[cfg(test)]
+#[path = "../../generic/egl/tests.rs"]
+mod egl_tests;
+
+#[cfg(test)]
+mod kms_tests;
@@ -0,0 +1,1016 @@
+// surfman/surfman/src/platform/unix/gbm/surface.rs
+//
+//! Surfaces backed by GBM buffer objects, and widget surfaces that are flipped onto displays.
+
+use crate::egl::types::{EGLDisplay, EGLint};
+use crate::egl;
+use crate::gl::types::{GLenum, GLuint};
+use crate::gl;
+use crate::platform::generic::egl::context;
+use crate::platform::generic::egl::dmabuf;
+use crate::platform::generic::egl::ffi::EGLImageKHR;
+use crate::platform::generic::egl::surface::{self, EGLBackedSurface, EGLSurfaceDataGuard};
+use crate::platform::generic::egl::surface::EGLSurfaceTexture;
+use crate::{Error, PixelReadback, ReadbackFormat, SurfaceAccess, SurfaceDescriptor};
+use crate::{SurfaceColorspace, SurfaceFormat, SurfaceInfo, SurfaceType, WindowingApiError};
+use super::connection::Mode;
+use super::context::{Context, GL_FUNCTIONS};
+use super::device::Device;
+use super::ffi::{DRM_EVENT_CONTEXT_VERSION, DRM_FUNCTIONS, DRM_MODE_PAGE_FLIP_EVENT};
+use super::ffi::{GBM_BO_IMPORT_FD_MODIFIER, GBM_BO_USE_RENDERING, GBM_BO_USE_SCANOUT};
+use super::ffi::{GBM_MAX_PLANES, drmEventContext, drmModeCrtc, drm_functions, gbm_bo};
+use super::ffi::{gbm_device, gbm_functions, gbm_import_fd_modifier_data, gbm_surface};
+
+use euclid::default::{Rect, Size2D};
+use std::cell::Cell;
+use std::io;
+use std::os::raw::{c_int, c_uint, c_void};
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::time::Duration;
+
+pub use crate::platform::generic::egl::dmabuf::{DRM_FORMAT_MOD_INVALID, DmabufDescriptor};
+pub use crate::platform::generic::egl::dmabuf::DmabufPlane;
+pub use crate::platform::generic::egl::shared_surface::SharedSurfaceHandle;
+
+// FIXME(pcwalton): Is this right, or should it be `TEXTURE_EXTERNAL_OES`?
+const SURFACE_GL_TEXTURE_TARGET: GLenum = gl::TEXTURE_2D;
+
+/// Represents a hardware buffer of pixels that can be rendered to via the CPU or GPU and either
+/// displayed in a native widget or bound to a texture for reading.
+/// 
+/// Surfaces come in two varieties: generic and widget surfaces. Generic surfaces can be bound to a
+/// texture but cannot be displayed in a widget (without using other APIs such as Core Animation,
+/// DirectComposition, or XPRESENT). Widget surfaces are the opposite: they can be displayed in a
+/// widget but not bound to a texture.
+/// 
+/// Surfaces are specific to a given context and cannot be rendered to from any context other than
+/// the one they were created with. However, they can be *read* from any context on any thread (as
+/// long as that context shares the same adapter and connection), by wrapping them in a
+/// `SurfaceTexture`.
+/// 
+/// Depending on the platform, each surface may be internally double-buffered.
+/// 
+/// On GBM, generic surfaces are stored in GBM buffer objects, which can be shared as DMA buffers
+/// and, where the driver allows it, scanned out to a display. Widget surfaces render to the
+/// buffers of a GBM surface, which are flipped onto a display when the surface is presented.
+/// 
+/// Surfaces must be destroyed with the `destroy_surface()` method, or a panic will occur.
+#[derive(Debug)]
+pub struct Surface {
+    pub(crate) egl_backed: EGLBackedSurface,
+    pub(crate) storage: SurfaceStorage,
+}
+
+#[derive(Debug)]
+pub(crate) enum SurfaceStorage {
+    // The buffer object that a generic surface renders to.
+    Buffer(*mut gbm_bo),
+    // The GBM surface that a widget surface renders to, and the display it is shown on.
+    Scanout(Box<Scanout>),
+}
+
+#[derive(Debug)]
+pub(crate) struct Scanout {
+    gbm_surface: *mut gbm_surface,
+    native_widget: NativeWidget,
+    // How the CRTC was set up before the widget took it over, so that it can be restored.
+    saved_crtc: *mut drmModeCrtc,
+    // The buffer on screen, once the surface has been presented.
+    front_buffer: Option<ScanoutBuffer>,
+}
+
+#[derive(Debug)]
+struct ScanoutBuffer {
+    bo: *mut gbm_bo,
+    framebuffer_id: u32,
+}
+
+/// Represents an OpenGL texture that wraps a surface.
+/// 
+/// Reading from the associated OpenGL texture reads from the surface. It is undefined behavior to
+/// write to such a texture (e.g. by binding it to a framebuffer and rendering to that
+/// framebuffer).
+/// 
+/// Surface textures are local to a context, but that context does not have to be the same context
+/// as that associated with the underlying surface. The texture must be destroyed with the
+/// `destroy_surface_texture()` method, or a panic will occur.
+#[derive(Debug)]
+pub struct SurfaceTexture {
+    pub(crate) egl_backed: EGLSurfaceTexture,
+    storage: SurfaceStorage,
+}
+
+/// A display connector, the CRTC that drives it, and the mode to show widget surfaces in.
+///
+/// Native widgets are created with `Connection::create_native_widget()`.
+#[derive(Clone, Debug)]
+pub struct NativeWidget {
+    pub(crate) connector_id: u32,
+    pub(crate) crtc_id: u32,
+    pub(crate) mode: Mode,
+}
+
+unsafe impl Send for Surface {}
+
+/// A request to be told when the compositor wants the next frame of a widget surface.
+///
+/// Frame callbacks aren't supported on this backend, so this type can't be constructed.
+#[derive(Debug)]
+pub enum FrameCallback {}
+
+impl Device {
+    /// Creates either a generic or a widget surface, depending on the supplied surface type.
+    /// 
+    /// Only the given context may ever render to the surface, but generic surfaces can be wrapped
+    /// up in a `SurfaceTexture` for reading by other contexts.
+    pub fn create_surface(&mut self,
+                          context: &Context,
+                          access: SurfaceAccess,
+                          surface_type: SurfaceType<NativeWidget>)
+                          -> Result<Surface, Error> {
+        match surface_type {
+            SurfaceType::Generic { size, descriptor } => {
+                self.create_generic_surface(context, access, &size, &descriptor)
+            }
+            SurfaceType::Widget { native_widget, colorspace } => {
+                unsafe {
+                    self.create_widget_surface(context, access, native_widget, colorspace)
+                }
+            }
+        }
+    }
+
+    fn create_generic_surface(&mut self,
+                              context: &Context,
+                              access: SurfaceAccess,
+                              size: &Size2D<i32>,
+                              descriptor: &SurfaceDescriptor)
+                              -> Result<Surface, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        let context_descriptor = self.context_descriptor(context);
+        let context_attributes = self.context_descriptor_attributes(&context_descriptor);
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                let egl_display = self.native_connection.egl_display;
+                let bo = create_bo(self.native_connection.gbm_device, size, descriptor.format)?;
+                let egl_image = match import_bo(egl_display, bo, size) {
+                    Ok(egl_image) => egl_image,
+                    Err(err) => {
+                        (gbm_functions().bo_destroy)(bo);
+                        return Err(err);
+                    }
+                };
+
+                match EGLBackedSurface::new_generic_from_egl_image(gl,
+                                                                   egl_display,
+                                                                   egl_image,
+                                                                   context.egl_backed.egl_context,
+                                                                   context.egl_backed.id,
+                                                                   access,
+                                                                   &context_attributes,
+                                                                   size,
+                                                                   descriptor) {
+                    Ok(egl_backed) => {
+                        Ok(Surface { egl_backed, storage: SurfaceStorage::Buffer(bo) })
+                    }
+                    Err(err) => {
+                        (gbm_functions().bo_destroy)(bo);
+                        Err(err)
+                    }
+                }
+            }
+        })
+    }
+
+    unsafe fn create_widget_surface(&mut self,
+                                    context: &Context,
+                                    access: SurfaceAccess,
+                                    native_widget: NativeWidget,
+                                    colorspace: SurfaceColorspace)
+                                    -> Result<Surface, Error> {
+        let drm = match *DRM_FUNCTIONS {
+            Some(ref drm) => drm,
+            None => return Err(Error::IncompatibleNativeWidget),
+        };
+
+        // Render nodes can't drive displays, so this fails unless the device is on the primary
+        // node that the widget came from.
+        let saved_crtc = (drm.mode_get_crtc)(self.native_connection.fd(), native_widget.crtc_id);
+        if saved_crtc.is_null() {
+            return Err(Error::IncompatibleNativeWidget);
+        }
+
+        // The buffers must be in the format that EGL expects for the context's config.
+        let egl_display = self.native_connection.egl_display;
+        let context_descriptor = self.context_descriptor(context);
+        let egl_config = context::egl_config_from_id(egl_display, context_descriptor.egl_config_id);
+        let format = context::get_config_attr(egl_display,
+                                              egl_config,
+                                              egl::NATIVE_VISUAL_ID as EGLint) as u32;
+
+        let size = native_widget.mode.size();
+        let gbm_surface = (gbm_functions().surface_create)(self.native_connection.gbm_device,
+                                                           size.width as u32,
+                                                           size.height as u32,
+                                                           format,
+                                                           GBM_BO_USE_SCANOUT |
+                                                           GBM_BO_USE_RENDERING);
+        if gbm_surface.is_null() {
+            (drm.mode_free_crtc)(saved_crtc);
+            return Err(Error::SurfaceCreationFailed(WindowingApiError::BadNativeWindow));
+        }
+
+        match EGLBackedSurface::new_window(egl_display,
+                                           egl_config,
+                                           gbm_surface as *mut c_void,
+                                           context.egl_backed.egl_context,
+                                           context.egl_backed.id,
+                                           access,
+                                           &size,
+                                           colorspace) {
+            Ok(egl_backed) => {
+                let scanout = Scanout {
+                    gbm_surface,
+                    native_widget,
+                    saved_crtc,
+                    front_buffer: None,
+                };
+                Ok(Surface { egl_backed, storage: SurfaceStorage::Scanout(Box::new(scanout)) })
+            }
+            Err(err) => {
+                (gbm_functions().surface_destroy)(gbm_surface);
+                (drm.mode_free_crtc)(saved_crtc);
+                Err(err)
+            }
+        }
+    }
+
+    /// Creates a generic surface backed by an existing Linux DMA buffer, such as a frame from a
+    /// camera or a hardware video decoder, without copying its contents.
+    /// 
+    /// The resulting surface is associated with the given context and can be wrapped in a
+    /// `SurfaceTexture` like any other generic surface. The caller retains ownership of the file
+    /// descriptors in the descriptor and may close them once this method returns. The surface is
+    /// not accessible from the CPU.
+    /// 
+    /// Only RGB formats with an alpha channel are supported. YUV formats such as NV12 and YUYV,
+    /// which many cameras and video decoders produce, can't be sampled as `GL_TEXTURE_2D`, and
+    /// formats whose alpha bits are padding, such as XR24, have no surface format equivalent, so
+    /// both return an `UnsupportedSurfaceFormat` error.
+    /// 
+    /// The buffer is imported into GBM as well as EGL, so the surface can be exported again with
+    /// `export_surface_dmabuf()`. Requires `EGL_EXT_image_dma_buf_import`, plus
+    /// `EGL_EXT_image_dma_buf_import_modifiers` if the descriptor has an explicit modifier; if
+    /// these are unavailable, a `RequiredExtensionUnavailable` error is returned.
+    pub fn create_surface_from_dmabuf(&mut self,
+                                      context: &Context,
+                                      descriptor: DmabufDescriptor)
+                                      -> Result<Surface, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        let context_descriptor = self.context_descriptor(context);
+        let context_attributes = self.context_descriptor_attributes(&context_descriptor);
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                let egl_display = self.native_connection.egl_display;
+                let format = dmabuf::surface_format(descriptor.fourcc)?;
+                let bo = import_dmabuf(self.native_connection.gbm_device, &descriptor)?;
+                let egl_image = match dmabuf::import_egl_image(egl_display, &descriptor) {
+                    Ok(egl_image) => egl_image,
+                    Err(err) => {
+                        (gbm_functions().bo_destroy)(bo);
+                        return Err(err);
+                    }
+                };
+
+                match EGLBackedSurface::new_from_egl_image(gl,
+                                                           egl_display,
+                                                           egl_image,
+                                                           context.egl_backed.egl_context,
+                                                           context.egl_backed.id,
+                                                           SurfaceAccess::GPUOnly,
+                                                           &context_attributes,
+                                                           &descriptor.size,
+                                                           format) {
+                    Ok(egl_backed) => {
+                        Ok(Surface { egl_backed, storage: SurfaceStorage::Buffer(bo) })
+                    }
+                    Err(err) => {
+                        (gbm_functions().bo_destroy)(bo);
+                        Err(err)
+                    }
+                }
+            }
+        })
+    }
+
+    /// Creates a surface texture from an existing generic surface for use with the given context.
+    /// 
+    /// The surface texture is local to the supplied context and takes ownership of the surface.
+    /// Destroying the surface texture allows you to retrieve the surface again.
+    /// 
+    /// *The supplied context does not have to be the same context that the surface is associated
+    /// with.* This allows you to render to a surface in one context and sample from that surface
+    /// in another context.
+    /// 
+    /// Calling this method on a widget surface returns a `WidgetAttached` error.
+    pub fn create_surface_texture(&self, context: &mut Context, surface: Surface)
+                                  -> Result<SurfaceTexture, (Error, Surface)> {
+        let _guard = match self.temporarily_make_context_current(context) {
+            Ok(guard) => guard,
+            Err(err) => return Err((err, surface)),
+        };
+
+        let Surface { egl_backed, storage } = surface;
+        GL_FUNCTIONS.with(|gl| {
+            match egl_backed.to_surface_texture(gl) {
+                Ok(egl_backed) => Ok(SurfaceTexture { egl_backed, storage }),
+                Err((err, egl_backed)) => Err((err, Surface { egl_backed, storage })),
+            }
+        })
+    }
+
+    /// Destroys a surface.
+    /// 
+    /// The supplied context must be the context the surface is associated with, or this returns
+    /// an `IncompatibleSurface` error.
+    /// 
+    /// You must explicitly call this method to dispose of a surface. Otherwise, a panic occurs in
+    /// the `drop` method.
+    /// 
+    /// Destroying a widget surface that has been presented puts the display back the way it was
+    /// before the surface was first presented.
+    pub fn destroy_surface(&self, context: &mut Context, surface: &mut Surface)
+                           -> Result<(), Error> {
+        GL_FUNCTIONS.with(|gl| {
+            let egl_display = self.native_connection.egl_display;
+            let window = surface.egl_backed.destroy(gl, egl_display, context.egl_backed.id)?;
+            unsafe {
+                match surface.storage {
+                    SurfaceStorage::Buffer(ref mut bo) => {
+                        debug_assert!(window.is_none());
+                        (gbm_functions().bo_destroy)(*bo);
+                        *bo = ptr::null_mut();
+                    }
+                    SurfaceStorage::Scanout(ref mut scanout) => {
+                        scanout.destroy(self.native_connection.fd())
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Destroys a surface texture and returns the underlying surface.
+    /// 
+    /// The supplied context must be the same context the surface texture was created with, or an
+    /// `IncompatibleSurfaceTexture` error is returned.
+    /// 
+    /// All surface textures must be explicitly destroyed with this function, or a panic will
+    /// occur.
+    pub fn destroy_surface_texture(&self,
+                                   context: &mut Context,
+                                   surface_texture: SurfaceTexture)
+                                   -> Result<Surface, (Error, SurfaceTexture)> {
+        match self.temporarily_make_context_current(context) {
+            Ok(_guard) => {
+                let SurfaceTexture { egl_backed, storage } = surface_texture;
+                GL_FUNCTIONS.with(|gl| Ok(Surface { egl_backed: egl_backed.destroy(gl), storage }))
+            }
+            Err(err) => Err((err, surface_texture)),
+        }
+    }
+
+    /// Displays the contents of a widget surface on screen.
+    /// 
+    /// Widget surfaces are internally double-buffered, so changes to them don't show up in their
+    /// associated widgets until this method is called.
+    /// 
+    /// The supplied context must match the context the surface was created with, or an
+    /// `IncompatibleSurface` error is returned.
+    /// 
+    /// On this backend, the first frame sets the mode of the widget's display. Each later frame
+    /// is flipped onto the display at the next vertical blank, and this method blocks until that
+    /// has happened. If another process, such as a display server, is the DRM master of the
+    /// device, a `PresentFailed` error is returned.
+    pub fn present_surface(&self, context: &Context, surface: &mut Surface) -> Result<(), Error> {
+        self.present_surface_with_damage(context, surface, &[])
+    }
+
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// have changed since the last time it was presented.
+    /// 
+    /// The regions are in device pixels, with the origin at the bottom left as in OpenGL. If the
+    /// display doesn't support `EGL_KHR_swap_buffers_with_damage`, or `damage` is empty, the
+    /// whole surface is presented.
+    pub fn present_surface_with_damage(&self,
+                                       context: &Context,
+                                       surface: &mut Surface,
+                                       damage: &[Rect<i32>])
+                                       -> Result<(), Error> {
+        surface.egl_backed.present_with_damage(self.native_connection.egl_display,
+                                               context.egl_backed.egl_context,
+                                               damage)?;
+        match surface.storage {
+            SurfaceStorage::Scanout(ref mut scanout) => unsafe {
+                scanout.flip(self.native_connection.fd())
+            },
+            SurfaceStorage::Buffer(_) => Ok(()),
+        }
+    }
+
+    /// Returns the number of frames ago that the contents of the back buffer of a widget surface
+    /// were presented, or 0 if they are undefined or the display doesn't support
+    /// `EGL_EXT_buffer_age`.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn surface_buffer_age(&self, context: &Context, surface: &Surface) -> Result<u32, Error> {
+        surface.egl_backed.buffer_age(self.native_connection.egl_display,
+                                      context.egl_backed.egl_context)
+    }
+
+    /// Sets the number of vertical blanks that presenting a widget surface waits for.
+    /// 
+    /// EGL clamps the interval to the range supported by the surface's config; use
+    /// `swap_interval()` to find out the interval actually in effect. EGL has no adaptive vsync,
+    /// so negative intervals return an `UnsupportedOnThisPlatform` error. Calling this method on
+    /// a generic surface returns a `NoWidgetAttached` error.
+    pub fn set_swap_interval(&self, context: &Context, surface: &Surface, interval: i32)
+                             -> Result<(), Error> {
+        surface.egl_backed.set_swap_interval(self.native_connection.egl_display,
+                                             context.egl_backed.egl_context,
+                                             interval)
+    }
+
+    /// Returns the swap interval that is in effect for a widget surface.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    #[inline]
+    pub fn swap_interval(&self, _: &Context, surface: &Surface) -> Result<i32, Error> {
+        surface.egl_backed.swap_interval()
+    }
+
+    /// Asks the compositor to signal a frame callback when it's a good time to draw the next frame
+    /// of a widget surface.
+    ///
+    /// Frame callbacks aren't supported on this backend, so this always returns an
+    /// `UnsupportedOnThisPlatform` error.
+    #[inline]
+    pub fn request_frame_callback(&self, _: &Surface) -> Result<FrameCallback, Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
+    /// Dispatches any frame callback events that have arrived, without blocking, and returns true
+    /// if the given frame callback has been signaled.
+    #[inline]
+    pub fn poll_frame_callback(&self, callback: &FrameCallback) -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Blocks the calling thread until the given frame callback is signaled or the timeout
+    /// elapses.
+    #[inline]
+    pub fn wait_for_frame_callback(&self, callback: &FrameCallback, _: Option<Duration>)
+                                   -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Destroys a frame callback.
+    #[inline]
+    pub fn destroy_frame_callback(&self, callback: &mut FrameCallback) {
+        match *callback {}
+    }
+
+    /// Resizes a surface, reallocating its storage.
+    /// 
+    /// If `preserve_contents` is true, the pixels in the region common to the old and new sizes,
+    /// anchored at the bottom left, are kept, and the rest of the surface is cleared to
+    /// transparent black; otherwise, the whole surface is cleared. The surface's ID and
+    /// framebuffer object change, so `surface_info()` must be called again afterward.
+    /// 
+    /// On this backend, a new GBM buffer object is allocated for the surface, so any DMA buffers
+    /// exported from the surface before it was resized no longer refer to it. Widget surfaces are
+    /// always the size of their widget's mode, so resizing them returns an
+    /// `UnsupportedOnThisPlatform` error; to change the mode, create a new widget surface.
+    /// 
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn resize_surface(&self,
+                          context: &Context,
+                          surface: &mut Surface,
+                          size: Size2D<i32>,
+                          preserve_contents: bool)
+                          -> Result<(), Error> {
+        if context.egl_backed.id != surface.egl_backed.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+        let old_bo = match surface.storage {
+            SurfaceStorage::Buffer(bo) => bo,
+            SurfaceStorage::Scanout(_) => return Err(Error::UnsupportedOnThisPlatform),
+        };
+
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                let egl_display = self.native_connection.egl_display;
+                let format = surface.egl_backed.format;
+                let bo = create_bo(self.native_connection.gbm_device, &size, format)?;
+                let egl_image = match import_bo(egl_display, bo, &size) {
+                    Ok(egl_image) => egl_image,
+                    Err(err) => {
+                        (gbm_functions().bo_destroy)(bo);
+                        return Err(err);
+                    }
+                };
+
+                match surface.egl_backed.resize_to_egl_image(gl,
+                                                             egl_display,
+                                                             egl_image,
+                                                             &size,
+                                                             preserve_contents) {
+                    Ok(()) => {
+                        (gbm_functions().bo_destroy)(old_bo);
+                        surface.storage = SurfaceStorage::Buffer(bo);
+                        Ok(())
+                    }
+                    Err(err) => {
+                        (gbm_functions().bo_destroy)(bo);
+                        Err(err)
+                    }
+                }
+            }
+        })
+    }
+
+    /// Returns a pointer to the underlying surface data for reading or writing by the CPU.
+    ///
+    /// The surface must have been created with `GPUCPU` or `GPUCPUWriteCombined` access, or a
+    /// `SurfaceDataInaccessible` error is returned. Calling this method on a widget surface returns
+    /// a `WidgetAttached` error.
+    ///
+    /// On this backend, the surface contents are copied into CPU memory when the surface is locked
+    /// and copied back when the guard is dropped. This must happen on the thread that owns the
+    /// surface's context.
+    pub fn lock_surface_data<'s>(&self, surface: &'s mut Surface)
+                                 -> Result<SurfaceDataGuard<'s>, Error> {
+        GL_FUNCTIONS.with(move |gl| {
+            let egl_display = self.native_connection.egl_display;
+            Ok(SurfaceDataGuard(surface.egl_backed.lock_data(gl, egl_display)?))
+        })
+    }
+
+    /// Returns the OpenGL texture target needed to read from this surface texture.
+    /// 
+    /// This will be `GL_TEXTURE_2D` or `GL_TEXTURE_RECTANGLE`, depending on platform.
+    #[inline]
+    pub fn surface_gl_texture_target(&self) -> GLenum {
+        SURFACE_GL_TEXTURE_TARGET
+    }
+
+    /// Returns various information about the surface, including the framebuffer object needed to
+    /// render to this surface.
+    /// 
+    /// Before rendering to a surface attached to a context, you must call `glBindFramebuffer()`
+    /// on the framebuffer object returned by this function. This framebuffer object may or not be
+    /// 0, the default framebuffer, depending on platform.
+    pub fn surface_info(&self, surface: &Surface) -> SurfaceInfo {
+        surface.egl_backed.info()
+    }
+
+    /// Returns the colorspaces that widget surfaces may request, depending on the EGL extensions
+    /// that the display supports.
+    pub fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        surface::supported_colorspaces(self.native_connection.egl_display)
+    }
+
+    /// Returns the OpenGL texture object containing the contents of this surface.
+    /// 
+    /// It is only legal to read from, not write to, this texture object.
+    #[inline]
+    pub fn surface_texture_object(&self, surface_texture: &SurfaceTexture) -> GLuint {
+        surface_texture.egl_backed.texture_object
+    }
+
+    /// Returns the OpenGL texture object containing the depth (and, if present, stencil) buffer
+    /// of the surface wrapped by this surface texture, if it was created with a sampleable depth
+    /// buffer.
+    /// 
+    /// It is only legal to read from, not write to, this texture object.
+    #[inline]
+    pub fn surface_texture_depth_object(&self, surface_texture: &SurfaceTexture)
+                                        -> Option<GLuint> {
+        surface_texture.egl_backed.depth_texture_object
+    }
+
+    /// Exports the GBM buffer object backing a generic surface as a Linux DMA buffer, so that it
+    /// can be consumed by other APIs or processes without copying.
+    /// 
+    /// The caller owns the returned file descriptors and must close them. The surface must stay
+    /// alive for as long as the buffer is in use elsewhere. Rendering to the surface is not
+    /// synchronized with consumers of the buffer; use a fence for that.
+    /// 
+    /// Calling this method on a widget surface returns a `WidgetAttached` error.
+    pub fn export_surface_dmabuf(&self, surface: &Surface) -> Result<DmabufDescriptor, Error> {
+        match surface.storage {
+            SurfaceStorage::Buffer(bo) => unsafe { export_bo(bo, &surface.egl_backed.size) },
+            SurfaceStorage::Scanout(_) => Err(Error::WidgetAttached),
+        }
+    }
+
+    /// Creates a handle that can be sent to another process in order to share the contents of a
+    /// generic surface with it.
+    /// 
+    /// On this backend, every generic surface can be exported as a dmabuf, so the handle always
+    /// refers to the surface itself, and the surface must stay alive for as long as the other
+    /// process uses it.
+    pub fn create_shared_surface_handle(&self, surface: &Surface)
+                                        -> Result<SharedSurfaceHandle, Error> {
+        Ok(SharedSurfaceHandle::from_dmabuf(self.export_surface_dmabuf(surface)?))
+    }
+
+    /// Creates a generic surface from a handle received from another process.
+    /// 
+    /// The resulting surface is associated with the given context and can be wrapped in a
+    /// `SurfaceTexture` like any other generic surface. If the handle refers to a dmabuf, the
+    /// surface shares its memory with the surface in the other process; otherwise, it is a copy of
+    /// that surface's contents at the time the handle was created.
+    pub fn import_shared_surface(&mut self, context: &Context, handle: SharedSurfaceHandle)
+                                 -> Result<Surface, Error> {
+        if let Some(descriptor) = handle.dmabuf() {
+            return self.create_surface_from_dmabuf(context, descriptor.clone());
+        }
+
+        let pixels = handle.read_pixels()?;
+        let mut surface = self.create_generic_surface(context,
+                                                      SurfaceAccess::GPUOnly,
+                                                      &handle.size(),
+                                                      &SurfaceDescriptor::default())?;
+        GL_FUNCTIONS.with(|gl| {
+            let egl_display = self.native_connection.egl_display;
+            match surface.egl_backed.upload_pixels(gl, egl_display, &pixels) {
+                Ok(()) => Ok(surface),
+                Err(err) => {
+                    drop(surface.egl_backed.destroy(gl, egl_display, context.egl_backed.id));
+                    if let SurfaceStorage::Buffer(bo) = surface.storage {
+                        unsafe {
+                            (gbm_functions().bo_destroy)(bo);
+                        }
+                    }
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    /// Names the OpenGL objects behind a surface after the given label, so that `KHR_debug`
+    /// messages and GL debuggers can refer to them.
+    /// 
+    /// Widget surfaces have no such objects, so this does nothing for them. The supplied context
+    /// must be the context the surface is associated with, or an `IncompatibleSurface` error is
+    /// returned.
+    pub fn set_surface_label(&self, context: &Context, surface: &Surface, label: &str)
+                             -> Result<(), Error> {
+        if context.egl_backed.id != surface.egl_backed.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| surface.egl_backed.set_label(gl, label));
+        Ok(())
+    }
+
+    /// Starts copying a region of a surface into CPU memory, without waiting for pending
+    /// rendering to finish.
+    ///
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned. The region is in device pixels, with the origin
+    /// at the bottom left as in OpenGL, and must lie within the surface, or an `InvalidRegion`
+    /// error is returned. For widget surfaces, this reads from the back buffer, so it must be
+    /// called before the surface is presented.
+    pub fn read_surface_pixels(&self,
+                               context: &Context,
+                               surface: &Surface,
+                               rect: Rect<i32>,
+                               format: ReadbackFormat)
+                               -> Result<PixelReadback, Error> {
+        if context.egl_backed.id != surface.egl_backed.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        GL_FUNCTIONS.with(|gl| {
+            surface.egl_backed.read_pixels(gl, self.native_connection.egl_display, &rect, format)
+        })
+    }
+
+    /// Returns true if the pixels of a readback can be retrieved without blocking.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned.
+    pub fn poll_pixel_readback(&self, context: &Context, readback: &PixelReadback)
+                               -> Result<bool, Error> {
+        if context.egl_backed.id != readback.context_id {
+            return Err(Error::IncompatibleContext);
+        }
+
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| unsafe { Ok(readback.is_ready(gl)) })
+    }
+
+    /// Waits for a readback to complete and returns its pixels, in the format it was started
+    /// with.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned.
+    pub fn finish_pixel_readback(&self, context: &Context, mut readback: PixelReadback)
+                                 -> Result<Vec<u8>, (Error, PixelReadback)> {
+        if context.egl_backed.id != readback.context_id {
+            return Err((Error::IncompatibleContext, readback));
+        }
+
+        let _guard = match self.temporarily_make_context_current(context) {
+            Ok(guard) => guard,
+            Err(err) => return Err((err, readback)),
+        };
+        let result = GL_FUNCTIONS.with(|gl| unsafe { readback.finish(gl) });
+        result.map_err(|err| (err, readback))
+    }
+}
+
+/// Represents the CPU view of the pixel data of this surface.
+///
+/// Any changes made to the data are written back to the surface when this guard is dropped.
+pub struct SurfaceDataGuard<'a>(EGLSurfaceDataGuard<'a>);
+
+impl<'a> SurfaceDataGuard<'a> {
+    /// Returns the number of bytes per row of the surface.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.0.stride()
+    }
+
+    /// Returns a mutable slice of the pixel data in this surface, in the surface's format (e.g.
+    /// RGBA for `RGBA8` surfaces, or half floats for `RGBA16F` ones).
+    ///
+    /// Rows are ordered bottom to top, as OpenGL does, and pixels are tightly packed.
+    #[inline]
+    pub fn data(&mut self) -> &mut [u8] {
+        self.0.data()
+    }
+}
+
+impl<'a> Drop for SurfaceDataGuard<'a> {
+    fn drop(&mut self) {
+        GL_FUNCTIONS.with(|gl| self.0.unlock(gl))
+    }
+}
+
+impl Scanout {
+    // Shows the frame that EGL has just finished rendering, and gives the buffer of the frame it
+    // replaces back to the GBM surface.
+    unsafe fn flip(&mut self, fd: RawFd) -> Result<(), Error> {
+        let (gbm, drm) = (gbm_functions(), drm_functions());
+        let bo = (gbm.surface_lock_front_buffer)(self.gbm_surface);
+        if bo.is_null() {
+            return Err(Error::PresentFailed(WindowingApiError::Failed));
+        }
+        let framebuffer_id = match add_framebuffer(fd, bo) {
+            Ok(framebuffer_id) => framebuffer_id,
+            Err(err) => {
+                (gbm.surface_release_buffer)(self.gbm_surface, bo);
+                return Err(err);
+            }
+        };
+
+        // The first frame sets the mode. Later ones are flipped in at vertical blank.
+        let crtc_id = self.native_widget.crtc_id;
+        let result = match self.front_buffer {
+            None => {
+                let mut connector_id = self.native_widget.connector_id;
+                let mut mode = self.native_widget.mode.0;
+                (drm.mode_set_crtc)(fd,
+                                    crtc_id,
+                                    framebuffer_id,
+                                    0,
+                                    0,
+                                    &mut connector_id,
+                                    1,
+                                    &mut mode)
+            }
+            Some(_) => page_flip(fd, crtc_id, framebuffer_id),
+        };
+        if result != 0 {
+            let err = drm_error();
+            (drm.mode_rm_fb)(fd, framebuffer_id);
+            (gbm.surface_release_buffer)(self.gbm_surface, bo);
+            return Err(Error::PresentFailed(err));
+        }
+
+        if let Some(old_buffer) = self.front_buffer.replace(ScanoutBuffer { bo, framebuffer_id }) {
+            (drm.mode_rm_fb)(fd, old_buffer.framebuffer_id);
+            (gbm.surface_release_buffer)(self.gbm_surface, old_buffer.bo);
+        }
+        Ok(())
+    }
+
+    // Restores the CRTC and frees the GBM surface. The EGL surface must already be destroyed.
+    unsafe fn destroy(&mut self, fd: RawFd) {
+        let (gbm, drm) = (gbm_functions(), drm_functions());
+        if self.front_buffer.is_some() && (*self.saved_crtc).mode_valid != 0 {
+            let saved_crtc = &mut *self.saved_crtc;
+            let mut connector_id = self.native_widget.connector_id;
+            (drm.mode_set_crtc)(fd,
+                                saved_crtc.crtc_id,
+                                saved_crtc.buffer_id,
+                                saved_crtc.x,
+                                saved_crtc.y,
+                                &mut connector_id,
+                                1,
+                                &mut saved_crtc.mode);
+        }
+        (drm.mode_free_crtc)(self.saved_crtc);
+        self.saved_crtc = ptr::null_mut();
+
+        // Removing the framebuffer turns the CRTC off if it wasn't restored above.
+        if let Some(front_buffer) = self.front_buffer.take() {
+            (drm.mode_rm_fb)(fd, front_buffer.framebuffer_id);
+            (gbm.surface_release_buffer)(self.gbm_surface, front_buffer.bo);
+        }
+        (gbm.surface_destroy)(self.gbm_surface);
+        self.gbm_surface = ptr::null_mut();
+    }
+}
+
+// Wraps a buffer object in a DRM framebuffer, so that it can be scanned out.
+unsafe fn add_framebuffer(fd: RawFd, bo: *mut gbm_bo) -> Result<u32, Error> {
+    let gbm = gbm_functions();
+    let (mut handles, mut pitches, mut offsets) = ([0; 4], [0; 4], [0; 4]);
+    let plane_count = (gbm.bo_get_plane_count)(bo).min(GBM_MAX_PLANES as c_int);
+    for plane_index in 0..plane_count {
+        handles[plane_index as usize] = (gbm.bo_get_handle_for_plane)(bo, plane_index).u32_;
+        pitches[plane_index as usize] = (gbm.bo_get_stride_for_plane)(bo, plane_index);
+        offsets[plane_index as usize] = (gbm.bo_get_offset)(bo, plane_index);
+    }
+
+    let mut framebuffer_id = 0;
+    let result = (drm_functions().mode_add_fb2)(fd,
+                                                (gbm.bo_get_width)(bo),
+                                                (gbm.bo_get_height)(bo),
+                                                (gbm.bo_get_format)(bo),
+                                                handles.as_ptr(),
+                                                pitches.as_ptr(),
+                                                offsets.as_ptr(),
+                                                &mut framebuffer_id,
+                                                0);
+    if result != 0 {
+        return Err(Error::PresentFailed(drm_error()));
+    }
+    Ok(framebuffer_id)
+}
+
+// Queues a page flip for the next vertical blank and waits for it to happen. Returns nonzero on
+// failure, like `libdrm` does.
+unsafe fn page_flip(fd: RawFd, crtc_id: u32, framebuffer_id: u32) -> c_int {
+    let drm = drm_functions();
+    // The flag is set behind the compiler's back by `handle_page_flip()`, so it must be a `Cell`
+    // for the loop below to observe the change.
+    let flipped = Cell::new(false);
+    let result = (drm.mode_page_flip)(fd,
+                                      crtc_id,
+                                      framebuffer_id,
+                                      DRM_MODE_PAGE_FLIP_EVENT,
+                                      &flipped as *const Cell<bool> as *mut c_void);
+    if result != 0 {
+        return result;
+    }
+
+    // Reading events from the device blocks until the flip event arrives.
+    let mut event_context = drmEventContext {
+        version: DRM_EVENT_CONTEXT_VERSION,
+        vblank_handler: None,
+        page_flip_handler: Some(handle_page_flip),
+    };
+    while !flipped.get() {
+        if (drm.handle_event)(fd, &mut event_context) != 0 &&
+                io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return -1;
+        }
+    }
+    0
+}
+
+unsafe extern "C" fn handle_page_flip(_: c_int,
+                                      _: c_uint,
+                                      _: c_uint,
+                                      _: c_uint,
+                                      flipped: *mut c_void) {
+    (*(flipped as *const Cell<bool>)).set(true);
+}
+
+// Describes why the last `libdrm` call failed.
+fn drm_error() -> WindowingApiError {
+    match io::Error::last_os_error().raw_os_error() {
+        // Only the DRM master of the device may set modes and flip pages.
+        Some(libc::EACCES) | Some(libc::EPERM) => WindowingApiError::BadAccess,
+        Some(libc::EINVAL) => WindowingApiError::BadParameter,
+        _ => WindowingApiError::Failed,
+    }
+}
+
+// Allocates a buffer object that can be rendered to, and scanned out if the driver allows it.
+unsafe fn create_bo(gbm_device: *mut gbm_device, size: &Size2D<i32>, format: SurfaceFormat)
+                    -> Result<*mut gbm_bo, Error> {
+    let gbm = gbm_functions();
+    let fourcc = match dmabuf::fourcc(format) {
+        Some(fourcc) => fourcc,
+        None => return Err(Error::UnsupportedSurfaceFormat),
+    };
+    if (gbm.device_is_format_supported)(gbm_device, fourcc, GBM_BO_USE_RENDERING) == 0 {
+        return Err(Error::UnsupportedSurfaceFormat);
+    }
+
+    for &flags in &[GBM_BO_USE_SCANOUT | GBM_BO_USE_RENDERING, GBM_BO_USE_RENDERING] {
+        let bo = (gbm.bo_create)(gbm_device,
+                                 size.width as u32,
+                                 size.height as u32,
+                                 fourcc,
+                                 flags);
+        if !bo.is_null() {
+            return Ok(bo);
+        }
+    }
+    Err(Error::SurfaceCreationFailed(WindowingApiError::Failed))
+}
+
+// Wraps a DMA buffer in a buffer object, so that it can be exported again. GBM doesn't take
+// ownership of the file descriptors in the descriptor.
+unsafe fn import_dmabuf(gbm_device: *mut gbm_device, descriptor: &DmabufDescriptor)
+                        -> Result<*mut gbm_bo, Error> {
+    if descriptor.planes.is_empty() || descriptor.planes.len() > GBM_MAX_PLANES {
+        return Err(Error::SurfaceImportFailed(WindowingApiError::BadParameter));
+    }
+
+    let mut import_data = gbm_import_fd_modifier_data {
+        width: descriptor.size.width as u32,
+        height: descriptor.size.height as u32,
+        format: descriptor.fourcc,
+        num_fds: descriptor.planes.len() as u32,
+        fds: [-1; GBM_MAX_PLANES],
+        strides: [0; GBM_MAX_PLANES],
+        offsets: [0; GBM_MAX_PLANES],
+        modifier: descriptor.modifier,
+    };
+    for (plane_index, plane) in descriptor.planes.iter().enumerate() {
+        import_data.fds[plane_index] = plane.fd;
+        import_data.strides[plane_index] = plane.stride as i32;
+        import_data.offsets[plane_index] = plane.offset as i32;
+    }
+
+    let bo = (gbm_functions().bo_import)(gbm_device,
+                                         GBM_BO_IMPORT_FD_MODIFIER,
+                                         &mut import_data as *mut _ as *mut c_void,
+                                         GBM_BO_USE_RENDERING);
+    if bo.is_null() {
+        return Err(Error::SurfaceImportFailed(WindowingApiError::Failed));
+    }
+    Ok(bo)
+}
+
+// Describes the memory behind a buffer object as a DMA buffer. The caller owns the file
+// descriptors in the returned descriptor.
+unsafe fn export_bo(bo: *mut gbm_bo, size: &Size2D<i32>) -> Result<DmabufDescriptor, Error> {
+    let gbm = gbm_functions();
+    let plane_count = (gbm.bo_get_plane_count)(bo);
+    let mut planes: Vec<DmabufPlane> = Vec::with_capacity(plane_count as usize);
+    for plane_index in 0..plane_count {
+        // Each call returns a new file descriptor, so every plane gets its own.
+        let fd = (gbm.bo_get_fd)(bo);
+        if fd < 0 {
+            for plane in planes {
+                libc::close(plane.fd);
+            }
+            return Err(Error::SurfaceExportFailed(WindowingApiError::Failed));
+        }
+        planes.push(DmabufPlane {
+            fd,
+            offset: (gbm.bo_get_offset)(bo, plane_index),
+            stride: (gbm.bo_get_stride_for_plane)(bo, plane_index),
+        });
+    }
+
+    Ok(DmabufDescriptor {
+        size: *size,
+        fourcc: (gbm.bo_get_format)(bo),
+        modifier: (gbm.bo_get_modifier)(bo),
+        planes,
+    })
+}
+
+// Wraps a buffer object in an EGL image, by way of a DMA buffer.
+unsafe fn import_bo(egl_display: EGLDisplay, bo: *mut gbm_bo, size: &Size2D<i32>)
+                    -> Result<EGLImageKHR, Error> {
+    let descriptor = export_bo(bo, size)?;
+    let egl_image = dmabuf::import_egl_image(egl_display, &descriptor);
+    for plane in &descriptor.planes {
+        libc::close(plane.fd);
+    }
+    egl_image
+}
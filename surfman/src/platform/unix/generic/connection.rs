@@ -3,12 +3,15 @@
 //! Represents a connection to a display server.
 
 use crate::Error;
-use crate::egl::types::{EGLAttrib, EGLDisplay};
+use crate::egl::types::{EGLAttrib, EGLDeviceEXT, EGLDisplay, EGLenum};
 use crate::egl;
 use crate::info::GLApi;
-use crate::platform::generic::egl::device::EGL_FUNCTIONS;
-use crate::platform::generic::egl::ffi::EGL_PLATFORM_SURFACELESS_MESA;
-use super::device::{Adapter, Device, NativeDevice};
+use crate::platform::generic::egl::device::{self, EGL_FUNCTIONS};
+use crate::platform::generic::egl::ffi::{EGL_PLATFORM_DEVICE_EXT, EGL_PLATFORM_SURFACELESS_MESA};
+use crate::platform::generic::multi::connection::BackendConnection;
+use crate::platform::unix::Backend;
+use super::device::{Adapter, AdapterCache, Device, NativeDevice, initialize_device_display};
+use super::device::terminate_device_display;
 use super::surface::NativeWidget;
 
 use euclid::default::Size2D;
@@ -32,6 +35,10 @@ pub struct NativeConnection(Arc<NativeConnectionWrapper>);
 /// Native connections.
 pub struct NativeConnectionWrapper {
     pub(crate) egl_display: EGLDisplay,
+    // Whether the display was opened on a specific device for this connection, and so must be
+    // terminated with it.
+    pub(crate) egl_display_is_owned: bool,
+    pub(crate) adapters: AdapterCache,
 }
 
 unsafe impl Send for NativeConnectionWrapper {}
@@ -42,32 +49,57 @@ impl Connection {
     #[inline]
     pub fn new() -> Result<Connection, Error> {
         unsafe {
-            EGL_FUNCTIONS.with(|egl| {
-                let egl_display_attributes = [egl::NONE as EGLAttrib];
-                let egl_display = egl.GetPlatformDisplay(EGL_PLATFORM_SURFACELESS_MESA,
-                                                         egl::DEFAULT_DISPLAY as *mut c_void,
-                                                         egl_display_attributes.as_ptr());
-                if egl_display == egl::NO_DISPLAY {
-                    return Err(Error::ConnectionFailed);
-                }
-
-                let (mut egl_major_version, mut egl_minor_version) = (0, 0);
-                let ok = egl.Initialize(egl_display,
-                                        &mut egl_major_version,
-                                        &mut egl_minor_version);
-                if ok == egl::FALSE {
-                    return Err(Error::ConnectionFailed);
-                }
-
-                let native_connection = NativeConnection(Arc::new(NativeConnectionWrapper {
-                    egl_display,
-                }));
-
-                Connection::from_native_connection(native_connection)
-            })
+            let egl_display = Connection::open_egl_display(EGL_PLATFORM_SURFACELESS_MESA,
+                                                           egl::DEFAULT_DISPLAY as *mut c_void)?;
+            let native_connection = NativeConnection(Arc::new(NativeConnectionWrapper {
+                egl_display,
+                egl_display_is_owned: false,
+                adapters: AdapterCache::default(),
+            }));
+            Connection::from_native_connection(native_connection)
         }
     }
 
+    // Opens and initializes an EGL display on a specific EGL device. It must be released with
+    // `terminate_device_display()`.
+    pub(crate) unsafe fn open_egl_device_display(egl_device: EGLDeviceEXT)
+                                                 -> Result<EGLDisplay, Error> {
+        if !device::display_supports_extension(egl::NO_DISPLAY, "EGL_EXT_platform_device") {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+        EGL_FUNCTIONS.with(|egl| {
+            let egl_display_attributes = [egl::NONE as EGLAttrib];
+            let egl_display = egl.GetPlatformDisplay(EGL_PLATFORM_DEVICE_EXT,
+                                                     egl_device as *mut c_void,
+                                                     egl_display_attributes.as_ptr());
+            if egl_display == egl::NO_DISPLAY || !initialize_device_display(egl_display) {
+                return Err(Error::ConnectionFailed);
+            }
+            Ok(egl_display)
+        })
+    }
+
+    unsafe fn open_egl_display(platform: EGLenum, native_display: *mut c_void)
+                               -> Result<EGLDisplay, Error> {
+        EGL_FUNCTIONS.with(|egl| {
+            let egl_display_attributes = [egl::NONE as EGLAttrib];
+            let egl_display = egl.GetPlatformDisplay(platform,
+                                                     native_display,
+                                                     egl_display_attributes.as_ptr());
+            if egl_display == egl::NO_DISPLAY {
+                return Err(Error::ConnectionFailed);
+            }
+
+            let (mut egl_major_version, mut egl_minor_version) = (0, 0);
+            let ok = egl.Initialize(egl_display, &mut egl_major_version, &mut egl_minor_version);
+            if ok == egl::FALSE {
+                return Err(Error::ConnectionFailed);
+            }
+
+            Ok(egl_display)
+        })
+    }
+
     /// An alias for `Connection::new()`, present for consistency with other backends.
     #[inline]
     pub unsafe fn from_native_connection(native_connection: NativeConnection)
@@ -100,7 +132,7 @@ impl Connection {
     /// On the OSMesa backend, this returns a software adapter.
     #[inline]
     pub fn create_hardware_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::hardware())
+        Adapter::hardware().select(&self.native_connection.adapters)
     }
 
     /// Returns the "best" adapter on this system, preferring low-power hardware adapters.
@@ -108,13 +140,29 @@ impl Connection {
     /// On the OSMesa backend, this returns a software adapter.
     #[inline]
     pub fn create_low_power_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::low_power())
+        Adapter::low_power().select(&self.native_connection.adapters)
     }
 
     /// Returns the "best" adapter on this system, preferring software adapters.
+    ///
+    /// If EGL can't find a software renderer, a `NoSoftwareAdapters` error is returned.
     #[inline]
     pub fn create_software_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::software())
+        Adapter::software().select(&self.native_connection.adapters)
+    }
+
+    /// Returns an adapter for each EGL device on the system, including software renderers.
+    /// 
+    /// The adapters are returned in the order that EGL enumerates them, which is stable for a
+    /// given system configuration, so a specific GPU can be selected by its position or by its
+    /// render node path. Any of these adapters may be passed to `create_device()`.
+    /// 
+    /// The adapters are enumerated once per connection and cached. Requires
+    /// `EGL_EXT_device_enumeration`; if it's unavailable, a `RequiredExtensionUnavailable` error is
+    /// returned.
+    #[inline]
+    pub fn adapters(&self) -> Result<Vec<Adapter>, Error> {
+        self.native_connection.adapters.adapters()
     }
 
     /// Opens the hardware device corresponding to the given adapter.
@@ -125,11 +173,11 @@ impl Connection {
         Device::new(self, adapter)
     }
 
-    /// An alias for `connection.create_device()` with the default adapter.
+    /// An alias for `connection.create_device()` with the adapter of the given native device.
     #[inline]
-    pub unsafe fn create_device_from_native_device(&self, _: NativeDevice)
+    pub unsafe fn create_device_from_native_device(&self, native_device: NativeDevice)
                                                    -> Result<Device, Error> {
-        Device::new(self, &self.create_adapter()?) 
+        Device::new(self, &native_device.adapter)
     }
 
     /// Opens the display connection corresponding to the given `winit` window.
@@ -163,3 +211,37 @@ impl Connection {
     }
 }
 
+impl Drop for NativeConnectionWrapper {
+    #[inline]
+    fn drop(&mut self) {
+        if self.egl_display_is_owned {
+            unsafe {
+                terminate_device_display(self.egl_display);
+            }
+        }
+    }
+}
+
+impl BackendConnection for Connection {
+    type Backend = Backend;
+
+    #[inline]
+    fn new_with_backend(backend: Backend) -> Option<Result<Connection, Error>> {
+        if backend == Backend::Surfaceless {
+            Some(Connection::new())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn backends() -> Vec<Backend> {
+        vec![Backend::Surfaceless]
+    }
+
+    #[inline]
+    fn backend(&self) -> Backend {
+        Backend::Surfaceless
+    }
+}
+
@@ -6,13 +6,16 @@ use crate::context::ContextID;
 use crate::egl::types::EGLint;
 use crate::egl;
 use crate::platform::generic::egl::context::{self, CurrentContextGuard, EGLBackedContext};
-use crate::{ContextAttributes, Error, Gl, SurfaceInfo};
+use crate::{ContextAttributes, ContextInfo, ContextResetStatus, Error, Gl, SurfaceInfo};
 use super::device::Device;
 use super::surface::Surface;
 
 use std::os::raw::c_void;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
 
 pub use crate::platform::generic::egl::context::{ContextDescriptor, NativeContext};
+pub use crate::platform::generic::egl::fence::Fence;
 
 thread_local! {
     #[doc(hidden)]
@@ -44,9 +47,6 @@ impl Device {
     #[inline]
     pub fn create_context_descriptor(&self, attributes: &ContextAttributes)
                                      -> Result<ContextDescriptor, Error> {
-        // Set environment variables as appropriate.
-        self.adapter.set_environment_variables();
-
         unsafe {
             ContextDescriptor::new(self.native_connection.egl_display, attributes, &[
                 egl::SURFACE_TYPE as EGLint,        egl::PBUFFER_BIT as EGLint,
@@ -105,9 +105,7 @@ impl Device {
     pub fn context_descriptor(&self, context: &Context) -> ContextDescriptor {
         GL_FUNCTIONS.with(|gl| {
             unsafe {
-                ContextDescriptor::from_egl_context(gl,
-                                                    self.native_connection.egl_display,
-                                                    context.0.egl_context)
+                context.0.descriptor(gl, self.native_connection.egl_display)
             }
         })
     }
@@ -150,6 +148,34 @@ impl Device {
         }
     }
 
+    /// Returns detailed information about the context, as reported by the driver: the renderer,
+    /// the vendor, the version that was actually created, and the supported extensions.
+    pub fn context_info(&self, context: &Context) -> Result<ContextInfo, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                context::current_context_info(gl, self.native_connection.egl_display)
+            }
+        })
+    }
+
+    /// Returns whether the context has been lost because of a GPU reset.
+    /// 
+    /// Resets are only reported to contexts created with the `LOSE_CONTEXT_ON_RESET` flag. See
+    /// `Device::context_reset_status()` in the `Device` trait for how to recover from a reset.
+    pub fn context_reset_status(&self, context: &Context) -> Result<ContextResetStatus, Error> {
+        unsafe {
+            context.0.reset_status(self.native_connection.egl_display)
+        }
+    }
+
+    /// Makes `context_reset_status()` report that the context was reset, so that recovery from
+    /// context loss can be tested without a real GPU reset.
+    #[doc(hidden)]
+    pub fn simulate_context_loss(&self, context: &mut Context) {
+        context.0.simulate_loss()
+    }
+
     /// Fetches the address of an OpenGL function associated with this context.
     /// 
     /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
@@ -213,5 +239,80 @@ impl Device {
     pub fn context_surface_info(&self, context: &Context) -> Result<Option<SurfaceInfo>, Error> {
         context.0.surface_info()
     }
-}
 
+    /// Inserts a fence into the command stream of the given context.
+    /// 
+    /// The fence is signaled once the GPU has finished all commands issued on the context before
+    /// this call. Pending commands are flushed, so the fence may be waited on from any thread.
+    /// 
+    /// Requires `EGL_KHR_fence_sync`; if it's unavailable, a `RequiredExtensionUnavailable` error
+    /// is returned. If the display supports `EGL_ANDROID_native_fence_sync`, the fence is backed
+    /// by a sync file and may be exported with `export_fence_fd()`.
+    pub fn create_fence(&self, context: &Context) -> Result<Fence, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                Fence::new(gl, self.native_connection.egl_display)
+            }
+        })
+    }
+
+    /// Imports a sync file, such as one received from another process, as a fence in the command
+    /// stream of the given context.
+    /// 
+    /// On success, the fence takes ownership of the file descriptor. On failure, the caller
+    /// retains ownership of it. Requires `EGL_ANDROID_native_fence_sync`.
+    pub fn create_fence_from_fd(&self, context: &Context, fd: RawFd) -> Result<Fence, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                Fence::from_fd(gl, self.native_connection.egl_display, fd)
+            }
+        })
+    }
+
+    /// Blocks the calling thread until the fence is signaled.
+    /// 
+    /// If `timeout` is `None`, this waits indefinitely. Returns true if the fence was signaled or
+    /// false if the timeout elapsed first.
+    #[inline]
+    pub fn wait_fence(&self, fence: &Fence, timeout: Option<Duration>) -> Result<bool, Error> {
+        unsafe {
+            fence.wait(self.native_connection.egl_display, timeout)
+        }
+    }
+
+    /// Makes the GPU wait for the fence to be signaled before executing any further commands
+    /// issued on the given context, without blocking the calling thread.
+    /// 
+    /// If `EGL_KHR_wait_sync` is unavailable, this falls back to blocking the calling thread until
+    /// the fence is signaled.
+    pub fn server_wait_fence(&self, context: &Context, fence: &Fence) -> Result<(), Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        unsafe {
+            fence.server_wait(self.native_connection.egl_display)
+        }
+    }
+
+    /// Returns a sync file that is signaled along with the fence, suitable for passing to other
+    /// processes or APIs such as Vulkan or KMS.
+    /// 
+    /// The caller takes ownership of the returned file descriptor. If the fence isn't backed by a
+    /// sync file, a `RequiredExtensionUnavailable` error is returned.
+    #[inline]
+    pub fn export_fence_fd(&self, fence: &Fence) -> Result<RawFd, Error> {
+        unsafe {
+            fence.export_fd(self.native_connection.egl_display)
+        }
+    }
+
+    /// Destroys a fence.
+    /// 
+    /// Fences must be explicitly destroyed with this function, or a panic will occur.
+    #[inline]
+    pub fn destroy_fence(&self, fence: &mut Fence) {
+        unsafe {
+            fence.destroy(self.native_connection.egl_display)
+        }
+    }
+}
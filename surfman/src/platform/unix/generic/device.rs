@@ -2,14 +2,25 @@
 //
 //! A wrapper around surfaceless Mesa `EGLDisplay`s.
 
+use crate::egl::types::{EGLAttrib, EGLDeviceEXT, EGLDisplay, EGLenum, EGLint};
+use crate::egl;
+use crate::gl;
+use crate::platform::generic::egl::context::CurrentContextGuard;
+use crate::platform::generic::egl::device::{self, EGL_FUNCTIONS};
+use crate::platform::generic::egl::ffi::{EGL_DEVICE_EXT, EGL_DRM_DEVICE_FILE_EXT};
+use crate::platform::generic::egl::ffi::EGL_DRM_RENDER_NODE_FILE_EXT;
+use crate::platform::generic::egl::ffi::{EGL_EXTENSION_FUNCTIONS, EGL_NO_DEVICE_EXT};
+use crate::platform::generic::egl::ffi::EGL_RENDERER_EXT;
 use crate::{Error, GLApi};
 use super::connection::{Connection, NativeConnectionWrapper};
+use super::context::GL_FUNCTIONS;
 
-use std::env;
-use std::sync::Arc;
-
-static MESA_SOFTWARE_RENDERING_ENV_VAR: &'static str = "LIBGL_ALWAYS_SOFTWARE";
-static MESA_DRI_PRIME_ENV_VAR: &'static str = "DRI_PRIME";
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::{Arc, Mutex};
 
 /// Represents a hardware display adapter that can be used for rendering (including the CPU).
 ///
@@ -22,8 +33,36 @@ pub enum Adapter {
     HardwarePrime,
     #[doc(hidden)]
     Software,
+    #[doc(hidden)]
+    Device(DeviceAdapter),
+}
+
+/// A specific EGL device, as enumerated by `Connection::adapters()`.
+#[derive(Clone, Debug)]
+pub struct DeviceAdapter {
+    pub(crate) egl_device: EGLDeviceEXT,
+    vendor: Option<String>,
+    renderer: Option<String>,
+    render_node: Option<PathBuf>,
+    is_software: bool,
 }
 
+unsafe impl Send for DeviceAdapter {}
+unsafe impl Sync for DeviceAdapter {}
+
+lazy_static! {
+    // The number of connections and adapter probes using each display that was opened on a
+    // specific device, keyed by address. EGL hands out the same display to everyone who opens the
+    // same device, so it can only be terminated once all of them are done with it.
+    static ref DEVICE_DISPLAY_USERS: Mutex<HashMap<usize, usize>> = Mutex::new(HashMap::new());
+}
+
+// The adapters that EGL enumerates, computed at most once per connection, since describing an
+// adapter may require opening a display and a context on its device. Clones share the same
+// adapters, so that devices opened on a connection report the same ones as the connection.
+#[derive(Clone, Default)]
+pub(crate) struct AdapterCache(Arc<Mutex<Option<Vec<Adapter>>>>);
+
 impl Adapter {
     #[inline]
     pub(crate) fn hardware() -> Adapter {
@@ -40,28 +79,291 @@ impl Adapter {
         Adapter::Software
     }
 
-    pub(crate) fn set_environment_variables(&self) {
-        match *self {
-            Adapter::Hardware | Adapter::HardwarePrime => {
-                env::remove_var(MESA_SOFTWARE_RENDERING_ENV_VAR);
+    // Resolves one of the generic adapter kinds above to a specific EGL device, so that the choice
+    // of adapter doesn't depend on process-wide state such as environment variables.
+    //
+    // "Hardware" means the last hardware device that EGL reports, which is where PRIME setups put
+    // the discrete GPU, and "low power" means the first. If no hardware device can be found, the
+    // connection's own display is used. Software adapters are never substituted with hardware
+    // ones, so if no software device can be found, `NoSoftwareAdapters` is returned.
+    pub(crate) fn select(self, cache: &AdapterCache) -> Result<Adapter, Error> {
+        if let Adapter::Device(_) = self {
+            return Ok(self);
+        }
+
+        let adapters = match cache.adapters() {
+            Ok(adapters) => adapters,
+            Err(_) if self.is_software() => return Err(Error::NoSoftwareAdapters),
+            Err(_) => return Ok(self),
+        };
+
+        let selected = {
+            let mut hardware_adapters = adapters.iter().filter(|adapter| !adapter.is_software());
+            match self {
+                Adapter::HardwarePrime => hardware_adapters.last(),
+                Adapter::Hardware => hardware_adapters.next(),
+                Adapter::Software | Adapter::Device(_) => {
+                    adapters.iter().find(|adapter| adapter.is_software())
+                }
             }
-            Adapter::Software => {
-                env::set_var(MESA_SOFTWARE_RENDERING_ENV_VAR, "1");
+        };
+        match selected {
+            Some(selected) => Ok(selected.clone()),
+            None if self.is_software() => Err(Error::NoSoftwareAdapters),
+            None => Ok(self),
+        }
+    }
+
+    // Returns an adapter for every device that EGL knows about, in the order EGL reports them.
+    pub(crate) fn enumerate() -> Result<Vec<Adapter>, Error> {
+        unsafe {
+            let query_devices = match EGL_EXTENSION_FUNCTIONS.QueryDevicesEXT {
+                Some(query_devices) if
+                        device::display_supports_extension(egl::NO_DISPLAY,
+                                                           "EGL_EXT_device_enumeration") => {
+                    query_devices
+                }
+                _ => return Err(Error::RequiredExtensionUnavailable),
+            };
+
+            let mut device_count = 0;
+            if query_devices(0, ptr::null_mut(), &mut device_count) == egl::FALSE {
+                return Err(Error::NoAdapterFound);
             }
+            let mut egl_devices = vec![EGL_NO_DEVICE_EXT; device_count as usize];
+            if query_devices(device_count, egl_devices.as_mut_ptr(), &mut device_count) ==
+                    egl::FALSE {
+                return Err(Error::NoAdapterFound);
+            }
+            egl_devices.truncate(device_count as usize);
+
+            Ok(egl_devices.into_iter().map(|egl_device| {
+                Adapter::Device(DeviceAdapter::new(egl_device))
+            }).collect())
         }
+    }
 
+    /// Returns the name of the company that makes this adapter, if known.
+    ///
+    /// This is only available for adapters returned by `Connection::adapters()`.
+    #[inline]
+    pub fn vendor(&self) -> Option<&str> {
         match *self {
-            Adapter::Software => {}
-            Adapter::Hardware => {
-                env::remove_var(MESA_DRI_PRIME_ENV_VAR);
+            Adapter::Device(ref adapter) => adapter.vendor.as_ref().map(|vendor| &**vendor),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the renderer behind this adapter, if known.
+    ///
+    /// This is only available for adapters returned by `Connection::adapters()`.
+    #[inline]
+    pub fn renderer(&self) -> Option<&str> {
+        match *self {
+            Adapter::Device(ref adapter) => adapter.renderer.as_ref().map(|renderer| &**renderer),
+            _ => None,
+        }
+    }
+
+    /// Returns the path to the DRM render node of this adapter (e.g. `/dev/dri/renderD128`), or
+    /// the primary node if the device has no render node.
+    ///
+    /// This is only available for adapters returned by `Connection::adapters()` that correspond
+    /// to DRM devices.
+    #[inline]
+    pub fn render_node(&self) -> Option<&Path> {
+        match *self {
+            Adapter::Device(ref adapter) => adapter.render_node.as_ref().map(|path| &**path),
+            _ => None,
+        }
+    }
+
+    /// Returns true if this adapter renders on the CPU.
+    #[inline]
+    pub fn is_software(&self) -> bool {
+        match *self {
+            Adapter::Software => true,
+            Adapter::Hardware | Adapter::HardwarePrime => false,
+            Adapter::Device(ref adapter) => adapter.is_software,
+        }
+    }
+}
+
+impl AdapterCache {
+    // Returns the adapters of the connection, enumerating them on first use.
+    pub(crate) fn adapters(&self) -> Result<Vec<Adapter>, Error> {
+        let mut adapters = self.0.lock().unwrap();
+        if adapters.is_none() {
+            *adapters = Some(Adapter::enumerate()?);
+        }
+        Ok(adapters.as_ref().unwrap().clone())
+    }
+}
+
+impl DeviceAdapter {
+    unsafe fn new(egl_device: EGLDeviceEXT) -> DeviceAdapter {
+        let extensions = query_device_string(egl_device, egl::EXTENSIONS as EGLint)
+            .unwrap_or_default();
+        let has_extension = |name| extensions.split_whitespace().any(|ext| ext == name);
+
+        let mut render_node = None;
+        if has_extension("EGL_EXT_device_drm_render_node") {
+            render_node = query_device_string(egl_device, EGL_DRM_RENDER_NODE_FILE_EXT as EGLint);
+        }
+        if render_node.is_none() && has_extension("EGL_EXT_device_drm") {
+            render_node = query_device_string(egl_device, EGL_DRM_DEVICE_FILE_EXT as EGLint);
+        }
+
+        let (mut vendor, mut renderer) = (None, None);
+        if has_extension("EGL_EXT_device_query_name") {
+            vendor = query_device_string(egl_device, egl::VENDOR as EGLint);
+            renderer = query_device_string(egl_device, EGL_RENDERER_EXT as EGLint);
+        }
+        if vendor.is_none() || renderer.is_none() {
+            if let Ok(egl_display) = Connection::open_egl_device_display(egl_device) {
+                if vendor.is_none() {
+                    vendor = EGL_FUNCTIONS.with(|egl| {
+                        to_string(egl.QueryString(egl_display, egl::VENDOR as EGLint))
+                    });
+                }
+                if renderer.is_none() {
+                    renderer = query_gl_renderer(egl_display);
+                }
+                terminate_device_display(egl_display);
             }
-            Adapter::HardwarePrime => {
-                env::set_var(MESA_DRI_PRIME_ENV_VAR, "1");
+        }
+
+        DeviceAdapter {
+            egl_device,
+            vendor,
+            renderer,
+            render_node: render_node.map(PathBuf::from),
+            is_software: has_extension("EGL_MESA_device_software"),
+        }
+    }
+
+    // Returns true if the given display renders with this adapter's device.
+    pub(crate) unsafe fn is_device_of(&self, egl_display: EGLDisplay) -> bool {
+        let query_display_attrib = match EGL_EXTENSION_FUNCTIONS.QueryDisplayAttribEXT {
+            Some(query_display_attrib) => query_display_attrib,
+            None => return false,
+        };
+        let mut egl_device = EGL_NO_DEVICE_EXT as EGLAttrib;
+        let ok = query_display_attrib(egl_display, EGL_DEVICE_EXT as EGLint, &mut egl_device);
+        ok != egl::FALSE && egl_device as EGLDeviceEXT == self.egl_device
+    }
+
+    // Opens a display on the given window system platform that renders with this adapter's
+    // device instead of the one the window system would pick. It must be released with
+    // `terminate_device_display()`.
+    pub(crate) unsafe fn open_platform_display(&self,
+                                               platform: EGLenum,
+                                               native_display: *mut c_void)
+                                               -> Result<EGLDisplay, Error> {
+        if !device::display_supports_extension(egl::NO_DISPLAY, "EGL_EXT_explicit_device") {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+        EGL_FUNCTIONS.with(|egl| {
+            let egl_display_attributes = [
+                EGL_DEVICE_EXT as EGLAttrib, self.egl_device as EGLAttrib,
+                egl::NONE as EGLAttrib,
+            ];
+            let egl_display = egl.GetPlatformDisplay(platform,
+                                                     native_display,
+                                                     egl_display_attributes.as_ptr());
+            if egl_display == egl::NO_DISPLAY || !initialize_device_display(egl_display) {
+                return Err(Error::DeviceOpenFailed);
             }
+            Ok(egl_display)
+        })
+    }
+}
+
+// Initializes a display that was opened on a specific device, counting the caller as one of its
+// users. It must be released with `terminate_device_display()`.
+pub(crate) unsafe fn initialize_device_display(egl_display: EGLDisplay) -> bool {
+    let mut device_display_users = DEVICE_DISPLAY_USERS.lock().unwrap();
+    if let Some(users) = device_display_users.get_mut(&(egl_display as usize)) {
+        *users += 1;
+        return true;
+    }
+
+    EGL_FUNCTIONS.with(|egl| {
+        // Displays that were initialized by someone else are theirs to terminate.
+        let initialized_elsewhere = !egl.QueryString(egl_display, egl::VERSION as EGLint)
+                                        .is_null();
+        let (mut egl_major_version, mut egl_minor_version) = (0, 0);
+        if egl.Initialize(egl_display, &mut egl_major_version, &mut egl_minor_version) ==
+                egl::FALSE {
+            return false;
+        }
+        if !initialized_elsewhere {
+            device_display_users.insert(egl_display as usize, 1);
         }
+        true
+    })
+}
+
+// Releases a display initialized with `initialize_device_display()`, terminating it once its last
+// user is done with it.
+pub(crate) unsafe fn terminate_device_display(egl_display: EGLDisplay) {
+    let mut device_display_users = DEVICE_DISPLAY_USERS.lock().unwrap();
+    let users = match device_display_users.get_mut(&(egl_display as usize)) {
+        None => return,
+        Some(users) => users,
+    };
+    *users -= 1;
+    if *users == 0 {
+        device_display_users.remove(&(egl_display as usize));
+        EGL_FUNCTIONS.with(|egl| egl.Terminate(egl_display));
+    }
+}
+
+unsafe fn query_device_string(egl_device: EGLDeviceEXT, name: EGLint) -> Option<String> {
+    match EGL_EXTENSION_FUNCTIONS.QueryDeviceStringEXT {
+        Some(query_device_string) => to_string(query_device_string(egl_device, name)),
+        None => None,
+    }
+}
+
+unsafe fn to_string(string: *const c_char) -> Option<String> {
+    if string.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(string).to_string_lossy().into_owned())
     }
 }
 
+// Asks OpenGL for the renderer name, using a throwaway context, since EGL has no portable way to
+// query it.
+unsafe fn query_gl_renderer(egl_display: EGLDisplay) -> Option<String> {
+    let _guard = CurrentContextGuard::new();
+    EGL_FUNCTIONS.with(|egl| {
+        if egl.BindAPI(egl::OPENGL_API) == egl::FALSE {
+            return None;
+        }
+        let context_attributes = [egl::NONE as EGLint];
+        let egl_context = egl.CreateContext(egl_display,
+                                            ptr::null(),
+                                            egl::NO_CONTEXT,
+                                            context_attributes.as_ptr());
+        if egl_context == egl::NO_CONTEXT {
+            return None;
+        }
+
+        let mut renderer = None;
+        if egl.MakeCurrent(egl_display, egl::NO_SURFACE, egl::NO_SURFACE, egl_context) !=
+                egl::FALSE {
+            renderer = GL_FUNCTIONS.with(|gl| {
+                to_string(gl.GetString(gl::RENDERER) as *const c_char)
+            });
+            egl.MakeCurrent(egl_display, egl::NO_SURFACE, egl::NO_SURFACE, egl::NO_CONTEXT);
+        }
+        egl.DestroyContext(egl_display, egl_context);
+        renderer
+    })
+}
+
 /// A thread-local handle to a device.
 ///
 /// Devices contain most of the relevant surface management methods.
@@ -82,10 +384,25 @@ pub struct NativeDevice {
 impl Device {
     #[inline]
     pub(crate) fn new(connection: &Connection, adapter: &Adapter) -> Result<Device, Error> {
-        Ok(Device {
-            native_connection: connection.native_connection.clone(),
-            adapter: (*adapter).clone(),
-        })
+        let adapter = adapter.clone().select(&connection.native_connection.adapters)?;
+
+        // Adapters that name a specific EGL device get a display of their own, so that devices
+        // on different adapters don't interfere with one another.
+        let native_connection = match adapter {
+            Adapter::Device(ref adapter) => {
+                let egl_display = unsafe {
+                    Connection::open_egl_device_display(adapter.egl_device)?
+                };
+                Arc::new(NativeConnectionWrapper {
+                    egl_display,
+                    egl_display_is_owned: true,
+                    adapters: connection.native_connection.adapters.clone(),
+                })
+            }
+            _ => connection.native_connection.clone(),
+        };
+
+        Ok(Device { native_connection, adapter })
     }
 
     /// Returns the native device corresponding to this device.
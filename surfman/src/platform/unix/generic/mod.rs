@@ -14,3 +14,7 @@ mod implementation;
 #[cfg(test)]
 #[path = "../../../tests.rs"]
 mod tests;
+
+#[cfg(test)]
+#[path = "../../generic/egl/tests.rs"]
+mod egl_tests;
@@ -0,0 +1,371 @@
+// surfman/surfman/src/platform/unix/glx/connection.rs
+//
+//! A wrapper for X11 server connections (`DISPLAY` variables) that render via GLX.
+
+use crate::error::{Error, WindowingApiError};
+use crate::info::GLApi;
+use crate::platform::generic::multi::connection::BackendConnection;
+use crate::platform::unix::generic::device::Adapter;
+use crate::platform::unix::x11::connection::X_THREADS_INIT;
+use crate::platform::unix::Backend;
+use super::context;
+use super::device::{Device, NativeDevice};
+use super::ffi::{glXBindTexImageEXT, glXCreateContextAttribsARB, glXReleaseTexImageEXT};
+use super::ffi::glXSwapIntervalEXT;
+use super::surface::NativeWidget;
+
+use euclid::default::Size2D;
+
+use parking_lot::Mutex;
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use x11::glx::{glXQueryExtension, glXQueryExtensionsString, glXQueryVersion};
+use x11::xlib::{self, Display, False, XCloseDisplay, XDefaultScreen, XErrorEvent, XOpenDisplay};
+use x11::xlib::{XSetErrorHandler, XSync};
+
+#[cfg(feature = "sm-winit")]
+use winit::Window;
+#[cfg(feature = "sm-winit")]
+use winit::os::unix::WindowExt;
+
+lazy_static! {
+    static ref X_ERROR_TRAP_MUTEX: Mutex<()> = Mutex::new(());
+}
+
+// The code of the first X error that occurred while errors were trapped, or 0 if there was none.
+static X_ERROR_CODE: AtomicU8 = AtomicU8::new(0);
+
+/// A connection to the X11 display server.
+#[derive(Clone)]
+pub struct Connection {
+    pub(crate) native_connection: Arc<NativeConnectionWrapper>,
+}
+
+unsafe impl Send for Connection {}
+
+pub(crate) struct NativeConnectionWrapper {
+    pub(crate) x11_display: *mut Display,
+    x11_display_is_owned: bool,
+    pub(crate) screen: c_int,
+    pub(crate) extensions: GLXExtensions,
+}
+
+/// Wrapper for an X11 display.
+#[derive(Clone)]
+pub struct NativeConnection {
+    /// The Xlib Display. This must be present; do not pass NULL.
+    pub x11_display: *mut Display,
+}
+
+// The optional GLX functionality that the display supports.
+pub(crate) struct GLXExtensions {
+    pub(crate) create_context_attribs: Option<glXCreateContextAttribsARB>,
+    pub(crate) create_context_robustness: bool,
+    pub(crate) bind_tex_image: Option<glXBindTexImageEXT>,
+    pub(crate) release_tex_image: Option<glXReleaseTexImageEXT>,
+    pub(crate) swap_interval: Option<glXSwapIntervalEXT>,
+    pub(crate) swap_control_tear: bool,
+    pub(crate) buffer_age: bool,
+}
+
+impl Drop for NativeConnectionWrapper {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            if self.x11_display_is_owned {
+                XCloseDisplay(self.x11_display);
+            }
+            self.x11_display = ptr::null_mut();
+        }
+    }
+}
+
+impl Connection {
+    /// Connects to the default display.
+    ///
+    /// If the display server doesn't support GLX 1.3 or later, a `RequiredExtensionUnavailable`
+    /// error is returned.
+    #[inline]
+    pub fn new() -> Result<Connection, Error> {
+        unsafe {
+            *X_THREADS_INIT;
+
+            let x11_display = XOpenDisplay(ptr::null());
+            if x11_display.is_null() {
+                return Err(Error::ConnectionFailed);
+            }
+
+            Connection::from_x11_display(x11_display, true)
+        }
+    }
+
+    /// Wraps an existing X11 `Display` in a `Connection`.
+    ///
+    /// Important: Before calling this function, X11 must have be initialized in a thread-safe
+    /// manner by using `XInitThreads()`. Otherwise, it will not be safe to use `surfman` from
+    /// multiple threads.
+    ///
+    /// The display is not retained, as there is no way to do that in the X11 API. Therefore, it is
+    /// the caller's responsibility to ensure that the display connection is not closed before this
+    /// `Connection` object is disposed of.
+    #[inline]
+    pub unsafe fn from_native_connection(native_connection: NativeConnection)
+                                         -> Result<Connection, Error> {
+        Connection::from_x11_display(native_connection.x11_display, false)
+    }
+
+    fn from_x11_display(x11_display: *mut Display, is_owned: bool) -> Result<Connection, Error> {
+        unsafe {
+            // Context creation, pixmaps, and make-current with separate read and draw drawables
+            // all need GLX 1.3.
+            let (mut error_base, mut event_base) = (0, 0);
+            let (mut major_version, mut minor_version) = (0, 0);
+            if glXQueryExtension(x11_display, &mut error_base, &mut event_base) == False ||
+                    glXQueryVersion(x11_display, &mut major_version, &mut minor_version) ==
+                    False ||
+                    (major_version, minor_version) < (1, 3) {
+                if is_owned {
+                    XCloseDisplay(x11_display);
+                }
+                return Err(Error::RequiredExtensionUnavailable);
+            }
+
+            let screen = XDefaultScreen(x11_display);
+            let extensions = GLXExtensions::query(x11_display, screen);
+            Ok(Connection {
+                native_connection: Arc::new(NativeConnectionWrapper {
+                    x11_display,
+                    x11_display_is_owned: is_owned,
+                    screen,
+                    extensions,
+                }),
+            })
+        }
+    }
+
+    /// Returns the underlying native connection.
+    #[inline]
+    pub fn native_connection(&self) -> NativeConnection {
+        NativeConnection { x11_display: self.native_connection.x11_display }
+    }
+
+    /// Returns the OpenGL API flavor that this connection supports (OpenGL or OpenGL ES).
+    #[inline]
+    pub fn gl_api(&self) -> GLApi {
+        GLApi::GL
+    }
+
+    /// Returns the "best" adapter on this system, preferring high-performance hardware adapters.
+    ///
+    /// This is an alias for `Connection::create_hardware_adapter()`.
+    #[inline]
+    pub fn create_adapter(&self) -> Result<Adapter, Error> {
+        self.create_hardware_adapter()
+    }
+
+    /// Returns the "best" adapter on this system, preferring high-performance hardware adapters.
+    #[inline]
+    pub fn create_hardware_adapter(&self) -> Result<Adapter, Error> {
+        Ok(Adapter::hardware())
+    }
+
+    /// Returns the "best" adapter on this system, preferring low-power hardware adapters.
+    #[inline]
+    pub fn create_low_power_adapter(&self) -> Result<Adapter, Error> {
+        Ok(Adapter::low_power())
+    }
+
+    /// Returns the "best" adapter on this system, preferring software adapters.
+    #[inline]
+    pub fn create_software_adapter(&self) -> Result<Adapter, Error> {
+        Ok(Adapter::software())
+    }
+
+    /// Returns every adapter that can be used with this connection.
+    ///
+    /// With GLX, the display server picks the device to render with, so this returns only the
+    /// default adapter.
+    #[inline]
+    pub fn adapters(&self) -> Result<Vec<Adapter>, Error> {
+        Ok(vec![self.create_adapter()?])
+    }
+
+    /// Opens the hardware device corresponding to the given adapter.
+    ///
+    /// Device handles are local to a single thread. If the display server doesn't support
+    /// `GLX_ARB_create_context`, a `RequiredExtensionUnavailable` error is returned.
+    #[inline]
+    pub fn create_device(&self, adapter: &Adapter) -> Result<Device, Error> {
+        Device::new(self, adapter)
+    }
+
+    /// Opens the hardware device corresponding to the adapter wrapped in the given native
+    /// device.
+    ///
+    /// This is present for compatibility with other backends.
+    #[inline]
+    pub unsafe fn create_device_from_native_device(&self, native_device: NativeDevice)
+                                                   -> Result<Device, Error> {
+        Device::new(self, &native_device.adapter)
+    }
+
+    /// Opens the display connection corresponding to the given `winit` window.
+    #[cfg(feature = "sm-winit")]
+    pub fn from_winit_window(window: &Window) -> Result<Connection, Error> {
+        if let Some(display) = window.get_xlib_display() {
+            Connection::from_x11_display(display as *mut Display, false)
+        } else {
+            Err(Error::IncompatibleWinitWindow)
+        }
+    }
+
+    /// Creates a native widget type from the given `winit` window.
+    ///
+    /// This type can be later used to create surfaces that render to the window.
+    #[cfg(feature = "sm-winit")]
+    pub fn create_native_widget_from_winit_window(&self, window: &Window)
+                                                  -> Result<NativeWidget, Error> {
+        match window.get_xlib_window() {
+            Some(window) => Ok(NativeWidget { window }),
+            None => Err(Error::IncompatibleNativeWidget),
+        }
+    }
+
+    /// Create a native widget from a raw pointer
+    pub unsafe fn create_native_widget_from_ptr(&self, raw: *mut c_void, _size: Size2D<i32>)
+                                                -> NativeWidget {
+        NativeWidget { window: raw as xlib::Window }
+    }
+
+    /// Create a native widget type from the given `raw_window_handle::HasRawWindowHandle`.
+    #[cfg(feature = "sm-raw-window-handle")]
+    pub fn create_native_widget_from_rwh(&self, raw_handle: raw_window_handle::RawWindowHandle)
+                                         -> Result<NativeWidget, Error> {
+        use raw_window_handle::RawWindowHandle::Xlib;
+
+        match raw_handle {
+            Xlib(handle) => Ok(NativeWidget { window: handle.window }),
+            _ => Err(Error::IncompatibleNativeWidget),
+        }
+    }
+}
+
+impl BackendConnection for Connection {
+    type Backend = Backend;
+
+    #[inline]
+    fn new_with_backend(backend: Backend) -> Option<Result<Connection, Error>> {
+        if backend == Backend::GLX {
+            Some(Connection::new())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn backends() -> Vec<Backend> {
+        vec![Backend::GLX]
+    }
+
+    #[inline]
+    fn backend(&self) -> Backend {
+        Backend::GLX
+    }
+}
+
+impl GLXExtensions {
+    unsafe fn query(x11_display: *mut Display, screen: c_int) -> GLXExtensions {
+        let extensions = glXQueryExtensionsString(x11_display, screen);
+        let extensions = if extensions.is_null() {
+            ""
+        } else {
+            CStr::from_ptr(extensions).to_str().unwrap_or("")
+        };
+        let supported = |name: &str| extensions.split_whitespace().any(|entry| entry == name);
+
+        let create_context_attribs = if supported("GLX_ARB_create_context") {
+            load_function::<glXCreateContextAttribsARB>("glXCreateContextAttribsARB")
+        } else {
+            None
+        };
+        let (bind_tex_image, release_tex_image) = if supported("GLX_EXT_texture_from_pixmap") {
+            (load_function::<glXBindTexImageEXT>("glXBindTexImageEXT"),
+             load_function::<glXReleaseTexImageEXT>("glXReleaseTexImageEXT"))
+        } else {
+            (None, None)
+        };
+        let swap_interval = if supported("GLX_EXT_swap_control") {
+            load_function::<glXSwapIntervalEXT>("glXSwapIntervalEXT")
+        } else {
+            None
+        };
+
+        GLXExtensions {
+            create_context_attribs,
+            create_context_robustness: supported("GLX_ARB_create_context_robustness"),
+            bind_tex_image,
+            release_tex_image,
+            swap_interval,
+            swap_control_tear: supported("GLX_EXT_swap_control_tear"),
+            buffer_age: supported("GLX_EXT_buffer_age"),
+        }
+    }
+
+    // Returns true if pixmaps can be bound to textures, which surface textures need.
+    #[inline]
+    pub(crate) fn texture_from_pixmap(&self) -> bool {
+        self.bind_tex_image.is_some() && self.release_tex_image.is_some()
+    }
+}
+
+// Runs `f`, recording any X errors that it causes instead of letting Xlib's default error handler
+// exit the process. GLX reports most failures this way, such as an unsupported context version
+// or an X visual that doesn't match a config.
+//
+// The error handler is process-wide, so errors on other displays that arrive in the meantime are
+// recorded too.
+pub(crate) unsafe fn trap_x_errors<F, T>(x11_display: *mut Display, f: F)
+                                         -> Result<T, WindowingApiError>
+                                         where F: FnOnce() -> T {
+    let _lock = X_ERROR_TRAP_MUTEX.lock();
+    XSync(x11_display, False);
+    X_ERROR_CODE.store(0, Ordering::SeqCst);
+    let old_error_handler = XSetErrorHandler(Some(record_x_error));
+
+    let result = f();
+
+    XSync(x11_display, False);
+    XSetErrorHandler(old_error_handler);
+    match X_ERROR_CODE.swap(0, Ordering::SeqCst) {
+        0 => Ok(result),
+        xlib::BadValue => Err(WindowingApiError::BadValue),
+        xlib::BadWindow => Err(WindowingApiError::BadWindow),
+        xlib::BadPixmap => Err(WindowingApiError::BadNativePixmap),
+        xlib::BadMatch => Err(WindowingApiError::BadMatch),
+        xlib::BadDrawable => Err(WindowingApiError::BadDrawable),
+        xlib::BadAlloc => Err(WindowingApiError::BadAlloc),
+        _ => Err(WindowingApiError::Failed),
+    }
+}
+
+unsafe extern "C" fn record_x_error(_: *mut Display, event: *mut XErrorEvent) -> c_int {
+    // Only the first error is reported, since later ones are usually consequences of it.
+    let _ = X_ERROR_CODE.compare_exchange(0,
+                                          (*event).error_code,
+                                          Ordering::SeqCst,
+                                          Ordering::SeqCst);
+    0
+}
+
+unsafe fn load_function<F>(symbol_name: &str) -> Option<F> where F: Copy {
+    let function = context::get_proc_address(symbol_name);
+    if function.is_null() {
+        None
+    } else {
+        Some(mem::transmute_copy::<*const c_void, F>(&function))
+    }
+}
@@ -0,0 +1,766 @@
+// surfman/surfman/src/platform/unix/glx/context.rs
+//
+//! OpenGL rendering contexts on X11 via GLX.
+
+use crate::context::{self, CREATE_CONTEXT_MUTEX, ContextID};
+use crate::surface::Framebuffer;
+use crate::{ContextAttributeFlags, ContextAttributes, ContextInfo, ContextResetStatus, Error};
+use crate::{GLVersion, Gl, SurfaceInfo, WindowingApiError};
+use super::connection::trap_x_errors;
+use super::device::Device;
+use super::ffi::{GLX_BIND_TO_TEXTURE_RGBA_EXT, GLX_BIND_TO_TEXTURE_TARGETS_EXT};
+use super::ffi::{GLX_CONTEXT_RESET_NOTIFICATION_STRATEGY_ARB, GLX_CONTEXT_ROBUST_ACCESS_BIT_ARB};
+use super::ffi::{GLX_LOSE_CONTEXT_ON_RESET_ARB, GLX_TEXTURE_2D_BIT_EXT};
+use super::surface::Surface;
+
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::thread;
+use std::time::Duration;
+use x11::glx::arb::{GLX_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB, GLX_CONTEXT_CORE_PROFILE_BIT_ARB};
+use x11::glx::arb::{GLX_CONTEXT_DEBUG_BIT_ARB, GLX_CONTEXT_FLAGS_ARB};
+use x11::glx::arb::{GLX_CONTEXT_MAJOR_VERSION_ARB, GLX_CONTEXT_MINOR_VERSION_ARB};
+use x11::glx::arb::GLX_CONTEXT_PROFILE_MASK_ARB;
+use x11::glx::{GLX_ALPHA_SIZE, GLX_BLUE_SIZE, GLX_DEPTH_SIZE, GLX_DRAWABLE_TYPE};
+use x11::glx::{GLX_FBCONFIG_ID, GLX_GREEN_SIZE, GLX_PBUFFER_BIT, GLX_PBUFFER_HEIGHT};
+use x11::glx::{GLX_PBUFFER_WIDTH, GLX_PIXMAP_BIT, GLX_RED_SIZE, GLX_RENDER_TYPE, GLX_RGBA_BIT};
+use x11::glx::{GLX_STENCIL_SIZE, GLX_WINDOW_BIT, GLX_X_RENDERABLE, GLXContext, GLXDrawable};
+use x11::glx::{GLXFBConfig, GLXPbuffer, glXChooseFBConfig, glXCreatePbuffer, glXDestroyContext};
+use x11::glx::{glXDestroyPbuffer, glXGetCurrentContext, glXGetCurrentDisplay};
+use x11::glx::{glXGetCurrentDrawable, glXGetCurrentReadDrawable, glXGetFBConfigAttrib};
+use x11::glx::{glXGetProcAddress, glXMakeContextCurrent, glXQueryContext};
+use x11::xlib::{Display, False, True, XFree};
+
+// The size of the pbuffer that contexts with no surface attached render to.
+const DUMMY_PBUFFER_SIZE: c_int = 16;
+
+thread_local! {
+    #[doc(hidden)]
+    pub static GL_FUNCTIONS: Gl = Gl::load_with(get_proc_address);
+}
+
+/// Represents an OpenGL rendering context.
+///
+/// A context allows you to issue rendering commands to a surface. When initially created, a
+/// context has no attached surface, so rendering commands will fail or be ignored. Typically, you
+/// attach a surface to the context before rendering.
+///
+/// Contexts take ownership of the surfaces attached to them. In order to mutate a surface in any
+/// way other than rendering to it (e.g. presenting it to a window, which causes a buffer swap), it
+/// must first be detached from its context. Each surface is associated with a single context upon
+/// creation and may not be rendered to from any other context. However, you can wrap a surface in
+/// a surface texture, which allows the surface to be read from another context.
+///
+/// OpenGL objects may not be shared across contexts directly, but surface textures effectively
+/// allow for sharing of texture data. Contexts are local to a single thread and device.
+///
+/// A context must be explicitly destroyed with `destroy_context()`, or a panic will occur.
+pub struct Context {
+    pub(crate) glx_context: GLXContext,
+    pub(crate) glx_fb_config: GLXFBConfig,
+    pub(crate) id: ContextID,
+    framebuffer: Framebuffer<Surface, ExternalDrawables>,
+    pub(crate) descriptor: ContextDescriptor,
+    // GLX can't make a context current without a drawable, so contexts with no surface attached
+    // render to this pbuffer. This is 0 if the context's config doesn't support pbuffers.
+    pbuffer: GLXPbuffer,
+    context_is_owned: bool,
+    // Set by `simulate_context_loss()`.
+    loss_simulated: bool,
+}
+
+/// Wrapper for a native `GLXContext`.
+#[derive(Clone, Copy)]
+pub struct NativeContext(pub GLXContext);
+
+/// Information needed to create a context. Some APIs call this a "config" or a "pixel format".
+///
+/// These are local to a device.
+#[derive(Clone)]
+pub struct ContextDescriptor {
+    pub(crate) attributes: ContextAttributes,
+    fb_config_id: c_int,
+}
+
+// The drawables that an externally-managed context was rendering to when it was wrapped.
+#[derive(Clone, Copy)]
+pub(crate) struct ExternalDrawables {
+    draw: GLXDrawable,
+    read: GLXDrawable,
+}
+
+#[must_use]
+pub(crate) struct CurrentContextGuard {
+    x11_display: *mut Display,
+    old_x11_display: *mut Display,
+    old_glx_context: GLXContext,
+    old_draw_drawable: GLXDrawable,
+    old_read_drawable: GLXDrawable,
+}
+
+impl Drop for Context {
+    #[inline]
+    fn drop(&mut self) {
+        if !self.glx_context.is_null() && !thread::panicking() {
+            panic!("Contexts must be destroyed explicitly with `destroy_context`!")
+        }
+    }
+}
+
+impl Drop for CurrentContextGuard {
+    fn drop(&mut self) {
+        unsafe {
+            if self.old_glx_context.is_null() {
+                glXMakeContextCurrent(self.x11_display, 0, 0, ptr::null_mut());
+            } else {
+                glXMakeContextCurrent(self.old_x11_display,
+                                      self.old_draw_drawable,
+                                      self.old_read_drawable,
+                                      self.old_glx_context);
+            }
+        }
+    }
+}
+
+/// A GPU fence.
+///
+/// Fences aren't supported on this backend yet, so this type can't be constructed.
+#[derive(Debug)]
+pub enum Fence {}
+
+impl Device {
+    /// Creates a context descriptor with the given attributes.
+    ///
+    /// Context descriptors are local to this device.
+    pub fn create_context_descriptor(&self, attributes: &ContextAttributes)
+                                     -> Result<ContextDescriptor, Error> {
+        // FIXME: Support multisampling by rendering to a multisampled framebuffer object and
+        // resolving into the pixmap, since multisampled configs can't be bound to textures.
+        if attributes.samples > 1 {
+            return Err(Error::Unimplemented);
+        }
+
+        let extensions = &self.native_connection.extensions;
+        if attributes.flags.intersects(ContextAttributeFlags::ROBUST_ACCESS |
+                                       ContextAttributeFlags::LOSE_CONTEXT_ON_RESET) &&
+                !extensions.create_context_robustness {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+
+        let flags = attributes.flags;
+        let depth_size   = if flags.contains(ContextAttributeFlags::DEPTH)   { 24 } else { 0 };
+        let stencil_size = if flags.contains(ContextAttributeFlags::STENCIL) { 8  } else { 0 };
+
+        // Generic surfaces are pixmaps, which always have an alpha channel so that they can be
+        // bound to RGBA textures.
+        let mut config_attributes = vec![
+            GLX_X_RENDERABLE,   True,
+            GLX_DRAWABLE_TYPE,  GLX_WINDOW_BIT | GLX_PIXMAP_BIT | GLX_PBUFFER_BIT,
+            GLX_RENDER_TYPE,    GLX_RGBA_BIT,
+            GLX_RED_SIZE,       8,
+            GLX_GREEN_SIZE,     8,
+            GLX_BLUE_SIZE,      8,
+            GLX_ALPHA_SIZE,     8,
+            GLX_DEPTH_SIZE,     depth_size,
+            GLX_STENCIL_SIZE,   stencil_size,
+        ];
+        if extensions.texture_from_pixmap() {
+            config_attributes.extend_from_slice(&[
+                GLX_BIND_TO_TEXTURE_RGBA_EXT,       True,
+                GLX_BIND_TO_TEXTURE_TARGETS_EXT,    GLX_TEXTURE_2D_BIT_EXT,
+            ]);
+        }
+        config_attributes.push(0);
+
+        unsafe {
+            let glx_fb_config = choose_fb_config(self.native_connection.x11_display,
+                                                 self.native_connection.screen,
+                                                 &config_attributes)
+                                    .ok_or(Error::NoPixelFormatFound)?;
+            let fb_config_id = get_fb_config_attr(self.native_connection.x11_display,
+                                                  glx_fb_config,
+                                                  GLX_FBCONFIG_ID);
+            Ok(ContextDescriptor { attributes: *attributes, fb_config_id })
+        }
+    }
+
+    /// Creates a new OpenGL context.
+    ///
+    /// The context initially has no surface attached. Until a surface is bound to it, rendering
+    /// commands will fail or have no effect.
+    pub fn create_context(&mut self, descriptor: &ContextDescriptor) -> Result<Context, Error> {
+        let mut next_context_id = CREATE_CONTEXT_MUTEX.lock().unwrap();
+
+        let x11_display = self.native_connection.x11_display;
+        let create_context_attribs = self.native_connection
+                                         .extensions
+                                         .create_context_attribs
+                                         .ok_or(Error::RequiredExtensionUnavailable)?;
+
+        let attributes = &descriptor.attributes;
+        let flags = attributes.flags;
+        let mut context_attributes = vec![
+            GLX_CONTEXT_MAJOR_VERSION_ARB,  attributes.version.major as c_int,
+            GLX_CONTEXT_MINOR_VERSION_ARB,  attributes.version.minor as c_int,
+        ];
+
+        // Profiles only exist in OpenGL 3.2 and up.
+        if attributes.version.major > 3 ||
+                (attributes.version.major == 3 && attributes.version.minor >= 2) {
+            let profile = if flags.contains(ContextAttributeFlags::COMPATIBILITY_PROFILE) {
+                GLX_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB
+            } else {
+                GLX_CONTEXT_CORE_PROFILE_BIT_ARB
+            };
+            context_attributes.extend_from_slice(&[GLX_CONTEXT_PROFILE_MASK_ARB, profile]);
+        }
+
+        let mut context_flags = 0;
+        if flags.contains(ContextAttributeFlags::DEBUG) {
+            context_flags |= GLX_CONTEXT_DEBUG_BIT_ARB;
+        }
+        if flags.contains(ContextAttributeFlags::ROBUST_ACCESS) {
+            context_flags |= GLX_CONTEXT_ROBUST_ACCESS_BIT_ARB;
+        }
+        context_attributes.extend_from_slice(&[GLX_CONTEXT_FLAGS_ARB, context_flags]);
+        if flags.contains(ContextAttributeFlags::LOSE_CONTEXT_ON_RESET) {
+            context_attributes.extend_from_slice(&[
+                GLX_CONTEXT_RESET_NOTIFICATION_STRATEGY_ARB,    GLX_LOSE_CONTEXT_ON_RESET_ARB,
+            ]);
+        }
+        context_attributes.push(0);
+
+        unsafe {
+            let glx_fb_config = fb_config_from_id(x11_display,
+                                                  self.native_connection.screen,
+                                                  descriptor.fb_config_id)
+                                    .ok_or(Error::IncompatibleContextDescriptor)?;
+
+            // GLX reports unsupported versions and profiles as X errors.
+            let glx_context = match trap_x_errors(x11_display, || {
+                create_context_attribs(x11_display,
+                                       glx_fb_config,
+                                       ptr::null_mut(),
+                                       True,
+                                       context_attributes.as_ptr())
+            }) {
+                Ok(glx_context) if !glx_context.is_null() => glx_context,
+                Ok(_) => return Err(Error::ContextCreationFailed(WindowingApiError::BadMatch)),
+                Err(err) => return Err(Error::ContextCreationFailed(err)),
+            };
+
+            let pbuffer = match create_dummy_pbuffer(x11_display, glx_fb_config) {
+                Ok(pbuffer) => pbuffer,
+                Err(err) => {
+                    glXDestroyContext(x11_display, glx_context);
+                    return Err(Error::ContextCreationFailed(err));
+                }
+            };
+
+            let context = Context {
+                glx_context,
+                glx_fb_config,
+                id: *next_context_id,
+                framebuffer: Framebuffer::None,
+                descriptor: (*descriptor).clone(),
+                pbuffer,
+                context_is_owned: true,
+                loss_simulated: false,
+            };
+            next_context_id.0 += 1;
+
+            if flags.contains(ContextAttributeFlags::DEBUG) {
+                let _guard = CurrentContextGuard::new(x11_display);
+                match context.make_current(x11_display) {
+                    Ok(()) => context::install_debug_message_callback(get_proc_address),
+                    Err(_) => {
+                        warn!("surfman: couldn't make the debug context current to install its \
+                               logger")
+                    }
+                }
+            }
+
+            Ok(context)
+        }
+    }
+
+    /// Wraps a `GLXContext` in a `surfman` context and returns it.
+    ///
+    /// The context must have been created on this device's display. If it is current, the
+    /// drawables it is rendering to are used as its render target. The context is not retained,
+    /// as there is no way to do this in the GLX API. Therefore, it is the caller's responsibility
+    /// to ensure that the returned `Context` object remains alive as long as the `GLXContext` is.
+    pub unsafe fn create_context_from_native_context(&self, native_context: NativeContext)
+                                                     -> Result<Context, Error> {
+        let x11_display = self.native_connection.x11_display;
+        let glx_context = native_context.0;
+        if glx_context.is_null() {
+            return Err(Error::IncompatibleNativeContext);
+        }
+
+        let mut fb_config_id = 0;
+        match trap_x_errors(x11_display, || {
+            glXQueryContext(x11_display, glx_context, GLX_FBCONFIG_ID, &mut fb_config_id)
+        }) {
+            Ok(0) => {}
+            Ok(_) | Err(_) => return Err(Error::IncompatibleNativeContext),
+        }
+        let glx_fb_config = fb_config_from_id(x11_display,
+                                              self.native_connection.screen,
+                                              fb_config_id)
+                                .ok_or(Error::IncompatibleNativeContext)?;
+
+        let framebuffer = if glXGetCurrentContext() == glx_context &&
+                glXGetCurrentDrawable() != 0 {
+            Framebuffer::External(ExternalDrawables {
+                draw: glXGetCurrentDrawable(),
+                read: glXGetCurrentReadDrawable(),
+            })
+        } else {
+            Framebuffer::None
+        };
+
+        // Without a pbuffer, the context can still be made current with no drawable, as long as
+        // it's for OpenGL 3.0 or later.
+        let drawable_type = get_fb_config_attr(x11_display, glx_fb_config, GLX_DRAWABLE_TYPE);
+        let pbuffer = if (drawable_type & GLX_PBUFFER_BIT) != 0 {
+            create_dummy_pbuffer(x11_display, glx_fb_config).unwrap_or(0)
+        } else {
+            0
+        };
+
+        let mut context = Context {
+            glx_context,
+            glx_fb_config,
+            id: ContextID(0),
+            framebuffer,
+            descriptor: ContextDescriptor { attributes: ContextAttributes::zeroed(), fb_config_id },
+            pbuffer,
+            context_is_owned: false,
+            loss_simulated: false,
+        };
+
+        // Query the version, profile, and buffers of the wrapped context.
+        {
+            let _guard = CurrentContextGuard::new(x11_display);
+            if let Err(err) = context.make_current(x11_display) {
+                self.destroy_context(&mut context)?;
+                return Err(err);
+            }
+            GL_FUNCTIONS.with(|gl| {
+                let mut flags = ContextAttributeFlags::empty();
+                let config_attributes = [
+                    (GLX_ALPHA_SIZE, ContextAttributeFlags::ALPHA),
+                    (GLX_DEPTH_SIZE, ContextAttributeFlags::DEPTH),
+                    (GLX_STENCIL_SIZE, ContextAttributeFlags::STENCIL),
+                ];
+                for &(config_attribute, flag) in &config_attributes {
+                    flags.set(flag,
+                              get_fb_config_attr(x11_display, glx_fb_config, config_attribute) >
+                              0);
+                }
+                flags.set(ContextAttributeFlags::COMPATIBILITY_PROFILE,
+                          context::current_context_uses_compatibility_profile(gl));
+                flags.set(ContextAttributeFlags::DEBUG, context::current_context_is_debug(gl));
+                let (robust_access, lose_context_on_reset) =
+                    context::current_context_robustness(gl);
+                flags.set(ContextAttributeFlags::ROBUST_ACCESS, robust_access);
+                flags.set(ContextAttributeFlags::LOSE_CONTEXT_ON_RESET, lose_context_on_reset);
+                context.descriptor.attributes = ContextAttributes {
+                    version: GLVersion::current(gl),
+                    flags,
+                    samples: 0,
+                };
+            });
+        }
+
+        let mut next_context_id = CREATE_CONTEXT_MUTEX.lock().unwrap();
+        context.id = *next_context_id;
+        next_context_id.0 += 1;
+        Ok(context)
+    }
+
+    /// Destroys a context.
+    ///
+    /// The context must have been created on this device.
+    pub fn destroy_context(&self, context: &mut Context) -> Result<(), Error> {
+        if context.glx_context.is_null() {
+            return Ok(());
+        }
+
+        if let Ok(Some(mut surface)) = self.unbind_surface_from_context(context) {
+            self.destroy_surface(context, &mut surface)?;
+        }
+
+        let x11_display = self.native_connection.x11_display;
+        unsafe {
+            if context.is_current() {
+                glXMakeContextCurrent(x11_display, 0, 0, ptr::null_mut());
+            }
+            if context.pbuffer != 0 {
+                glXDestroyPbuffer(x11_display, context.pbuffer);
+                context.pbuffer = 0;
+            }
+            if context.context_is_owned {
+                glXDestroyContext(x11_display, context.glx_context);
+            }
+        }
+
+        context.glx_context = ptr::null_mut();
+        Ok(())
+    }
+
+    /// Given a context, returns its underlying `GLXContext`.
+    #[inline]
+    pub fn native_context(&self, context: &Context) -> NativeContext {
+        NativeContext(context.glx_context)
+    }
+
+    /// Returns the descriptor that this context was created with.
+    #[inline]
+    pub fn context_descriptor(&self, context: &Context) -> ContextDescriptor {
+        context.descriptor.clone()
+    }
+
+    /// Makes the context the current OpenGL context for this thread.
+    ///
+    /// After calling this function, it is valid to use OpenGL rendering commands.
+    #[inline]
+    pub fn make_context_current(&self, context: &Context) -> Result<(), Error> {
+        context.make_current(self.native_connection.x11_display)
+    }
+
+    /// Removes the current OpenGL context from this thread.
+    ///
+    /// After calling this function, OpenGL rendering commands will fail until a new context is
+    /// made current.
+    #[inline]
+    pub fn make_no_context_current(&self) -> Result<(), Error> {
+        unsafe {
+            let x11_display = self.native_connection.x11_display;
+            if glXMakeContextCurrent(x11_display, 0, 0, ptr::null_mut()) == False {
+                return Err(Error::MakeCurrentFailed(WindowingApiError::Failed));
+            }
+            Ok(())
+        }
+    }
+
+    #[inline]
+    pub(crate) fn temporarily_make_context_current(&self, context: &Context)
+                                                   -> Result<CurrentContextGuard, Error> {
+        let guard = CurrentContextGuard::new(self.native_connection.x11_display);
+        self.make_context_current(context)?;
+        Ok(guard)
+    }
+
+    /// Returns the attributes that the context descriptor was created with.
+    #[inline]
+    pub fn context_descriptor_attributes(&self, context_descriptor: &ContextDescriptor)
+                                         -> ContextAttributes {
+        context_descriptor.attributes
+    }
+
+    /// Returns detailed information about the context, as reported by the driver: the renderer,
+    /// the vendor, the version that was actually created, and the supported extensions.
+    pub fn context_info(&self, context: &Context) -> Result<ContextInfo, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(ContextInfo::current)
+    }
+
+    /// Returns whether the context has been lost because of a GPU reset.
+    ///
+    /// Resets are only reported to contexts created with the `LOSE_CONTEXT_ON_RESET` flag. See
+    /// `Device::context_reset_status()` in the `Device` trait for how to recover from a reset.
+    pub fn context_reset_status(&self, context: &Context) -> Result<ContextResetStatus, Error> {
+        if context.loss_simulated {
+            return Ok(ContextResetStatus::Guilty);
+        }
+
+        let _guard = self.temporarily_make_context_current(context)?;
+        unsafe {
+            Ok(context::current_context_reset_status(get_proc_address))
+        }
+    }
+
+    /// Makes `context_reset_status()` report that the context was reset, so that recovery from
+    /// context loss can be tested without a real GPU reset.
+    #[doc(hidden)]
+    pub fn simulate_context_loss(&self, context: &mut Context) {
+        context.loss_simulated = true;
+    }
+
+    /// Fetches the address of an OpenGL function associated with this context.
+    ///
+    /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
+    /// with any other context.
+    ///
+    /// This method is typically used with a function like `gl::load_with()` from the `gl` crate to
+    /// load OpenGL function pointers.
+    #[inline]
+    pub fn get_proc_address(&self, _: &Context, symbol_name: &str) -> *const c_void {
+        get_proc_address(symbol_name)
+    }
+
+    /// Attaches a surface to a context for rendering.
+    ///
+    /// This function takes ownership of the surface. The surface must have been created with this
+    /// context, or an `IncompatibleSurface` error is returned.
+    ///
+    /// If this function is called with a surface already bound, a `SurfaceAlreadyBound` error is
+    /// returned. To avoid this error, first unbind the existing surface with
+    /// `unbind_surface_from_context`.
+    ///
+    /// If an error is returned, the surface is returned alongside it.
+    pub fn bind_surface_to_context(&self, context: &mut Context, surface: Surface)
+                                   -> Result<(), (Error, Surface)> {
+        if context.id != surface.context_id {
+            return Err((Error::IncompatibleSurface, surface));
+        }
+
+        match context.framebuffer {
+            Framebuffer::None => context.framebuffer = Framebuffer::Surface(surface),
+            Framebuffer::External(_) => return Err((Error::ExternalRenderTarget, surface)),
+            Framebuffer::Surface(_) => return Err((Error::SurfaceAlreadyBound, surface)),
+        }
+
+        // If we're current, make the context current again to switch to the new drawable.
+        if context.is_current() {
+            drop(context.make_current(self.native_connection.x11_display))
+        }
+
+        Ok(())
+    }
+
+    /// Removes and returns any attached surface from this context.
+    ///
+    /// Any pending OpenGL commands targeting this surface will be automatically flushed, so the
+    /// surface is safe to read from immediately when this function returns.
+    pub fn unbind_surface_from_context(&self, context: &mut Context)
+                                       -> Result<Option<Surface>, Error> {
+        match context.framebuffer {
+            Framebuffer::None => return Ok(None),
+            Framebuffer::Surface(_) => {}
+            Framebuffer::External(_) => return Err(Error::ExternalRenderTarget),
+        }
+
+        // Other contexts, possibly on other display connections, read the surface through the X
+        // server, so rendering must have reached it before the surface is handed back.
+        {
+            let _guard = self.temporarily_make_context_current(context)?;
+            GL_FUNCTIONS.with(|gl| unsafe { gl.Finish() });
+            unsafe {
+                x11::glx::glXWaitGL();
+                x11::xlib::XSync(self.native_connection.x11_display, False);
+            }
+        }
+
+        let surface = match mem::replace(&mut context.framebuffer, Framebuffer::None) {
+            Framebuffer::Surface(surface) => surface,
+            Framebuffer::None | Framebuffer::External(_) => unreachable!(),
+        };
+
+        // If we're current, we stay current, but with no surface attached.
+        if context.is_current() {
+            drop(context.make_current(self.native_connection.x11_display))
+        }
+
+        Ok(Some(surface))
+    }
+
+    /// Returns a unique ID representing a context.
+    ///
+    /// This ID is unique to all currently-allocated contexts. If you destroy a context and create
+    /// a new one, the new context might have the same ID as the destroyed one.
+    #[inline]
+    pub fn context_id(&self, context: &Context) -> ContextID {
+        context.id
+    }
+
+    /// Returns various information about the surface attached to a context.
+    ///
+    /// This includes, most notably, the OpenGL framebuffer object needed to render to the surface.
+    pub fn context_surface_info(&self, context: &Context) -> Result<Option<SurfaceInfo>, Error> {
+        match context.framebuffer {
+            Framebuffer::None => Ok(None),
+            Framebuffer::External(_) => Err(Error::ExternalRenderTarget),
+            Framebuffer::Surface(ref surface) => Ok(Some(self.surface_info(surface))),
+        }
+    }
+
+    /// Inserts a fence into the command stream of the given context.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    #[inline]
+    pub fn create_fence(&self, _: &Context) -> Result<Fence, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Imports a sync file as a fence in the command stream of the given context.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    /// The caller retains ownership of the file descriptor.
+    #[inline]
+    pub fn create_fence_from_fd(&self, _: &Context, _: RawFd) -> Result<Fence, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Blocks the calling thread until the fence is signaled.
+    #[inline]
+    pub fn wait_fence(&self, fence: &Fence, _: Option<Duration>) -> Result<bool, Error> {
+        match *fence {}
+    }
+
+    /// Makes the GPU wait for the fence to be signaled before executing any further commands
+    /// issued on the given context.
+    #[inline]
+    pub fn server_wait_fence(&self, _: &Context, fence: &Fence) -> Result<(), Error> {
+        match *fence {}
+    }
+
+    /// Returns a sync file that is signaled along with the fence.
+    #[inline]
+    pub fn export_fence_fd(&self, fence: &Fence) -> Result<RawFd, Error> {
+        match *fence {}
+    }
+
+    /// Destroys a fence.
+    #[inline]
+    pub fn destroy_fence(&self, fence: &mut Fence) {
+        match *fence {}
+    }
+}
+
+impl Context {
+    fn make_current(&self, x11_display: *mut Display) -> Result<(), Error> {
+        let (draw_drawable, read_drawable) = match self.framebuffer {
+            Framebuffer::Surface(ref surface) => (surface.glx_drawable, surface.glx_drawable),
+            Framebuffer::External(drawables) => (drawables.draw, drawables.read),
+            Framebuffer::None => (self.pbuffer, self.pbuffer),
+        };
+        self.make_current_with_drawables(x11_display, draw_drawable, read_drawable)
+    }
+
+    // Makes the context current with the given drawables, regardless of its own render target.
+    // This is how surfaces that aren't bound to the context are rendered to and read from.
+    pub(crate) fn make_current_with_drawables(&self,
+                                              x11_display: *mut Display,
+                                              draw_drawable: GLXDrawable,
+                                              read_drawable: GLXDrawable)
+                                              -> Result<(), Error> {
+        unsafe {
+            let ok = glXMakeContextCurrent(x11_display,
+                                           draw_drawable,
+                                           read_drawable,
+                                           self.glx_context);
+            if ok == False {
+                return Err(Error::MakeCurrentFailed(WindowingApiError::Failed));
+            }
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn is_current(&self) -> bool {
+        unsafe {
+            glXGetCurrentContext() == self.glx_context
+        }
+    }
+}
+
+impl NativeContext {
+    /// Returns the current GLX context.
+    ///
+    /// If there is no current GLX context, this returns a `NoCurrentContext` error.
+    pub fn current() -> Result<NativeContext, Error> {
+        unsafe {
+            let glx_context = glXGetCurrentContext();
+            if glx_context.is_null() {
+                Err(Error::NoCurrentContext)
+            } else {
+                Ok(NativeContext(glx_context))
+            }
+        }
+    }
+}
+
+impl CurrentContextGuard {
+    // Remembers the current context so that it can be restored later. The display is used to
+    // release the context that gets made current in the meantime if none was current before.
+    pub(crate) fn new(x11_display: *mut Display) -> CurrentContextGuard {
+        unsafe {
+            CurrentContextGuard {
+                x11_display,
+                old_x11_display: glXGetCurrentDisplay(),
+                old_glx_context: glXGetCurrentContext(),
+                old_draw_drawable: glXGetCurrentDrawable(),
+                old_read_drawable: glXGetCurrentReadDrawable(),
+            }
+        }
+    }
+}
+
+// Returns the first config that matches the given zero-terminated attribute list, in GLX's order
+// of preference.
+pub(crate) unsafe fn choose_fb_config(x11_display: *mut Display,
+                                      screen: c_int,
+                                      attributes: &[c_int])
+                                      -> Option<GLXFBConfig> {
+    let configs = choose_fb_configs(x11_display, screen, attributes);
+    configs.first().cloned()
+}
+
+// Returns every config that matches the given zero-terminated attribute list, in GLX's order of
+// preference.
+pub(crate) unsafe fn choose_fb_configs(x11_display: *mut Display,
+                                       screen: c_int,
+                                       attributes: &[c_int])
+                                       -> Vec<GLXFBConfig> {
+    debug_assert_eq!(attributes.last(), Some(&0));
+    let mut config_count = 0;
+    let configs = glXChooseFBConfig(x11_display, screen, attributes.as_ptr(), &mut config_count);
+    if configs.is_null() {
+        return vec![];
+    }
+    let result = (0..(config_count as usize)).map(|index| *configs.add(index)).collect();
+    XFree(configs as *mut c_void);
+    result
+}
+
+pub(crate) unsafe fn get_fb_config_attr(x11_display: *mut Display,
+                                        glx_fb_config: GLXFBConfig,
+                                        attribute: c_int)
+                                        -> c_int {
+    let mut value = 0;
+    glXGetFBConfigAttrib(x11_display, glx_fb_config, attribute, &mut value);
+    value
+}
+
+unsafe fn fb_config_from_id(x11_display: *mut Display, screen: c_int, fb_config_id: c_int)
+                            -> Option<GLXFBConfig> {
+    choose_fb_config(x11_display, screen, &[GLX_FBCONFIG_ID, fb_config_id, 0])
+}
+
+unsafe fn create_dummy_pbuffer(x11_display: *mut Display, glx_fb_config: GLXFBConfig)
+                               -> Result<GLXPbuffer, WindowingApiError> {
+    let pbuffer_attributes = [
+        GLX_PBUFFER_WIDTH,  DUMMY_PBUFFER_SIZE,
+        GLX_PBUFFER_HEIGHT, DUMMY_PBUFFER_SIZE,
+        0,
+    ];
+    trap_x_errors(x11_display, || {
+        glXCreatePbuffer(x11_display, glx_fb_config, pbuffer_attributes.as_ptr())
+    })
+}
+
+pub(crate) fn get_proc_address(symbol_name: &str) -> *const c_void {
+    unsafe {
+        let symbol_name: CString = CString::new(symbol_name).unwrap();
+        match glXGetProcAddress(symbol_name.as_ptr() as *const u8) {
+            Some(function) => function as *const c_void,
+            None => ptr::null(),
+        }
+    }
+}
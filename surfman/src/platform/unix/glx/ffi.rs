@@ -0,0 +1,47 @@
+// surfman/surfman/src/platform/unix/glx/ffi.rs
+//
+//! GLX extension definitions that the `x11` crate doesn't provide.
+
+#![allow(non_camel_case_types, non_upper_case_globals)]
+
+use std::os::raw::c_int;
+use x11::glx::{GLXContext, GLXDrawable, GLXFBConfig};
+use x11::xlib::{Bool, Display};
+
+// `GLX_ARB_create_context_robustness`
+pub(crate) const GLX_CONTEXT_ROBUST_ACCESS_BIT_ARB: c_int = 0x0004;
+pub(crate) const GLX_LOSE_CONTEXT_ON_RESET_ARB: c_int = 0x8252;
+pub(crate) const GLX_CONTEXT_RESET_NOTIFICATION_STRATEGY_ARB: c_int = 0x8256;
+
+// `GLX_EXT_texture_from_pixmap`
+pub(crate) const GLX_BIND_TO_TEXTURE_RGBA_EXT: c_int = 0x20d1;
+pub(crate) const GLX_BIND_TO_TEXTURE_TARGETS_EXT: c_int = 0x20d3;
+pub(crate) const GLX_TEXTURE_FORMAT_EXT: c_int = 0x20d5;
+pub(crate) const GLX_TEXTURE_TARGET_EXT: c_int = 0x20d6;
+pub(crate) const GLX_TEXTURE_FORMAT_RGBA_EXT: c_int = 0x20da;
+pub(crate) const GLX_TEXTURE_2D_EXT: c_int = 0x20dc;
+pub(crate) const GLX_FRONT_LEFT_EXT: c_int = 0x20de;
+pub(crate) const GLX_TEXTURE_2D_BIT_EXT: c_int = 0x0002;
+
+// `GLX_EXT_buffer_age`
+pub(crate) const GLX_BACK_BUFFER_AGE_EXT: c_int = 0x20f4;
+
+pub(crate) type glXCreateContextAttribsARB = unsafe extern "C" fn(display: *mut Display,
+                                                                  config: GLXFBConfig,
+                                                                  share_context: GLXContext,
+                                                                  direct: Bool,
+                                                                  attrib_list: *const c_int)
+                                                                  -> GLXContext;
+
+pub(crate) type glXBindTexImageEXT = unsafe extern "C" fn(display: *mut Display,
+                                                          drawable: GLXDrawable,
+                                                          buffer: c_int,
+                                                          attrib_list: *const c_int);
+
+pub(crate) type glXReleaseTexImageEXT = unsafe extern "C" fn(display: *mut Display,
+                                                             drawable: GLXDrawable,
+                                                             buffer: c_int);
+
+pub(crate) type glXSwapIntervalEXT = unsafe extern "C" fn(display: *mut Display,
+                                                          drawable: GLXDrawable,
+                                                          interval: c_int);
@@ -0,0 +1,17 @@
+// surfman/surfman/src/platform/unix/glx/mod.rs
+//
+//! Bindings to GLX via Xlib, for X servers and drivers that don't support EGL.
+
+pub mod connection;
+pub mod context;
+pub mod device;
+pub mod surface;
+
+mod ffi;
+
+#[path = "../../../implementation/mod.rs"]
+mod implementation;
+
+#[cfg(test)]
+#[path = "../../../tests.rs"]
+mod tests;
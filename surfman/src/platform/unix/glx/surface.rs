@@ -0,0 +1,978 @@
+// surfman/surfman/src/platform/unix/glx/surface.rs
+//
+//! Surface management for GLX, backed by X pixmaps and windows.
+
+use crate::context::ContextID;
+use crate::gl::types::{GLenum, GLint, GLuint};
+use crate::gl;
+use crate::gl_utils;
+use crate::{Error, PixelReadback, ReadbackFormat, SurfaceAccess, SurfaceColorspace};
+use crate::{SurfaceDescriptor, SurfaceFormat, SurfaceID, SurfaceInfo, SurfaceType};
+use crate::{ContextAttributeFlags, WindowingApiError};
+use super::connection::{NativeConnectionWrapper, trap_x_errors};
+use super::context::{self, Context, CurrentContextGuard, GL_FUNCTIONS};
+use super::device::Device;
+use super::ffi::{GLX_BACK_BUFFER_AGE_EXT, GLX_FRONT_LEFT_EXT, GLX_TEXTURE_2D_EXT};
+use super::ffi::{GLX_TEXTURE_FORMAT_EXT, GLX_TEXTURE_FORMAT_RGBA_EXT, GLX_TEXTURE_TARGET_EXT};
+
+use euclid::default::{Rect, Size2D};
+use std::cell::Cell;
+use std::fmt::{self, Debug, Formatter};
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::{c_int, c_uint, c_ulong, c_void};
+use std::ptr;
+use std::thread;
+use std::time::Duration;
+use x11::glx::{GLX_DEPTH_SIZE, GLX_DOUBLEBUFFER, GLX_DRAWABLE_TYPE, GLX_RENDER_TYPE};
+use x11::glx::{GLX_RGBA_BIT, GLX_STENCIL_SIZE, GLX_VISUAL_ID, GLX_WINDOW_BIT, GLX_X_RENDERABLE};
+use x11::glx::{GLXDrawable, GLXFBConfig, GLXPixmap, glXCreatePixmap, glXCreateWindow};
+use x11::glx::{glXDestroyPixmap, glXDestroyWindow, glXGetVisualFromFBConfig, glXQueryDrawable};
+use x11::glx::glXSwapBuffers;
+use x11::xlib::{self, Display, Pixmap, True, Window, XCreateGC, XCreatePixmap, XDestroyImage};
+use x11::xlib::{XFree, XFreeGC, XFreePixmap, XGetImage, XGetPixel, XGetWindowAttributes, XImage};
+use x11::xlib::{XPutImage, XPutPixel, XRootWindow, XSync, XVisualIDFromVisual};
+use x11::xlib::{XWindowAttributes, ZPixmap};
+
+const SURFACE_GL_TEXTURE_TARGET: GLenum = gl::TEXTURE_2D;
+
+/// Represents a hardware buffer of pixels that can be rendered to via the CPU or GPU and either
+/// displayed in a native widget or bound to a texture for reading.
+///
+/// On GLX, generic surfaces are X pixmaps, and widget surfaces are X windows. Pixmaps are always
+/// single-buffered, so generic surfaces are rendered to through OpenGL's default framebuffer.
+///
+/// Surfaces are specific to a given context and cannot be rendered to from any context other than
+/// the one they were created with. However, they can be *read* from any context on any thread (as
+/// long as that context shares the same adapter and connection), by wrapping them in a
+/// `SurfaceTexture`.
+///
+/// Depending on the platform, each surface may be internally double-buffered.
+///
+/// Surfaces must be destroyed with the `destroy_surface()` method, or a panic will occur.
+pub struct Surface {
+    pub(crate) glx_drawable: GLXDrawable,
+    pub(crate) size: Size2D<i32>,
+    pub(crate) context_id: ContextID,
+    access: SurfaceAccess,
+    kind: SurfaceKind,
+    destroyed: bool,
+}
+
+enum SurfaceKind {
+    Generic { pixmap: Pixmap },
+    // The swap interval isn't queryable without `GLX_EXT_swap_control`, so it's tracked here.
+    Widget { window: Window, swap_interval: Cell<i32> },
+}
+
+/// Represents an OpenGL texture that wraps a surface.
+///
+/// On GLX, the texture is bound to the surface's pixmap via `GLX_EXT_texture_from_pixmap`, so no
+/// copy is made. Reading from the associated OpenGL texture reads from the surface. It is
+/// undefined behavior to write to such a texture (e.g. by binding it to a framebuffer and
+/// rendering to that framebuffer).
+///
+/// Surface textures are local to a context, but that context does not have to be the same context
+/// as that associated with the underlying surface. The texture must be destroyed with the
+/// `destroy_surface_texture()` method, or a panic will occur.
+pub struct SurfaceTexture {
+    pub(crate) surface: Surface,
+    // The consumer context's own GLX pixmap for the surface's X pixmap.
+    glx_pixmap: GLXPixmap,
+    pub(crate) texture_object: GLuint,
+    pub(crate) phantom: PhantomData<*const ()>,
+}
+
+/// A wrapper for an X11 window.
+#[derive(Clone)]
+pub struct NativeWidget {
+    pub(crate) window: Window,
+}
+
+/// Represents the CPU view of the pixel data of this surface.
+///
+/// On GLX, the pixels are copied out of the X pixmap when the surface is locked and written back
+/// when this guard is dropped.
+pub struct SurfaceDataGuard<'a> {
+    surface: &'a mut Surface,
+    x11_display: *mut Display,
+    image: *mut XImage,
+    pixels: Vec<u8>,
+}
+
+// The bit masks of each channel in an X image.
+#[derive(Clone, Copy)]
+struct PixelMasks {
+    red: c_ulong,
+    green: c_ulong,
+    blue: c_ulong,
+    alpha: c_ulong,
+}
+
+unsafe impl Send for Surface {}
+
+impl Debug for Surface {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Surface({:x})", self.id().0)
+    }
+}
+
+impl Debug for SurfaceTexture {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "SurfaceTexture({:?})", self.surface)
+    }
+}
+
+impl Drop for Surface {
+    fn drop(&mut self) {
+        if !self.destroyed && !thread::panicking() {
+            panic!("Should have destroyed the surface first with `destroy_surface()`!")
+        }
+    }
+}
+
+impl<'a> Drop for SurfaceDataGuard<'a> {
+    fn drop(&mut self) {
+        let pixmap = match self.surface.kind {
+            SurfaceKind::Generic { pixmap } => pixmap,
+            SurfaceKind::Widget { .. } => unreachable!(),
+        };
+
+        unsafe {
+            let masks = PixelMasks::from_image(self.image);
+            let (width, height) = (self.surface.size.width, self.surface.size.height);
+            let stride = self.stride();
+            for y in 0..height {
+                let row = &self.pixels[((height - y - 1) as usize * stride)..];
+                for x in 0..width {
+                    let offset = x as usize * 4;
+                    let rgba = [row[offset], row[offset + 1], row[offset + 2], row[offset + 3]];
+                    XPutPixel(self.image, x, y, masks.to_pixel(rgba));
+                }
+            }
+
+            let gc = XCreateGC(self.x11_display, pixmap, 0, ptr::null_mut());
+            XPutImage(self.x11_display,
+                      pixmap,
+                      gc,
+                      self.image,
+                      0,
+                      0,
+                      0,
+                      0,
+                      width as c_uint,
+                      height as c_uint);
+            XFreeGC(self.x11_display, gc);
+            XSync(self.x11_display, xlib::False);
+            XDestroyImage(self.image);
+        }
+    }
+}
+
+/// A request to be told when the compositor wants the next frame of a widget surface.
+///
+/// Frame callbacks aren't supported on this backend, so this type can't be constructed.
+#[derive(Debug)]
+pub enum FrameCallback {}
+
+impl Device {
+    /// Creates either a generic or a widget surface, depending on the supplied surface type.
+    ///
+    /// Only the given context may ever render to the surface, but generic surfaces can be wrapped
+    /// up in a `SurfaceTexture` for reading by other contexts.
+    ///
+    /// Generic surfaces are X pixmaps in the context's pixel format, so they must be `RGBA8`, or
+    /// an `UnsupportedSurfaceFormat` error is returned.
+    pub fn create_surface(&mut self,
+                          context: &Context,
+                          access: SurfaceAccess,
+                          surface_type: SurfaceType<NativeWidget>)
+                          -> Result<Surface, Error> {
+        match surface_type {
+            SurfaceType::Generic { size, descriptor } => {
+                self.create_generic_surface(context, access, &size, &descriptor)
+            }
+            SurfaceType::Widget { native_widget, colorspace } => {
+                unsafe {
+                    self.create_window_surface(context, access, native_widget.window, colorspace)
+                }
+            }
+        }
+    }
+
+    fn create_generic_surface(&mut self,
+                              context: &Context,
+                              access: SurfaceAccess,
+                              size: &Size2D<i32>,
+                              descriptor: &SurfaceDescriptor)
+                              -> Result<Surface, Error> {
+        if descriptor.format != SurfaceFormat::RGBA8 {
+            return Err(Error::UnsupportedSurfaceFormat);
+        }
+        // FIXME: Support sampleable depth buffers.
+        if descriptor.sampleable_depth {
+            return Err(Error::Unimplemented);
+        }
+        if size.width <= 0 || size.height <= 0 {
+            return Err(Error::SurfaceCreationFailed(WindowingApiError::BadParameter));
+        }
+
+        unsafe {
+            let (pixmap, glx_pixmap) = create_pixmap(&self.native_connection,
+                                                     context.glx_fb_config,
+                                                     size)
+                                           .map_err(Error::SurfaceCreationFailed)?;
+
+            // Clear before the surface exists, so that a failure only has the pixmaps to free.
+            if let Some(ref clear_color) = descriptor.clear_color {
+                let x11_display = self.native_connection.x11_display;
+                let _guard = CurrentContextGuard::new(x11_display);
+                if let Err(err) = context.make_current_with_drawables(x11_display,
+                                                                      glx_pixmap,
+                                                                      glx_pixmap) {
+                    glXDestroyPixmap(x11_display, glx_pixmap);
+                    XFreePixmap(x11_display, pixmap);
+                    return Err(err);
+                }
+                GL_FUNCTIONS.with(|gl| {
+                    gl_utils::clear_framebuffer(gl, 0, clear_color);
+                    gl.Finish();
+                });
+            }
+
+            Ok(Surface {
+                glx_drawable: glx_pixmap,
+                size: *size,
+                context_id: context.id,
+                access,
+                kind: SurfaceKind::Generic { pixmap },
+                destroyed: false,
+            })
+        }
+    }
+
+    unsafe fn create_window_surface(&mut self,
+                                    context: &Context,
+                                    access: SurfaceAccess,
+                                    window: Window,
+                                    colorspace: SurfaceColorspace)
+                                    -> Result<Surface, Error> {
+        if colorspace != SurfaceColorspace::Default {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+
+        let x11_display = self.native_connection.x11_display;
+        let mut window_attributes: XWindowAttributes = mem::zeroed();
+        if XGetWindowAttributes(x11_display, window, &mut window_attributes) == 0 {
+            return Err(Error::SurfaceCreationFailed(WindowingApiError::BadNativeWindow));
+        }
+        let visual_id = XVisualIDFromVisual(window_attributes.visual);
+
+        // Windows are double-buffered and must use a config matching the window's visual, which
+        // may not be the context's config.
+        let flags = context.descriptor.attributes.flags;
+        let depth_size   = if flags.contains(ContextAttributeFlags::DEPTH)   { 24 } else { 0 };
+        let stencil_size = if flags.contains(ContextAttributeFlags::STENCIL) { 8  } else { 0 };
+        let config_attributes = [
+            GLX_X_RENDERABLE,   True,
+            GLX_DRAWABLE_TYPE,  GLX_WINDOW_BIT,
+            GLX_RENDER_TYPE,    GLX_RGBA_BIT,
+            GLX_DOUBLEBUFFER,   True,
+            GLX_DEPTH_SIZE,     depth_size,
+            GLX_STENCIL_SIZE,   stencil_size,
+            0,
+        ];
+        let glx_fb_config = context::choose_fb_configs(x11_display,
+                                                       self.native_connection.screen,
+                                                       &config_attributes)
+            .into_iter()
+            .find(|&glx_fb_config| {
+                context::get_fb_config_attr(x11_display, glx_fb_config, GLX_VISUAL_ID) as c_ulong ==
+                    visual_id
+            })
+            .ok_or(Error::SurfaceCreationFailed(WindowingApiError::BadVisual))?;
+
+        let glx_window = match trap_x_errors(x11_display, || {
+            glXCreateWindow(x11_display, glx_fb_config, window, [0].as_ptr())
+        }) {
+            Ok(glx_window) if glx_window != 0 => glx_window,
+            Ok(_) | Err(_) => {
+                return Err(Error::SurfaceCreationFailed(WindowingApiError::BadNativeWindow))
+            }
+        };
+
+        Ok(Surface {
+            glx_drawable: glx_window,
+            size: Size2D::new(window_attributes.width, window_attributes.height),
+            context_id: context.id,
+            access,
+            kind: SurfaceKind::Widget { window, swap_interval: Cell::new(1) },
+            destroyed: false,
+        })
+    }
+
+    /// Creates a surface texture from an existing generic surface for use with the given context.
+    ///
+    /// The surface texture is local to the supplied context and takes ownership of the surface.
+    /// Destroying the surface texture allows you to retrieve the surface again.
+    ///
+    /// *The supplied context does not have to be the same context that the surface is associated
+    /// with.* This allows you to render to a surface in one context and sample from that surface
+    /// in another context.
+    ///
+    /// This requires `GLX_EXT_texture_from_pixmap`; if the X server doesn't support it, a
+    /// `RequiredExtensionUnavailable` error is returned. Calling this method on a widget surface
+    /// returns a `WidgetAttached` error.
+    pub fn create_surface_texture(&self, context: &mut Context, surface: Surface)
+                                  -> Result<SurfaceTexture, (Error, Surface)> {
+        let pixmap = match surface.kind {
+            SurfaceKind::Generic { pixmap } => pixmap,
+            SurfaceKind::Widget { .. } => return Err((Error::WidgetAttached, surface)),
+        };
+
+        let extensions = &self.native_connection.extensions;
+        let bind_tex_image = match extensions.bind_tex_image {
+            Some(bind_tex_image) if extensions.texture_from_pixmap() => bind_tex_image,
+            _ => return Err((Error::RequiredExtensionUnavailable, surface)),
+        };
+
+        let _guard = match self.temporarily_make_context_current(context) {
+            Ok(guard) => guard,
+            Err(err) => return Err((err, surface)),
+        };
+
+        unsafe {
+            let x11_display = self.native_connection.x11_display;
+            let pixmap_attributes = [
+                GLX_TEXTURE_TARGET_EXT, GLX_TEXTURE_2D_EXT,
+                GLX_TEXTURE_FORMAT_EXT, GLX_TEXTURE_FORMAT_RGBA_EXT,
+                0,
+            ];
+            let glx_pixmap = match trap_x_errors(x11_display, || {
+                glXCreatePixmap(x11_display,
+                                context.glx_fb_config,
+                                pixmap,
+                                pixmap_attributes.as_ptr())
+            }) {
+                Ok(glx_pixmap) if glx_pixmap != 0 => glx_pixmap,
+                Ok(_) => {
+                    let err = Error::SurfaceTextureCreationFailed(WindowingApiError::Failed);
+                    return Err((err, surface));
+                }
+                Err(err) => return Err((Error::SurfaceTextureCreationFailed(err), surface)),
+            };
+
+            GL_FUNCTIONS.with(|gl| {
+                let mut old_texture_object = 0;
+                gl.GetIntegerv(gl::TEXTURE_BINDING_2D, &mut old_texture_object);
+
+                let mut texture_object = 0;
+                gl.GenTextures(1, &mut texture_object);
+                gl.BindTexture(gl::TEXTURE_2D, texture_object);
+                bind_tex_image(x11_display, glx_pixmap, GLX_FRONT_LEFT_EXT, ptr::null());
+                gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+                gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+                gl.TexParameteri(gl::TEXTURE_2D,
+                                 gl::TEXTURE_WRAP_S,
+                                 gl::CLAMP_TO_EDGE as GLint);
+                gl.TexParameteri(gl::TEXTURE_2D,
+                                 gl::TEXTURE_WRAP_T,
+                                 gl::CLAMP_TO_EDGE as GLint);
+
+                gl.BindTexture(gl::TEXTURE_2D, old_texture_object as GLuint);
+
+                Ok(SurfaceTexture { surface, glx_pixmap, texture_object, phantom: PhantomData })
+            })
+        }
+    }
+
+    /// Destroys a surface.
+    ///
+    /// The supplied context must be the context the surface is associated with, or this returns
+    /// an `IncompatibleSurface` error.
+    ///
+    /// You must explicitly call this method to dispose of a surface. Otherwise, a panic occurs in
+    /// the `drop` method.
+    pub fn destroy_surface(&self, context: &mut Context, surface: &mut Surface)
+                           -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let x11_display = self.native_connection.x11_display;
+        unsafe {
+            match surface.kind {
+                SurfaceKind::Generic { pixmap } => {
+                    glXDestroyPixmap(x11_display, surface.glx_drawable);
+                    XFreePixmap(x11_display, pixmap);
+                }
+                SurfaceKind::Widget { .. } => glXDestroyWindow(x11_display, surface.glx_drawable),
+            }
+        }
+
+        surface.glx_drawable = 0;
+        surface.destroyed = true;
+        Ok(())
+    }
+
+    /// Destroys a surface texture and returns the underlying surface.
+    ///
+    /// The supplied context must be the same context the surface texture was created with, or an
+    /// `IncompatibleSurfaceTexture` error is returned.
+    ///
+    /// All surface textures must be explicitly destroyed with this function, or a panic will
+    /// occur.
+    pub fn destroy_surface_texture(&self,
+                                   context: &mut Context,
+                                   mut surface_texture: SurfaceTexture)
+                                   -> Result<Surface, (Error, SurfaceTexture)> {
+        let _guard = match self.temporarily_make_context_current(context) {
+            Ok(guard) => guard,
+            Err(err) => return Err((err, surface_texture)),
+        };
+
+        let x11_display = self.native_connection.x11_display;
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                if let Some(release_tex_image) = self.native_connection
+                                                     .extensions
+                                                     .release_tex_image {
+                    release_tex_image(x11_display, surface_texture.glx_pixmap, GLX_FRONT_LEFT_EXT);
+                }
+                gl.DeleteTextures(1, &surface_texture.texture_object);
+                surface_texture.texture_object = 0;
+                glXDestroyPixmap(x11_display, surface_texture.glx_pixmap);
+                surface_texture.glx_pixmap = 0;
+            }
+        });
+
+        Ok(surface_texture.surface)
+    }
+
+    /// Displays the contents of a widget surface on screen.
+    ///
+    /// Widget surfaces are internally double-buffered, so changes to them don't show up in their
+    /// associated widgets until this method is called.
+    ///
+    /// The supplied context must match the context the surface was created with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn present_surface(&self, context: &Context, surface: &mut Surface) -> Result<(), Error> {
+        if let SurfaceKind::Generic { .. } = surface.kind {
+            return Err(Error::NoWidgetAttached);
+        }
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let x11_display = self.native_connection.x11_display;
+        unsafe {
+            // The swap only flushes pending rendering if the window is current.
+            let _guard = CurrentContextGuard::new(x11_display);
+            context.make_current_with_drawables(x11_display,
+                                                surface.glx_drawable,
+                                                surface.glx_drawable)?;
+            glXSwapBuffers(x11_display, surface.glx_drawable);
+        }
+        Ok(())
+    }
+
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// have changed.
+    ///
+    /// GLX has no way to pass the damaged regions along, so this presents the whole surface.
+    #[inline]
+    pub fn present_surface_with_damage(&self,
+                                       context: &Context,
+                                       surface: &mut Surface,
+                                       _: &[Rect<i32>])
+                                       -> Result<(), Error> {
+        self.present_surface(context, surface)
+    }
+
+    /// Returns the age of the back buffer of a widget surface, in frames.
+    ///
+    /// If `GLX_EXT_buffer_age` is unavailable, this returns 0, meaning that the contents of the
+    /// back buffer are undefined and the whole surface must be redrawn. Generic surfaces have no
+    /// back buffer, so calling this on one returns a `NoWidgetAttached` error.
+    pub fn surface_buffer_age(&self, context: &Context, surface: &Surface) -> Result<u32, Error> {
+        if let SurfaceKind::Generic { .. } = surface.kind {
+            return Err(Error::NoWidgetAttached);
+        }
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+        if !self.native_connection.extensions.buffer_age {
+            return Ok(0);
+        }
+
+        let x11_display = self.native_connection.x11_display;
+        unsafe {
+            // The buffer age can only be queried for the current drawable.
+            let _guard = CurrentContextGuard::new(x11_display);
+            context.make_current_with_drawables(x11_display,
+                                                surface.glx_drawable,
+                                                surface.glx_drawable)?;
+            let mut age = 0;
+            glXQueryDrawable(x11_display,
+                             surface.glx_drawable,
+                             GLX_BACK_BUFFER_AGE_EXT,
+                             &mut age);
+            Ok(age)
+        }
+    }
+
+    /// Sets the number of vertical blanks to wait for before presenting a widget surface.
+    ///
+    /// This requires `GLX_EXT_swap_control`, or a `RequiredExtensionUnavailable` error is
+    /// returned. Negative intervals request adaptive vsync, which additionally requires
+    /// `GLX_EXT_swap_control_tear`, or an `UnsupportedOnThisPlatform` error is returned. Generic
+    /// surfaces are never presented, so calling this on one returns a `NoWidgetAttached` error.
+    pub fn set_swap_interval(&self, context: &Context, surface: &Surface, interval: i32)
+                             -> Result<(), Error> {
+        let swap_interval = match surface.kind {
+            SurfaceKind::Widget { ref swap_interval, .. } => swap_interval,
+            SurfaceKind::Generic { .. } => return Err(Error::NoWidgetAttached),
+        };
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let extensions = &self.native_connection.extensions;
+        let set_interval = extensions.swap_interval.ok_or(Error::RequiredExtensionUnavailable)?;
+        if interval < 0 && !extensions.swap_control_tear {
+            return Err(Error::UnsupportedOnThisPlatform);
+        }
+
+        let x11_display = self.native_connection.x11_display;
+        unsafe {
+            match trap_x_errors(x11_display, || {
+                set_interval(x11_display, surface.glx_drawable, interval)
+            }) {
+                Ok(()) => {}
+                Err(err) => return Err(Error::SwapIntervalFailed(err)),
+            }
+        }
+
+        swap_interval.set(interval);
+        Ok(())
+    }
+
+    /// Returns the swap interval of a widget surface.
+    ///
+    /// This is the last interval set with `set_swap_interval()`, or 1 if none was set. Generic
+    /// surfaces are never presented, so calling this on one returns a `NoWidgetAttached` error.
+    pub fn swap_interval(&self, context: &Context, surface: &Surface) -> Result<i32, Error> {
+        let swap_interval = match surface.kind {
+            SurfaceKind::Widget { ref swap_interval, .. } => swap_interval,
+            SurfaceKind::Generic { .. } => return Err(Error::NoWidgetAttached),
+        };
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+        Ok(swap_interval.get())
+    }
+
+    /// Asks the compositor to signal a frame callback when it's a good time to draw the next frame
+    /// of a widget surface.
+    ///
+    /// Frame callbacks aren't supported on this backend, so this always returns an
+    /// `UnsupportedOnThisPlatform` error.
+    #[inline]
+    pub fn request_frame_callback(&self, _: &Surface) -> Result<FrameCallback, Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
+    /// Dispatches any frame callback events that have arrived, without blocking, and returns true
+    /// if the given frame callback has been signaled.
+    #[inline]
+    pub fn poll_frame_callback(&self, callback: &FrameCallback) -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Blocks the calling thread until the given frame callback is signaled or the timeout
+    /// elapses.
+    #[inline]
+    pub fn wait_for_frame_callback(&self, callback: &FrameCallback, _: Option<Duration>)
+                                   -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Destroys a frame callback.
+    #[inline]
+    pub fn destroy_frame_callback(&self, callback: &mut FrameCallback) {
+        match *callback {}
+    }
+
+    /// Resizes a surface.
+    ///
+    /// Widget surfaces follow the size of their window, so the size argument is ignored for them
+    /// and the window's current size is used instead. Generic surfaces are reallocated at the new
+    /// size. If `preserve_contents` is true, the pixels in the region common to the old and new
+    /// sizes are preserved, and the rest of the surface is cleared to transparent black;
+    /// otherwise, the whole surface is cleared.
+    ///
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn resize_surface(&self,
+                          context: &Context,
+                          surface: &mut Surface,
+                          size: Size2D<i32>,
+                          preserve_contents: bool)
+                          -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let x11_display = self.native_connection.x11_display;
+        let old_pixmap = match surface.kind {
+            SurfaceKind::Generic { pixmap } => pixmap,
+            SurfaceKind::Widget { window, .. } => {
+                unsafe {
+                    let mut window_attributes: XWindowAttributes = mem::zeroed();
+                    if XGetWindowAttributes(x11_display, window, &mut window_attributes) == 0 {
+                        return Err(Error::SurfaceCreationFailed(
+                            WindowingApiError::BadNativeWindow));
+                    }
+                    surface.size = Size2D::new(window_attributes.width, window_attributes.height);
+                }
+                return Ok(());
+            }
+        };
+
+        if size.width <= 0 || size.height <= 0 {
+            return Err(Error::SurfaceCreationFailed(WindowingApiError::BadParameter));
+        }
+
+        unsafe {
+            let (new_pixmap, new_glx_pixmap) = create_pixmap(&self.native_connection,
+                                                             context.glx_fb_config,
+                                                             &size)
+                                                   .map_err(Error::SurfaceCreationFailed)?;
+
+            // Draw to the new pixmap while reading from the old one, so that the common region
+            // can be copied across in one blit.
+            {
+                let _guard = CurrentContextGuard::new(x11_display);
+                if let Err(err) = context.make_current_with_drawables(x11_display,
+                                                                      new_glx_pixmap,
+                                                                      surface.glx_drawable) {
+                    glXDestroyPixmap(x11_display, new_glx_pixmap);
+                    XFreePixmap(x11_display, new_pixmap);
+                    return Err(err);
+                }
+
+                GL_FUNCTIONS.with(|gl| {
+                    gl_utils::clear_framebuffer(gl, 0, &[0.0; 4]);
+                    if preserve_contents {
+                        let common_width = surface.size.width.min(size.width);
+                        let common_height = surface.size.height.min(size.height);
+                        blit_default_framebuffer(gl, common_width, common_height);
+                    }
+                    gl.Finish();
+                });
+            }
+
+            glXDestroyPixmap(x11_display, surface.glx_drawable);
+            XFreePixmap(x11_display, old_pixmap);
+            surface.glx_drawable = new_glx_pixmap;
+            surface.kind = SurfaceKind::Generic { pixmap: new_pixmap };
+            surface.size = size;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a pointer to the underlying surface data for reading or writing by the CPU.
+    ///
+    /// The surface must have been created with CPU access, or a `SurfaceDataInaccessible` error
+    /// is returned. Make sure the surface is unbound from its context first, so that all pending
+    /// rendering has completed. Widget surfaces can't be locked; attempting to do so returns a
+    /// `WidgetAttached` error.
+    ///
+    /// On GLX, locking copies the pixels out of the X server, and dropping the guard copies them
+    /// back in.
+    pub fn lock_surface_data<'s>(&self, surface: &'s mut Surface)
+                                 -> Result<SurfaceDataGuard<'s>, Error> {
+        if !surface.access.cpu_access_allowed() {
+            return Err(Error::SurfaceDataInaccessible);
+        }
+        let pixmap = match surface.kind {
+            SurfaceKind::Generic { pixmap } => pixmap,
+            SurfaceKind::Widget { .. } => return Err(Error::WidgetAttached),
+        };
+
+        let x11_display = self.native_connection.x11_display;
+        unsafe {
+            let (width, height) = (surface.size.width, surface.size.height);
+            let image = XGetImage(x11_display,
+                                  pixmap,
+                                  0,
+                                  0,
+                                  width as c_uint,
+                                  height as c_uint,
+                                  !0,
+                                  ZPixmap);
+            if image.is_null() {
+                return Err(Error::SurfaceDataInaccessible);
+            }
+
+            // X images are stored top to bottom, but surface data is stored bottom to top as
+            // OpenGL does.
+            let masks = PixelMasks::from_image(image);
+            let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+            for y in (0..height).rev() {
+                for x in 0..width {
+                    pixels.extend_from_slice(&masks.to_rgba(XGetPixel(image, x, y)));
+                }
+            }
+
+            Ok(SurfaceDataGuard { surface, x11_display, image, pixels })
+        }
+    }
+
+    /// Returns the OpenGL texture target needed to read from this surface texture.
+    ///
+    /// This will be `GL_TEXTURE_2D` or `GL_TEXTURE_RECTANGLE`, depending on platform.
+    #[inline]
+    pub fn surface_gl_texture_target(&self) -> GLenum {
+        SURFACE_GL_TEXTURE_TARGET
+    }
+
+    /// Returns various information about the surface, including the framebuffer object needed to
+    /// render to this surface.
+    ///
+    /// Before rendering to a surface attached to a context, you must call `glBindFramebuffer()`
+    /// on the framebuffer object returned by this function. On GLX, this is always 0, the default
+    /// framebuffer.
+    #[inline]
+    pub fn surface_info(&self, surface: &Surface) -> SurfaceInfo {
+        SurfaceInfo {
+            size: surface.size,
+            id: surface.id(),
+            context_id: surface.context_id,
+            framebuffer_object: 0,
+            format: SurfaceFormat::RGBA8,
+        }
+    }
+
+    /// Returns the colorspaces that widget surfaces may request.
+    ///
+    /// GLX has no colorspace extensions, so only the default colorspace is supported.
+    #[inline]
+    pub fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        vec![SurfaceColorspace::Default]
+    }
+
+    /// Returns the OpenGL texture object containing the contents of this surface.
+    ///
+    /// It is only legal to read from, not write to, this texture object.
+    #[inline]
+    pub fn surface_texture_object(&self, surface_texture: &SurfaceTexture) -> GLuint {
+        surface_texture.texture_object
+    }
+
+    /// Returns the OpenGL texture object containing the depth buffer of the surface wrapped by
+    /// this surface texture.
+    ///
+    /// Sampleable depth buffers aren't supported on this backend, so this always returns `None`.
+    #[inline]
+    pub fn surface_texture_depth_object(&self, _: &SurfaceTexture) -> Option<GLuint> {
+        None
+    }
+
+    /// Names the OpenGL objects behind a surface after the given label.
+    ///
+    /// GLX surfaces are X drawables with no OpenGL objects behind them, so this only checks that
+    /// the supplied context is the context the surface is associated with, returning an
+    /// `IncompatibleSurface` error if not.
+    pub fn set_surface_label(&self, context: &Context, surface: &Surface, _: &str)
+                             -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+        Ok(())
+    }
+
+    /// Starts copying a region of a surface into CPU memory.
+    ///
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned. The region is in device pixels, with the origin
+    /// at the bottom left as in OpenGL, and must lie within the surface, or an `InvalidRegion`
+    /// error is returned. The surface doesn't need to be bound to the context.
+    pub fn read_surface_pixels(&self,
+                               context: &Context,
+                               surface: &Surface,
+                               rect: Rect<i32>,
+                               format: ReadbackFormat)
+                               -> Result<PixelReadback, Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let x11_display = self.native_connection.x11_display;
+        let _guard = CurrentContextGuard::new(x11_display);
+        context.make_current_with_drawables(x11_display,
+                                            surface.glx_drawable,
+                                            surface.glx_drawable)?;
+        GL_FUNCTIONS.with(|gl| unsafe {
+            PixelReadback::start(gl, context.id, 0, &surface.size, &rect, format)
+        })
+    }
+
+    /// Returns true if the pixels of a readback can be retrieved without blocking.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned.
+    pub fn poll_pixel_readback(&self, context: &Context, readback: &PixelReadback)
+                               -> Result<bool, Error> {
+        if context.id != readback.context_id {
+            return Err(Error::IncompatibleContext);
+        }
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| unsafe { Ok(readback.is_ready(gl)) })
+    }
+
+    /// Returns the pixels of a readback, in the format it was started with.
+    ///
+    /// This blocks until the copy has finished. The supplied context must be the context the
+    /// readback was started on, or an `IncompatibleContext` error is returned.
+    pub fn finish_pixel_readback(&self, context: &Context, mut readback: PixelReadback)
+                                 -> Result<Vec<u8>, (Error, PixelReadback)> {
+        if context.id != readback.context_id {
+            return Err((Error::IncompatibleContext, readback));
+        }
+        let _guard = match self.temporarily_make_context_current(context) {
+            Ok(guard) => guard,
+            Err(err) => return Err((err, readback)),
+        };
+        let result = GL_FUNCTIONS.with(|gl| unsafe { readback.finish(gl) });
+        result.map_err(|err| (err, readback))
+    }
+}
+
+impl Surface {
+    #[inline]
+    fn id(&self) -> SurfaceID {
+        SurfaceID(self.glx_drawable as usize)
+    }
+}
+
+impl<'a> SurfaceDataGuard<'a> {
+    /// Returns the number of bytes per row of the surface.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.surface.size.width as usize * 4
+    }
+
+    /// Returns a mutable slice of the pixel data in this surface, in RGBA format.
+    ///
+    /// Rows are ordered bottom to top.
+    #[inline]
+    pub fn data(&mut self) -> &mut [u8] {
+        &mut self.pixels
+    }
+}
+
+impl PixelMasks {
+    unsafe fn from_image(image: *mut XImage) -> PixelMasks {
+        let (red, green, blue) = ((*image).red_mask, (*image).green_mask, (*image).blue_mask);
+        // Whatever bits of a 32-bit pixel aren't color are alpha.
+        let alpha = if (*image).depth == 32 { !(red | green | blue) & 0xffff_ffff } else { 0 };
+        PixelMasks { red, green, blue, alpha }
+    }
+
+    fn to_rgba(self, pixel: c_ulong) -> [u8; 4] {
+        [
+            channel_from_pixel(pixel, self.red),
+            channel_from_pixel(pixel, self.green),
+            channel_from_pixel(pixel, self.blue),
+            channel_from_pixel(pixel, self.alpha),
+        ]
+    }
+
+    fn to_pixel(self, rgba: [u8; 4]) -> c_ulong {
+        channel_to_pixel(rgba[0], self.red) | channel_to_pixel(rgba[1], self.green) |
+            channel_to_pixel(rgba[2], self.blue) | channel_to_pixel(rgba[3], self.alpha)
+    }
+}
+
+// Channels that the pixel format lacks read as fully opaque.
+fn channel_from_pixel(pixel: c_ulong, mask: c_ulong) -> u8 {
+    if mask == 0 {
+        return 255;
+    }
+    let max = mask >> mask.trailing_zeros();
+    (((pixel & mask) >> mask.trailing_zeros()) * 255 / max) as u8
+}
+
+fn channel_to_pixel(channel: u8, mask: c_ulong) -> c_ulong {
+    if mask == 0 {
+        return 0;
+    }
+    let max = mask >> mask.trailing_zeros();
+    ((channel as c_ulong * max + 127) / 255) << mask.trailing_zeros()
+}
+
+// Creates an X pixmap in the visual of the given config, along with a GLX pixmap for rendering to
+// it.
+unsafe fn create_pixmap(native_connection: &NativeConnectionWrapper,
+                        glx_fb_config: GLXFBConfig,
+                        size: &Size2D<i32>)
+                        -> Result<(Pixmap, GLXPixmap), WindowingApiError> {
+    let x11_display = native_connection.x11_display;
+    let visual_info = glXGetVisualFromFBConfig(x11_display, glx_fb_config);
+    if visual_info.is_null() {
+        return Err(WindowingApiError::BadVisual);
+    }
+    let depth = (*visual_info).depth as c_uint;
+    XFree(visual_info as *mut c_void);
+
+    let root_window = XRootWindow(x11_display, native_connection.screen);
+    let pixmap = trap_x_errors(x11_display, || {
+        XCreatePixmap(x11_display,
+                      root_window,
+                      size.width as c_uint,
+                      size.height as c_uint,
+                      depth)
+    })?;
+
+    let pixmap_attributes: [c_int; 1] = [0];
+    match trap_x_errors(x11_display, || {
+        glXCreatePixmap(x11_display, glx_fb_config, pixmap, pixmap_attributes.as_ptr())
+    }) {
+        Ok(glx_pixmap) if glx_pixmap != 0 => Ok((pixmap, glx_pixmap)),
+        Ok(_) => {
+            XFreePixmap(x11_display, pixmap);
+            Err(WindowingApiError::BadNativePixmap)
+        }
+        Err(err) => {
+            XFreePixmap(x11_display, pixmap);
+            Err(err)
+        }
+    }
+}
+
+// Copies the bottom-left `width` × `height` region of the current read drawable to the current
+// draw drawable, restoring the framebuffer bindings and scissor test afterward.
+fn blit_default_framebuffer(gl: &gl::Gl, width: i32, height: i32) {
+    unsafe {
+        let (mut old_draw_framebuffer, mut old_read_framebuffer) = (0, 0);
+        gl.GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut old_draw_framebuffer);
+        gl.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut old_read_framebuffer);
+        let scissor_enabled = gl.IsEnabled(gl::SCISSOR_TEST) != gl::FALSE;
+
+        gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+        gl.BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        gl.Disable(gl::SCISSOR_TEST);
+        gl.BlitFramebuffer(0, 0, width, height,
+                           0, 0, width, height,
+                           gl::COLOR_BUFFER_BIT,
+                           gl::NEAREST);
+
+        if scissor_enabled {
+            gl.Enable(gl::SCISSOR_TEST);
+        }
+        gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, old_draw_framebuffer as GLuint);
+        gl.BindFramebuffer(gl::READ_FRAMEBUFFER, old_read_framebuffer as GLuint);
+    }
+}
@@ -2,17 +2,21 @@
 //
 //! Backends specific to Unix-like systems, particularly Linux.
 
-// The default when x11 is enabled
-#[cfg(x11)]
-pub mod default;
+mod backend;
+pub use self::backend::Backend;
 
-// The default when x11 is not enabled
-#[cfg(not(x11))]
-pub use wayland as default;
+#[cfg(linux)]
+pub mod default;
 
 #[cfg(linux)]
 pub mod generic;
 
+#[cfg(gbm)]
+pub mod gbm;
+#[cfg(glx)]
+pub mod glx;
+#[cfg(osmesa)]
+pub mod osmesa;
 #[cfg(linux)]
 pub mod wayland;
 #[cfg(x11)]
@@ -0,0 +1,167 @@
+// surfman/surfman/src/platform/unix/osmesa/connection.rs
+//
+//! Represents a connection to a display server.
+//!
+//! OSMesa needs no display server, so connections are just a check that the library is present.
+
+use crate::Error;
+use crate::info::GLApi;
+use crate::platform::generic::multi::connection::BackendConnection;
+use crate::platform::unix::Backend;
+use super::device::{Adapter, Device, NativeDevice};
+use super::surface::NativeWidget;
+
+use euclid::default::Size2D;
+use osmesa_sys::OsMesa;
+
+use std::os::raw::c_void;
+
+#[cfg(feature = "sm-winit")]
+use winit::Window;
+
+/// A no-op connection.
+#[derive(Clone)]
+pub struct Connection;
+
+/// A no-op native connection.
+#[derive(Clone)]
+pub struct NativeConnection;
+
+impl Connection {
+    /// Loads the OSMesa library.
+    ///
+    /// If `libOSMesa.so` can't be found, this returns a `NoGLLibraryFound` error.
+    #[inline]
+    pub fn new() -> Result<Connection, Error> {
+        match OsMesa::try_loading() {
+            Ok(_) => Ok(Connection),
+            Err(_) => Err(Error::NoGLLibraryFound),
+        }
+    }
+
+    /// An alias for `Connection::new()`, present for consistency with other backends.
+    #[inline]
+    pub unsafe fn from_native_connection(_: NativeConnection) -> Result<Connection, Error> {
+        Connection::new()
+    }
+
+    /// Returns the underlying native connection.
+    #[inline]
+    pub fn native_connection(&self) -> NativeConnection {
+        NativeConnection
+    }
+
+    /// Returns the OpenGL API flavor that this connection supports (OpenGL or OpenGL ES).
+    #[inline]
+    pub fn gl_api(&self) -> GLApi {
+        GLApi::GL
+    }
+
+    /// Returns the "best" adapter on this system.
+    ///
+    /// This is an alias for `Connection::create_hardware_adapter()`.
+    #[inline]
+    pub fn create_adapter(&self) -> Result<Adapter, Error> {
+        self.create_hardware_adapter()
+    }
+
+    /// Returns the "best" adapter on this system, preferring high-performance hardware adapters.
+    ///
+    /// On the OSMesa backend, this returns a software adapter.
+    #[inline]
+    pub fn create_hardware_adapter(&self) -> Result<Adapter, Error> {
+        Ok(Adapter)
+    }
+
+    /// Returns the "best" adapter on this system, preferring low-power hardware adapters.
+    ///
+    /// On the OSMesa backend, this returns a software adapter.
+    #[inline]
+    pub fn create_low_power_adapter(&self) -> Result<Adapter, Error> {
+        Ok(Adapter)
+    }
+
+    /// Returns the "best" adapter on this system, preferring software adapters.
+    #[inline]
+    pub fn create_software_adapter(&self) -> Result<Adapter, Error> {
+        Ok(Adapter)
+    }
+
+    /// Returns every adapter that can be used with this connection.
+    ///
+    /// OSMesa always renders on the CPU, so this returns a single software adapter.
+    #[inline]
+    pub fn adapters(&self) -> Result<Vec<Adapter>, Error> {
+        Ok(vec![Adapter])
+    }
+
+    /// Opens the device corresponding to the given adapter.
+    ///
+    /// Device handles are local to a single thread.
+    #[inline]
+    pub fn create_device(&self, adapter: &Adapter) -> Result<Device, Error> {
+        Device::new(self, adapter)
+    }
+
+    /// An alias for `connection.create_device()` with the adapter wrapped in the native device.
+    #[inline]
+    pub unsafe fn create_device_from_native_device(&self, native_device: NativeDevice)
+                                                   -> Result<Device, Error> {
+        Device::new(self, &native_device.adapter)
+    }
+
+    /// Opens the display connection corresponding to the given `winit` window.
+    ///
+    /// OSMesa has no display server, so this always returns an `IncompatibleNativeWidget` error.
+    #[inline]
+    #[cfg(feature = "sm-winit")]
+    pub fn from_winit_window(_: &Window) -> Result<Connection, Error> {
+        Err(Error::IncompatibleNativeWidget)
+    }
+
+    /// Creates a native widget type from the given `winit` window.
+    ///
+    /// OSMesa has no display server, so this always returns an `IncompatibleNativeWidget` error.
+    #[inline]
+    #[cfg(feature = "sm-winit")]
+    pub fn create_native_widget_from_winit_window(&self, _: &Window)
+                                                  -> Result<NativeWidget, Error> {
+        Err(Error::IncompatibleNativeWidget)
+    }
+
+    /// Create a native widget from a raw pointer
+    pub unsafe fn create_native_widget_from_ptr(&self, _raw: *mut c_void, _size: Size2D<i32>) -> NativeWidget {
+        NativeWidget
+    }
+
+    /// Create a native widget type from the given `raw_window_handle::RawWindowHandle`.
+    #[cfg(feature = "sm-raw-window-handle")]
+    #[inline]
+    pub fn create_native_widget_from_rwh(&self, _: raw_window_handle::RawWindowHandle)
+                                                  -> Result<NativeWidget, Error> {
+        Err(Error::IncompatibleNativeWidget)
+    }
+}
+
+impl BackendConnection for Connection {
+    type Backend = Backend;
+
+    #[inline]
+    fn new_with_backend(backend: Backend) -> Option<Result<Connection, Error>> {
+        if backend == Backend::OSMesa {
+            Some(Connection::new())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn backends() -> Vec<Backend> {
+        vec![Backend::OSMesa]
+    }
+
+    #[inline]
+    fn backend(&self) -> Backend {
+        Backend::OSMesa
+    }
+}
@@ -0,0 +1,600 @@
+// surfman/surfman/src/platform/unix/osmesa/context.rs
+//
+//! OpenGL rendering contexts on OSMesa.
+
+use crate::context::{self, CREATE_CONTEXT_MUTEX, ContextID};
+use crate::gl;
+use crate::surface::Framebuffer;
+use crate::{ContextAttributeFlags, ContextAttributes, ContextInfo, ContextResetStatus, Error};
+use crate::{GLVersion, Gl, SurfaceInfo, WindowingApiError};
+use super::device::Device;
+use super::surface::Surface;
+
+use euclid::default::Size2D;
+use osmesa_sys::{OSMESA_COMPAT_PROFILE, OSMESA_CONTEXT_MAJOR_VERSION};
+use osmesa_sys::{OSMESA_CONTEXT_MINOR_VERSION, OSMESA_CORE_PROFILE, OSMESA_DEPTH_BITS};
+use osmesa_sys::{OSMESA_FORMAT, OSMESA_PROFILE, OSMESA_RGBA, OSMESA_STENCIL_BITS, OSMesaContext};
+use osmesa_sys::{OSMesaCreateContextAttribs, OSMesaDestroyContext, OSMesaGetColorBuffer};
+use osmesa_sys::{OSMesaGetCurrentContext, OSMesaGetProcAddress, OSMesaMakeCurrent};
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::thread;
+use std::time::Duration;
+
+const DUMMY_BUFFER_SIZE: i32 = 16;
+
+thread_local! {
+    #[doc(hidden)]
+    pub static GL_FUNCTIONS: Gl = Gl::load_with(get_proc_address);
+}
+
+/// Represents an OpenGL rendering context.
+///
+/// A context allows you to issue rendering commands to a surface. When initially created, a
+/// context has no attached surface, so rendering commands will fail or be ignored. Typically, you
+/// attach a surface to the context before rendering.
+///
+/// Contexts take ownership of the surfaces attached to them. In order to mutate a surface in any
+/// way other than rendering to it (e.g. presenting it to a window, which causes a buffer swap), it
+/// must first be detached from its context. Each surface is associated with a single context upon
+/// creation and may not be rendered to from any other context. However, you can wrap a surface in
+/// a surface texture, which allows the surface to be read from another context.
+///
+/// OpenGL objects may not be shared across contexts directly, but surface textures effectively
+/// allow for sharing of texture data. Contexts are local to a single thread and device.
+///
+/// A context must be explicitly destroyed with `destroy_context()`, or a panic will occur.
+pub struct Context {
+    pub(crate) osmesa_context: OSMesaContext,
+    pub(crate) id: ContextID,
+    framebuffer: Framebuffer<Surface, ()>,
+    descriptor: ContextDescriptor,
+    // OSMesa can't make a context current without a color buffer, so contexts with no surface
+    // attached render here.
+    dummy_pixels: Box<[u32]>,
+    context_is_owned: bool,
+    // Set by `simulate_context_loss()`, since OSMesa contexts can't really be lost.
+    loss_simulated: bool,
+}
+
+/// Wrapper for a native `OSMesaContext`.
+#[derive(Clone, Copy)]
+pub struct NativeContext(pub OSMesaContext);
+
+/// Information needed to create a context. Some APIs call this a "config" or a "pixel format".
+///
+/// These are local to a device.
+#[derive(Clone)]
+pub struct ContextDescriptor {
+    attributes: ContextAttributes,
+}
+
+// The color buffer that a context is rendering to. Surfaces can be destroyed and externally-managed
+// contexts can switch buffers at any time, so this is looked up when needed and never kept.
+struct ColorBuffer {
+    pixels: *mut c_void,
+    size: Size2D<i32>,
+}
+
+#[must_use]
+pub(crate) struct CurrentContextGuard {
+    old_osmesa_context: OSMesaContext,
+}
+
+impl Drop for Context {
+    #[inline]
+    fn drop(&mut self) {
+        if !self.osmesa_context.is_null() && !thread::panicking() {
+            panic!("Contexts must be destroyed explicitly with `destroy_context`!")
+        }
+    }
+}
+
+impl Drop for CurrentContextGuard {
+    fn drop(&mut self) {
+        unsafe {
+            // The old context's color buffer is looked up again rather than remembered, since the
+            // buffer it had may have been freed in the meantime.
+            let old_color_buffer = if self.old_osmesa_context.is_null() {
+                None
+            } else {
+                get_color_buffer(self.old_osmesa_context)
+            };
+            match old_color_buffer {
+                Some(color_buffer) => {
+                    OSMesaMakeCurrent(self.old_osmesa_context,
+                                      color_buffer.pixels,
+                                      gl::UNSIGNED_BYTE,
+                                      color_buffer.size.width,
+                                      color_buffer.size.height);
+                }
+                None => {
+                    OSMesaMakeCurrent(ptr::null_mut(), ptr::null_mut(), 0, 0, 0);
+                }
+            }
+        }
+    }
+}
+
+/// A GPU fence.
+///
+/// Fences aren't supported on this backend yet, so this type can't be constructed.
+#[derive(Debug)]
+pub enum Fence {}
+
+impl Device {
+    /// Creates a context descriptor with the given attributes.
+    ///
+    /// Context descriptors are local to this device.
+    #[inline]
+    pub fn create_context_descriptor(&self, attributes: &ContextAttributes)
+                                     -> Result<ContextDescriptor, Error> {
+        // Mesa doesn't support the OpenGL compatibility profile post version 3.0. Take that into
+        // account.
+        if attributes.flags.contains(ContextAttributeFlags::COMPATIBILITY_PROFILE) &&
+                (attributes.version.major > 3 ||
+                 attributes.version.major == 3 && attributes.version.minor > 0) {
+            return Err(Error::UnsupportedGLProfile);
+        }
+
+        // OSMesa has no way to request a robust context.
+        if attributes.flags.intersects(ContextAttributeFlags::ROBUST_ACCESS |
+                                       ContextAttributeFlags::LOSE_CONTEXT_ON_RESET) {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+
+        // Surfaces render straight into CPU memory, so there is nothing to resolve from.
+        if attributes.samples > 1 {
+            return Err(Error::Unimplemented);
+        }
+
+        Ok(ContextDescriptor { attributes: *attributes })
+    }
+
+    /// Creates a new OpenGL context.
+    ///
+    /// The context initially has no surface attached. Until a surface is bound to it, rendering
+    /// commands will fail or have no effect.
+    pub fn create_context(&mut self, descriptor: &ContextDescriptor) -> Result<Context, Error> {
+        let mut next_context_id = CREATE_CONTEXT_MUTEX.lock().unwrap();
+
+        let attributes = &descriptor.attributes;
+        let flags = attributes.flags;
+        let depth_bits   = if flags.contains(ContextAttributeFlags::DEPTH)   { 24 } else { 0 };
+        let stencil_bits = if flags.contains(ContextAttributeFlags::STENCIL) { 8  } else { 0 };
+
+        // The core profile only exists in OpenGL 3.2 and up.
+        let profile = if flags.contains(ContextAttributeFlags::COMPATIBILITY_PROFILE) ||
+                attributes.version.major < 3 ||
+                (attributes.version.major == 3 && attributes.version.minor < 2) {
+            OSMESA_COMPAT_PROFILE
+        } else {
+            OSMESA_CORE_PROFILE
+        };
+
+        let osmesa_attributes = [
+            OSMESA_FORMAT,                  OSMESA_RGBA as c_int,
+            OSMESA_DEPTH_BITS,              depth_bits,
+            OSMESA_STENCIL_BITS,            stencil_bits,
+            OSMESA_PROFILE,                 profile,
+            OSMESA_CONTEXT_MAJOR_VERSION,   attributes.version.major as c_int,
+            OSMESA_CONTEXT_MINOR_VERSION,   attributes.version.minor as c_int,
+            0,                              0,
+        ];
+
+        unsafe {
+            let osmesa_context = OSMesaCreateContextAttribs(osmesa_attributes.as_ptr(),
+                                                            ptr::null_mut());
+            if osmesa_context.is_null() {
+                // OSMesa doesn't tell us why context creation failed. By far the most common
+                // reason is an unsupported version/profile combination.
+                return Err(Error::ContextCreationFailed(WindowingApiError::BadPixelFormat));
+            }
+
+            let context = Context {
+                osmesa_context,
+                id: *next_context_id,
+                framebuffer: Framebuffer::None,
+                descriptor: (*descriptor).clone(),
+                dummy_pixels: create_dummy_pixels(),
+                context_is_owned: true,
+                loss_simulated: false,
+            };
+            next_context_id.0 += 1;
+
+            // OSMesa has no debug context flag, but Mesa reports `KHR_debug` messages to any
+            // context that asks for them.
+            if flags.contains(ContextAttributeFlags::DEBUG) {
+                let _guard = CurrentContextGuard::new();
+                match context.make_current() {
+                    Ok(()) => context::install_debug_message_callback(get_proc_address),
+                    Err(_) => {
+                        warn!("surfman: couldn't make the debug context current to install its \
+                               logger")
+                    }
+                }
+            }
+
+            Ok(context)
+        }
+    }
+
+    /// Wraps an `OSMesaContext` in a `surfman` context and returns it.
+    ///
+    /// The context is not retained, as there is no way to do this in the OSMesa API. Therefore,
+    /// it is the caller's responsibility to ensure that the returned `Context` object remains
+    /// alive as long as the `OSMesaContext` is.
+    pub unsafe fn create_context_from_native_context(&self, native_context: NativeContext)
+                                                     -> Result<Context, Error> {
+        let osmesa_context = native_context.0;
+        if osmesa_context.is_null() {
+            return Err(Error::IncompatibleNativeContext);
+        }
+
+        let framebuffer = match get_color_buffer(osmesa_context) {
+            Some(_) => Framebuffer::External(()),
+            None => Framebuffer::None,
+        };
+
+        let mut context = Context {
+            osmesa_context,
+            id: ContextID(0),
+            framebuffer,
+            descriptor: ContextDescriptor { attributes: ContextAttributes::zeroed() },
+            dummy_pixels: create_dummy_pixels(),
+            context_is_owned: false,
+            loss_simulated: false,
+        };
+
+        // Query the version and profile of the wrapped context.
+        {
+            let _guard = CurrentContextGuard::new();
+            context.make_current()?;
+            GL_FUNCTIONS.with(|gl| {
+                let mut flags = ContextAttributeFlags::ALPHA;
+                flags.set(ContextAttributeFlags::COMPATIBILITY_PROFILE,
+                          context::current_context_uses_compatibility_profile(gl));
+                flags.set(ContextAttributeFlags::DEBUG, context::current_context_is_debug(gl));
+                context.descriptor.attributes = ContextAttributes {
+                    version: GLVersion::current(gl),
+                    flags,
+                    samples: 0,
+                };
+            });
+        }
+
+        let mut next_context_id = CREATE_CONTEXT_MUTEX.lock().unwrap();
+        context.id = *next_context_id;
+        next_context_id.0 += 1;
+        Ok(context)
+    }
+
+    /// Destroys a context.
+    ///
+    /// The context must have been created on this device.
+    pub fn destroy_context(&self, context: &mut Context) -> Result<(), Error> {
+        if context.osmesa_context.is_null() {
+            return Ok(());
+        }
+
+        if let Ok(Some(mut surface)) = self.unbind_surface_from_context(context) {
+            self.destroy_surface(context, &mut surface)?;
+        }
+
+        unsafe {
+            if OSMesaGetCurrentContext() == context.osmesa_context {
+                OSMesaMakeCurrent(ptr::null_mut(), ptr::null_mut(), 0, 0, 0);
+            }
+            if context.context_is_owned {
+                OSMesaDestroyContext(context.osmesa_context);
+            }
+        }
+
+        context.osmesa_context = ptr::null_mut();
+        Ok(())
+    }
+
+    /// Given a context, returns its underlying `OSMesaContext`.
+    #[inline]
+    pub fn native_context(&self, context: &Context) -> NativeContext {
+        NativeContext(context.osmesa_context)
+    }
+
+    /// Returns the descriptor that this context was created with.
+    #[inline]
+    pub fn context_descriptor(&self, context: &Context) -> ContextDescriptor {
+        context.descriptor.clone()
+    }
+
+    /// Makes the context the current OpenGL context for this thread.
+    ///
+    /// After calling this function, it is valid to use OpenGL rendering commands.
+    #[inline]
+    pub fn make_context_current(&self, context: &Context) -> Result<(), Error> {
+        context.make_current()
+    }
+
+    /// Removes the current OpenGL context from this thread.
+    ///
+    /// After calling this function, OpenGL rendering commands will fail until a new context is
+    /// made current.
+    #[inline]
+    pub fn make_no_context_current(&self) -> Result<(), Error> {
+        unsafe {
+            OSMesaMakeCurrent(ptr::null_mut(), ptr::null_mut(), 0, 0, 0);
+            Ok(())
+        }
+    }
+
+    #[inline]
+    pub(crate) fn temporarily_make_context_current(&self, context: &Context)
+                                                   -> Result<CurrentContextGuard, Error> {
+        let guard = CurrentContextGuard::new();
+        self.make_context_current(context)?;
+        Ok(guard)
+    }
+
+    /// Returns the attributes that the context descriptor was created with.
+    #[inline]
+    pub fn context_descriptor_attributes(&self, context_descriptor: &ContextDescriptor)
+                                         -> ContextAttributes {
+        context_descriptor.attributes
+    }
+
+    /// Returns detailed information about the context, as reported by the driver: the renderer,
+    /// the vendor, the version that was actually created, and the supported extensions.
+    pub fn context_info(&self, context: &Context) -> Result<ContextInfo, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| ContextInfo::current(gl))
+    }
+
+    /// Returns whether the context has been lost because of a GPU reset.
+    ///
+    /// OSMesa renders on the CPU, so contexts are only ever reset by `simulate_context_loss()`.
+    pub fn context_reset_status(&self, context: &Context) -> Result<ContextResetStatus, Error> {
+        if context.loss_simulated {
+            Ok(ContextResetStatus::Guilty)
+        } else {
+            Ok(ContextResetStatus::NoError)
+        }
+    }
+
+    /// Makes `context_reset_status()` report that the context was reset, so that recovery from
+    /// context loss can be tested without a real GPU reset.
+    #[doc(hidden)]
+    pub fn simulate_context_loss(&self, context: &mut Context) {
+        context.loss_simulated = true;
+    }
+
+    /// Fetches the address of an OpenGL function associated with this context.
+    ///
+    /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
+    /// with any other context.
+    ///
+    /// This method is typically used with a function like `gl::load_with()` from the `gl` crate to
+    /// load OpenGL function pointers.
+    #[inline]
+    pub fn get_proc_address(&self, _: &Context, symbol_name: &str) -> *const c_void {
+        get_proc_address(symbol_name)
+    }
+
+    /// Attaches a surface to a context for rendering.
+    ///
+    /// This function takes ownership of the surface. The surface must have been created with this
+    /// context, or an `IncompatibleSurface` error is returned.
+    ///
+    /// If this function is called with a surface already bound, a `SurfaceAlreadyBound` error is
+    /// returned. To avoid this error, first unbind the existing surface with
+    /// `unbind_surface_from_context`.
+    ///
+    /// If an error is returned, the surface is returned alongside it.
+    pub fn bind_surface_to_context(&self, context: &mut Context, surface: Surface)
+                                   -> Result<(), (Error, Surface)> {
+        if context.id != surface.context_id {
+            return Err((Error::IncompatibleSurface, surface));
+        }
+
+        match context.framebuffer {
+            Framebuffer::None => context.framebuffer = Framebuffer::Surface(surface),
+            Framebuffer::External(_) => return Err((Error::ExternalRenderTarget, surface)),
+            Framebuffer::Surface(_) => return Err((Error::SurfaceAlreadyBound, surface)),
+        }
+
+        // If we're current, make the context current again to switch to the new color buffer.
+        if context.is_current() {
+            drop(context.make_current())
+        }
+
+        Ok(())
+    }
+
+    /// Removes and returns any attached surface from this context.
+    ///
+    /// Any pending OpenGL commands targeting this surface will be automatically flushed, so the
+    /// surface is safe to read from immediately when this function returns.
+    pub fn unbind_surface_from_context(&self, context: &mut Context)
+                                       -> Result<Option<Surface>, Error> {
+        match context.framebuffer {
+            Framebuffer::None => return Ok(None),
+            Framebuffer::Surface(_) => {}
+            Framebuffer::External(_) => return Err(Error::ExternalRenderTarget),
+        }
+
+        // OSMesa renders straight into the surface's pixels, so make sure that rendering is
+        // complete before handing the surface back.
+        if context.is_current() {
+            GL_FUNCTIONS.with(|gl| unsafe { gl.Finish() });
+        }
+
+        let surface = match mem::replace(&mut context.framebuffer, Framebuffer::None) {
+            Framebuffer::Surface(surface) => surface,
+            Framebuffer::None | Framebuffer::External(_) => unreachable!(),
+        };
+
+        // If we're current, we stay current, but with no surface attached.
+        if context.is_current() {
+            drop(context.make_current())
+        }
+
+        Ok(Some(surface))
+    }
+
+    /// Returns a unique ID representing a context.
+    ///
+    /// This ID is unique to all currently-allocated contexts. If you destroy a context and create
+    /// a new one, the new context might have the same ID as the destroyed one.
+    #[inline]
+    pub fn context_id(&self, context: &Context) -> ContextID {
+        context.id
+    }
+
+    /// Returns various information about the surface attached to a context.
+    ///
+    /// This includes, most notably, the OpenGL framebuffer object needed to render to the surface.
+    pub fn context_surface_info(&self, context: &Context) -> Result<Option<SurfaceInfo>, Error> {
+        match context.framebuffer {
+            Framebuffer::None => Ok(None),
+            Framebuffer::External(_) => Err(Error::ExternalRenderTarget),
+            Framebuffer::Surface(ref surface) => Ok(Some(self.surface_info(surface))),
+        }
+    }
+
+    /// Inserts a fence into the command stream of the given context.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    #[inline]
+    pub fn create_fence(&self, _: &Context) -> Result<Fence, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Imports a sync file as a fence in the command stream of the given context.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    /// The caller retains ownership of the file descriptor.
+    #[inline]
+    pub fn create_fence_from_fd(&self, _: &Context, _: RawFd) -> Result<Fence, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Blocks the calling thread until the fence is signaled.
+    #[inline]
+    pub fn wait_fence(&self, fence: &Fence, _: Option<Duration>) -> Result<bool, Error> {
+        match *fence {}
+    }
+
+    /// Makes the GPU wait for the fence to be signaled before executing any further commands
+    /// issued on the given context.
+    #[inline]
+    pub fn server_wait_fence(&self, _: &Context, fence: &Fence) -> Result<(), Error> {
+        match *fence {}
+    }
+
+    /// Returns a sync file that is signaled along with the fence.
+    #[inline]
+    pub fn export_fence_fd(&self, fence: &Fence) -> Result<RawFd, Error> {
+        match *fence {}
+    }
+
+    /// Destroys a fence.
+    #[inline]
+    pub fn destroy_fence(&self, fence: &mut Fence) {
+        match *fence {}
+    }
+}
+
+impl Context {
+    fn make_current(&self) -> Result<(), Error> {
+        let (pixels, size) = match self.framebuffer {
+            Framebuffer::Surface(ref surface) => {
+                (surface.pixels.as_ptr() as *mut c_void, surface.size)
+            }
+            Framebuffer::External(()) => {
+                match unsafe { get_color_buffer(self.osmesa_context) } {
+                    Some(color_buffer) => (color_buffer.pixels, color_buffer.size),
+                    None => self.dummy_color_buffer(),
+                }
+            }
+            Framebuffer::None => self.dummy_color_buffer(),
+        };
+
+        unsafe {
+            let ok = OSMesaMakeCurrent(self.osmesa_context,
+                                       pixels,
+                                       gl::UNSIGNED_BYTE,
+                                       size.width,
+                                       size.height);
+            if ok == 0 {
+                return Err(Error::MakeCurrentFailed(WindowingApiError::Failed));
+            }
+            Ok(())
+        }
+    }
+
+    fn dummy_color_buffer(&self) -> (*mut c_void, Size2D<i32>) {
+        let size = Size2D::new(DUMMY_BUFFER_SIZE, DUMMY_BUFFER_SIZE);
+        (self.dummy_pixels.as_ptr() as *mut c_void, size)
+    }
+
+    #[inline]
+    fn is_current(&self) -> bool {
+        unsafe {
+            OSMesaGetCurrentContext() == self.osmesa_context
+        }
+    }
+}
+
+impl NativeContext {
+    /// Returns the current OSMesa context.
+    ///
+    /// If there is no current OSMesa context, this returns a `NoCurrentContext` error.
+    pub fn current() -> Result<NativeContext, Error> {
+        unsafe {
+            let osmesa_context = OSMesaGetCurrentContext();
+            if osmesa_context.is_null() {
+                Err(Error::NoCurrentContext)
+            } else {
+                Ok(NativeContext(osmesa_context))
+            }
+        }
+    }
+}
+
+impl CurrentContextGuard {
+    pub(crate) fn new() -> CurrentContextGuard {
+        unsafe {
+            CurrentContextGuard { old_osmesa_context: OSMesaGetCurrentContext() }
+        }
+    }
+}
+
+unsafe fn get_color_buffer(osmesa_context: OSMesaContext) -> Option<ColorBuffer> {
+    let (mut width, mut height, mut format) = (0, 0, 0);
+    let mut pixels = ptr::null_mut();
+    let ok = OSMesaGetColorBuffer(osmesa_context,
+                                  &mut width,
+                                  &mut height,
+                                  &mut format,
+                                  &mut pixels);
+    if ok == 0 || pixels.is_null() {
+        None
+    } else {
+        Some(ColorBuffer { pixels, size: Size2D::new(width, height) })
+    }
+}
+
+fn create_dummy_pixels() -> Box<[u32]> {
+    vec![0; (DUMMY_BUFFER_SIZE * DUMMY_BUFFER_SIZE) as usize].into_boxed_slice()
+}
+
+fn get_proc_address(symbol_name: &str) -> *const c_void {
+    unsafe {
+        let symbol_name: CString = CString::new(symbol_name).unwrap();
+        match OSMesaGetProcAddress(symbol_name.as_ptr()) {
+            Some(function) => function as *const c_void,
+            None => ptr::null(),
+        }
+    }
+}
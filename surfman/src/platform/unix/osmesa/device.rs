@@ -0,0 +1,64 @@
+// surfman/surfman/src/platform/unix/osmesa/device.rs
+//
+//! A handle to the OSMesa software renderer.
+
+use crate::{Error, GLApi};
+use super::connection::Connection;
+
+/// Represents a hardware display adapter that can be used for rendering (including the CPU).
+///
+/// On OSMesa, the only adapter is the CPU.
+///
+/// Adapters can be sent between threads. To render with an adapter, open a thread-local `Device`.
+#[derive(Clone, Debug)]
+pub struct Adapter;
+
+/// A thread-local handle to a device.
+///
+/// Devices contain most of the relevant surface management methods.
+pub struct Device {
+    pub(crate) adapter: Adapter,
+}
+
+/// Wraps an adapter.
+///
+/// On OSMesa, devices and adapters are essentially identical types.
+#[derive(Clone)]
+pub struct NativeDevice {
+    /// The adapter corresponding to this device.
+    pub adapter: Adapter,
+}
+
+impl Device {
+    #[inline]
+    pub(crate) fn new(_: &Connection, adapter: &Adapter) -> Result<Device, Error> {
+        Ok(Device { adapter: (*adapter).clone() })
+    }
+
+    /// Returns the native device corresponding to this device.
+    ///
+    /// This method is essentially an alias for the `adapter()` method on OSMesa, since there is
+    /// no explicit concept of a device on this backend.
+    #[inline]
+    pub fn native_device(&self) -> NativeDevice {
+        NativeDevice { adapter: self.adapter() }
+    }
+
+    /// Returns the display server connection that this device was created with.
+    #[inline]
+    pub fn connection(&self) -> Connection {
+        Connection
+    }
+
+    /// Returns the adapter that this device was created with.
+    #[inline]
+    pub fn adapter(&self) -> Adapter {
+        self.adapter.clone()
+    }
+
+    /// Returns the OpenGL API flavor that this device supports (OpenGL or OpenGL ES).
+    #[inline]
+    pub fn gl_api(&self) -> GLApi {
+        GLApi::GL
+    }
+}
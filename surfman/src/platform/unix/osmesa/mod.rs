@@ -0,0 +1,16 @@
+// surfman/surfman/src/platform/unix/osmesa/mod.rs
+//
+//! The OSMesa ("off-screen Mesa") software backend, which renders into surfaces that live in CPU
+//! memory and needs neither a display server nor a GPU.
+
+pub mod connection;
+pub mod context;
+pub mod device;
+pub mod surface;
+
+#[path = "../../../implementation/mod.rs"]
+mod implementation;
+
+#[cfg(test)]
+#[path = "../../../tests.rs"]
+mod tests;
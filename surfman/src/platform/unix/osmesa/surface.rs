@@ -0,0 +1,518 @@
+// surfman/surfman/src/platform/unix/osmesa/surface.rs
+//
+//! Surfaces in CPU memory on OSMesa.
+
+use crate::context::ContextID;
+use crate::gl::types::{GLenum, GLint, GLuint};
+use crate::gl;
+use crate::{Error, PixelReadback, ReadbackFormat, SurfaceAccess, SurfaceID, SurfaceInfo};
+use crate::{SurfaceColorspace, SurfaceFormat, SurfaceType};
+use super::context::{Context, GL_FUNCTIONS};
+use super::device::Device;
+
+use euclid::default::{Rect, Size2D};
+use std::fmt::{self, Debug, Formatter};
+use std::marker::PhantomData;
+use std::mem;
+use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+const SURFACE_GL_TEXTURE_TARGET: GLenum = gl::TEXTURE_2D;
+
+// Surfaces have no native object to take an ID from, and their pixel buffers can be empty or
+// reused after they're freed, so IDs are allocated from a counter instead.
+static NEXT_SURFACE_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// Represents a buffer of pixels that can be rendered to via the CPU or GPU and bound to a texture
+/// for reading.
+///
+/// On OSMesa, surfaces live in CPU memory, and the OpenGL implementation renders directly into
+/// them. Pixels are stored as 8-bit RGBA, with rows ordered bottom to top as OpenGL does.
+///
+/// Surfaces are specific to a given context and cannot be rendered to from any context other than
+/// the one they were created with. However, they can be *read* from any context on any thread, by
+/// wrapping them in a `SurfaceTexture`.
+///
+/// Widget surfaces are not supported on this backend.
+///
+/// Surfaces must be destroyed with the `destroy_surface()` method, or a panic will occur.
+pub struct Surface {
+    pub(crate) pixels: Box<[u32]>,
+    pub(crate) size: Size2D<i32>,
+    pub(crate) context_id: ContextID,
+    id: SurfaceID,
+    access: SurfaceAccess,
+    destroyed: bool,
+}
+
+/// Represents an OpenGL texture that wraps a surface.
+///
+/// On OSMesa, the contents of the surface are uploaded to the texture when the surface texture is
+/// created. It is undefined behavior to write to such a texture (e.g. by binding it to a
+/// framebuffer and rendering to that framebuffer).
+///
+/// Surface textures are local to a context, but that context does not have to be the same context
+/// as that associated with the underlying surface. The texture must be destroyed with the
+/// `destroy_surface_texture()` method, or a panic will occur.
+pub struct SurfaceTexture {
+    pub(crate) surface: Surface,
+    pub(crate) texture_object: GLuint,
+    pub(crate) phantom: PhantomData<*const ()>,
+}
+
+/// A placeholder wrapper for a native widget.
+#[derive(Clone)]
+pub struct NativeWidget;
+
+/// Represents the CPU view of the pixel data of this surface.
+pub struct SurfaceDataGuard<'a> {
+    surface: &'a mut Surface,
+}
+
+unsafe impl Send for Surface {}
+
+impl Debug for Surface {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Surface({:x})", self.id().0)
+    }
+}
+
+impl Debug for SurfaceTexture {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "SurfaceTexture({:?})", self.surface)
+    }
+}
+
+impl Drop for Surface {
+    fn drop(&mut self) {
+        if !self.destroyed && !thread::panicking() {
+            panic!("Should have destroyed the surface first with `destroy_surface()`!")
+        }
+    }
+}
+
+/// A request to be told when the compositor wants the next frame of a widget surface.
+///
+/// Frame callbacks aren't supported on this backend, so this type can't be constructed.
+#[derive(Debug)]
+pub enum FrameCallback {}
+
+impl Device {
+    /// Creates either a generic or a widget surface, depending on the supplied surface type.
+    ///
+    /// Only the given context may ever render to the surface, but generic surfaces can be wrapped
+    /// up in a `SurfaceTexture` for reading by other contexts.
+    ///
+    /// Widget surfaces are not supported on OSMesa; requesting one returns an
+    /// `UnsupportedOnThisPlatform` error. Since OSMesa renders straight into the surface's memory
+    /// in the context's pixel format, generic surfaces must be `RGBA8`, or an
+    /// `UnsupportedSurfaceFormat` error is returned.
+    pub fn create_surface(&mut self,
+                          context: &Context,
+                          access: SurfaceAccess,
+                          surface_type: SurfaceType<NativeWidget>)
+                          -> Result<Surface, Error> {
+        match surface_type {
+            SurfaceType::Generic { size, descriptor } => {
+                if descriptor.format != SurfaceFormat::RGBA8 {
+                    return Err(Error::UnsupportedSurfaceFormat);
+                }
+                // FIXME: Support sampleable depth buffers.
+                if descriptor.sampleable_depth {
+                    return Err(Error::Unimplemented);
+                }
+
+                let mut pixels = allocate_pixels(&size);
+                if let Some(ref clear_color) = descriptor.clear_color {
+                    let mut rgba = [0; 4];
+                    for (byte, &channel) in rgba.iter_mut().zip(clear_color.iter()) {
+                        *byte = (channel.max(0.0).min(1.0) * 255.0).round() as u8;
+                    }
+                    for pixel in pixels.iter_mut() {
+                        *pixel = u32::from_le_bytes(rgba);
+                    }
+                }
+
+                Ok(Surface {
+                    pixels,
+                    size,
+                    context_id: context.id,
+                    id: SurfaceID(NEXT_SURFACE_ID.fetch_add(1, Ordering::Relaxed)),
+                    access,
+                    destroyed: false,
+                })
+            }
+            SurfaceType::Widget { .. } => Err(Error::UnsupportedOnThisPlatform),
+        }
+    }
+
+    /// Creates a surface texture from an existing generic surface for use with the given context.
+    ///
+    /// The surface texture is local to the supplied context and takes ownership of the surface.
+    /// Destroying the surface texture allows you to retrieve the surface again.
+    ///
+    /// *The supplied context does not have to be the same context that the surface is associated
+    /// with.* This allows you to render to a surface in one context and sample from that surface
+    /// in another context.
+    ///
+    /// On OSMesa, the surface's pixels are copied into the texture at the time this method is
+    /// called.
+    pub fn create_surface_texture(&self, context: &mut Context, surface: Surface)
+                                  -> Result<SurfaceTexture, (Error, Surface)> {
+        let _guard = match self.temporarily_make_context_current(context) {
+            Ok(guard) => guard,
+            Err(err) => return Err((err, surface)),
+        };
+
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                let mut old_texture_object = 0;
+                gl.GetIntegerv(gl::TEXTURE_BINDING_2D, &mut old_texture_object);
+
+                let mut texture_object = 0;
+                gl.GenTextures(1, &mut texture_object);
+                gl.BindTexture(gl::TEXTURE_2D, texture_object);
+                gl.TexImage2D(gl::TEXTURE_2D,
+                              0,
+                              gl::RGBA8 as GLint,
+                              surface.size.width,
+                              surface.size.height,
+                              0,
+                              gl::RGBA,
+                              gl::UNSIGNED_BYTE,
+                              surface.pixels.as_ptr() as *const _);
+                gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+                gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+                gl.TexParameteri(gl::TEXTURE_2D,
+                                 gl::TEXTURE_WRAP_S,
+                                 gl::CLAMP_TO_EDGE as GLint);
+                gl.TexParameteri(gl::TEXTURE_2D,
+                                 gl::TEXTURE_WRAP_T,
+                                 gl::CLAMP_TO_EDGE as GLint);
+
+                gl.BindTexture(gl::TEXTURE_2D, old_texture_object as GLuint);
+
+                Ok(SurfaceTexture { surface, texture_object, phantom: PhantomData })
+            }
+        })
+    }
+
+    /// Destroys a surface.
+    ///
+    /// The supplied context must be the context the surface is associated with, or this returns
+    /// an `IncompatibleSurface` error.
+    ///
+    /// You must explicitly call this method to dispose of a surface. Otherwise, a panic occurs in
+    /// the `drop` method.
+    pub fn destroy_surface(&self, context: &mut Context, surface: &mut Surface)
+                           -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        surface.pixels = Box::new([]);
+        surface.destroyed = true;
+        Ok(())
+    }
+
+    /// Destroys a surface texture and returns the underlying surface.
+    ///
+    /// The supplied context must be the same context the surface texture was created with, or an
+    /// `IncompatibleSurfaceTexture` error is returned.
+    ///
+    /// All surface textures must be explicitly destroyed with this function, or a panic will
+    /// occur.
+    pub fn destroy_surface_texture(&self,
+                                   context: &mut Context,
+                                   mut surface_texture: SurfaceTexture)
+                                   -> Result<Surface, (Error, SurfaceTexture)> {
+        let _guard = match self.temporarily_make_context_current(context) {
+            Ok(guard) => guard,
+            Err(err) => return Err((err, surface_texture)),
+        };
+
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                gl.DeleteTextures(1, &surface_texture.texture_object);
+                surface_texture.texture_object = 0;
+            }
+        });
+
+        Ok(surface_texture.surface)
+    }
+
+    /// Displays the contents of a widget surface on screen.
+    ///
+    /// OSMesa has no widget surfaces, so this always returns a `NoWidgetAttached` error.
+    #[inline]
+    pub fn present_surface(&self, _: &Context, _: &mut Surface) -> Result<(), Error> {
+        Err(Error::NoWidgetAttached)
+    }
+
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// have changed.
+    ///
+    /// OSMesa has no widget surfaces, so this always returns a `NoWidgetAttached` error.
+    #[inline]
+    pub fn present_surface_with_damage(&self, _: &Context, _: &mut Surface, _: &[Rect<i32>])
+                                       -> Result<(), Error> {
+        Err(Error::NoWidgetAttached)
+    }
+
+    /// Returns the age of the back buffer of a widget surface.
+    ///
+    /// OSMesa has no widget surfaces, so this always returns a `NoWidgetAttached` error.
+    #[inline]
+    pub fn surface_buffer_age(&self, _: &Context, _: &Surface) -> Result<u32, Error> {
+        Err(Error::NoWidgetAttached)
+    }
+
+    /// Sets the swap interval of a widget surface.
+    ///
+    /// OSMesa has no widget surfaces, so this always returns a `NoWidgetAttached` error.
+    #[inline]
+    pub fn set_swap_interval(&self, _: &Context, _: &Surface, _: i32) -> Result<(), Error> {
+        Err(Error::NoWidgetAttached)
+    }
+
+    /// Returns the swap interval of a widget surface.
+    ///
+    /// OSMesa has no widget surfaces, so this always returns a `NoWidgetAttached` error.
+    #[inline]
+    pub fn swap_interval(&self, _: &Context, _: &Surface) -> Result<i32, Error> {
+        Err(Error::NoWidgetAttached)
+    }
+
+    /// Asks the compositor to signal a frame callback when it's a good time to draw the next frame
+    /// of a widget surface.
+    ///
+    /// Frame callbacks aren't supported on this backend, so this always returns an
+    /// `UnsupportedOnThisPlatform` error.
+    #[inline]
+    pub fn request_frame_callback(&self, _: &Surface) -> Result<FrameCallback, Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
+    /// Dispatches any frame callback events that have arrived, without blocking, and returns true
+    /// if the given frame callback has been signaled.
+    #[inline]
+    pub fn poll_frame_callback(&self, callback: &FrameCallback) -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Blocks the calling thread until the given frame callback is signaled or the timeout
+    /// elapses.
+    #[inline]
+    pub fn wait_for_frame_callback(&self, callback: &FrameCallback, _: Option<Duration>)
+                                   -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Destroys a frame callback.
+    #[inline]
+    pub fn destroy_frame_callback(&self, callback: &mut FrameCallback) {
+        match *callback {}
+    }
+
+    /// Resizes a surface.
+    ///
+    /// If `preserve_contents` is true, the pixels in the region common to the old and new sizes
+    /// are preserved, and the rest of the surface is cleared to transparent black; otherwise, the
+    /// whole surface is cleared.
+    ///
+    /// If the surface is attached to a context, it must be unbound and rebound for rendering to
+    /// target the resized buffer.
+    pub fn resize_surface(&self,
+                          _: &Context,
+                          surface: &mut Surface,
+                          size: Size2D<i32>,
+                          preserve_contents: bool)
+                          -> Result<(), Error> {
+        let mut new_pixels = allocate_pixels(&size);
+        if preserve_contents {
+            let common_width = surface.size.width.min(size.width).max(0) as usize;
+            let common_height = surface.size.height.min(size.height).max(0) as usize;
+            for y in 0..common_height {
+                let old_start = y * surface.size.width as usize;
+                let new_start = y * size.width as usize;
+                new_pixels[new_start..(new_start + common_width)].copy_from_slice(
+                    &surface.pixels[old_start..(old_start + common_width)]);
+            }
+        }
+
+        surface.pixels = new_pixels;
+        surface.size = size;
+        Ok(())
+    }
+
+    /// Returns a pointer to the underlying surface data for reading or writing by the CPU.
+    ///
+    /// The surface must have been created with CPU access, or a `SurfaceDataInaccessible` error
+    /// is returned. Make sure the surface is unbound from its context first, so that all pending
+    /// rendering has completed.
+    #[inline]
+    pub fn lock_surface_data<'s>(&self, surface: &'s mut Surface)
+                                 -> Result<SurfaceDataGuard<'s>, Error> {
+        if !surface.access.cpu_access_allowed() {
+            return Err(Error::SurfaceDataInaccessible);
+        }
+        Ok(SurfaceDataGuard { surface })
+    }
+
+    /// Returns the OpenGL texture target needed to read from this surface texture.
+    ///
+    /// This will be `GL_TEXTURE_2D` or `GL_TEXTURE_RECTANGLE`, depending on platform.
+    #[inline]
+    pub fn surface_gl_texture_target(&self) -> GLenum {
+        SURFACE_GL_TEXTURE_TARGET
+    }
+
+    /// Returns various information about the surface, including the framebuffer object needed to
+    /// render to this surface.
+    ///
+    /// Before rendering to a surface attached to a context, you must call `glBindFramebuffer()`
+    /// on the framebuffer object returned by this function. On OSMesa, this is always 0, the
+    /// default framebuffer.
+    #[inline]
+    pub fn surface_info(&self, surface: &Surface) -> SurfaceInfo {
+        SurfaceInfo {
+            size: surface.size,
+            id: surface.id(),
+            context_id: surface.context_id,
+            framebuffer_object: 0,
+            format: SurfaceFormat::RGBA8,
+        }
+    }
+
+    /// Returns the colorspaces that widget surfaces may request.
+    ///
+    /// This backend doesn't support widget surfaces, so the list is always empty.
+    #[inline]
+    pub fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        vec![]
+    }
+
+    /// Returns the OpenGL texture object containing the contents of this surface.
+    ///
+    /// It is only legal to read from, not write to, this texture object.
+    #[inline]
+    pub fn surface_texture_object(&self, surface_texture: &SurfaceTexture) -> GLuint {
+        surface_texture.texture_object
+    }
+
+    /// Returns the OpenGL texture object containing the depth buffer of the surface wrapped by
+    /// this surface texture.
+    ///
+    /// Sampleable depth buffers aren't supported on this backend, so this always returns `None`.
+    #[inline]
+    pub fn surface_texture_depth_object(&self, _: &SurfaceTexture) -> Option<GLuint> {
+        None
+    }
+
+    /// Names the OpenGL objects behind a surface after the given label.
+    ///
+    /// OSMesa surfaces are plain memory with no OpenGL objects behind them, so this only checks
+    /// that the supplied context is the context the surface is associated with, returning an
+    /// `IncompatibleSurface` error if not.
+    pub fn set_surface_label(&self, context: &Context, surface: &Surface, _: &str)
+                             -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+        Ok(())
+    }
+
+    /// Copies a region of a surface into CPU memory.
+    ///
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned. The region is in device pixels, with the origin
+    /// at the bottom left as in OpenGL, and must lie within the surface, or an `InvalidRegion`
+    /// error is returned.
+    ///
+    /// On OSMesa, surfaces already live in CPU memory, so the copy happens immediately.
+    pub fn read_surface_pixels(&self,
+                               context: &Context,
+                               surface: &Surface,
+                               rect: Rect<i32>,
+                               format: ReadbackFormat)
+                               -> Result<PixelReadback, Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+        if rect.origin.x < 0 || rect.origin.y < 0 || rect.size.width < 0 ||
+                rect.size.height < 0 || rect.max_x() > surface.size.width ||
+                rect.max_y() > surface.size.height {
+            return Err(Error::InvalidRegion);
+        }
+
+        let mut pixels = Vec::with_capacity(rect.size.width as usize *
+                                            rect.size.height as usize * 4);
+        for y in rect.origin.y..rect.max_y() {
+            let start = (y * surface.size.width + rect.origin.x) as usize;
+            let end = start + rect.size.width as usize;
+            for pixel in &surface.pixels[start..end] {
+                pixels.extend_from_slice(&pixel.to_ne_bytes());
+            }
+        }
+
+        Ok(PixelReadback::from_pixels(context.id, rect.size, format, pixels))
+    }
+
+    /// Returns true if the pixels of a readback can be retrieved without blocking.
+    ///
+    /// On OSMesa, this always returns true for readbacks started on the supplied context.
+    pub fn poll_pixel_readback(&self, context: &Context, readback: &PixelReadback)
+                               -> Result<bool, Error> {
+        if context.id != readback.context_id {
+            return Err(Error::IncompatibleContext);
+        }
+        Ok(true)
+    }
+
+    /// Returns the pixels of a readback, in the format it was started with.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned.
+    pub fn finish_pixel_readback(&self, context: &Context, mut readback: PixelReadback)
+                                 -> Result<Vec<u8>, (Error, PixelReadback)> {
+        if context.id != readback.context_id {
+            return Err((Error::IncompatibleContext, readback));
+        }
+        let result = GL_FUNCTIONS.with(|gl| unsafe { readback.finish(gl) });
+        result.map_err(|err| (err, readback))
+    }
+}
+
+impl Surface {
+    #[inline]
+    fn id(&self) -> SurfaceID {
+        self.id
+    }
+}
+
+impl<'a> SurfaceDataGuard<'a> {
+    /// Returns the number of bytes per row of the surface.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.surface.size.width as usize * mem::size_of::<u32>()
+    }
+
+    /// Returns a mutable slice of the pixel data in this surface, in RGBA format.
+    ///
+    /// Rows are ordered bottom to top.
+    #[inline]
+    pub fn data(&mut self) -> &mut [u8] {
+        let pixels = &mut self.surface.pixels;
+        unsafe {
+            slice::from_raw_parts_mut(pixels.as_mut_ptr() as *mut u8,
+                                      pixels.len() * mem::size_of::<u32>())
+        }
+    }
+}
+
+fn allocate_pixels(size: &Size2D<i32>) -> Box<[u32]> {
+    let pixel_count = size.width.max(0) as usize * size.height.max(0) as usize;
+    vec![0; pixel_count].into_boxed_slice()
+}
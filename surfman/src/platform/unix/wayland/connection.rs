@@ -8,6 +8,9 @@ use crate::egl;
 use crate::info::GLApi;
 use crate::platform::generic::egl::device::EGL_FUNCTIONS;
 use crate::platform::generic::egl::ffi::EGL_PLATFORM_WAYLAND_KHR;
+use crate::platform::generic::multi::connection::BackendConnection;
+use crate::platform::unix::generic::device::{AdapterCache, terminate_device_display};
+use crate::platform::unix::Backend;
 use super::device::{Adapter, Device, NativeDevice};
 use super::surface::NativeWidget;
 
@@ -15,7 +18,7 @@ use euclid::default::Size2D;
 use std::os::raw::c_void;
 use std::ptr;
 use std::sync::Arc;
-use wayland_sys::client::{WAYLAND_CLIENT_HANDLE, wl_display, wl_proxy};
+use wayland_sys::client::{WAYLAND_CLIENT_HANDLE, wl_display, wl_event_queue, wl_proxy};
 
 #[cfg(feature = "sm-winit")]
 use winit::Window;
@@ -30,7 +33,23 @@ pub struct Connection {
 
 pub(crate) struct NativeConnectionWrapper {
     pub(crate) egl_display: EGLDisplay,
-    wayland_display: Option<*mut wl_display>,
+    // Whether the EGL display was opened on a specific device for this connection, and so must
+    // be terminated with it.
+    egl_display_is_owned: bool,
+    pub(crate) wayland: Option<WaylandDisplay>,
+    pub(crate) adapters: AdapterCache,
+    // The connection that owns the Wayland display, if this one only renders to it with another
+    // device.
+    _parent: Option<Arc<NativeConnectionWrapper>>,
+}
+
+// The Wayland display underlying a connection, if known, along with the event queue that frame
+// callbacks are dispatched on. Keeping them off the default queue means that waiting for a frame
+// callback never dispatches events that belong to the application.
+pub(crate) struct WaylandDisplay {
+    pub(crate) display: *mut wl_display,
+    pub(crate) event_queue: *mut wl_event_queue,
+    is_owned: bool,
 }
 
 /// An EGL display wrapping a Wayland display.
@@ -81,19 +100,74 @@ impl Connection {
     /// Returns the "best" adapter on this system, preferring high-performance hardware adapters.
     #[inline]
     pub fn create_hardware_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::hardware())
+        Adapter::hardware().select(&self.native_connection.adapters)
     }
 
     /// Returns the "best" adapter on this system, preferring low-power hardware adapters.
     #[inline]
     pub fn create_low_power_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::low_power())
+        Adapter::low_power().select(&self.native_connection.adapters)
     }
 
     /// Returns the "best" adapter on this system, preferring software adapters.
+    ///
+    /// If EGL can't find a software renderer, a `NoSoftwareAdapters` error is returned.
     #[inline]
     pub fn create_software_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::software())
+        Adapter::software().select(&self.native_connection.adapters)
+    }
+
+    /// Returns every adapter that can be used with this connection.
+    ///
+    /// There is one adapter for each EGL device on the system. The adapters are enumerated once
+    /// per connection and cached. Requires `EGL_EXT_device_enumeration`; if it's unavailable, a
+    /// `RequiredExtensionUnavailable` error is returned.
+    #[inline]
+    pub fn adapters(&self) -> Result<Vec<Adapter>, Error> {
+        self.native_connection.adapters.adapters()
+    }
+
+    // Returns the native connection that devices on the given adapter render with. Adapters that
+    // name a device other than the one EGL picked for the Wayland display get an EGL display of
+    // their own on the same Wayland display.
+    pub(crate) fn native_connection_for_adapter(&self, adapter: &Adapter)
+                                                -> Result<Arc<NativeConnectionWrapper>, Error> {
+        let device_adapter = match *adapter {
+            Adapter::Device(ref device_adapter) => device_adapter,
+            _ => return Ok(self.native_connection.clone()),
+        };
+        unsafe {
+            if device_adapter.is_device_of(self.native_connection.egl_display) {
+                return Ok(self.native_connection.clone());
+            }
+
+            // Without the Wayland display, there's nothing to open another EGL display on.
+            let wayland_display = match self.native_connection.wayland {
+                Some(ref wayland) => wayland.display,
+                None => return Err(Error::IncompatibleAdapter),
+            };
+            let egl_display =
+                device_adapter.open_platform_display(EGL_PLATFORM_WAYLAND_KHR,
+                                                     wayland_display as *mut c_void)?;
+            let event_queue = (WAYLAND_CLIENT_HANDLE.wl_display_create_queue)(wayland_display);
+            if event_queue.is_null() {
+                terminate_device_display(egl_display);
+                return Err(Error::ConnectionFailed);
+            }
+
+            let wayland = WaylandDisplay {
+                display: wayland_display,
+                event_queue,
+                is_owned: false,
+            };
+            Ok(Arc::new(NativeConnectionWrapper {
+                egl_display,
+                egl_display_is_owned: true,
+                wayland: Some(wayland),
+                adapters: self.native_connection.adapters.clone(),
+                _parent: Some(self.native_connection.clone()),
+            }))
+        }
     }
 
     /// Opens the hardware device corresponding to the given adapter.
@@ -133,15 +207,29 @@ impl Connection {
             let ok = egl.Initialize(egl_display, &mut egl_major_version, &mut egl_minor_version);
             assert_ne!(ok, egl::FALSE);
 
-            let owned_display = if is_owned { Some(wayland_display) } else { None };
-            Connection::from_egl_display(egl_display, owned_display)
+            let event_queue = (WAYLAND_CLIENT_HANDLE.wl_display_create_queue)(wayland_display);
+            if event_queue.is_null() {
+                if is_owned {
+                    (WAYLAND_CLIENT_HANDLE.wl_display_disconnect)(wayland_display);
+                }
+                return Err(Error::ConnectionFailed);
+            }
+
+            let wayland = WaylandDisplay { display: wayland_display, event_queue, is_owned };
+            Connection::from_egl_display(egl_display, Some(wayland))
         })
     }
 
-    fn from_egl_display(egl_display: EGLDisplay, wayland_display: Option<*mut wl_display>)
+    fn from_egl_display(egl_display: EGLDisplay, wayland: Option<WaylandDisplay>)
                         -> Result<Connection, Error> {
         Ok(Connection {
-            native_connection: Arc::new(NativeConnectionWrapper { egl_display, wayland_display })
+            native_connection: Arc::new(NativeConnectionWrapper {
+                egl_display,
+                egl_display_is_owned: false,
+                wayland,
+                adapters: AdapterCache::default(),
+                _parent: None,
+            }),
         })
     }
 
@@ -208,11 +296,40 @@ impl Connection {
     }
 }
 
+impl BackendConnection for Connection {
+    type Backend = Backend;
+
+    #[inline]
+    fn new_with_backend(backend: Backend) -> Option<Result<Connection, Error>> {
+        if backend == Backend::Wayland {
+            Some(Connection::new())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn backends() -> Vec<Backend> {
+        vec![Backend::Wayland]
+    }
+
+    #[inline]
+    fn backend(&self) -> Backend {
+        Backend::Wayland
+    }
+}
+
 impl Drop for NativeConnectionWrapper {
     fn drop(&mut self) {
         unsafe {
-            if let Some(wayland_display) = self.wayland_display {
-                (WAYLAND_CLIENT_HANDLE.wl_display_disconnect)(wayland_display);
+            if self.egl_display_is_owned {
+                terminate_device_display(self.egl_display);
+            }
+            if let Some(ref wayland) = self.wayland {
+                (WAYLAND_CLIENT_HANDLE.wl_event_queue_destroy)(wayland.event_queue);
+                if wayland.is_owned {
+                    (WAYLAND_CLIENT_HANDLE.wl_display_disconnect)(wayland.display);
+                }
             }
         }
     }
@@ -0,0 +1,69 @@
+// surfman/surfman/src/platform/unix/wayland/ffi.rs
+//
+//! Protocol definitions that `wayland-sys` doesn't provide.
+
+#![allow(non_camel_case_types)]
+
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use wayland_sys::client::wl_proxy;
+use wayland_sys::common::{wl_interface, wl_message};
+
+pub(crate) const WL_SURFACE_FRAME: u32 = 3;
+
+pub(crate) const WL_EGL_WINDOW_SURFACE_VERSION: isize = 3;
+
+// `struct wl_egl_window` from `wayland-egl-backend.h`. This is the stable interface between
+// `libwayland-egl` and EGL drivers. The `surface` field is only present from version 3 onward.
+#[repr(C)]
+pub(crate) struct wl_egl_window {
+    pub(crate) version: isize,
+    pub(crate) width: c_int,
+    pub(crate) height: c_int,
+    pub(crate) dx: c_int,
+    pub(crate) dy: c_int,
+    pub(crate) attached_width: c_int,
+    pub(crate) attached_height: c_int,
+    pub(crate) driver_private: *mut c_void,
+    pub(crate) resize_callback: Option<unsafe extern "C" fn(*mut wl_egl_window, *mut c_void)>,
+    pub(crate) destroy_window_callback: Option<unsafe extern "C" fn(*mut c_void)>,
+    pub(crate) surface: *mut wl_proxy,
+}
+
+#[repr(C)]
+pub(crate) struct wl_callback_listener {
+    pub(crate) done: unsafe extern "C" fn(data: *mut c_void, callback: *mut wl_proxy, time: u32),
+}
+
+// The protocol tables contain raw pointers, but they are immutable.
+#[repr(transparent)]
+pub(crate) struct ProtocolTable<T>(T);
+
+unsafe impl<T> Sync for ProtocolTable<T> {}
+
+static WL_CALLBACK_DONE_TYPES: ProtocolTable<[*const wl_interface; 1]> =
+    ProtocolTable([ptr::null()]);
+
+static WL_CALLBACK_EVENTS: ProtocolTable<[wl_message; 1]> = ProtocolTable([
+    wl_message {
+        name: b"done\0".as_ptr() as *const c_char,
+        signature: b"u\0".as_ptr() as *const c_char,
+        types: &WL_CALLBACK_DONE_TYPES as *const _ as *const *const wl_interface,
+    },
+]);
+
+pub(crate) static WL_CALLBACK_INTERFACE: ProtocolTable<wl_interface> = ProtocolTable(wl_interface {
+    name: b"wl_callback\0".as_ptr() as *const c_char,
+    version: 1,
+    request_count: 0,
+    requests: ptr::null(),
+    event_count: 1,
+    events: &WL_CALLBACK_EVENTS as *const _ as *const wl_message,
+});
+
+impl<T> ProtocolTable<T> {
+    #[inline]
+    pub(crate) fn get(&self) -> &T {
+        &self.0
+    }
+}
@@ -0,0 +1,246 @@
+// surfman/surfman/src/platform/unix/wayland/frame_callback_tests.rs
+//
+//! Unit tests for Wayland frame callbacks.
+//!
+//! These start a headless Weston compositor and render to a window on it through the default
+//! backend, so they also check that frame callbacks are forwarded by the multi-backend types. On
+//! machines without Weston they return early.
+
+#![allow(missing_docs)]
+
+use crate::platform::unix::default::connection::Connection;
+use crate::platform::unix::default::context::Context;
+use crate::platform::unix::default::device::Device;
+use crate::platform::unix::default::surface::Surface;
+use crate::{ContextAttributeFlags, ContextAttributes, Error, GLVersion, SurfaceAccess};
+use crate::{SurfaceColorspace, SurfaceDescriptor, SurfaceType};
+
+use euclid::default::Size2D;
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::{self, Child, Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use winit::dpi::LogicalSize;
+use winit::{Event, EventsLoop, Window, WindowBuilder, WindowEvent};
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+const FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    // `winit` only connects to the display named by the environment, so tests that change it
+    // take turns.
+    static ref ENVIRONMENT_LOCK: Mutex<()> = Mutex::new(());
+}
+
+struct HeadlessWeston {
+    process: Child,
+    socket_name: String,
+}
+
+impl HeadlessWeston {
+    fn launch(test_name: &str) -> Option<HeadlessWeston> {
+        let runtime_dir = PathBuf::from(env::var_os("XDG_RUNTIME_DIR")?);
+        let socket_name = format!("surfman-{}-{}", test_name, process::id());
+        let process = Command::new("weston").arg("--backend=headless-backend.so")
+                                            .arg(format!("--socket={}", socket_name))
+                                            .arg("--idle-time=0")
+                                            .stdin(Stdio::null())
+                                            .stdout(Stdio::null())
+                                            .stderr(Stdio::null())
+                                            .spawn()
+                                            .ok()?;
+        let mut weston = HeadlessWeston { process, socket_name };
+
+        // Wait for the compositor to start listening.
+        let socket_path = runtime_dir.join(&weston.socket_name);
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        while !socket_path.exists() {
+            if Instant::now() >= deadline {
+                return None;
+            }
+            if let Ok(Some(_)) = weston.process.try_wait() {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Some(weston)
+    }
+
+    // Opens a window on the compositor, waiting until it has been configured so that it can be
+    // rendered to.
+    fn open_window(&self) -> Option<(EventsLoop, Window)> {
+        let (mut events_loop, window) = {
+            let _guard = ENVIRONMENT_LOCK.lock().unwrap();
+            let old_display = env::var_os("WAYLAND_DISPLAY");
+            let old_backend = env::var_os("WINIT_UNIX_BACKEND");
+            env::set_var("WAYLAND_DISPLAY", &self.socket_name);
+            env::set_var("WINIT_UNIX_BACKEND", "wayland");
+            let events_loop = EventsLoop::new();
+            let window = WindowBuilder::new().with_dimensions(LogicalSize::new(64.0, 64.0))
+                                             .build(&events_loop);
+            restore_var("WAYLAND_DISPLAY", old_display);
+            restore_var("WINIT_UNIX_BACKEND", old_backend);
+            (events_loop, window.ok()?)
+        };
+
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        let mut configured = false;
+        while !configured {
+            if Instant::now() >= deadline {
+                return None;
+            }
+            events_loop.poll_events(|event| {
+                if let Event::WindowEvent { event: WindowEvent::Resized(_), .. } = event {
+                    configured = true;
+                }
+            });
+            thread::sleep(Duration::from_millis(10));
+        }
+        Some((events_loop, window))
+    }
+}
+
+impl Drop for HeadlessWeston {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+fn restore_var(name: &str, value: Option<OsString>) {
+    match value {
+        Some(value) => env::set_var(name, value),
+        None => env::remove_var(name),
+    }
+}
+
+fn create_context(device: &mut Device) -> Context {
+    let context_descriptor = device.create_context_descriptor(&ContextAttributes {
+        version: GLVersion::new(3, 0),
+        flags: ContextAttributeFlags::empty(),
+        samples: 0,
+    }).unwrap();
+    device.create_context(&context_descriptor).unwrap()
+}
+
+fn destroy(device: &mut Device, context: &mut Context, surface: &mut Surface) {
+    device.destroy_surface(context, surface).unwrap();
+    device.destroy_context(context).unwrap();
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_frame_callback_is_signaled_after_present() {
+    let weston = match HeadlessWeston::launch("frame-callback") {
+        None => return,
+        Some(weston) => weston,
+    };
+    let (_events_loop, window) = match weston.open_window() {
+        None => return,
+        Some(window) => window,
+    };
+
+    let connection = Connection::from_winit_window(&window).unwrap();
+    let native_widget = connection.create_native_widget_from_winit_window(&window).unwrap();
+    let adapter = connection.create_adapter().unwrap();
+    let mut device = connection.create_device(&adapter).unwrap();
+    let mut context = create_context(&mut device);
+    let surface_type = SurfaceType::Widget {
+        native_widget,
+        colorspace: SurfaceColorspace::Default,
+    };
+    let mut surface = device.create_surface(&context, SurfaceAccess::GPUOnly, surface_type)
+                            .unwrap();
+    match device.set_swap_interval(&context, &surface, 0) {
+        Ok(()) | Err(Error::UnsupportedOnThisPlatform) => {}
+        Err(err) => panic!("Failed to set the swap interval: {:?}", err),
+    }
+
+    let mut callback = match device.request_frame_callback(&surface) {
+        Ok(callback) => callback,
+        Err(Error::UnsupportedOnThisPlatform) => {
+            // `libwayland-egl` is too old to expose the window's surface.
+            destroy(&mut device, &mut context, &mut surface);
+            return;
+        }
+        Err(err) => panic!("Failed to request a frame callback: {:?}", err),
+    };
+
+    // The request only reaches the compositor with the next commit, so it can't be signaled yet.
+    assert!(!device.poll_frame_callback(&callback).unwrap());
+
+    device.present_surface(&context, &mut surface).unwrap();
+    assert!(device.wait_for_frame_callback(&callback, Some(FRAME_TIMEOUT)).unwrap(),
+            "The compositor never asked for the next frame!");
+    assert!(device.poll_frame_callback(&callback).unwrap());
+
+    device.destroy_frame_callback(&mut callback);
+    destroy(&mut device, &mut context, &mut surface);
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_frame_callback_can_be_destroyed_before_present() {
+    let weston = match HeadlessWeston::launch("frame-callback-destroy") {
+        None => return,
+        Some(weston) => weston,
+    };
+    let (_events_loop, window) = match weston.open_window() {
+        None => return,
+        Some(window) => window,
+    };
+
+    let connection = Connection::from_winit_window(&window).unwrap();
+    let native_widget = connection.create_native_widget_from_winit_window(&window).unwrap();
+    let adapter = connection.create_adapter().unwrap();
+    let mut device = connection.create_device(&adapter).unwrap();
+    let mut context = create_context(&mut device);
+    let surface_type = SurfaceType::Widget {
+        native_widget,
+        colorspace: SurfaceColorspace::Default,
+    };
+    let mut surface = device.create_surface(&context, SurfaceAccess::GPUOnly, surface_type)
+                            .unwrap();
+
+    match device.request_frame_callback(&surface) {
+        Ok(mut callback) => device.destroy_frame_callback(&mut callback),
+        Err(Error::UnsupportedOnThisPlatform) => {}
+        Err(err) => panic!("Failed to request a frame callback: {:?}", err),
+    }
+
+    // Destroying the callback early mustn't leave anything behind for the present to trip over.
+    device.present_surface(&context, &mut surface).unwrap();
+    destroy(&mut device, &mut context, &mut surface);
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_generic_surfaces_have_no_frame_callbacks() {
+    let weston = match HeadlessWeston::launch("frame-callback-generic") {
+        None => return,
+        Some(weston) => weston,
+    };
+    let (_events_loop, window) = match weston.open_window() {
+        None => return,
+        Some(window) => window,
+    };
+
+    let connection = Connection::from_winit_window(&window).unwrap();
+    let adapter = connection.create_adapter().unwrap();
+    let mut device = connection.create_device(&adapter).unwrap();
+    let mut context = create_context(&mut device);
+    let surface_type = SurfaceType::Generic {
+        size: Size2D::new(64, 64),
+        descriptor: SurfaceDescriptor::default(),
+    };
+    let mut surface = device.create_surface(&context, SurfaceAccess::GPUOnly, surface_type)
+                            .unwrap();
+
+    match device.request_frame_callback(&surface) {
+        Err(Error::NoWidgetAttached) => {}
+        Err(err) => panic!("Expected `NoWidgetAttached` but got {:?}", err),
+        Ok(_) => panic!("Generic surfaces shouldn't have frame callbacks!"),
+    }
+
+    destroy(&mut device, &mut context, &mut surface);
+}
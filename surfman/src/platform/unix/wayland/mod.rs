@@ -7,6 +7,8 @@ pub mod context;
 pub mod device;
 pub mod surface;
 
+mod ffi;
+
 #[path = "../../../implementation/mod.rs"]
 mod implementation;
 
@@ -14,3 +16,10 @@ mod implementation;
 #[path = "../../../tests.rs"]
 mod tests;
 
+#[cfg(test)]
+#[path = "../../generic/egl/tests.rs"]
+mod egl_tests;
+
+
+#[cfg(all(test, feature = "sm-winit"))]
+mod frame_callback_tests;
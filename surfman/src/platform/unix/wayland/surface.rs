@@ -5,17 +5,31 @@
 use crate::gl::types::{GLenum, GLuint};
 use crate::gl;
 use crate::platform::generic::egl::context;
-use crate::platform::generic::egl::surface::{EGLBackedSurface, EGLSurfaceTexture};
-use crate::{Error, SurfaceAccess, SurfaceInfo, SurfaceType};
+use crate::platform::generic::egl::dmabuf;
+use crate::platform::generic::egl::surface::{self, EGLBackedSurface, EGLSurfaceDataGuard};
+use crate::platform::generic::egl::surface::EGLSurfaceTexture;
+use crate::{Error, PixelReadback, ReadbackFormat, SurfaceAccess, SurfaceDescriptor};
+use crate::{SurfaceColorspace, SurfaceInfo, SurfaceType};
+use super::connection::WaylandDisplay;
 use super::context::{Context, GL_FUNCTIONS};
 use super::device::Device;
+use super::ffi::{self, WL_CALLBACK_INTERFACE, WL_EGL_WINDOW_SURFACE_VERSION, WL_SURFACE_FRAME};
 
-use euclid::default::Size2D;
-use std::marker::PhantomData;
-use std::os::raw::c_void;
-use wayland_sys::client::wl_proxy;
+use euclid::default::{Rect, Size2D};
+use std::fmt::{self, Debug, Formatter};
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use wayland_sys::client::{WAYLAND_CLIENT_HANDLE, wl_proxy};
+use wayland_sys::common::wl_argument;
 use wayland_sys::egl::{WAYLAND_EGL_HANDLE, wl_egl_window};
 
+pub use crate::platform::generic::egl::dmabuf::{DRM_FORMAT_MOD_INVALID, DmabufDescriptor};
+pub use crate::platform::generic::egl::dmabuf::DmabufPlane;
+pub use crate::platform::generic::egl::shared_surface::SharedSurfaceHandle;
+
 // FIXME(pcwalton): Is this right, or should it be `TEXTURE_EXTERNAL_OES`?
 const SURFACE_GL_TEXTURE_TARGET: GLenum = gl::TEXTURE_2D;
 
@@ -57,8 +71,46 @@ pub struct NativeWidget {
     pub(crate) size: Size2D<i32>,
 }
 
+/// A request to be notified when the compositor is ready for a widget surface to be redrawn.
+/// 
+/// Waiting on frame callbacks lets a renderer pace itself to the compositor without blocking
+/// inside `present_surface()`, which may never return while the window is hidden or occluded.
+/// Unlike a blocking present, the caller decides how long to wait.
+/// 
+/// Frame callbacks must be destroyed with `destroy_frame_callback()`, or a panic will occur.
+pub struct FrameCallback {
+    wl_callback: *mut wl_proxy,
+    // Boxed so that its address, which is registered with the callback's listener, is stable.
+    done: Box<AtomicBool>,
+}
+
 unsafe impl Send for Surface {}
 
+unsafe impl Send for FrameCallback {}
+
+impl Debug for FrameCallback {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f, "FrameCallback({:?})", self.wl_callback)
+    }
+}
+
+impl Drop for FrameCallback {
+    fn drop(&mut self) {
+        if !self.wl_callback.is_null() && !thread::panicking() {
+            panic!("Should have destroyed the frame callback first with \
+                    `destroy_frame_callback()`!")
+        }
+    }
+}
+
+static FRAME_CALLBACK_LISTENER: ffi::wl_callback_listener = ffi::wl_callback_listener {
+    done: frame_callback_done,
+};
+
+unsafe extern "C" fn frame_callback_done(data: *mut c_void, _: *mut wl_proxy, _: u32) {
+    (*(data as *const AtomicBool)).store(true, Ordering::Release);
+}
+
 impl Device {
     /// Creates either a generic or a widget surface, depending on the supplied surface type.
     /// 
@@ -66,40 +118,52 @@ impl Device {
     /// up in a `SurfaceTexture` for reading by other contexts.
     pub fn create_surface(&mut self,
                           context: &Context,
-                          _: SurfaceAccess,
+                          access: SurfaceAccess,
                           surface_type: SurfaceType<NativeWidget>)
                           -> Result<Surface, Error> {
         match surface_type {
-            SurfaceType::Generic { size } => self.create_generic_surface(context, &size),
-            SurfaceType::Widget { native_widget } => {
+            SurfaceType::Generic { size, descriptor } => {
+                self.create_generic_surface(context, access, &size, &descriptor)
+            }
+            SurfaceType::Widget { native_widget, colorspace } => {
                 unsafe {
                     self.create_window_surface(context,
+                                               access,
                                                native_widget.wayland_surface,
-                                               &native_widget.size)
+                                               &native_widget.size,
+                                               colorspace)
                 }
             }
         }
     }
 
-    fn create_generic_surface(&mut self, context: &Context, size: &Size2D<i32>)
+    fn create_generic_surface(&mut self,
+                              context: &Context,
+                              access: SurfaceAccess,
+                              size: &Size2D<i32>,
+                              descriptor: &SurfaceDescriptor)
                               -> Result<Surface, Error> {
         let _guard = self.temporarily_make_context_current(context)?;
         let context_descriptor = self.context_descriptor(context);
         let context_attributes = self.context_descriptor_attributes(&context_descriptor);
         GL_FUNCTIONS.with(|gl| {
-            Ok(Surface(EGLBackedSurface::new_generic(gl,
-                                                     self.native_connection.egl_display,
-                                                     context.0.egl_context,
-                                                     context.0.id,
-                                                     &context_attributes,
-                                                     size)))
+            EGLBackedSurface::new_generic(gl,
+                                          self.native_connection.egl_display,
+                                          context.0.egl_context,
+                                          context.0.id,
+                                          access,
+                                          &context_attributes,
+                                          size,
+                                          descriptor).map(Surface)
         })
     }
 
     unsafe fn create_window_surface(&mut self,
                                     context: &Context,
+                                    access: SurfaceAccess,
                                     wayland_surface: *mut wl_proxy,
-                                    size: &Size2D<i32>)
+                                    size: &Size2D<i32>,
+                                    colorspace: SurfaceColorspace)
                                     -> Result<Surface, Error> {
         let egl_window = (WAYLAND_EGL_HANDLE.wl_egl_window_create)(wayland_surface,
                                                                    size.width,
@@ -110,11 +174,61 @@ impl Device {
         let egl_config = context::egl_config_from_id(self.native_connection.egl_display,
                                                      context_descriptor.egl_config_id);
 
-        Ok(Surface(EGLBackedSurface::new_window(self.native_connection.egl_display,
-                                                egl_config,
-                                                egl_window as *mut c_void,
-                                                context.0.id,
-                                                size)))
+        match EGLBackedSurface::new_window(self.native_connection.egl_display,
+                                           egl_config,
+                                           egl_window as *mut c_void,
+                                           context.0.egl_context,
+                                           context.0.id,
+                                           access,
+                                           size,
+                                           colorspace) {
+            Ok(surface) => Ok(Surface(surface)),
+            Err(err) => {
+                (WAYLAND_EGL_HANDLE.wl_egl_window_destroy)(egl_window);
+                Err(err)
+            }
+        }
+    }
+
+    /// Creates a generic surface backed by an existing Linux DMA buffer, such as a frame from a
+    /// camera or a hardware video decoder, without copying its contents.
+    /// 
+    /// The resulting surface is associated with the given context and can be wrapped in a
+    /// `SurfaceTexture` like any other generic surface. The caller retains ownership of the file
+    /// descriptors in the descriptor and may close them once this method returns. The surface is
+    /// not accessible from the CPU.
+    /// 
+    /// Only RGB formats with an alpha channel are supported. YUV formats such as NV12 and YUYV,
+    /// which many cameras and video decoders produce, can't be sampled as `GL_TEXTURE_2D`, and
+    /// formats whose alpha bits are padding, such as XR24, have no surface format equivalent, so
+    /// both return an `UnsupportedSurfaceFormat` error.
+    /// 
+    /// Requires `EGL_EXT_image_dma_buf_import`, plus `EGL_EXT_image_dma_buf_import_modifiers` if
+    /// the descriptor has an explicit modifier; if these are unavailable, a
+    /// `RequiredExtensionUnavailable` error is returned.
+    pub fn create_surface_from_dmabuf(&mut self,
+                                      context: &Context,
+                                      descriptor: DmabufDescriptor)
+                                      -> Result<Surface, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        let context_descriptor = self.context_descriptor(context);
+        let context_attributes = self.context_descriptor_attributes(&context_descriptor);
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                let format = dmabuf::surface_format(descriptor.fourcc)?;
+                let egl_image = dmabuf::import_egl_image(self.native_connection.egl_display,
+                                                         &descriptor)?;
+                EGLBackedSurface::new_from_egl_image(gl,
+                                                     self.native_connection.egl_display,
+                                                     egl_image,
+                                                     context.0.egl_context,
+                                                     context.0.id,
+                                                     SurfaceAccess::GPUOnly,
+                                                     &context_attributes,
+                                                     &descriptor.size,
+                                                     format).map(Surface)
+            }
+        })
     }
 
     /// Creates a surface texture from an existing generic surface for use with the given context.
@@ -189,19 +303,213 @@ impl Device {
         surface.0.present(self.native_connection.egl_display, context.0.egl_context)
     }
 
-    /// Resizes a widget surface.
-    pub fn resize_surface(&self, _context: &Context, surface: &mut Surface, size: Size2D<i32>) -> Result<(), Error> {
-        let wayland_egl_window = surface.0.native_window()? as *mut c_void as *mut wl_egl_window;
-	unsafe { (WAYLAND_EGL_HANDLE.wl_egl_window_resize)(wayland_egl_window, size.width, size.height, 0, 0) };
-	surface.0.size = size;
-	Ok(())
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// have changed since the last time it was presented.
+    /// 
+    /// The regions are in device pixels, with the origin at the bottom left as in OpenGL. If the
+    /// display doesn't support `EGL_KHR_swap_buffers_with_damage`, or `damage` is empty, the
+    /// whole surface is presented.
+    pub fn present_surface_with_damage(&self,
+                                       context: &Context,
+                                       surface: &mut Surface,
+                                       damage: &[Rect<i32>])
+                                       -> Result<(), Error> {
+        surface.0.present_with_damage(self.native_connection.egl_display,
+                                      context.0.egl_context,
+                                      damage)
     }
 
-    /// Returns a pointer to the underlying surface data for reading or writing by the CPU.
+    /// Returns the number of frames ago that the contents of the back buffer of a widget surface
+    /// were presented, or 0 if they are undefined or the display doesn't support
+    /// `EGL_EXT_buffer_age`.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn surface_buffer_age(&self, context: &Context, surface: &Surface) -> Result<u32, Error> {
+        surface.0.buffer_age(self.native_connection.egl_display, context.0.egl_context)
+    }
+
+    /// Sets the number of vertical blanks that presenting a widget surface waits for.
+    /// 
+    /// EGL clamps the interval to the range supported by the surface's config; use
+    /// `swap_interval()` to find out the interval actually in effect. EGL has no adaptive vsync,
+    /// so negative intervals return an `UnsupportedOnThisPlatform` error. Calling this method on
+    /// a generic surface returns a `NoWidgetAttached` error.
+    pub fn set_swap_interval(&self, context: &Context, surface: &Surface, interval: i32)
+                             -> Result<(), Error> {
+        surface.0.set_swap_interval(self.native_connection.egl_display,
+                                    context.0.egl_context,
+                                    interval)
+    }
+
+    /// Returns the swap interval that is in effect for a widget surface.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    #[inline]
+    pub fn swap_interval(&self, _: &Context, surface: &Surface) -> Result<i32, Error> {
+        surface.0.swap_interval()
+    }
+
+    /// Requests a callback that is signaled when the compositor is ready for a new frame of the
+    /// given widget surface to be drawn.
+    /// 
+    /// The request takes effect when the surface is next presented, so this must be called before
+    /// `present_surface()`. The callback is delivered on the connection's own event queue, not
+    /// the application's; use `poll_frame_callback()` or `wait_for_frame_callback()` to dispatch
+    /// it. When pacing presentation this way, set the swap interval of the surface to 0 with
+    /// `set_swap_interval()`, so that presenting doesn't also block waiting for the compositor.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error. If the
+    /// connection was created from a native EGL display, there is no Wayland display to receive
+    /// the callback on, and a `ConnectionRequired` error is returned. Versions of
+    /// `libwayland-egl` older than 1.15 don't expose the surface of a window, so an
+    /// `UnsupportedOnThisPlatform` error is returned on those.
+    pub fn request_frame_callback(&self, surface: &Surface) -> Result<FrameCallback, Error> {
+        let wayland = self.wayland_display()?;
+        let egl_window = surface.0.native_window()? as *const ffi::wl_egl_window;
+
+        unsafe {
+            if (*egl_window).version < WL_EGL_WINDOW_SURFACE_VERSION {
+                return Err(Error::UnsupportedOnThisPlatform);
+            }
+
+            // Create the callback through a wrapper, so that it's assigned to our event queue
+            // before the compositor can possibly signal it.
+            let wrapper = (WAYLAND_CLIENT_HANDLE.wl_proxy_create_wrapper)((*egl_window).surface);
+            assert!(!wrapper.is_null());
+            (WAYLAND_CLIENT_HANDLE.wl_proxy_set_queue)(wrapper, wayland.event_queue);
+
+            let mut arguments = [wl_argument { o: ptr::null() }];
+            let wl_callback =
+                (WAYLAND_CLIENT_HANDLE.wl_proxy_marshal_array_constructor)(
+                    wrapper,
+                    WL_SURFACE_FRAME,
+                    arguments.as_mut_ptr(),
+                    WL_CALLBACK_INTERFACE.get());
+            (WAYLAND_CLIENT_HANDLE.wl_proxy_wrapper_destroy)(wrapper);
+            assert!(!wl_callback.is_null());
+
+            let done = Box::new(AtomicBool::new(false));
+            (WAYLAND_CLIENT_HANDLE.wl_proxy_add_listener)(
+                wl_callback,
+                &FRAME_CALLBACK_LISTENER as *const _ as *mut extern "C" fn(),
+                &*done as *const AtomicBool as *mut c_void);
+
+            Ok(FrameCallback { wl_callback, done })
+        }
+    }
+
+    /// Dispatches any frame callback events that have arrived, without blocking, and returns true
+    /// if the given frame callback has been signaled.
     #[inline]
-    pub fn lock_surface_data<'s>(&self, _: &'s mut Surface)
+    pub fn poll_frame_callback(&self, callback: &FrameCallback) -> Result<bool, Error> {
+        self.wait_for_frame_callback(callback, Some(Duration::from_secs(0)))
+    }
+
+    /// Blocks the calling thread until the given frame callback is signaled or the timeout
+    /// elapses.
+    /// 
+    /// If `timeout` is `None`, this waits indefinitely, which may be forever if the window is
+    /// hidden. Returns true if the frame callback was signaled. Only the connection's own event
+    /// queue is dispatched, so no application events are handled while waiting.
+    pub fn wait_for_frame_callback(&self, callback: &FrameCallback, timeout: Option<Duration>)
+                                   -> Result<bool, Error> {
+        let wayland = self.wayland_display()?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let remaining = deadline.map(|deadline| {
+                deadline.saturating_duration_since(Instant::now())
+            });
+            unsafe {
+                dispatch_frame_callback_events(wayland, &callback.done, remaining)?;
+            }
+            if callback.done.load(Ordering::Acquire) {
+                return Ok(true);
+            }
+            if remaining == Some(Duration::from_secs(0)) {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Destroys a frame callback.
+    /// 
+    /// Frame callbacks may be destroyed whether or not they have been signaled.
+    pub fn destroy_frame_callback(&self, callback: &mut FrameCallback) {
+        if !callback.wl_callback.is_null() {
+            unsafe {
+                (WAYLAND_CLIENT_HANDLE.wl_proxy_destroy)(callback.wl_callback);
+            }
+            callback.wl_callback = ptr::null_mut();
+        }
+    }
+
+    fn wayland_display(&self) -> Result<&WaylandDisplay, Error> {
+        match self.native_connection.wayland {
+            Some(ref wayland) => Ok(wayland),
+            None => Err(Error::ConnectionRequired),
+        }
+    }
+
+    /// Resizes a surface.
+    /// 
+    /// Generic surfaces are reallocated at the new size. If `preserve_contents` is true, the
+    /// pixels in the region common to the old and new sizes, anchored at the bottom left, are
+    /// kept, and the rest of the surface is cleared to transparent black; otherwise, the whole
+    /// surface is cleared. The surface's ID and framebuffer object change, so `surface_info()`
+    /// must be called again afterward.
+    /// 
+    /// Widget surfaces resize their `wl_egl_window` to `size`, which takes effect the next time
+    /// the surface is presented. `preserve_contents` has no effect on them.
+    /// 
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn resize_surface(&self,
+                          context: &Context,
+                          surface: &mut Surface,
+                          size: Size2D<i32>,
+                          preserve_contents: bool)
+                          -> Result<(), Error> {
+        if context.0.id != surface.0.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        match surface.0.native_window() {
+            Ok(wayland_egl_window) => {
+                let wayland_egl_window = wayland_egl_window as *mut c_void as *mut wl_egl_window;
+                unsafe {
+                    (WAYLAND_EGL_HANDLE.wl_egl_window_resize)(wayland_egl_window,
+                                                              size.width,
+                                                              size.height,
+                                                              0,
+                                                              0);
+                }
+                surface.0.size = size;
+                Ok(())
+            }
+            Err(_) => {
+                GL_FUNCTIONS.with(|gl| {
+                    let egl_display = self.native_connection.egl_display;
+                    surface.0.resize(gl, egl_display, &size, preserve_contents)
+                })
+            }
+        }
+    }
+
+    /// Returns a pointer to the underlying surface data for reading or writing by the CPU.
+    ///
+    /// The surface must have been created with `GPUCPU` or `GPUCPUWriteCombined` access, or a
+    /// `SurfaceDataInaccessible` error is returned. Calling this method on a widget surface returns
+    /// a `WidgetAttached` error.
+    ///
+    /// On this backend, the surface contents are copied into CPU memory when the surface is locked
+    /// and copied back when the guard is dropped. This must happen on the thread that owns the
+    /// surface's context.
+    pub fn lock_surface_data<'s>(&self, surface: &'s mut Surface)
                                  -> Result<SurfaceDataGuard<'s>, Error> {
-        Err(Error::Unimplemented)
+        GL_FUNCTIONS.with(move |gl| {
+            let egl_display = self.native_connection.egl_display;
+            Ok(SurfaceDataGuard(surface.0.lock_data(gl, egl_display)?))
+        })
     }
 
     /// Returns the OpenGL texture target needed to read from this surface texture.
@@ -222,6 +530,12 @@ impl Device {
         surface.0.info()
     }
 
+    /// Returns the colorspaces that widget surfaces may request, depending on the EGL extensions
+    /// that the display supports.
+    pub fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        surface::supported_colorspaces(self.native_connection.egl_display)
+    }
+
     /// Returns the OpenGL texture object containing the contents of this surface.
     /// 
     /// It is only legal to read from, not write to, this texture object.
@@ -229,9 +543,231 @@ impl Device {
     pub fn surface_texture_object(&self, surface_texture: &SurfaceTexture) -> GLuint {
         surface_texture.0.texture_object
     }
+
+    /// Returns the OpenGL texture object containing the depth (and, if present, stencil) buffer
+    /// of the surface wrapped by this surface texture, if it was created with a sampleable depth
+    /// buffer.
+    /// 
+    /// It is only legal to read from, not write to, this texture object.
+    #[inline]
+    pub fn surface_texture_depth_object(&self, surface_texture: &SurfaceTexture)
+                                        -> Option<GLuint> {
+        surface_texture.0.depth_texture_object
+    }
+
+    /// Exports the memory backing a generic surface as a Linux DMA buffer, so that it can be
+    /// consumed by other APIs or processes without copying.
+    /// 
+    /// The caller owns the returned file descriptors and must close them. The surface must stay
+    /// alive for as long as the buffer is in use elsewhere. Rendering to the surface is not
+    /// synchronized with consumers of the buffer; use a fence for that.
+    /// 
+    /// Requires `EGL_MESA_image_dma_buf_export`; if it's unavailable, a
+    /// `RequiredExtensionUnavailable` error is returned. Calling this method on a widget surface
+    /// returns a `WidgetAttached` error.
+    #[inline]
+    pub fn export_surface_dmabuf(&self, surface: &Surface) -> Result<DmabufDescriptor, Error> {
+        surface.0.export_dmabuf(self.native_connection.egl_display)
+    }
+
+    /// Creates a handle that can be sent to another process in order to share the contents of a
+    /// generic surface with it.
+    /// 
+    /// If the surface can be exported as a dmabuf, the handle refers to the surface itself, and
+    /// the surface must stay alive for as long as the other process uses it. Otherwise, the handle
+    /// holds a copy of the surface's current contents in a memory file; this fallback must happen
+    /// on the thread that owns the surface's context.
+    /// 
+    /// Calling this method on a widget surface returns a `WidgetAttached` error.
+    pub fn create_shared_surface_handle(&self, surface: &Surface)
+                                        -> Result<SharedSurfaceHandle, Error> {
+        match self.export_surface_dmabuf(surface) {
+            Ok(descriptor) => return Ok(SharedSurfaceHandle::from_dmabuf(descriptor)),
+            Err(Error::RequiredExtensionUnavailable) | Err(Error::SurfaceExportFailed(_)) => {}
+            Err(err) => return Err(err),
+        }
+
+        GL_FUNCTIONS.with(|gl| {
+            let pixels = surface.0.read_all_pixels(gl, self.native_connection.egl_display)?;
+            SharedSurfaceHandle::from_pixels(&surface.0.size, &pixels)
+        })
+    }
+
+    /// Creates a generic surface from a handle received from another process.
+    /// 
+    /// The resulting surface is associated with the given context and can be wrapped in a
+    /// `SurfaceTexture` like any other generic surface. If the handle refers to a dmabuf, the
+    /// surface shares its memory with the surface in the other process; otherwise, it is a copy of
+    /// that surface's contents at the time the handle was created.
+    pub fn import_shared_surface(&mut self, context: &Context, handle: SharedSurfaceHandle)
+                                 -> Result<Surface, Error> {
+        if let Some(descriptor) = handle.dmabuf() {
+            return self.create_surface_from_dmabuf(context, descriptor.clone());
+        }
+
+        let pixels = handle.read_pixels()?;
+        let mut surface = self.create_generic_surface(context,
+                                                      SurfaceAccess::GPUOnly,
+                                                      &handle.size(),
+                                                      &SurfaceDescriptor::default())?;
+        GL_FUNCTIONS.with(|gl| {
+            let egl_display = self.native_connection.egl_display;
+            match surface.0.upload_pixels(gl, egl_display, &pixels) {
+                Ok(()) => Ok(surface),
+                Err(err) => {
+                    drop(surface.0.destroy(gl, egl_display, context.0.id));
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    /// Names the OpenGL objects behind a surface after the given label, so that `KHR_debug`
+    /// messages and GL debuggers can refer to them.
+    /// 
+    /// Widget surfaces have no such objects, so this does nothing for them. The supplied context
+    /// must be the context the surface is associated with, or an `IncompatibleSurface` error is
+    /// returned.
+    pub fn set_surface_label(&self, context: &Context, surface: &Surface, label: &str)
+                             -> Result<(), Error> {
+        if context.0.id != surface.0.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| surface.0.set_label(gl, label));
+        Ok(())
+    }
+
+    /// Starts copying a region of a surface into CPU memory, without waiting for pending
+    /// rendering to finish.
+    ///
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned. The region is in device pixels, with the origin
+    /// at the bottom left as in OpenGL, and must lie within the surface, or an `InvalidRegion`
+    /// error is returned. For widget surfaces, this reads from the back buffer, so it must be
+    /// called before the surface is presented.
+    pub fn read_surface_pixels(&self,
+                               context: &Context,
+                               surface: &Surface,
+                               rect: Rect<i32>,
+                               format: ReadbackFormat)
+                               -> Result<PixelReadback, Error> {
+        if context.0.id != surface.0.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        GL_FUNCTIONS.with(|gl| {
+            surface.0.read_pixels(gl, self.native_connection.egl_display, &rect, format)
+        })
+    }
+
+    /// Returns true if the pixels of a readback can be retrieved without blocking.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned.
+    pub fn poll_pixel_readback(&self, context: &Context, readback: &PixelReadback)
+                               -> Result<bool, Error> {
+        if context.0.id != readback.context_id {
+            return Err(Error::IncompatibleContext);
+        }
+
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| unsafe { Ok(readback.is_ready(gl)) })
+    }
+
+    /// Waits for a readback to complete and returns its pixels, in the format it was started
+    /// with.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned.
+    pub fn finish_pixel_readback(&self, context: &Context, mut readback: PixelReadback)
+                                 -> Result<Vec<u8>, (Error, PixelReadback)> {
+        if context.0.id != readback.context_id {
+            return Err((Error::IncompatibleContext, readback));
+        }
+
+        let _guard = match self.temporarily_make_context_current(context) {
+            Ok(guard) => guard,
+            Err(err) => return Err((err, readback)),
+        };
+        let result = GL_FUNCTIONS.with(|gl| unsafe { readback.finish(gl) });
+        result.map_err(|err| (err, readback))
+    }
 }
 
 /// Represents the CPU view of the pixel data of this surface.
-pub struct SurfaceDataGuard<'a> {
-    phantom: PhantomData<&'a ()>,
+///
+/// Any changes made to the data are written back to the surface when this guard is dropped.
+pub struct SurfaceDataGuard<'a>(EGLSurfaceDataGuard<'a>);
+
+impl<'a> SurfaceDataGuard<'a> {
+    /// Returns the number of bytes per row of the surface.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.0.stride()
+    }
+
+    /// Returns a mutable slice of the pixel data in this surface, in the surface's format (e.g.
+    /// RGBA for `RGBA8` surfaces, or half floats for `RGBA16F` ones).
+    ///
+    /// Rows are ordered bottom to top, as OpenGL does, and pixels are tightly packed.
+    #[inline]
+    pub fn data(&mut self) -> &mut [u8] {
+        self.0.data()
+    }
+}
+
+impl<'a> Drop for SurfaceDataGuard<'a> {
+    fn drop(&mut self) {
+        GL_FUNCTIONS.with(|gl| self.0.unlock(gl))
+    }
+}
+
+// Dispatches the events already queued on the connection's frame callback queue. Then, unless
+// that signaled `done`, waits up to `timeout` for more events to arrive from the compositor and
+// dispatches those too. Waiting is interrupted early by events for any frame callback.
+unsafe fn dispatch_frame_callback_events(wayland: &WaylandDisplay,
+                                         done: &AtomicBool,
+                                         timeout: Option<Duration>)
+                                         -> Result<(), Error> {
+    while (WAYLAND_CLIENT_HANDLE.wl_display_prepare_read_queue)(wayland.display,
+                                                                wayland.event_queue) != 0 {
+        if (WAYLAND_CLIENT_HANDLE.wl_display_dispatch_queue_pending)(wayland.display,
+                                                                     wayland.event_queue) < 0 {
+            return Err(Error::ConnectionLost);
+        }
+    }
+
+    if done.load(Ordering::Acquire) {
+        (WAYLAND_CLIENT_HANDLE.wl_display_cancel_read)(wayland.display);
+        return Ok(());
+    }
+
+    // Make sure the compositor has our requests before waiting on its reply. If the socket is
+    // full, the rest are sent on a later flush.
+    (WAYLAND_CLIENT_HANDLE.wl_display_flush)(wayland.display);
+
+    let mut poll_fd = libc::pollfd {
+        fd: (WAYLAND_CLIENT_HANDLE.wl_display_get_fd)(wayland.display),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = match timeout {
+        None => -1,
+        Some(timeout) => timeout.as_millis().min(c_int::MAX as u128) as c_int,
+    };
+    if libc::poll(&mut poll_fd, 1, timeout) > 0 {
+        if (WAYLAND_CLIENT_HANDLE.wl_display_read_events)(wayland.display) < 0 {
+            return Err(Error::ConnectionLost);
+        }
+    } else {
+        (WAYLAND_CLIENT_HANDLE.wl_display_cancel_read)(wayland.display);
+    }
+
+    if (WAYLAND_CLIENT_HANDLE.wl_display_dispatch_queue_pending)(wayland.display,
+                                                                 wayland.event_queue) < 0 {
+        return Err(Error::ConnectionLost);
+    }
+    Ok(())
 }
@@ -8,7 +8,9 @@ use crate::error::Error;
 use crate::info::GLApi;
 use crate::platform::generic::egl::device::EGL_FUNCTIONS;
 use crate::platform::generic::egl::ffi::EGL_PLATFORM_X11_KHR;
-use crate::platform::unix::generic::device::Adapter;
+use crate::platform::generic::multi::connection::BackendConnection;
+use crate::platform::unix::generic::device::{Adapter, AdapterCache, terminate_device_display};
+use crate::platform::unix::Backend;
 use super::device::{Device, NativeDevice};
 use super::surface::NativeWidget;
 
@@ -26,7 +28,7 @@ use winit::Window;
 use winit::os::unix::WindowExt;
 
 lazy_static! {
-    static ref X_THREADS_INIT: () = {
+    pub(crate) static ref X_THREADS_INIT: () = {
         unsafe {
             XInitThreads();
         }
@@ -45,6 +47,13 @@ pub(crate) struct NativeConnectionWrapper {
     pub(crate) egl_display: EGLDisplay,
     x11_display: *mut Display,
     x11_display_is_owned: bool,
+    // Whether the EGL display was opened on a specific device for this connection, and so must
+    // be terminated with it.
+    egl_display_is_owned: bool,
+    pub(crate) adapters: AdapterCache,
+    // The connection that owns the X11 display, if this one only renders to it with another
+    // device.
+    _parent: Option<Arc<NativeConnectionWrapper>>,
 }
 
 /// Wrapper for an X11 and EGL display.
@@ -64,6 +73,9 @@ impl Drop for NativeConnectionWrapper {
     #[inline]
     fn drop(&mut self) {
         unsafe {
+            if self.egl_display_is_owned {
+                terminate_device_display(self.egl_display);
+            }
             if self.x11_display_is_owned {
                 XCloseDisplay(self.x11_display);
             }
@@ -91,6 +103,9 @@ impl Connection {
                     x11_display,
                     x11_display_is_owned: true,
                     egl_display,
+                    egl_display_is_owned: false,
+                    adapters: AdapterCache::default(),
+                    _parent: None,
                 }),
             })
         }
@@ -113,6 +128,9 @@ impl Connection {
                 egl_display: native_connection.egl_display,
                 x11_display: native_connection.x11_display,
                 x11_display_is_owned: false,
+                egl_display_is_owned: false,
+                adapters: AdapterCache::default(),
+                _parent: None,
             }),
         })
     }
@@ -125,6 +143,9 @@ impl Connection {
                     egl_display,
                     x11_display,
                     x11_display_is_owned: is_owned,
+                    egl_display_is_owned: false,
+                    adapters: AdapterCache::default(),
+                    _parent: None,
                 }),
             })
         }
@@ -156,19 +177,59 @@ impl Connection {
     /// Returns the "best" adapter on this system, preferring high-performance hardware adapters.
     #[inline]
     pub fn create_hardware_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::hardware())
+        Adapter::hardware().select(&self.native_connection.adapters)
     }
 
     /// Returns the "best" adapter on this system, preferring low-power hardware adapters.
     #[inline]
     pub fn create_low_power_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::low_power())
+        Adapter::low_power().select(&self.native_connection.adapters)
     }
 
     /// Returns the "best" adapter on this system, preferring software adapters.
+    ///
+    /// If EGL can't find a software renderer, a `NoSoftwareAdapters` error is returned.
     #[inline]
     pub fn create_software_adapter(&self) -> Result<Adapter, Error> {
-        Ok(Adapter::software())
+        Adapter::software().select(&self.native_connection.adapters)
+    }
+
+    /// Returns every adapter that can be used with this connection.
+    ///
+    /// There is one adapter for each EGL device on the system. The adapters are enumerated once
+    /// per connection and cached. Requires `EGL_EXT_device_enumeration`; if it's unavailable, a
+    /// `RequiredExtensionUnavailable` error is returned.
+    #[inline]
+    pub fn adapters(&self) -> Result<Vec<Adapter>, Error> {
+        self.native_connection.adapters.adapters()
+    }
+
+    // Returns the native connection that devices on the given adapter render with. Adapters that
+    // name a device other than the one EGL picked for the X11 display get an EGL display of their
+    // own on the same X11 display.
+    pub(crate) fn native_connection_for_adapter(&self, adapter: &Adapter)
+                                                -> Result<Arc<NativeConnectionWrapper>, Error> {
+        let device_adapter = match *adapter {
+            Adapter::Device(ref device_adapter) => device_adapter,
+            _ => return Ok(self.native_connection.clone()),
+        };
+        unsafe {
+            if device_adapter.is_device_of(self.native_connection.egl_display) {
+                return Ok(self.native_connection.clone());
+            }
+
+            let x11_display = self.native_connection.x11_display;
+            let egl_display = device_adapter.open_platform_display(EGL_PLATFORM_X11_KHR,
+                                                                   x11_display as *mut c_void)?;
+            Ok(Arc::new(NativeConnectionWrapper {
+                egl_display,
+                x11_display,
+                x11_display_is_owned: false,
+                egl_display_is_owned: true,
+                adapters: self.native_connection.adapters.clone(),
+                _parent: Some(self.native_connection.clone()),
+            }))
+        }
     }
 
     /// Opens the hardware device corresponding to the given adapter.
@@ -233,6 +294,29 @@ impl Connection {
     }
 }
 
+impl BackendConnection for Connection {
+    type Backend = Backend;
+
+    #[inline]
+    fn new_with_backend(backend: Backend) -> Option<Result<Connection, Error>> {
+        if backend == Backend::X11 {
+            Some(Connection::new())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn backends() -> Vec<Backend> {
+        vec![Backend::X11]
+    }
+
+    #[inline]
+    fn backend(&self) -> Backend {
+        Backend::X11
+    }
+}
+
 impl NativeConnectionWrapper {
     #[inline]
     pub(crate) fn lock_display(&self) -> DisplayGuard {
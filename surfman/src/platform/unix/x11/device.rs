@@ -29,10 +29,9 @@ pub struct NativeDevice {
 impl Device {
     #[inline]
     pub(crate) fn new(connection: &Connection, adapter: &Adapter) -> Result<Device, Error> {
-        Ok(Device {
-            native_connection: connection.native_connection.clone(),
-            adapter: (*adapter).clone(),
-        })
+        let adapter = adapter.clone().select(&connection.native_connection.adapters)?;
+        let native_connection = connection.native_connection_for_adapter(&adapter)?;
+        Ok(Device { native_connection, adapter })
     }
 
     /// Returns the native device corresponding to this device.
@@ -7,16 +7,23 @@ use crate::gl;
 use crate::egl;
 use crate::egl::types::EGLint;
 use crate::platform::generic::egl::context;
-use crate::platform::generic::egl::surface::{EGLBackedSurface, EGLSurfaceTexture};
-use crate::{Error, SurfaceAccess, SurfaceInfo, SurfaceType};
+use crate::platform::generic::egl::dmabuf;
+use crate::platform::generic::egl::surface::{self, EGLBackedSurface, EGLSurfaceDataGuard};
+use crate::platform::generic::egl::surface::{EGLSurfaceObjects, EGLSurfaceTexture};
+use crate::{Error, PixelReadback, ReadbackFormat, SurfaceAccess, SurfaceDescriptor};
+use crate::{SurfaceColorspace, SurfaceInfo, SurfaceType};
 use super::context::{Context, GL_FUNCTIONS};
 use super::device::Device;
 
-use euclid::default::Size2D;
-use std::marker::PhantomData;
+use euclid::default::{Rect, Size2D};
 use std::os::raw::c_void;
+use std::time::Duration;
 use x11::xlib::{Window, XGetGeometry};
 
+pub use crate::platform::generic::egl::dmabuf::{DRM_FORMAT_MOD_INVALID, DmabufDescriptor};
+pub use crate::platform::generic::egl::dmabuf::DmabufPlane;
+pub use crate::platform::generic::egl::shared_surface::SharedSurfaceHandle;
+
 // FIXME(pcwalton): Is this right, or should it be `TEXTURE_EXTERNAL_OES`?
 const SURFACE_GL_TEXTURE_TARGET: GLenum = gl::TEXTURE_2D;
 
@@ -59,6 +66,12 @@ pub struct NativeWidget {
 
 unsafe impl Send for Surface {}
 
+/// A request to be told when the compositor wants the next frame of a widget surface.
+///
+/// Frame callbacks aren't supported on this backend, so this type can't be constructed.
+#[derive(Debug)]
+pub enum FrameCallback {}
+
 impl Device {
     /// Creates either a generic or a widget surface, depending on the supplied surface type.
     /// 
@@ -66,35 +79,47 @@ impl Device {
     /// up in a `SurfaceTexture` for reading by other contexts.
     pub fn create_surface(&mut self,
                           context: &Context,
-                          _: SurfaceAccess,
+                          access: SurfaceAccess,
                           surface_type: SurfaceType<NativeWidget>)
                           -> Result<Surface, Error> {
         match surface_type {
-            SurfaceType::Generic { size } => self.create_generic_surface(context, &size),
-            SurfaceType::Widget { native_widget } => {
+            SurfaceType::Generic { size, descriptor } => {
+                self.create_generic_surface(context, access, &size, &descriptor)
+            }
+            SurfaceType::Widget { native_widget, colorspace } => {
                 unsafe {
-                    self.create_window_surface(context, native_widget.window)
+                    self.create_window_surface(context, access, native_widget.window, colorspace)
                 }
             }
         }
     }
 
-    fn create_generic_surface(&mut self, context: &Context, size: &Size2D<i32>)
+    fn create_generic_surface(&mut self,
+                              context: &Context,
+                              access: SurfaceAccess,
+                              size: &Size2D<i32>,
+                              descriptor: &SurfaceDescriptor)
                               -> Result<Surface, Error> {
         let _guard = self.temporarily_make_context_current(context)?;
         let context_descriptor = self.context_descriptor(context);
         let context_attributes = self.context_descriptor_attributes(&context_descriptor);
         GL_FUNCTIONS.with(|gl| {
-            Ok(Surface(EGLBackedSurface::new_generic(gl,
-                                                     self.native_connection.egl_display,
-                                                     context.0.egl_context,
-                                                     context.0.id,
-                                                     &context_attributes,
-                                                     size)))
+            EGLBackedSurface::new_generic(gl,
+                                          self.native_connection.egl_display,
+                                          context.0.egl_context,
+                                          context.0.id,
+                                          access,
+                                          &context_attributes,
+                                          size,
+                                          descriptor).map(Surface)
         })
     }
 
-    unsafe fn create_window_surface(&mut self, context: &Context, mut x11_window: Window)
+    unsafe fn create_window_surface(&mut self,
+                                    context: &Context,
+                                    access: SurfaceAccess,
+                                    mut x11_window: Window,
+                                    colorspace: SurfaceColorspace)
                                     -> Result<Surface, Error> {
         let egl_config_id = context::get_context_attr(self.native_connection.egl_display, context.0.egl_context, egl::CONFIG_ID as EGLint);
         let egl_config = context::egl_config_from_id(self.native_connection.egl_display, egl_config_id);
@@ -113,11 +138,55 @@ impl Device {
                      &mut depth);
         let size = Size2D::new(width as i32, height as i32);
 
-        Ok(Surface(EGLBackedSurface::new_window(self.native_connection.egl_display,
-                                                egl_config,
-                                                &mut x11_window as *mut Window as *mut c_void,
-                                                context.0.id,
-                                                &size)))
+        EGLBackedSurface::new_window(self.native_connection.egl_display,
+                                     egl_config,
+                                     &mut x11_window as *mut Window as *mut c_void,
+                                     context.0.egl_context,
+                                     context.0.id,
+                                     access,
+                                     &size,
+                                     colorspace).map(Surface)
+    }
+
+    /// Creates a generic surface backed by an existing Linux DMA buffer, such as a frame from a
+    /// camera or a hardware video decoder, without copying its contents.
+    /// 
+    /// The resulting surface is associated with the given context and can be wrapped in a
+    /// `SurfaceTexture` like any other generic surface. The caller retains ownership of the file
+    /// descriptors in the descriptor and may close them once this method returns. The surface is
+    /// not accessible from the CPU.
+    /// 
+    /// Only RGB formats with an alpha channel are supported. YUV formats such as NV12 and YUYV,
+    /// which many cameras and video decoders produce, can't be sampled as `GL_TEXTURE_2D`, and
+    /// formats whose alpha bits are padding, such as XR24, have no surface format equivalent, so
+    /// both return an `UnsupportedSurfaceFormat` error.
+    /// 
+    /// Requires `EGL_EXT_image_dma_buf_import`, plus `EGL_EXT_image_dma_buf_import_modifiers` if
+    /// the descriptor has an explicit modifier; if these are unavailable, a
+    /// `RequiredExtensionUnavailable` error is returned.
+    pub fn create_surface_from_dmabuf(&mut self,
+                                      context: &Context,
+                                      descriptor: DmabufDescriptor)
+                                      -> Result<Surface, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        let context_descriptor = self.context_descriptor(context);
+        let context_attributes = self.context_descriptor_attributes(&context_descriptor);
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                let format = dmabuf::surface_format(descriptor.fourcc)?;
+                let egl_image = dmabuf::import_egl_image(self.native_connection.egl_display,
+                                                         &descriptor)?;
+                EGLBackedSurface::new_from_egl_image(gl,
+                                                     self.native_connection.egl_display,
+                                                     egl_image,
+                                                     context.0.egl_context,
+                                                     context.0.id,
+                                                     SurfaceAccess::GPUOnly,
+                                                     &context_attributes,
+                                                     &descriptor.size,
+                                                     format).map(Surface)
+            }
+        })
     }
 
     /// Creates a surface texture from an existing generic surface for use with the given context.
@@ -187,17 +256,132 @@ impl Device {
         surface.0.present(self.native_connection.egl_display, context.0.egl_context)
     }
 
-    /// Resizes a widget surface.
-    pub fn resize_surface(&self, _context: &Context, surface: &mut Surface, size: Size2D<i32>) -> Result<(), Error> {
-        surface.0.size = size;
-        Ok(())
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// have changed since the last time it was presented.
+    /// 
+    /// The regions are in device pixels, with the origin at the bottom left as in OpenGL. If the
+    /// display doesn't support `EGL_KHR_swap_buffers_with_damage`, or `damage` is empty, the
+    /// whole surface is presented.
+    pub fn present_surface_with_damage(&self,
+                                       context: &Context,
+                                       surface: &mut Surface,
+                                       damage: &[Rect<i32>])
+                                       -> Result<(), Error> {
+        surface.0.present_with_damage(self.native_connection.egl_display,
+                                      context.0.egl_context,
+                                      damage)
     }
 
-    /// Returns a pointer to the underlying surface data for reading or writing by the CPU.
+    /// Returns the number of frames ago that the contents of the back buffer of a widget surface
+    /// were presented, or 0 if they are undefined or the display doesn't support
+    /// `EGL_EXT_buffer_age`.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn surface_buffer_age(&self, context: &Context, surface: &Surface) -> Result<u32, Error> {
+        surface.0.buffer_age(self.native_connection.egl_display, context.0.egl_context)
+    }
+
+    /// Sets the number of vertical blanks that presenting a widget surface waits for.
+    /// 
+    /// EGL clamps the interval to the range supported by the surface's config; use
+    /// `swap_interval()` to find out the interval actually in effect. EGL has no adaptive vsync,
+    /// so negative intervals return an `UnsupportedOnThisPlatform` error. Calling this method on
+    /// a generic surface returns a `NoWidgetAttached` error.
+    pub fn set_swap_interval(&self, context: &Context, surface: &Surface, interval: i32)
+                             -> Result<(), Error> {
+        surface.0.set_swap_interval(self.native_connection.egl_display,
+                                    context.0.egl_context,
+                                    interval)
+    }
+
+    /// Returns the swap interval that is in effect for a widget surface.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    #[inline]
+    pub fn swap_interval(&self, _: &Context, surface: &Surface) -> Result<i32, Error> {
+        surface.0.swap_interval()
+    }
+
+    /// Asks the compositor to signal a frame callback when it's a good time to draw the next frame
+    /// of a widget surface.
+    ///
+    /// Frame callbacks aren't supported on this backend, so this always returns an
+    /// `UnsupportedOnThisPlatform` error.
+    #[inline]
+    pub fn request_frame_callback(&self, _: &Surface) -> Result<FrameCallback, Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
+    /// Dispatches any frame callback events that have arrived, without blocking, and returns true
+    /// if the given frame callback has been signaled.
+    #[inline]
+    pub fn poll_frame_callback(&self, callback: &FrameCallback) -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Blocks the calling thread until the given frame callback is signaled or the timeout
+    /// elapses.
+    #[inline]
+    pub fn wait_for_frame_callback(&self, callback: &FrameCallback, _: Option<Duration>)
+                                   -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Destroys a frame callback.
     #[inline]
-    pub fn lock_surface_data<'s>(&self, _: &'s mut Surface)
+    pub fn destroy_frame_callback(&self, callback: &mut FrameCallback) {
+        match *callback {}
+    }
+
+    /// Resizes a surface.
+    /// 
+    /// Generic surfaces are reallocated at the new size. If `preserve_contents` is true, the
+    /// pixels in the region common to the old and new sizes, anchored at the bottom left, are
+    /// kept, and the rest of the surface is cleared to transparent black; otherwise, the whole
+    /// surface is cleared. The surface's ID and framebuffer object change, so `surface_info()`
+    /// must be called again afterward.
+    /// 
+    /// Widget surfaces take on the current size of their X11 window, and ignore `size` and
+    /// `preserve_contents`.
+    /// 
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn resize_surface(&self,
+                          context: &Context,
+                          surface: &mut Surface,
+                          size: Size2D<i32>,
+                          preserve_contents: bool)
+                          -> Result<(), Error> {
+        if context.0.id != surface.0.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let egl_display = self.native_connection.egl_display;
+        match surface.0.objects {
+            EGLSurfaceObjects::Window { .. } => surface.0.update_window_size(egl_display),
+            EGLSurfaceObjects::TextureImage { .. } => {
+                GL_FUNCTIONS.with(|gl| {
+                    surface.0.resize(gl, egl_display, &size, preserve_contents)
+                })
+            }
+        }
+    }
+
+    /// Returns a pointer to the underlying surface data for reading or writing by the CPU.
+    ///
+    /// The surface must have been created with `GPUCPU` or `GPUCPUWriteCombined` access, or a
+    /// `SurfaceDataInaccessible` error is returned. Calling this method on a widget surface returns
+    /// a `WidgetAttached` error.
+    ///
+    /// On this backend, the surface contents are copied into CPU memory when the surface is locked
+    /// and copied back when the guard is dropped. This must happen on the thread that owns the
+    /// surface's context.
+    pub fn lock_surface_data<'s>(&self, surface: &'s mut Surface)
                                  -> Result<SurfaceDataGuard<'s>, Error> {
-        Err(Error::Unimplemented)
+        GL_FUNCTIONS.with(move |gl| {
+            let egl_display = self.native_connection.egl_display;
+            Ok(SurfaceDataGuard(surface.0.lock_data(gl, egl_display)?))
+        })
     }
 
     /// Returns the OpenGL texture target needed to read from this surface texture.
@@ -218,6 +402,12 @@ impl Device {
         surface.0.info()
     }
 
+    /// Returns the colorspaces that widget surfaces may request, depending on the EGL extensions
+    /// that the display supports.
+    pub fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        surface::supported_colorspaces(self.native_connection.egl_display)
+    }
+
     /// Returns the OpenGL texture object containing the contents of this surface.
     /// 
     /// It is only legal to read from, not write to, this texture object.
@@ -225,9 +415,183 @@ impl Device {
     pub fn surface_texture_object(&self, surface_texture: &SurfaceTexture) -> GLuint {
         surface_texture.0.texture_object
     }
+
+    /// Returns the OpenGL texture object containing the depth (and, if present, stencil) buffer
+    /// of the surface wrapped by this surface texture, if it was created with a sampleable depth
+    /// buffer.
+    /// 
+    /// It is only legal to read from, not write to, this texture object.
+    #[inline]
+    pub fn surface_texture_depth_object(&self, surface_texture: &SurfaceTexture)
+                                        -> Option<GLuint> {
+        surface_texture.0.depth_texture_object
+    }
+
+    /// Exports the memory backing a generic surface as a Linux DMA buffer, so that it can be
+    /// consumed by other APIs or processes without copying.
+    /// 
+    /// The caller owns the returned file descriptors and must close them. The surface must stay
+    /// alive for as long as the buffer is in use elsewhere. Rendering to the surface is not
+    /// synchronized with consumers of the buffer; use a fence for that.
+    /// 
+    /// Requires `EGL_MESA_image_dma_buf_export`; if it's unavailable, a
+    /// `RequiredExtensionUnavailable` error is returned. Calling this method on a widget surface
+    /// returns a `WidgetAttached` error.
+    #[inline]
+    pub fn export_surface_dmabuf(&self, surface: &Surface) -> Result<DmabufDescriptor, Error> {
+        surface.0.export_dmabuf(self.native_connection.egl_display)
+    }
+
+    /// Creates a handle that can be sent to another process in order to share the contents of a
+    /// generic surface with it.
+    /// 
+    /// If the surface can be exported as a dmabuf, the handle refers to the surface itself, and
+    /// the surface must stay alive for as long as the other process uses it. Otherwise, the handle
+    /// holds a copy of the surface's current contents in a memory file; this fallback must happen
+    /// on the thread that owns the surface's context.
+    /// 
+    /// Calling this method on a widget surface returns a `WidgetAttached` error.
+    pub fn create_shared_surface_handle(&self, surface: &Surface)
+                                        -> Result<SharedSurfaceHandle, Error> {
+        match self.export_surface_dmabuf(surface) {
+            Ok(descriptor) => return Ok(SharedSurfaceHandle::from_dmabuf(descriptor)),
+            Err(Error::RequiredExtensionUnavailable) | Err(Error::SurfaceExportFailed(_)) => {}
+            Err(err) => return Err(err),
+        }
+
+        GL_FUNCTIONS.with(|gl| {
+            let pixels = surface.0.read_all_pixels(gl, self.native_connection.egl_display)?;
+            SharedSurfaceHandle::from_pixels(&surface.0.size, &pixels)
+        })
+    }
+
+    /// Creates a generic surface from a handle received from another process.
+    /// 
+    /// The resulting surface is associated with the given context and can be wrapped in a
+    /// `SurfaceTexture` like any other generic surface. If the handle refers to a dmabuf, the
+    /// surface shares its memory with the surface in the other process; otherwise, it is a copy of
+    /// that surface's contents at the time the handle was created.
+    pub fn import_shared_surface(&mut self, context: &Context, handle: SharedSurfaceHandle)
+                                 -> Result<Surface, Error> {
+        if let Some(descriptor) = handle.dmabuf() {
+            return self.create_surface_from_dmabuf(context, descriptor.clone());
+        }
+
+        let pixels = handle.read_pixels()?;
+        let mut surface = self.create_generic_surface(context,
+                                                      SurfaceAccess::GPUOnly,
+                                                      &handle.size(),
+                                                      &SurfaceDescriptor::default())?;
+        GL_FUNCTIONS.with(|gl| {
+            let egl_display = self.native_connection.egl_display;
+            match surface.0.upload_pixels(gl, egl_display, &pixels) {
+                Ok(()) => Ok(surface),
+                Err(err) => {
+                    drop(surface.0.destroy(gl, egl_display, context.0.id));
+                    Err(err)
+                }
+            }
+        })
+    }
+
+    /// Names the OpenGL objects behind a surface after the given label, so that `KHR_debug`
+    /// messages and GL debuggers can refer to them.
+    /// 
+    /// Widget surfaces have no such objects, so this does nothing for them. The supplied context
+    /// must be the context the surface is associated with, or an `IncompatibleSurface` error is
+    /// returned.
+    pub fn set_surface_label(&self, context: &Context, surface: &Surface, label: &str)
+                             -> Result<(), Error> {
+        if context.0.id != surface.0.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| surface.0.set_label(gl, label));
+        Ok(())
+    }
+
+    /// Starts copying a region of a surface into CPU memory, without waiting for pending
+    /// rendering to finish.
+    ///
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned. The region is in device pixels, with the origin
+    /// at the bottom left as in OpenGL, and must lie within the surface, or an `InvalidRegion`
+    /// error is returned. For widget surfaces, this reads from the back buffer, so it must be
+    /// called before the surface is presented.
+    pub fn read_surface_pixels(&self,
+                               context: &Context,
+                               surface: &Surface,
+                               rect: Rect<i32>,
+                               format: ReadbackFormat)
+                               -> Result<PixelReadback, Error> {
+        if context.0.id != surface.0.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        GL_FUNCTIONS.with(|gl| {
+            surface.0.read_pixels(gl, self.native_connection.egl_display, &rect, format)
+        })
+    }
+
+    /// Returns true if the pixels of a readback can be retrieved without blocking.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned.
+    pub fn poll_pixel_readback(&self, context: &Context, readback: &PixelReadback)
+                               -> Result<bool, Error> {
+        if context.0.id != readback.context_id {
+            return Err(Error::IncompatibleContext);
+        }
+
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| unsafe { Ok(readback.is_ready(gl)) })
+    }
+
+    /// Waits for a readback to complete and returns its pixels, in the format it was started
+    /// with.
+    ///
+    /// The supplied context must be the context the readback was started on, or an
+    /// `IncompatibleContext` error is returned.
+    pub fn finish_pixel_readback(&self, context: &Context, mut readback: PixelReadback)
+                                 -> Result<Vec<u8>, (Error, PixelReadback)> {
+        if context.0.id != readback.context_id {
+            return Err((Error::IncompatibleContext, readback));
+        }
+
+        let _guard = match self.temporarily_make_context_current(context) {
+            Ok(guard) => guard,
+            Err(err) => return Err((err, readback)),
+        };
+        let result = GL_FUNCTIONS.with(|gl| unsafe { readback.finish(gl) });
+        result.map_err(|err| (err, readback))
+    }
 }
 
 /// Represents the CPU view of the pixel data of this surface.
-pub struct SurfaceDataGuard<'a> {
-    phantom: PhantomData<&'a ()>,
+///
+/// Any changes made to the data are written back to the surface when this guard is dropped.
+pub struct SurfaceDataGuard<'a>(EGLSurfaceDataGuard<'a>);
+
+impl<'a> SurfaceDataGuard<'a> {
+    /// Returns the number of bytes per row of the surface.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        self.0.stride()
+    }
+
+    /// Returns a mutable slice of the pixel data in this surface, in the surface's format (e.g.
+    /// RGBA for `RGBA8` surfaces, or half floats for `RGBA16F` ones).
+    ///
+    /// Rows are ordered bottom to top, as OpenGL does, and pixels are tightly packed.
+    #[inline]
+    pub fn data(&mut self) -> &mut [u8] {
+        self.0.data()
+    }
+}
+
+impl<'a> Drop for SurfaceDataGuard<'a> {
+    fn drop(&mut self) {
+        GL_FUNCTIONS.with(|gl| self.0.unlock(gl))
+    }
 }
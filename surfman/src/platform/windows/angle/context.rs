@@ -2,7 +2,7 @@
 //
 //! Wrapper for EGL contexts managed by ANGLE using Direct3D 11 as a backend on Windows.
 
-use crate::context::{CREATE_CONTEXT_MUTEX, ContextID};
+use crate::context::{CREATE_CONTEXT_MUTEX, ContextID, current_context_reset_status};
 use crate::egl::types::{EGLConfig, EGLContext, EGLint};
 use crate::egl;
 use crate::platform::generic::egl::context::{self, CurrentContextGuard};
@@ -10,13 +10,14 @@ use crate::platform::generic::egl::device::EGL_FUNCTIONS;
 use crate::platform::generic::egl::error::ToWindowingApiError;
 use crate::platform::generic::egl::surface::ExternalEGLSurfaces;
 use crate::surface::Framebuffer;
-use crate::{ContextAttributes, Error, Gl, SurfaceInfo};
+use crate::{ContextAttributes, ContextInfo, ContextResetStatus, Error, Gl, SurfaceInfo};
 use super::device::Device;
 use super::surface::{Surface, Synchronization, Win32Objects};
 
 use std::mem;
 use std::os::raw::c_void;
 use std::thread;
+use std::time::Duration;
 use winapi::shared::winerror::S_OK;
 use winapi::um::winbase::INFINITE;
 
@@ -59,6 +60,12 @@ impl Drop for Context {
     }
 }
 
+/// A GPU fence.
+///
+/// Fences aren't supported on this backend yet, so this type can't be constructed.
+#[derive(Debug)]
+pub enum Fence {}
+
 impl Device {
     /// Creates a context descriptor with the given attributes.
     /// 
@@ -66,6 +73,11 @@ impl Device {
     #[inline]
     pub fn create_context_descriptor(&self, attributes: &ContextAttributes)
                                      -> Result<ContextDescriptor, Error> {
+        // FIXME: Support multisampled surfaces.
+        if attributes.samples > 1 {
+            return Err(Error::Unimplemented);
+        }
+
         unsafe {
             ContextDescriptor::new(self.egl_display, attributes, &[
                 egl::BIND_TO_TEXTURE_RGBA as EGLint,    1 as EGLint,
@@ -221,6 +233,28 @@ impl Device {
         }
     }
 
+    /// Returns detailed information about the context, as reported by the driver: the renderer,
+    /// the vendor, the version that was actually created, and the supported extensions.
+    pub fn context_info(&self, context: &Context) -> Result<ContextInfo, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        GL_FUNCTIONS.with(|gl| {
+            unsafe {
+                context::current_context_info(gl, self.egl_display)
+            }
+        })
+    }
+
+    /// Returns whether the context has been lost because of a GPU reset.
+    /// 
+    /// Resets are only reported to contexts created with the `LOSE_CONTEXT_ON_RESET` flag. See
+    /// `Device::context_reset_status()` in the `Device` trait for how to recover from a reset.
+    pub fn context_reset_status(&self, context: &Context) -> Result<ContextResetStatus, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        unsafe {
+            Ok(current_context_reset_status(context::get_proc_address))
+        }
+    }
+
     /// Fetches the address of an OpenGL function associated with this context.
     /// 
     /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
@@ -360,4 +394,31 @@ impl Device {
             egl_read_surface,
         }
     }
+
+    /// Inserts a fence into the command stream of the given context.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    #[inline]
+    pub fn create_fence(&self, _: &Context) -> Result<Fence, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Blocks the calling thread until the fence is signaled.
+    #[inline]
+    pub fn wait_fence(&self, fence: &Fence, _: Option<Duration>) -> Result<bool, Error> {
+        match *fence {}
+    }
+
+    /// Makes the GPU wait for the fence to be signaled before executing any further commands
+    /// issued on the given context.
+    #[inline]
+    pub fn server_wait_fence(&self, _: &Context, fence: &Fence) -> Result<(), Error> {
+        match *fence {}
+    }
+
+    /// Destroys a fence.
+    #[inline]
+    pub fn destroy_fence(&self, fence: &mut Fence) {
+        match *fence {}
+    }
 }
@@ -14,16 +14,21 @@ use crate::platform::generic::egl::ffi::EGL_D3D_TEXTURE_2D_SHARE_HANDLE_ANGLE;
 use crate::platform::generic::egl::ffi::EGL_D3D_TEXTURE_ANGLE;
 use crate::platform::generic::egl::ffi::EGL_DXGI_KEYED_MUTEX_ANGLE;
 use crate::platform::generic::egl::ffi::EGL_EXTENSION_FUNCTIONS;
-use crate::{Error, SurfaceAccess, SurfaceID, SurfaceInfo, SurfaceType};
+use crate::platform::generic::egl::surface::{self, query_buffer_age, swap_buffers_with_damage};
+use crate::platform::generic::egl::surface::{default_swap_interval, set_swap_interval};
+use crate::{Error, PixelReadback, ReadbackFormat, SurfaceAccess, SurfaceID, SurfaceInfo};
+use crate::{SurfaceColorspace, SurfaceFormat, SurfaceType};
 use super::context::{Context, ContextDescriptor, GL_FUNCTIONS};
 use super::device::Device;
 
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
+use std::cell::Cell;
 use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
 use std::os::raw::c_void;
 use std::ptr;
 use std::thread;
+use std::time::Duration;
 use winapi::shared::dxgi::IDXGIKeyedMutex;
 use winapi::shared::winerror::S_OK;
 use winapi::um::d3d11;
@@ -98,7 +103,9 @@ impl Debug for SurfaceTexture {
 }
 
 pub(crate) enum Win32Objects {
-    Window,
+    Window {
+        swap_interval: Cell<i32>,
+    },
     Pbuffer {
         share_handle: HANDLE,
         synchronization: Synchronization,
@@ -120,20 +127,38 @@ pub struct NativeWidget {
     pub egl_native_window: EGLNativeWindowType,
 }
 
+/// A request to be told when the compositor wants the next frame of a widget surface.
+///
+/// Frame callbacks aren't supported on this backend, so this type can't be constructed.
+#[derive(Debug)]
+pub enum FrameCallback {}
+
 impl Device {
     /// Creates either a generic or a widget surface, depending on the supplied surface type.
     /// 
     /// Only the given context may ever render to the surface, but generic surfaces can be wrapped
     /// up in a `SurfaceTexture` for reading by other contexts.
+    /// 
+    /// Generic surfaces are pbuffers in the context's pixel format, so they must be `RGBA8`, or an
+    /// `UnsupportedSurfaceFormat` error is returned.
     pub fn create_surface(&mut self,
                           context: &Context,
                           _: SurfaceAccess,
                           surface_type: SurfaceType<NativeWidget>)
                           -> Result<Surface, Error> {
         match surface_type {
-            SurfaceType::Generic { ref size } => self.create_pbuffer_surface(context, size, None),
-            SurfaceType::Widget { ref native_widget } => {
-                self.create_window_surface(context, native_widget)
+            SurfaceType::Generic { ref size, ref descriptor } => {
+                if descriptor.format != SurfaceFormat::RGBA8 {
+                    return Err(Error::UnsupportedSurfaceFormat);
+                }
+                // FIXME: Clear pbuffers upon creation, and support sampleable depth buffers.
+                if descriptor.clear_color.is_some() || descriptor.sampleable_depth {
+                    return Err(Error::Unimplemented);
+                }
+                self.create_pbuffer_surface(context, size, None)
+            }
+            SurfaceType::Widget { ref native_widget, colorspace } => {
+                self.create_window_surface(context, native_widget, colorspace)
             }
         }
     }
@@ -232,19 +257,30 @@ impl Device {
         self.create_pbuffer_surface(context, size, Some(texture))
     }
 
-    fn create_window_surface(&mut self, context: &Context, native_widget: &NativeWidget)
-                              -> Result<Surface, Error> {
+    fn create_window_surface(&mut self,
+                             context: &Context,
+                             native_widget: &NativeWidget,
+                             colorspace: SurfaceColorspace)
+                             -> Result<Surface, Error> {
         let context_descriptor = self.context_descriptor(context);
         let egl_config = self.context_descriptor_to_egl_config(&context_descriptor);
 
+        let mut attributes = vec![];
+        if let Some(value) = surface::colorspace_attribute(self.egl_display, colorspace)? {
+            attributes.extend_from_slice(&[egl::GL_COLORSPACE as EGLint, value as EGLint]);
+        }
+        attributes.push(egl::NONE as EGLint);
+
         unsafe {
             EGL_FUNCTIONS.with(|egl| {
-                let attributes = [egl::NONE as EGLint];
                 let egl_surface = egl.CreateWindowSurface(self.egl_display,
                                                           egl_config,
                                                           native_widget.egl_native_window,
                                                           attributes.as_ptr());
-                assert_ne!(egl_surface, egl::NO_SURFACE);
+                if egl_surface == egl::NO_SURFACE {
+                    let windowing_api_error = egl.GetError().to_windowing_api_error();
+                    return Err(Error::SurfaceCreationFailed(windowing_api_error));
+                }
 
                 let mut width = 0;
                 let mut height = 0;
@@ -253,12 +289,15 @@ impl Device {
                 assert_ne!(width, 0);
                 assert_ne!(height, 0);
 
+                let swap_interval = default_swap_interval(self.egl_display, egl_surface);
                 Ok(Surface {
                     egl_surface,
                     size: Size2D::new(width, height),
                     context_id: context.id,
                     context_descriptor,
-                    win32_objects: Win32Objects::Window,
+                    win32_objects: Win32Objects::Window {
+                        swap_interval: Cell::new(swap_interval),
+                    },
                 })
             })
         }
@@ -278,7 +317,7 @@ impl Device {
     pub fn create_surface_texture(&self, context: &mut Context, surface: Surface)
                                   -> Result<SurfaceTexture, (Error, Surface)> {
         let share_handle = match surface.win32_objects {
-            Win32Objects::Window => return Err((Error::WidgetAttached, surface)),
+            Win32Objects::Window { .. } => return Err((Error::WidgetAttached, surface)),
             Win32Objects::Pbuffer { share_handle, .. } => share_handle,
         };
 
@@ -460,10 +499,135 @@ impl Device {
         })
     }
 
-    /// Resizes a widget surface.
-    pub fn resize_surface(&self, _context: &Context, surface: &mut Surface, size: Size2D<i32>) -> Result<(), Error> {
-        surface.size = size;
-        Ok(())
+    /// Displays the contents of a widget surface on screen, hinting that only the given regions
+    /// have changed since the last time it was presented.
+    /// 
+    /// The regions are in device pixels, with the origin at the bottom left as in OpenGL. If the
+    /// display doesn't support `EGL_KHR_swap_buffers_with_damage`, or `damage` is empty, the
+    /// whole surface is presented.
+    pub fn present_surface_with_damage(&self,
+                                       _: &Context,
+                                       surface: &mut Surface,
+                                       damage: &[Rect<i32>])
+                                       -> Result<(), Error> {
+        match surface.win32_objects {
+            Win32Objects::Window { .. } => {}
+            _ => return Err(Error::NoWidgetAttached),
+        }
+
+        unsafe {
+            swap_buffers_with_damage(self.egl_display, surface.egl_surface, damage)
+        }
+    }
+
+    /// Returns the number of frames ago that the contents of the back buffer of a widget surface
+    /// were presented, or 0 if they are undefined or the display doesn't support
+    /// `EGL_EXT_buffer_age`.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn surface_buffer_age(&self, context: &Context, surface: &Surface) -> Result<u32, Error> {
+        match surface.win32_objects {
+            Win32Objects::Window { .. } => {}
+            _ => return Err(Error::NoWidgetAttached),
+        }
+
+        unsafe {
+            query_buffer_age(self.egl_display, surface.egl_surface, context.egl_context)
+        }
+    }
+
+    /// Sets the number of vertical blanks that presenting a widget surface waits for.
+    /// 
+    /// EGL clamps the interval to the range supported by the surface's config; use
+    /// `swap_interval()` to find out the interval actually in effect. EGL has no adaptive vsync,
+    /// so negative intervals return an `UnsupportedOnThisPlatform` error. Calling this method on
+    /// a generic surface returns a `NoWidgetAttached` error.
+    pub fn set_swap_interval(&self, context: &Context, surface: &Surface, interval: i32)
+                             -> Result<(), Error> {
+        let swap_interval = match surface.win32_objects {
+            Win32Objects::Window { ref swap_interval } => swap_interval,
+            _ => return Err(Error::NoWidgetAttached),
+        };
+
+        unsafe {
+            let effective_interval = set_swap_interval(self.egl_display,
+                                                       surface.egl_surface,
+                                                       context.egl_context,
+                                                       interval)?;
+            swap_interval.set(effective_interval);
+            Ok(())
+        }
+    }
+
+    /// Returns the swap interval that is in effect for a widget surface.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn swap_interval(&self, _: &Context, surface: &Surface) -> Result<i32, Error> {
+        match surface.win32_objects {
+            Win32Objects::Window { ref swap_interval } => Ok(swap_interval.get()),
+            _ => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    /// Asks the compositor to signal a frame callback when it's a good time to draw the next frame
+    /// of a widget surface.
+    ///
+    /// Frame callbacks aren't supported on this backend, so this always returns an
+    /// `UnsupportedOnThisPlatform` error.
+    #[inline]
+    pub fn request_frame_callback(&self, _: &Surface) -> Result<FrameCallback, Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
+    /// Dispatches any frame callback events that have arrived, without blocking, and returns true
+    /// if the given frame callback has been signaled.
+    #[inline]
+    pub fn poll_frame_callback(&self, callback: &FrameCallback) -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Blocks the calling thread until the given frame callback is signaled or the timeout
+    /// elapses.
+    #[inline]
+    pub fn wait_for_frame_callback(&self, callback: &FrameCallback, _: Option<Duration>)
+                                   -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Destroys a frame callback.
+    #[inline]
+    pub fn destroy_frame_callback(&self, callback: &mut FrameCallback) {
+        match *callback {}
+    }
+
+    /// Resizes a surface.
+    /// 
+    /// Widget surfaces take on the current size of their native window, and ignore `size` and
+    /// `preserve_contents`. Resizing generic surfaces isn't supported yet on this backend, and
+    /// returns an `Unimplemented` error.
+    /// 
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn resize_surface(&self,
+                          context: &Context,
+                          surface: &mut Surface,
+                          _: Size2D<i32>,
+                          _: bool)
+                          -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        match surface.win32_objects {
+            Win32Objects::Window { .. } => {
+                surface.size = unsafe {
+                    surface::query_surface_size(self.egl_display, surface.egl_surface)
+                };
+                Ok(())
+            }
+            // FIXME: Recreate the pbuffer and its D3D share handle.
+            Win32Objects::Pbuffer { .. } => Err(Error::Unimplemented),
+        }
     }
 
     /// Returns various information about the surface, including the framebuffer object needed to
@@ -479,9 +643,16 @@ impl Device {
             id: surface.id(),
             context_id: surface.context_id,
             framebuffer_object: 0,
+            format: SurfaceFormat::RGBA8,
         }
     }
 
+    /// Returns the colorspaces that widget surfaces may request, depending on the EGL extensions
+    /// that the display supports.
+    pub fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        surface::supported_colorspaces(self.egl_display)
+    }
+
     /// Returns the OpenGL texture object containing the contents of this surface.
     /// 
     /// It is only legal to read from, not write to, this texture object.
@@ -489,6 +660,57 @@ impl Device {
     pub fn surface_texture_object(&self, surface_texture: &SurfaceTexture) -> GLuint {
         surface_texture.gl_texture
     }
+
+    /// Returns the OpenGL texture object containing the depth buffer of the surface wrapped by
+    /// this surface texture.
+    /// 
+    /// Sampleable depth buffers aren't supported on this backend, so this always returns `None`.
+    #[inline]
+    pub fn surface_texture_depth_object(&self, _: &SurfaceTexture) -> Option<GLuint> {
+        None
+    }
+
+    /// Names the OpenGL objects behind a surface after the given label, so that `KHR_debug`
+    /// messages and GL debuggers can refer to them.
+    /// 
+    /// On ANGLE, surfaces are EGL pbuffers or windows with no OpenGL objects behind them, so this
+    /// only checks that the supplied context is the context the surface is associated with,
+    /// returning an `IncompatibleSurface` error if not.
+    pub fn set_surface_label(&self, context: &Context, surface: &Surface, _: &str)
+                             -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+        Ok(())
+    }
+
+    /// Starts copying a region of a surface into CPU memory, without waiting for pending
+    /// rendering to finish.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    #[inline]
+    pub fn read_surface_pixels(&self,
+                               _: &Context,
+                               _: &Surface,
+                               _: Rect<i32>,
+                               _: ReadbackFormat)
+                               -> Result<PixelReadback, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Returns true if the pixels of a readback can be retrieved without blocking.
+    #[inline]
+    pub fn poll_pixel_readback(&self, _: &Context, _: &PixelReadback) -> Result<bool, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Waits for a readback to complete and returns its pixels, in the format it was started
+    /// with.
+    #[inline]
+    pub fn finish_pixel_readback(&self, _: &Context, readback: PixelReadback)
+                                 -> Result<Vec<u8>, (Error, PixelReadback)> {
+        Err((Error::Unimplemented, readback))
+    }
 }
 
 impl Surface {
@@ -83,6 +83,14 @@ impl Connection {
         self.create_low_power_adapter()
     }
 
+    /// Returns every adapter that can be used with this connection.
+    ///
+    /// This backend can't enumerate adapters yet, so this returns only the default adapter.
+    #[inline]
+    pub fn adapters(&self) -> Result<Vec<Adapter>, Error> {
+        Ok(vec![self.create_adapter()?])
+    }
+
     /// Opens a device.
     #[inline]
     pub fn create_device(&self, adapter: &Adapter) -> Result<Device, Error> {
@@ -2,10 +2,11 @@
 //
 //! Wrapper for WGL contexts on Windows.
 
-use crate::context::{self, CREATE_CONTEXT_MUTEX};
+use crate::context::{self, CREATE_CONTEXT_MUTEX, current_context_reset_status};
 use crate::surface::Framebuffer;
-use crate::{ContextAttributeFlags, ContextAttributes, ContextID, Error, GLVersion};
-use crate::{SurfaceInfo, WindowingApiError};
+use crate::{ContextAttributeFlags, ContextAttributes, ContextID, ContextResetStatus, Error};
+use crate::GLVersion;
+use crate::{ContextInfo, SurfaceInfo, WindowingApiError};
 use super::device::{DCGuard, Device, HiddenWindow};
 use super::surface::{Surface, Win32Objects};
 
@@ -18,6 +19,7 @@ use std::mem;
 use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 use std::thread;
+use std::time::Duration;
 use winapi::shared::minwindef::{BOOL, FALSE, FLOAT, HMODULE, LPARAM, LPVOID, LRESULT, UINT};
 use winapi::shared::minwindef::{WORD, WPARAM};
 use winapi::shared::ntdef::{HANDLE, LPCSTR};
@@ -163,6 +165,12 @@ lazy_static! {
     };
 }
 
+/// A GPU fence.
+///
+/// Fences aren't supported on this backend yet, so this type can't be constructed.
+#[derive(Debug)]
+pub enum Fence {}
+
 impl Device {
     /// Creates a context descriptor with the given attributes.
     /// 
@@ -176,6 +184,19 @@ impl Device {
         let stencil_bits = if flags.contains(ContextAttributeFlags::STENCIL) { 8  } else { 0 };
         let compatibility_profile = flags.contains(ContextAttributeFlags::COMPATIBILITY_PROFILE);
 
+        // FIXME: Support robust contexts via `WGL_ARB_create_context_robustness`, and debug
+        // contexts via `WGL_CONTEXT_DEBUG_BIT_ARB`.
+        if flags.intersects(ContextAttributeFlags::ROBUST_ACCESS |
+                            ContextAttributeFlags::LOSE_CONTEXT_ON_RESET |
+                            ContextAttributeFlags::DEBUG) {
+            return Err(Error::RequiredExtensionUnavailable);
+        }
+
+        // FIXME: Support multisampled surfaces.
+        if attributes.samples > 1 {
+            return Err(Error::Unimplemented);
+        }
+
         let attrib_i_list = [
             WGL_DRAW_TO_WINDOW_ARB as c_int, gl::TRUE as c_int,
             WGL_SUPPORT_OPENGL_ARB as c_int, gl::TRUE as c_int,
@@ -390,6 +411,7 @@ impl Device {
             let mut attributes = ContextAttributes {
                 version: context_descriptor.gl_version,
                 flags: ContextAttributeFlags::empty(),
+                samples: 0,
             };
             if alpha_bits > 0 {
                 attributes.flags.insert(ContextAttributeFlags::ALPHA);
@@ -454,6 +476,24 @@ impl Device {
         }
     }
 
+    /// Returns detailed information about the context, as reported by the driver: the renderer,
+    /// the vendor, the version that was actually created, and the supported extensions.
+    pub fn context_info(&self, context: &Context) -> Result<ContextInfo, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        ContextInfo::current(&context.gl)
+    }
+
+    /// Returns whether the context has been lost because of a GPU reset.
+    /// 
+    /// Resets are only reported to contexts created with the `LOSE_CONTEXT_ON_RESET` flag. See
+    /// `Device::context_reset_status()` in the `Device` trait for how to recover from a reset.
+    pub fn context_reset_status(&self, context: &Context) -> Result<ContextResetStatus, Error> {
+        let _guard = self.temporarily_make_context_current(context)?;
+        unsafe {
+            Ok(current_context_reset_status(get_proc_address))
+        }
+    }
+
     /// Fetches the address of an OpenGL function associated with this context.
     /// 
     /// OpenGL functions are local to a context. You should not use OpenGL functions on one context
@@ -566,6 +606,33 @@ impl Device {
     pub fn native_context(&self, context: &Context) -> NativeContext {
         NativeContext(context.glrc)
     }
+
+    /// Inserts a fence into the command stream of the given context.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    #[inline]
+    pub fn create_fence(&self, _: &Context) -> Result<Fence, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Blocks the calling thread until the fence is signaled.
+    #[inline]
+    pub fn wait_fence(&self, fence: &Fence, _: Option<Duration>) -> Result<bool, Error> {
+        match *fence {}
+    }
+
+    /// Makes the GPU wait for the fence to be signaled before executing any further commands
+    /// issued on the given context.
+    #[inline]
+    pub fn server_wait_fence(&self, _: &Context, fence: &Fence) -> Result<(), Error> {
+        match *fence {}
+    }
+
+    /// Destroys a fence.
+    #[inline]
+    pub fn destroy_fence(&self, fence: &mut Fence) {
+        match *fence {}
+    }
 }
 
 impl NativeContext {
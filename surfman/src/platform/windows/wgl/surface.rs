@@ -4,23 +4,29 @@
 
 use crate::error::WindowingApiError;
 use crate::renderbuffers::Renderbuffers;
-use crate::{ContextID, Error, SurfaceAccess, SurfaceID, SurfaceInfo, SurfaceType};
+use crate::{ContextID, Error, PixelReadback, ReadbackFormat, SurfaceAccess, SurfaceDescriptor};
+use crate::{SurfaceColorspace, SurfaceFormat, SurfaceID, SurfaceInfo, SurfaceType};
 use super::context::{self, Context, WGL_EXTENSION_FUNCTIONS};
 use super::device::Device;
 
 use crate::gl::types::{GLenum, GLint, GLuint};
 use crate::gl;
 use crate::gl_utils;
-use euclid::default::Size2D;
+use euclid::default::{Rect, Size2D};
 use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
 use std::mem;
 use std::os::raw::c_void;
 use std::ptr;
 use std::thread;
+use std::time::Duration;
 use winapi::Interface;
 use winapi::shared::dxgi::IDXGIResource;
-use winapi::shared::dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM;
+use winapi::shared::dxgiformat::{DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_UNORM};
+use winapi::shared::dxgiformat::{DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT};
+use winapi::shared::dxgiformat::{DXGI_FORMAT_R32G32B32A32_FLOAT, DXGI_FORMAT_R8G8B8A8_UNORM};
+use winapi::shared::dxgiformat::{DXGI_FORMAT_R8G8B8A8_UNORM_SRGB, DXGI_FORMAT_R8G8_UNORM};
+use winapi::shared::dxgiformat::DXGI_FORMAT_R8_UNORM;
 use winapi::shared::dxgitype::DXGI_SAMPLE_DESC;
 use winapi::shared::minwindef::{FALSE, UINT};
 use winapi::shared::ntdef::HANDLE;
@@ -58,6 +64,7 @@ const WGL_ACCESS_READ_WRITE_NV: GLenum = 0x0001;
 pub struct Surface {
     pub(crate) size: Size2D<i32>,
     pub(crate) context_id: ContextID,
+    pub(crate) format: SurfaceFormat,
     pub(crate) win32_objects: Win32Objects,
     pub(crate) destroyed: bool,
 }
@@ -124,6 +131,12 @@ pub struct NativeWidget {
     pub window_handle: HWND,
 }
 
+/// A request to be told when the compositor wants the next frame of a widget surface.
+///
+/// Frame callbacks aren't supported on this backend, so this type can't be constructed.
+#[derive(Debug)]
+pub enum FrameCallback {}
+
 impl Device {
     /// Creates either a generic or a widget surface, depending on the supplied surface type.
     /// 
@@ -135,15 +148,29 @@ impl Device {
                           surface_type: SurfaceType<NativeWidget>)
                           -> Result<Surface, Error> {
         match surface_type {
-            SurfaceType::Generic { size } => self.create_generic_surface(context, &size),
-            SurfaceType::Widget { native_widget } => {
+            SurfaceType::Generic { size, descriptor } => {
+                self.create_generic_surface(context, &size, &descriptor)
+            }
+            SurfaceType::Widget { native_widget, colorspace } => {
+                // FIXME: Support other colorspaces via `WGL_EXT_colorspace`.
+                if colorspace != SurfaceColorspace::Default {
+                    return Err(Error::UnsupportedOnThisPlatform);
+                }
                 self.create_widget_surface(context, native_widget)
             }
         }
     }
 
-    fn create_generic_surface(&mut self, context: &Context, size: &Size2D<i32>)
+    fn create_generic_surface(&mut self,
+                              context: &Context,
+                              size: &Size2D<i32>,
+                              descriptor: &SurfaceDescriptor)
                               -> Result<Surface, Error> {
+        // FIXME: Support sampleable depth buffers.
+        if descriptor.sampleable_depth {
+            return Err(Error::Unimplemented);
+        }
+
         let dx_interop_functions = match WGL_EXTENSION_FUNCTIONS.dx_interop_functions {
             None => return Err(Error::RequiredExtensionUnavailable),
             Some(ref dx_interop_functions) => dx_interop_functions,
@@ -158,7 +185,7 @@ impl Device {
                 Height: size.height as UINT,
                 MipLevels: 1,
                 ArraySize: 1,
-                Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                Format: dxgi_format(descriptor.format),
                 SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
                 Usage: D3D11_USAGE_DEFAULT,
                 BindFlags: D3D11_BIND_SHADER_RESOURCE | D3D11_BIND_RENDER_TARGET,
@@ -225,15 +252,17 @@ impl Device {
             // Create renderbuffers as appropriate, and attach them.
             let context_descriptor = self.context_descriptor(context);
             let context_attributes = self.context_descriptor_attributes(&context_descriptor);
-            let renderbuffers = Renderbuffers::new(&context.gl, &size, &context_attributes);
+            let framebuffer_attributes = descriptor.framebuffer_attributes(&context_attributes);
+            let renderbuffers = Renderbuffers::new(&context.gl, &size, &framebuffer_attributes);
             renderbuffers.bind_to_current_framebuffer(&context.gl);
 
             // FIXME(pcwalton): Do we need to acquire the keyed mutex, or does the GL driver do
             // that?
 
-            Ok(Surface {
+            let surface = Surface {
                 size: *size,
                 context_id: context.id,
+                format: descriptor.format,
                 win32_objects: Win32Objects::Texture {
                     d3d11_texture,
                     dxgi_share_handle,
@@ -243,7 +272,15 @@ impl Device {
                     renderbuffers,
                 },
                 destroyed: false,
-            })
+            };
+
+            if let Some(ref clear_color) = descriptor.clear_color {
+                self.lock_surface(&surface);
+                gl_utils::clear_framebuffer(&context.gl, gl_framebuffer, clear_color);
+                self.unlock_surface(&surface);
+            }
+
+            Ok(surface)
         }
     }
 
@@ -269,6 +306,7 @@ impl Device {
                 size: Size2D::new(widget_rect.right - widget_rect.left,
                                   widget_rect.bottom - widget_rect.top),
                 context_id: context.id,
+                format: SurfaceFormat::RGBA8,
                 win32_objects: Win32Objects::Widget {
                     window_handle: native_widget.window_handle,
                 },
@@ -534,10 +572,118 @@ impl Device {
         }
     }
 
-    /// Resizes a widget surface.
-    pub fn resize_surface(&self, _scontext: &Context, surface: &mut Surface, size: Size2D<i32>) -> Result<(), Error> {
-        surface.size = size;
-        Ok(())
+    /// Displays the contents of a widget surface on screen.
+    /// 
+    /// Damage regions aren't supported on this backend, so the whole surface is always
+    /// presented.
+    #[inline]
+    pub fn present_surface_with_damage(&self,
+                                       context: &Context,
+                                       surface: &mut Surface,
+                                       _: &[Rect<i32>])
+                                       -> Result<(), Error> {
+        self.present_surface(context, surface)
+    }
+
+    /// Returns the age of the back buffer of a widget surface.
+    /// 
+    /// Buffer ages aren't available on this backend, so this returns 0, meaning that the contents
+    /// of the back buffer are undefined. Calling this method on a generic surface returns a
+    /// `NoWidgetAttached` error.
+    pub fn surface_buffer_age(&self, _: &Context, surface: &Surface) -> Result<u32, Error> {
+        match surface.win32_objects {
+            Win32Objects::Widget { .. } => Ok(0),
+            _ => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    /// Sets the number of vertical blanks that presenting a widget surface waits for.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn set_swap_interval(&self, _: &Context, surface: &Surface, _: i32) -> Result<(), Error> {
+        match surface.win32_objects {
+            // FIXME: Use `wglSwapIntervalEXT()`.
+            Win32Objects::Widget { .. } => Err(Error::Unimplemented),
+            _ => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    /// Returns the swap interval that is in effect for a widget surface.
+    /// 
+    /// Calling this method on a generic surface returns a `NoWidgetAttached` error.
+    pub fn swap_interval(&self, _: &Context, surface: &Surface) -> Result<i32, Error> {
+        match surface.win32_objects {
+            // FIXME: Use `wglGetSwapIntervalEXT()`.
+            Win32Objects::Widget { .. } => Err(Error::Unimplemented),
+            _ => Err(Error::NoWidgetAttached),
+        }
+    }
+
+    /// Asks the compositor to signal a frame callback when it's a good time to draw the next frame
+    /// of a widget surface.
+    ///
+    /// Frame callbacks aren't supported on this backend, so this always returns an
+    /// `UnsupportedOnThisPlatform` error.
+    #[inline]
+    pub fn request_frame_callback(&self, _: &Surface) -> Result<FrameCallback, Error> {
+        Err(Error::UnsupportedOnThisPlatform)
+    }
+
+    /// Dispatches any frame callback events that have arrived, without blocking, and returns true
+    /// if the given frame callback has been signaled.
+    #[inline]
+    pub fn poll_frame_callback(&self, callback: &FrameCallback) -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Blocks the calling thread until the given frame callback is signaled or the timeout
+    /// elapses.
+    #[inline]
+    pub fn wait_for_frame_callback(&self, callback: &FrameCallback, _: Option<Duration>)
+                                   -> Result<bool, Error> {
+        match *callback {}
+    }
+
+    /// Destroys a frame callback.
+    #[inline]
+    pub fn destroy_frame_callback(&self, callback: &mut FrameCallback) {
+        match *callback {}
+    }
+
+    /// Resizes a surface.
+    /// 
+    /// Widget surfaces take on the current size of their window, and ignore `size` and
+    /// `preserve_contents`. Resizing generic surfaces isn't supported yet on this backend, and
+    /// returns an `Unimplemented` error.
+    /// 
+    /// The supplied context must be the context the surface is associated with, or an
+    /// `IncompatibleSurface` error is returned.
+    pub fn resize_surface(&self,
+                          context: &Context,
+                          surface: &mut Surface,
+                          _: Size2D<i32>,
+                          _: bool)
+                          -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        match surface.win32_objects {
+            Win32Objects::Widget { window_handle } => {
+                unsafe {
+                    let mut widget_rect = mem::zeroed();
+                    let ok = winuser::GetWindowRect(window_handle, &mut widget_rect);
+                    if ok == FALSE {
+                        return Err(Error::InvalidNativeWidget);
+                    }
+                    surface.size = Size2D::new(widget_rect.right - widget_rect.left,
+                                               widget_rect.bottom - widget_rect.top);
+                }
+                Ok(())
+            }
+            // FIXME: Recreate the D3D11 texture and its DX interop object.
+            Win32Objects::Texture { .. } => Err(Error::Unimplemented),
+        }
     }
 
     /// Returns various information about the surface, including the framebuffer object needed to
@@ -556,9 +702,18 @@ impl Device {
                 Win32Objects::Texture { gl_framebuffer, .. } => gl_framebuffer,
                 Win32Objects::Widget { .. } => 0,
             },
+            format: surface.format,
         }
     }
 
+    /// Returns the colorspaces that widget surfaces may request.
+    /// 
+    /// Only the default colorspace is currently supported on this backend.
+    #[inline]
+    pub fn supported_colorspaces(&self) -> Vec<SurfaceColorspace> {
+        vec![SurfaceColorspace::Default]
+    }
+
     /// Returns the OpenGL texture object containing the contents of this surface.
     /// 
     /// It is only legal to read from, not write to, this texture object.
@@ -566,6 +721,69 @@ impl Device {
     pub fn surface_texture_object(&self, surface_texture: &SurfaceTexture) -> GLuint {
         surface_texture.gl_texture
     }
+
+    /// Returns the OpenGL texture object containing the depth buffer of the surface wrapped by
+    /// this surface texture.
+    /// 
+    /// Sampleable depth buffers aren't supported on this backend, so this always returns `None`.
+    #[inline]
+    pub fn surface_texture_depth_object(&self, _: &SurfaceTexture) -> Option<GLuint> {
+        None
+    }
+
+    /// Names the OpenGL objects behind a surface after the given label, so that `KHR_debug`
+    /// messages and GL debuggers can refer to them.
+    /// 
+    /// Widget surfaces have no such objects, so this does nothing for them. The supplied context
+    /// must be the context the surface is associated with, or an `IncompatibleSurface` error is
+    /// returned.
+    pub fn set_surface_label(&self, context: &Context, surface: &Surface, label: &str)
+                             -> Result<(), Error> {
+        if context.id != surface.context_id {
+            return Err(Error::IncompatibleSurface);
+        }
+
+        let _guard = self.temporarily_make_context_current(context)?;
+        match surface.win32_objects {
+            Win32Objects::Texture { gl_texture, gl_framebuffer, ref renderbuffers, .. } => {
+                gl_utils::set_surface_objects_label(&context.gl,
+                                                    label,
+                                                    gl_framebuffer,
+                                                    gl_texture,
+                                                    renderbuffers);
+            }
+            Win32Objects::Widget { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Starts copying a region of a surface into CPU memory, without waiting for pending
+    /// rendering to finish.
+    ///
+    /// This is not yet implemented on this backend and always returns an `Unimplemented` error.
+    #[inline]
+    pub fn read_surface_pixels(&self,
+                               _: &Context,
+                               _: &Surface,
+                               _: Rect<i32>,
+                               _: ReadbackFormat)
+                               -> Result<PixelReadback, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Returns true if the pixels of a readback can be retrieved without blocking.
+    #[inline]
+    pub fn poll_pixel_readback(&self, _: &Context, _: &PixelReadback) -> Result<bool, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Waits for a readback to complete and returns its pixels, in the format it was started
+    /// with.
+    #[inline]
+    pub fn finish_pixel_readback(&self, _: &Context, readback: PixelReadback)
+                                 -> Result<Vec<u8>, (Error, PixelReadback)> {
+        Err((Error::Unimplemented, readback))
+    }
 }
 
 impl Surface {
@@ -583,3 +801,16 @@ impl Surface {
 pub struct SurfaceDataGuard<'a> {
     phantom: PhantomData<&'a ()>,
 }
+
+fn dxgi_format(format: SurfaceFormat) -> DXGI_FORMAT {
+    match format {
+        SurfaceFormat::RGBA8 => DXGI_FORMAT_R8G8B8A8_UNORM,
+        SurfaceFormat::BGRA8 => DXGI_FORMAT_B8G8R8A8_UNORM,
+        SurfaceFormat::RGB10_A2 => DXGI_FORMAT_R10G10B10A2_UNORM,
+        SurfaceFormat::RGBA16F => DXGI_FORMAT_R16G16B16A16_FLOAT,
+        SurfaceFormat::RGBA32F => DXGI_FORMAT_R32G32B32A32_FLOAT,
+        SurfaceFormat::R8 => DXGI_FORMAT_R8_UNORM,
+        SurfaceFormat::RG8 => DXGI_FORMAT_R8G8_UNORM,
+        SurfaceFormat::SRGB8_A8 => DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+    }
+}
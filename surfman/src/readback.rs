@@ -0,0 +1,307 @@
+// surfman/surfman/src/readback.rs
+//
+//! Asynchronous readback of surface pixels into CPU memory.
+
+use crate::context::ContextID;
+use crate::error::Error;
+use crate::gl::types::{GLenum, GLint, GLsizeiptr, GLsync, GLuint};
+use crate::gl;
+use crate::info;
+use crate::{GLApi, Gl};
+
+use euclid::default::{Rect, Size2D};
+use std::fmt::{self, Debug, Formatter};
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+use std::slice;
+use std::thread;
+
+/// The order of the color channels in each pixel of a readback.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelOrder {
+    /// Red, green, blue, alpha: the native OpenGL order.
+    RGBA,
+    /// Blue, green, red, alpha: the order most windowing systems and image encoders expect.
+    BGRA,
+}
+
+/// Describes the layout of the pixel data returned by a readback.
+///
+/// Pixels are always 8 bits per channel, with 4 channels per pixel and no padding between rows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReadbackFormat {
+    /// The order of the color channels in each pixel.
+    pub channel_order: ChannelOrder,
+    /// If true, rows are returned top to bottom. Otherwise, they are returned bottom to top, as
+    /// OpenGL stores them.
+    pub flip_y: bool,
+}
+
+/// An in-flight copy of a region of a surface into CPU memory.
+///
+/// Readbacks are started with `Device::read_surface_pixels()`. The copy proceeds on the GPU
+/// without stalling the pipeline; use `Device::poll_pixel_readback()` to check whether it has
+/// completed and `Device::finish_pixel_readback()` to retrieve the pixels.
+///
+/// Readbacks are local to the context they were started on. They must be finished with
+/// `finish_pixel_readback()`, or a panic will occur.
+pub struct PixelReadback {
+    pub(crate) context_id: ContextID,
+    size: Size2D<i32>,
+    format: ReadbackFormat,
+    state: ReadbackState,
+}
+
+enum ReadbackState {
+    // A pixel pack buffer that the GPU is writing into, guarded by a fence.
+    Pending { pixel_pack_buffer: GLuint, fence: GLsync },
+    // The pixels are already in CPU memory, in OpenGL's layout.
+    Ready(Vec<u8>),
+    Finished,
+}
+
+impl Debug for PixelReadback {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "PixelReadback({:?}, {:?})", self.size, self.format)
+    }
+}
+
+impl Drop for PixelReadback {
+    fn drop(&mut self) {
+        match self.state {
+            ReadbackState::Finished => {}
+            _ if thread::panicking() => {}
+            _ => panic!("Should have finished the readback with `finish_pixel_readback()`!"),
+        }
+    }
+}
+
+// What the current context supports for reading pixels back. A symbol being loaded doesn't mean
+// the context supports it, so this goes by the version and extensions.
+struct ReadbackSupport {
+    // Whether `GL_READ_FRAMEBUFFER` can be bound separately from the draw framebuffer.
+    read_framebuffer: bool,
+    // Whether `GL_PIXEL_PACK_BUFFER` exists.
+    pixel_pack_buffers: bool,
+    // Whether pixel pack buffers can be fenced and mapped, so that reads can be asynchronous.
+    asynchronous: bool,
+}
+
+impl ReadbackSupport {
+    fn current(gl: &Gl) -> ReadbackSupport {
+        let (api, version) = info::current_api_and_version(gl);
+        match api {
+            GLApi::GLES => {
+                let gles3 = version.is_at_least(3, 0);
+                ReadbackSupport {
+                    read_framebuffer: gles3,
+                    pixel_pack_buffers: gles3,
+                    asynchronous: gles3 && gl.FenceSync.is_loaded(),
+                }
+            }
+            GLApi::GL => {
+                // OpenGL 3.2 has everything, so only look at the extensions of older contexts.
+                let extensions = if version.is_at_least(3, 2) {
+                    vec![]
+                } else {
+                    info::current_context_extensions(gl)
+                };
+                let has_extension = |name| extensions.iter().any(|extension| extension == name);
+                let pixel_pack_buffers = version.is_at_least(2, 1) ||
+                    has_extension("GL_ARB_pixel_buffer_object");
+                let sync = version.is_at_least(3, 2) ||
+                    (version.is_at_least(3, 0) && has_extension("GL_ARB_sync"));
+                ReadbackSupport {
+                    read_framebuffer: version.is_at_least(3, 0) ||
+                        has_extension("GL_ARB_framebuffer_object"),
+                    pixel_pack_buffers,
+                    asynchronous: pixel_pack_buffers && sync && gl.FenceSync.is_loaded(),
+                }
+            }
+        }
+    }
+}
+
+impl PixelReadback {
+    // Starts a readback of `rect` from the given framebuffer, which is `surface_size` in size.
+    //
+    // The context must be current. Falls back to a synchronous `glReadPixels()` if the context
+    // doesn't support fences and pixel pack buffers.
+    pub(crate) unsafe fn start(gl: &Gl,
+                               context_id: ContextID,
+                               framebuffer_object: GLuint,
+                               surface_size: &Size2D<i32>,
+                               rect: &Rect<i32>,
+                               format: ReadbackFormat)
+                               -> Result<PixelReadback, Error> {
+        if rect.origin.x < 0 || rect.origin.y < 0 || rect.size.width < 0 ||
+                rect.size.height < 0 || rect.max_x() > surface_size.width ||
+                rect.max_y() > surface_size.height {
+            return Err(Error::InvalidRegion);
+        }
+
+        let byte_length = rect.size.width as usize * rect.size.height as usize * 4;
+        let support = ReadbackSupport::current(gl);
+
+        let (framebuffer_target, framebuffer_binding): (GLenum, GLenum) =
+            if support.read_framebuffer {
+                (gl::READ_FRAMEBUFFER, gl::READ_FRAMEBUFFER_BINDING)
+            } else {
+                (gl::FRAMEBUFFER, gl::FRAMEBUFFER_BINDING)
+            };
+        let (mut old_framebuffer, mut old_pack_buffer, mut old_pack_alignment) = (0, 0, 0);
+        gl.GetIntegerv(framebuffer_binding, &mut old_framebuffer);
+        if support.pixel_pack_buffers {
+            gl.GetIntegerv(gl::PIXEL_PACK_BUFFER_BINDING, &mut old_pack_buffer);
+        }
+        gl.GetIntegerv(gl::PACK_ALIGNMENT, &mut old_pack_alignment);
+        gl.BindFramebuffer(framebuffer_target, framebuffer_object);
+        gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+
+        let state = if support.asynchronous {
+            let mut pixel_pack_buffer = 0;
+            gl.GenBuffers(1, &mut pixel_pack_buffer);
+            gl.BindBuffer(gl::PIXEL_PACK_BUFFER, pixel_pack_buffer);
+            gl.BufferData(gl::PIXEL_PACK_BUFFER,
+                          byte_length as GLsizeiptr,
+                          ptr::null(),
+                          gl::STREAM_READ);
+            gl.ReadPixels(rect.origin.x,
+                          rect.origin.y,
+                          rect.size.width,
+                          rect.size.height,
+                          gl::RGBA,
+                          gl::UNSIGNED_BYTE,
+                          ptr::null_mut());
+
+            let fence = gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            // Make sure the fence gets submitted so that polling can make progress.
+            gl.Flush();
+            ReadbackState::Pending { pixel_pack_buffer, fence }
+        } else {
+            let mut pixels = vec![0; byte_length];
+            if support.pixel_pack_buffers {
+                gl.BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            }
+            gl.ReadPixels(rect.origin.x,
+                          rect.origin.y,
+                          rect.size.width,
+                          rect.size.height,
+                          gl::RGBA,
+                          gl::UNSIGNED_BYTE,
+                          pixels.as_mut_ptr() as *mut c_void);
+            ReadbackState::Ready(pixels)
+        };
+
+        gl.PixelStorei(gl::PACK_ALIGNMENT, old_pack_alignment);
+        if support.pixel_pack_buffers {
+            gl.BindBuffer(gl::PIXEL_PACK_BUFFER, old_pack_buffer as GLuint);
+        }
+        gl.BindFramebuffer(framebuffer_target, old_framebuffer as GLuint);
+
+        Ok(PixelReadback { context_id, size: rect.size, format, state })
+    }
+
+    // Wraps pixels that are already in CPU memory, in OpenGL's bottom-to-top RGBA layout. Only
+    // backends that render in CPU memory need this.
+    #[cfg(osmesa)]
+    pub(crate) fn from_pixels(context_id: ContextID,
+                              size: Size2D<i32>,
+                              format: ReadbackFormat,
+                              pixels: Vec<u8>)
+                              -> PixelReadback {
+        PixelReadback { context_id, size, format, state: ReadbackState::Ready(pixels) }
+    }
+
+    /// Returns the size of the region being read, in device pixels.
+    #[inline]
+    pub fn size(&self) -> Size2D<i32> {
+        self.size
+    }
+
+    /// Returns the layout that the pixels will be returned in.
+    #[inline]
+    pub fn format(&self) -> ReadbackFormat {
+        self.format
+    }
+
+    // Returns true if the pixels can be retrieved without blocking. The context must be current.
+    pub(crate) unsafe fn is_ready(&self, gl: &Gl) -> bool {
+        match self.state {
+            ReadbackState::Pending { fence, .. } => {
+                let mut status = 0;
+                gl.GetSynciv(fence, gl::SYNC_STATUS, 1, ptr::null_mut(), &mut status);
+                status == gl::SIGNALED as GLint
+            }
+            ReadbackState::Ready(_) | ReadbackState::Finished => true,
+        }
+    }
+
+    // Waits for the readback to complete and returns the pixels. The context must be current.
+    //
+    // If the pixels can't be mapped into CPU memory, a `PixelReadbackFailed` error is returned and
+    // the readback is finished anyway.
+    pub(crate) unsafe fn finish(&mut self, gl: &Gl) -> Result<Vec<u8>, Error> {
+        let pixels = match mem::replace(&mut self.state, ReadbackState::Finished) {
+            ReadbackState::Ready(pixels) => pixels,
+            ReadbackState::Finished => return Ok(vec![]),
+            ReadbackState::Pending { mut pixel_pack_buffer, fence } => {
+                gl.ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, gl::TIMEOUT_IGNORED);
+                gl.DeleteSync(fence);
+
+                let byte_length = self.size.width as usize * self.size.height as usize * 4;
+                let mut old_pack_buffer = 0;
+                gl.GetIntegerv(gl::PIXEL_PACK_BUFFER_BINDING, &mut old_pack_buffer);
+                gl.BindBuffer(gl::PIXEL_PACK_BUFFER, pixel_pack_buffer);
+
+                let mut pixels = Ok(vec![0; byte_length]);
+                if byte_length > 0 {
+                    let data = gl.MapBufferRange(gl::PIXEL_PACK_BUFFER,
+                                                 0,
+                                                 byte_length as GLsizeiptr,
+                                                 gl::MAP_READ_BIT);
+                    if data.is_null() {
+                        pixels = Err(Error::PixelReadbackFailed);
+                    } else {
+                        if let Ok(ref mut pixels) = pixels {
+                            pixels.copy_from_slice(slice::from_raw_parts(data as *const u8,
+                                                                         byte_length));
+                        }
+                        // The buffer's contents may have been lost while it was mapped, for
+                        // example by a mode switch.
+                        if gl.UnmapBuffer(gl::PIXEL_PACK_BUFFER) == gl::FALSE {
+                            pixels = Err(Error::PixelReadbackFailed);
+                        }
+                    }
+                }
+
+                gl.BindBuffer(gl::PIXEL_PACK_BUFFER, old_pack_buffer as GLuint);
+                gl.DeleteBuffers(1, &mut pixel_pack_buffer);
+                pixels?
+            }
+        };
+
+        Ok(convert_pixels(pixels, &self.size, &self.format))
+    }
+}
+
+// Converts pixels from OpenGL's bottom-to-top RGBA layout to the requested one.
+fn convert_pixels(mut pixels: Vec<u8>, size: &Size2D<i32>, format: &ReadbackFormat) -> Vec<u8> {
+    if format.channel_order == ChannelOrder::BGRA {
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    if format.flip_y {
+        let stride = size.width as usize * 4;
+        let height = size.height as usize;
+        for y in 0..(height / 2) {
+            let (top, bottom) = pixels.split_at_mut((height - y - 1) * stride);
+            top[(y * stride)..((y + 1) * stride)].swap_with_slice(&mut bottom[0..stride]);
+        }
+    }
+
+    pixels
+}
@@ -4,10 +4,13 @@
 
 use crate::Gl;
 use crate::context::{ContextAttributeFlags, ContextAttributes};
-use crate::gl::types::GLuint;
+use crate::gl::types::{GLenum, GLsizei, GLuint};
 use crate::gl;
+use crate::gl_utils;
+use crate::surface::SurfaceFormat;
 
 use euclid::default::Size2D;
+use std::cmp;
 
 pub(crate) enum Renderbuffers {
     IndividualDepthStencil {
@@ -27,19 +30,32 @@ impl Drop for Renderbuffers {
     }
 }
 
+// The color buffer and FBO that a multisampled surface renders to. Rendering is resolved into the
+// surface's texture with `resolve()`.
+pub(crate) struct MultisampleFramebuffer {
+    pub(crate) framebuffer_object: GLuint,
+    color_renderbuffer: GLuint,
+}
+
+impl Drop for MultisampleFramebuffer {
+    fn drop(&mut self) {
+        if self.framebuffer_object != 0 || self.color_renderbuffer != 0 {
+            panic!("Should have destroyed the multisample framebuffer with `destroy()`!");
+        }
+    }
+}
+
 impl Renderbuffers {
     pub(crate) fn new(gl: &Gl, size: &Size2D<i32>, attributes: &ContextAttributes)
                       -> Renderbuffers {
+        let samples = sample_count(gl, attributes);
         unsafe {
             if attributes.flags.contains(ContextAttributeFlags::DEPTH |
                                          ContextAttributeFlags::STENCIL) {
                 let mut renderbuffer = 0;
                 gl.GenRenderbuffers(1, &mut renderbuffer);
                 gl.BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
-                gl.RenderbufferStorage(gl::RENDERBUFFER,
-                                       gl::DEPTH24_STENCIL8,
-                                       size.width,
-                                       size.height);
+                renderbuffer_storage(gl, samples, gl::DEPTH24_STENCIL8, size);
                 gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
                 return Renderbuffers::CombinedDepthStencil(renderbuffer);
             }
@@ -48,18 +64,12 @@ impl Renderbuffers {
             if attributes.flags.contains(ContextAttributeFlags::DEPTH) {
                 gl.GenRenderbuffers(1, &mut depth_renderbuffer);
                 gl.BindRenderbuffer(gl::RENDERBUFFER, depth_renderbuffer);
-                gl.RenderbufferStorage(gl::RENDERBUFFER,
-                                       gl::DEPTH_COMPONENT24,
-                                       size.width,
-                                       size.height);
+                renderbuffer_storage(gl, samples, gl::DEPTH_COMPONENT24, size);
             }
             if attributes.flags.contains(ContextAttributeFlags::STENCIL) {
                 gl.GenRenderbuffers(1, &mut stencil_renderbuffer);
                 gl.BindRenderbuffer(gl::RENDERBUFFER, stencil_renderbuffer);
-                gl.RenderbufferStorage(gl::RENDERBUFFER,
-                                       gl::STENCIL_INDEX8,
-                                       size.width,
-                                       size.height);
+                renderbuffer_storage(gl, samples, gl::STENCIL_INDEX8, size);
             }
             gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
 
@@ -102,6 +112,27 @@ impl Renderbuffers {
         }
     }
 
+    pub(crate) fn set_label(&self, gl: &Gl, label: &str) {
+        match *self {
+            Renderbuffers::CombinedDepthStencil(renderbuffer) => {
+                gl_utils::set_object_label(gl,
+                                           gl::RENDERBUFFER,
+                                           renderbuffer,
+                                           &format!("{} depth/stencil renderbuffer", label));
+            }
+            Renderbuffers::IndividualDepthStencil { depth, stencil } => {
+                gl_utils::set_object_label(gl,
+                                           gl::RENDERBUFFER,
+                                           depth,
+                                           &format!("{} depth renderbuffer", label));
+                gl_utils::set_object_label(gl,
+                                           gl::RENDERBUFFER,
+                                           stencil,
+                                           &format!("{} stencil renderbuffer", label));
+            }
+        }
+    }
+
     pub(crate) fn destroy(&mut self, gl: &Gl) {
         unsafe {
             gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
@@ -130,3 +161,128 @@ impl Renderbuffers {
         }
     }
 }
+
+impl MultisampleFramebuffer {
+    // Creates and binds a multisampled FBO with a color renderbuffer of the given format, if the
+    // attributes ask for multisampling and the implementation supports it.
+    pub(crate) fn new(gl: &Gl,
+                      size: &Size2D<i32>,
+                      attributes: &ContextAttributes,
+                      format: SurfaceFormat)
+                      -> Option<MultisampleFramebuffer> {
+        let samples = sample_count(gl, attributes);
+        if samples == 0 {
+            return None;
+        }
+
+        unsafe {
+            let mut color_renderbuffer = 0;
+            gl.GenRenderbuffers(1, &mut color_renderbuffer);
+            gl.BindRenderbuffer(gl::RENDERBUFFER, color_renderbuffer);
+            renderbuffer_storage(gl, samples, format.gl_renderbuffer_format(), size);
+            gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            let mut framebuffer_object = 0;
+            gl.GenFramebuffers(1, &mut framebuffer_object);
+            gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+            gl.FramebufferRenderbuffer(gl::FRAMEBUFFER,
+                                       gl::COLOR_ATTACHMENT0,
+                                       gl::RENDERBUFFER,
+                                       color_renderbuffer);
+
+            Some(MultisampleFramebuffer { framebuffer_object, color_renderbuffer })
+        }
+    }
+
+    // Resolves the multisampled color buffer into the given single-sampled FBO.
+    pub(crate) fn resolve(&self, gl: &Gl, resolve_framebuffer_object: GLuint, size: &Size2D<i32>) {
+        blit_color(gl, self.framebuffer_object, resolve_framebuffer_object, size);
+    }
+
+    // Copies the contents of the given single-sampled FBO back into the multisampled color buffer,
+    // so that CPU writes to the resolved texture aren't lost on the next resolve. Desktop OpenGL
+    // replicates each pixel to all of its samples; OpenGL ES doesn't support this.
+    pub(crate) fn unresolve(&self,
+                            gl: &Gl,
+                            resolve_framebuffer_object: GLuint,
+                            size: &Size2D<i32>) {
+        blit_color(gl, resolve_framebuffer_object, self.framebuffer_object, size);
+    }
+
+    pub(crate) fn set_label(&self, gl: &Gl, label: &str) {
+        gl_utils::set_object_label(gl,
+                                   gl::FRAMEBUFFER,
+                                   self.framebuffer_object,
+                                   &format!("{} multisample FBO", label));
+        gl_utils::set_object_label(gl,
+                                   gl::RENDERBUFFER,
+                                   self.color_renderbuffer,
+                                   &format!("{} multisample color renderbuffer", label));
+    }
+
+    pub(crate) fn destroy(&mut self, gl: &Gl) {
+        gl_utils::destroy_framebuffer(gl, self.framebuffer_object);
+        self.framebuffer_object = 0;
+        unsafe {
+            gl.DeleteRenderbuffers(1, &self.color_renderbuffer);
+        }
+        self.color_renderbuffer = 0;
+    }
+}
+
+// Returns the number of samples that renderbuffers for the given attributes should have, clamped
+// to what the implementation supports, or 0 if they shouldn't be multisampled.
+fn sample_count(gl: &Gl, attributes: &ContextAttributes) -> GLsizei {
+    if attributes.samples <= 1 {
+        return 0;
+    }
+    let mut max_samples = 0;
+    unsafe {
+        gl.GetIntegerv(gl::MAX_SAMPLES, &mut max_samples);
+    }
+    match cmp::min(attributes.samples, max_samples as u32) {
+        0 | 1 => 0,
+        samples => samples as GLsizei,
+    }
+}
+
+unsafe fn renderbuffer_storage(gl: &Gl, samples: GLsizei, format: GLenum, size: &Size2D<i32>) {
+    if samples == 0 {
+        gl.RenderbufferStorage(gl::RENDERBUFFER, format, size.width, size.height);
+    } else {
+        gl.RenderbufferStorageMultisample(gl::RENDERBUFFER,
+                                          samples,
+                                          format,
+                                          size.width,
+                                          size.height);
+    }
+}
+
+// Copies the color buffer of one framebuffer into another, from the bottom left corner up to the
+// given size, leaving the framebuffer bindings as they were.
+pub(crate) fn blit_color(gl: &Gl,
+                         read_framebuffer_object: GLuint,
+                         draw_framebuffer_object: GLuint,
+                         size: &Size2D<i32>) {
+    unsafe {
+        let (mut old_read_framebuffer, mut old_draw_framebuffer) = (0, 0);
+        gl.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut old_read_framebuffer);
+        gl.GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut old_draw_framebuffer);
+
+        gl.BindFramebuffer(gl::READ_FRAMEBUFFER, read_framebuffer_object);
+        gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, draw_framebuffer_object);
+        gl.BlitFramebuffer(0,
+                           0,
+                           size.width,
+                           size.height,
+                           0,
+                           0,
+                           size.width,
+                           size.height,
+                           gl::COLOR_BUFFER_BIT,
+                           gl::NEAREST);
+
+        gl.BindFramebuffer(gl::READ_FRAMEBUFFER, old_read_framebuffer as GLuint);
+        gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, old_draw_framebuffer as GLuint);
+    }
+}
@@ -2,9 +2,10 @@
 //
 //! Information related to hardware surfaces.
 
-use crate::context::ContextID;
+use crate::context::{ContextAttributeFlags, ContextAttributes, ContextID};
 
-use crate::gl::types::GLuint;
+use crate::gl::types::{GLenum, GLuint};
+use crate::gl;
 use euclid::default::Size2D;
 use std::fmt::{self, Display, Formatter};
 
@@ -28,6 +29,10 @@ pub struct SurfaceInfo {
     /// 
     /// This is only valid when the surface is actually attached to a context.
     pub framebuffer_object: GLuint,
+    /// The pixel format of the surface.
+    /// 
+    /// Widget surfaces always report `RGBA8`.
+    pub format: SurfaceFormat,
 }
 
 // The default framebuffer for a context.
@@ -101,7 +106,9 @@ pub enum SurfaceType<NativeWidget> {
         /// The size of the surface.
         /// 
         /// For HiDPI screens, this is a physical size, not a logical size.
-        size: Size2D<i32>
+        size: Size2D<i32>,
+        /// The format, initial contents, and intended usage of the surface.
+        descriptor: SurfaceDescriptor,
     },
     /// A surface displayed inside a native widget (window or view). The size of a widget surface
     /// is automatically determined based on the size of the widget. (For example, if the widget is
@@ -112,9 +119,189 @@ pub enum SurfaceType<NativeWidget> {
         /// 
         /// For example, on Windows this wraps an `HWND`.
         native_widget: NativeWidget,
+        /// The colorspace that the windowing system interprets the contents of the surface in.
+        /// 
+        /// `Device::supported_colorspaces()` lists the colorspaces that are available.
+        colorspace: SurfaceColorspace,
     },
 }
 
+/// The pixel format of a generic surface.
+/// 
+/// Not every backend or driver supports every format. Creating a surface with an unsupported
+/// format results in an `UnsupportedSurfaceFormat` error. `Device::lock_surface_data()` exposes
+/// the surface data in the surface's own format, such as half floats for `RGBA16F`, while pixel
+/// readbacks always return 8-bit RGBA pixels, converting as necessary.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SurfaceFormat {
+    /// 8-bit unsigned normalized red, green, blue, and alpha channels.
+    RGBA8,
+    /// 8-bit unsigned normalized blue, green, red, and alpha channels, in that order in memory.
+    /// 
+    /// This is the layout that most compositors and windowing systems prefer for sharing.
+    BGRA8,
+    /// 10-bit unsigned normalized red, green, and blue channels, with a 2-bit alpha channel.
+    RGB10_A2,
+    /// 16-bit floating point red, green, blue, and alpha channels, for HDR rendering.
+    RGBA16F,
+    /// 32-bit floating point red, green, blue, and alpha channels.
+    RGBA32F,
+    /// A single 8-bit unsigned normalized red channel.
+    R8,
+    /// 8-bit unsigned normalized red and green channels.
+    RG8,
+    /// 8-bit sRGB-encoded red, green, and blue channels, with an 8-bit linear alpha channel.
+    /// 
+    /// On desktop OpenGL, `GL_FRAMEBUFFER_SRGB` must be enabled for rendering to encode colors.
+    SRGB8_A8,
+}
+
+/// The colorspace of a widget surface, which determines how the windowing system interprets the
+/// values written to the surface.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SurfaceColorspace {
+    /// The windowing system's default colorspace. OpenGL writes colors unmodified.
+    Default,
+    /// sRGB. OpenGL encodes linear colors written to the surface with the sRGB transfer function.
+    SRGB,
+    /// Display P3, the wide-gamut colorspace of many displays, with the sRGB transfer function.
+    DisplayP3,
+    /// BT.2020 primaries with the PQ (SMPTE ST 2084) transfer function, for HDR output.
+    BT2020PQ,
+    /// scRGB: sRGB primaries with extended-range values, usually used with floating point
+    /// surfaces for HDR output.
+    SCRGB,
+}
+
+impl Default for SurfaceColorspace {
+    #[inline]
+    fn default() -> SurfaceColorspace {
+        SurfaceColorspace::Default
+    }
+}
+
+bitflags! {
+    /// Hints about how a generic surface is going to be used.
+    /// 
+    /// Backends may use these to avoid allocating resources that the surface won't need, but they
+    /// never make an operation fail.
+    pub struct SurfaceUsageFlags: u8 {
+        /// The surface will be rendered to through its framebuffer object. Without this hint,
+        /// the depth, stencil, and multisample buffers requested by the context are not
+        /// allocated, which suits surfaces that are only uploaded to and sampled.
+        const RENDER_TARGET = 0x01;
+        /// The surface will be wrapped in surface textures and sampled from.
+        const SAMPLED       = 0x02;
+    }
+}
+
+/// Describes the format, initial contents, and intended usage of a generic surface.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SurfaceDescriptor {
+    /// The pixel format of the surface.
+    pub format: SurfaceFormat,
+    /// If present, the surface is cleared to this RGBA color upon creation. Otherwise, the
+    /// initial contents of the surface are undefined.
+    pub clear_color: Option<[f32; 4]>,
+    /// Hints about how the surface is going to be used.
+    pub usage: SurfaceUsageFlags,
+    /// If true, the depth buffer (and the stencil buffer, if the context has one) is allocated
+    /// as a texture instead of a private renderbuffer, so that surface textures wrapping this
+    /// surface can sample it on other contexts via `Device::surface_texture_depth_object()`.
+    /// 
+    /// This has no effect if the context has no depth buffer or the surface won't be rendered
+    /// to.
+    pub sampleable_depth: bool,
+}
+
+impl Default for SurfaceDescriptor {
+    /// Returns a descriptor for an `RGBA8` surface with undefined initial contents and private
+    /// depth and stencil buffers that will be rendered to and sampled from.
+    #[inline]
+    fn default() -> SurfaceDescriptor {
+        SurfaceDescriptor {
+            format: SurfaceFormat::RGBA8,
+            clear_color: None,
+            usage: SurfaceUsageFlags::RENDER_TARGET | SurfaceUsageFlags::SAMPLED,
+            sampleable_depth: false,
+        }
+    }
+}
+
+impl SurfaceDescriptor {
+    // Returns the context attributes that govern the framebuffer attachments of a surface with
+    // this descriptor: surfaces that won't be rendered to get no depth, stencil, or multisample
+    // buffers.
+    #[allow(dead_code)]
+    pub(crate) fn framebuffer_attributes(&self, context_attributes: &ContextAttributes)
+                                         -> ContextAttributes {
+        let mut attributes = *context_attributes;
+        if !self.usage.contains(SurfaceUsageFlags::RENDER_TARGET) {
+            attributes.flags.remove(ContextAttributeFlags::DEPTH | ContextAttributeFlags::STENCIL);
+            attributes.samples = 0;
+        }
+        attributes
+    }
+}
+
+// `GL_BGRA_EXT` from `GL_EXT_texture_format_BGRA8888`, which the OpenGL ES bindings lack.
+#[cfg(target_os = "android")]
+const GL_BGRA_EXT: GLenum = 0x80e1;
+
+impl SurfaceFormat {
+    // Returns the internal format, format, and type to pass to `glTexImage2D()` to allocate a
+    // texture of this format.
+    #[allow(dead_code)]
+    pub(crate) fn gl_texture_formats(self) -> (GLenum, GLenum, GLenum) {
+        match self {
+            // Unsized, so that this works on OpenGL ES 2.0 too.
+            SurfaceFormat::RGBA8 => (gl::RGBA, gl::RGBA, gl::UNSIGNED_BYTE),
+            #[cfg(not(target_os = "android"))]
+            SurfaceFormat::BGRA8 => (gl::RGBA8, gl::BGRA, gl::UNSIGNED_BYTE),
+            #[cfg(target_os = "android")]
+            SurfaceFormat::BGRA8 => (GL_BGRA_EXT, GL_BGRA_EXT, gl::UNSIGNED_BYTE),
+            SurfaceFormat::RGB10_A2 => {
+                (gl::RGB10_A2, gl::RGBA, gl::UNSIGNED_INT_2_10_10_10_REV)
+            }
+            SurfaceFormat::RGBA16F => (gl::RGBA16F, gl::RGBA, gl::HALF_FLOAT),
+            SurfaceFormat::RGBA32F => (gl::RGBA32F, gl::RGBA, gl::FLOAT),
+            SurfaceFormat::R8 => (gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+            SurfaceFormat::RG8 => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE),
+            SurfaceFormat::SRGB8_A8 => (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE),
+        }
+    }
+
+    // Returns the number of bytes that a pixel of this format takes up when it is transferred
+    // to or from the CPU with the format and type returned by `gl_texture_formats()`.
+    #[allow(dead_code)]
+    pub(crate) fn bytes_per_pixel(self) -> usize {
+        match self {
+            SurfaceFormat::R8 => 1,
+            SurfaceFormat::RG8 => 2,
+            SurfaceFormat::RGBA8 | SurfaceFormat::BGRA8 | SurfaceFormat::RGB10_A2 |
+            SurfaceFormat::SRGB8_A8 => 4,
+            SurfaceFormat::RGBA16F => 8,
+            SurfaceFormat::RGBA32F => 16,
+        }
+    }
+
+    // Returns the sized internal format of a multisampled renderbuffer that resolves into a
+    // texture of this format.
+    #[allow(dead_code)]
+    pub(crate) fn gl_renderbuffer_format(self) -> GLenum {
+        match self {
+            SurfaceFormat::RGBA8 | SurfaceFormat::BGRA8 => gl::RGBA8,
+            SurfaceFormat::RGB10_A2 => gl::RGB10_A2,
+            SurfaceFormat::RGBA16F => gl::RGBA16F,
+            SurfaceFormat::RGBA32F => gl::RGBA32F,
+            SurfaceFormat::R8 => gl::R8,
+            SurfaceFormat::RG8 => gl::RG8,
+            SurfaceFormat::SRGB8_A8 => gl::SRGB8_ALPHA8,
+        }
+    }
+}
+
 impl SurfaceAccess {
     #[allow(dead_code)]
     #[inline]
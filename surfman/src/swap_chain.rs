@@ -0,0 +1,272 @@
+// surfman/surfman/src/swap_chain.rs
+//
+//! A rotating set of generic surfaces for streaming frames from one context to another.
+
+use crate::device::Device;
+use crate::{ContextID, Error, SurfaceAccess, SurfaceDescriptor, SurfaceType};
+
+use euclid::default::Size2D;
+use parking_lot::Mutex;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+/// How many surfaces a swap chain keeps in rotation.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SwapChainBuffering {
+    /// Two surfaces: one that the producer renders to, and one that the consumer reads from.
+    Double,
+    /// Three surfaces, so that the producer can finish a frame while the consumer is still
+    /// reading an older one and the newest frame is waiting to be taken.
+    Triple,
+}
+
+/// A set of generic surfaces that frames are rendered to by a producer context and read from by
+/// a consumer context, possibly on another thread.
+///
+/// The producer renders to the *back buffer*, which the swap chain keeps bound to the producer
+/// context. Calling `swap()` turns the back buffer into the *front buffer* and binds a fresh back
+/// buffer. The consumer calls `take_surface_texture()` to wrap the front buffer in a surface
+/// texture, and hands the texture back with `recycle_surface_texture()` once it's done reading.
+/// If the producer swaps again before the consumer has taken the front buffer, the older frame
+/// is skipped and its surface reused.
+///
+/// Surfaces are reused whenever their size matches the size of the swap chain, and reallocated
+/// after `resize()`. The swap chain keeps at most two or three surfaces alive, depending on its
+/// buffering. If the consumer holds on to more surface textures than that allows, extra surfaces
+/// are created on demand and destroyed once they come back.
+///
+/// Swap chains can be cloned to share them between the producer and consumer. They must be
+/// destroyed with `destroy()` on the producer context; otherwise, the surfaces they own panic
+/// when dropped.
+pub struct SwapChain<D> where D: Device {
+    data: Arc<Mutex<SwapChainData<D>>>,
+}
+
+struct SwapChainData<D> where D: Device {
+    context_id: ContextID,
+    access: SurfaceAccess,
+    size: Size2D<i32>,
+    descriptor: SurfaceDescriptor,
+    buffering: SwapChainBuffering,
+    front_buffer: Option<D::Surface>,
+    free_buffers: Vec<D::Surface>,
+    // The number of surfaces that the consumer has wrapped in surface textures.
+    consumer_buffer_count: usize,
+}
+
+impl<D> Clone for SwapChain<D> where D: Device {
+    #[inline]
+    fn clone(&self) -> SwapChain<D> {
+        SwapChain { data: self.data.clone() }
+    }
+}
+
+impl<D> Debug for SwapChain<D> where D: Device {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        let data = self.data.lock();
+        write!(f, "SwapChain({:?}, {:?})", data.context_id, data.size)
+    }
+}
+
+impl<D> SwapChain<D> where D: Device {
+    /// Creates a swap chain for the given producer context and binds its first back buffer to
+    /// that context.
+    ///
+    /// If the context already has a surface bound, a `SurfaceAlreadyBound` error is returned.
+    pub fn new(device: &mut D,
+               context: &mut D::Context,
+               access: SurfaceAccess,
+               size: Size2D<i32>,
+               descriptor: SurfaceDescriptor,
+               buffering: SwapChainBuffering)
+               -> Result<SwapChain<D>, Error> {
+        let mut data = SwapChainData {
+            context_id: device.context_id(context),
+            access,
+            size,
+            descriptor,
+            buffering,
+            front_buffer: None,
+            free_buffers: vec![],
+            consumer_buffer_count: 0,
+        };
+        data.bind_back_buffer(device, context)?;
+        Ok(SwapChain { data: Arc::new(Mutex::new(data)) })
+    }
+
+    /// Returns the size of the surfaces that the producer renders to.
+    #[inline]
+    pub fn size(&self) -> Size2D<i32> {
+        self.data.lock().size
+    }
+
+    /// Makes the back buffer the front buffer, so that the consumer can take it, and binds a new
+    /// back buffer to the producer context.
+    ///
+    /// Pending rendering commands targeting the back buffer are flushed. The supplied context
+    /// must be the one the swap chain was created with, or an `IncompatibleContext` error is
+    /// returned. As with any newly bound surface, the framebuffer object to render to must be
+    /// fetched again with `context_surface_info()` afterward.
+    pub fn swap(&self, device: &mut D, context: &mut D::Context) -> Result<(), Error> {
+        let mut data = self.data.lock();
+        data.check_context(device, context)?;
+
+        if let Some(back_buffer) = device.unbind_surface_from_context(context)? {
+            if let Some(skipped_buffer) = data.front_buffer.replace(back_buffer) {
+                data.free_buffers.push(skipped_buffer);
+            }
+        }
+        data.bind_back_buffer(device, context)
+    }
+
+    /// Changes the size of the surfaces that the producer renders to.
+    ///
+    /// The back buffer is replaced with one of the new size, and idle surfaces of the old size
+    /// are destroyed. Any front buffer that was already swapped keeps its old size. The supplied
+    /// context must be the one the swap chain was created with, or an `IncompatibleContext` error
+    /// is returned.
+    pub fn resize(&self, device: &mut D, context: &mut D::Context, size: Size2D<i32>)
+                  -> Result<(), Error> {
+        let mut data = self.data.lock();
+        data.check_context(device, context)?;
+        if data.size == size {
+            return Ok(());
+        }
+
+        data.size = size;
+        if let Some(mut back_buffer) = device.unbind_surface_from_context(context)? {
+            device.destroy_surface(context, &mut back_buffer)?;
+        }
+        data.bind_back_buffer(device, context)
+    }
+
+    /// Wraps the front buffer in a surface texture for reading with the given consumer context.
+    ///
+    /// Returns `None` if the producer hasn't swapped since the front buffer was last taken. The
+    /// surface texture must be returned to the swap chain with `recycle_surface_texture()`.
+    pub fn take_surface_texture(&self, device: &D, context: &mut D::Context)
+                                -> Result<Option<D::SurfaceTexture>, Error> {
+        let mut data = self.data.lock();
+        let front_buffer = match data.front_buffer.take() {
+            None => return Ok(None),
+            Some(front_buffer) => front_buffer,
+        };
+
+        match device.create_surface_texture(context, front_buffer) {
+            Ok(surface_texture) => {
+                data.consumer_buffer_count += 1;
+                Ok(Some(surface_texture))
+            }
+            Err((err, front_buffer)) => {
+                data.front_buffer = Some(front_buffer);
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns a surface texture obtained from `take_surface_texture()` to the swap chain, so
+    /// that the producer can render to its surface again.
+    ///
+    /// The supplied context must be the one the surface texture was created with. If the swap
+    /// chain has no surface textures outstanding, an `IncompatibleSurfaceTexture` error is
+    /// returned. If an error is returned, the surface texture is returned alongside it.
+    pub fn recycle_surface_texture(&self,
+                                   device: &D,
+                                   context: &mut D::Context,
+                                   surface_texture: D::SurfaceTexture)
+                                   -> Result<(), (Error, D::SurfaceTexture)> {
+        let mut data = self.data.lock();
+        let consumer_buffer_count = match data.consumer_buffer_count.checked_sub(1) {
+            None => return Err((Error::IncompatibleSurfaceTexture, surface_texture)),
+            Some(consumer_buffer_count) => consumer_buffer_count,
+        };
+        let surface = device.destroy_surface_texture(context, surface_texture)?;
+        data.consumer_buffer_count = consumer_buffer_count;
+        data.free_buffers.push(surface);
+        Ok(())
+    }
+
+    /// Destroys all the surfaces of the swap chain, including the back buffer bound to the
+    /// producer context.
+    ///
+    /// The supplied context must be the one the swap chain was created with, or an
+    /// `IncompatibleContext` error is returned. Every surface texture taken by the consumer must
+    /// have been recycled first, or a `SurfaceInUse` error is returned and nothing is destroyed.
+    /// The swap chain must not be used afterward.
+    pub fn destroy(&self, device: &mut D, context: &mut D::Context) -> Result<(), Error> {
+        let mut data = self.data.lock();
+        data.check_context(device, context)?;
+        if data.consumer_buffer_count > 0 {
+            return Err(Error::SurfaceInUse);
+        }
+
+        if let Some(mut back_buffer) = device.unbind_surface_from_context(context)? {
+            device.destroy_surface(context, &mut back_buffer)?;
+        }
+        if let Some(mut front_buffer) = data.front_buffer.take() {
+            device.destroy_surface(context, &mut front_buffer)?;
+        }
+        while let Some(mut surface) = data.free_buffers.pop() {
+            device.destroy_surface(context, &mut surface)?;
+        }
+        Ok(())
+    }
+}
+
+impl<D> SwapChainData<D> where D: Device {
+    fn check_context(&self, device: &D, context: &D::Context) -> Result<(), Error> {
+        if device.context_id(context) == self.context_id {
+            Ok(())
+        } else {
+            Err(Error::IncompatibleContext)
+        }
+    }
+
+    // Binds an idle surface of the right size to the producer context, or a new surface if there
+    // is none. Idle surfaces of the wrong size, and those beyond what the buffering allows, are
+    // destroyed.
+    fn bind_back_buffer(&mut self, device: &mut D, context: &mut D::Context)
+                        -> Result<(), Error> {
+        let mut index = 0;
+        while index < self.free_buffers.len() {
+            if device.surface_info(&self.free_buffers[index]).size == self.size {
+                index += 1;
+            } else {
+                let mut surface = self.free_buffers.swap_remove(index);
+                device.destroy_surface(context, &mut surface)?;
+            }
+        }
+
+        let back_buffer = match self.free_buffers.pop() {
+            Some(surface) => surface,
+            None => {
+                let surface_type = SurfaceType::Generic {
+                    size: self.size,
+                    descriptor: self.descriptor,
+                };
+                device.create_surface(context, self.access, surface_type)?
+            }
+        };
+        if let Err((err, mut back_buffer)) = device.bind_surface_to_context(context, back_buffer) {
+            // Report why binding failed, even if cleaning up fails too.
+            drop(device.destroy_surface(context, &mut back_buffer));
+            return Err(err);
+        }
+
+        // The back buffer and the front buffer, if any, count against the limit too.
+        let max_buffer_count = match self.buffering {
+            SwapChainBuffering::Double => 2,
+            SwapChainBuffering::Triple => 3,
+        };
+        let in_use_buffer_count =
+            1 + self.front_buffer.is_some() as usize + self.consumer_buffer_count;
+        while in_use_buffer_count + self.free_buffers.len() > max_buffer_count {
+            let mut surface = match self.free_buffers.pop() {
+                None => break,
+                Some(surface) => surface,
+            };
+            device.destroy_surface(context, &mut surface)?;
+        }
+        Ok(())
+    }
+}
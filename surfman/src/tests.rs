@@ -11,7 +11,7 @@
 use crate::gl::types::{GLenum, GLuint};
 use crate::gl;
 use crate::{ContextAttributeFlags, ContextAttributes, Error, GLApi, GLVersion, Gl, SurfaceAccess};
-use crate::{SurfaceType, WindowingApiError};
+use crate::{SurfaceDescriptor, SurfaceType, WindowingApiError};
 use super::connection::{Connection, NativeConnection};
 use super::context::{Context, ContextDescriptor, NativeContext};
 use super::device::{Adapter, Device};
@@ -43,7 +43,280 @@ pub fn test_adapter_creation() {
     let connection = Connection::new().unwrap();
     connection.create_hardware_adapter().unwrap();
     connection.create_low_power_adapter().unwrap();
-    connection.create_software_adapter().unwrap();
+    match connection.create_software_adapter() {
+        Ok(_) | Err(Error::NoSoftwareAdapters) => {}
+        Err(err) => panic!("Failed to create software adapter: {:?}", err),
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_adapter_enumeration() {
+    let connection = Connection::new().unwrap();
+    let adapters = match connection.adapters() {
+        Ok(adapters) => adapters,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to enumerate adapters: {:?}", err),
+    };
+    assert!(!adapters.is_empty());
+
+    // Every enumerated adapter should be usable for rendering, including after another device on
+    // the same adapter has been dropped.
+    for adapter in &adapters {
+        let mut device = match connection.create_device(adapter) {
+            Ok(device) => device,
+            Err(Error::RequiredExtensionUnavailable) => continue,
+            Err(err) => panic!("Failed to create device: {:?}", err),
+        };
+        drop(connection.create_device(adapter).unwrap());
+        assert_eq!(device.connection().adapters().unwrap().len(), adapters.len());
+        let context_descriptor = device.create_context_descriptor(&ContextAttributes {
+            version: GLVersion::new(3, 0),
+            flags: ContextAttributeFlags::empty(),
+            samples: 0,
+        }).unwrap();
+        let mut context = device.create_context(&context_descriptor).unwrap();
+        device.destroy_context(&mut context).unwrap();
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_devices_on_different_adapters() {
+    use std::env;
+
+    let connection = Connection::new().unwrap();
+    let environment = env::vars_os().collect::<Vec<_>>();
+
+    // Devices on different adapters must be able to live side by side without changing any
+    // process-wide state.
+    let context_attributes = ContextAttributes {
+        version: GLVersion::new(3, 0),
+        flags: ContextAttributeFlags::empty(),
+        samples: 0,
+    };
+    let software_adapter = match connection.create_software_adapter() {
+        Ok(software_adapter) => software_adapter,
+        Err(Error::NoSoftwareAdapters) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create software adapter: {:?}", err),
+    };
+    let mut devices_and_contexts = vec![];
+    for adapter in &[connection.create_low_power_adapter().unwrap(), software_adapter] {
+        let mut device = match connection.create_device(adapter) {
+            Ok(device) => device,
+            Err(Error::RequiredExtensionUnavailable) => {
+                // Can't run these tests on this hardware.
+                return;
+            }
+            Err(err) => panic!("Failed to create device: {:?}", err),
+        };
+        let context_descriptor = device.create_context_descriptor(&context_attributes).unwrap();
+        let context = device.create_context(&context_descriptor).unwrap();
+        devices_and_contexts.push((device, context));
+    }
+
+    for &(ref device, ref context) in &devices_and_contexts {
+        device.make_context_current(context).unwrap();
+    }
+    assert_eq!(env::vars_os().collect::<Vec<_>>(), environment);
+
+    for (device, mut context) in devices_and_contexts {
+        device.destroy_context(&mut context).unwrap();
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_backend_preference() {
+    use crate::Backend;
+    use crate::platform::generic::multi::connection::BackendConnection;
+
+    let connection = Connection::new().unwrap();
+    let backend = connection.backend();
+    assert_eq!(backend.name().parse::<Backend>().unwrap(), backend);
+
+    // The default connection provides exactly the backends that are compiled in.
+    let backends = crate::Connection::backends();
+    assert!(backends.contains(&Backend::Wayland));
+    assert!(backends.contains(&Backend::Surfaceless));
+    assert_eq!(backends.contains(&Backend::X11), cfg!(feature = "sm-x11"));
+    assert_eq!(backends.contains(&Backend::GLX), cfg!(feature = "sm-glx"));
+    assert_eq!(backends.contains(&Backend::GBM), cfg!(feature = "sm-gbm"));
+    assert_eq!(backends.contains(&Backend::OSMesa), cfg!(feature = "sm-osmesa"));
+
+    // The default connection must open exactly the backend that was asked for, even if it would
+    // otherwise have picked another one.
+    match crate::Connection::new_with_preference(&[backend]) {
+        Ok(default_connection) => assert_eq!(default_connection.backend(), backend),
+        Err(Error::UnknownBackend) => {
+            // This backend isn't one of the defaults.
+        }
+        Err(err) => panic!("Failed to open the {} backend: {:?}", backend.name(), err),
+    }
+
+    match crate::Connection::new_with_preference(&[]) {
+        Err(Error::UnknownBackend) => {}
+        _ => panic!("An empty preference list shouldn't open a connection!"),
+    }
+
+    // Without `SURFMAN_BACKEND`, the environment doesn't change which backend is opened.
+    if std::env::var_os("SURFMAN_BACKEND").is_none() {
+        let default_connection = crate::Connection::new().unwrap();
+        let env_connection = crate::Connection::new_from_env().unwrap();
+        assert_eq!(env_connection.backend(), default_connection.backend());
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_context_info() {
+    let connection = Connection::new().unwrap();
+    let adapter = connection.create_low_power_adapter().expect("Failed to create adapter!");
+    let mut device = match connection.create_device(&adapter) {
+        Ok(device) => device,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create device: {:?}", err),
+    };
+
+    let context_descriptor = device.create_context_descriptor(&ContextAttributes {
+        version: GLVersion::new(3, 0),
+        flags: ContextAttributeFlags::empty(),
+        samples: 0,
+    }).unwrap();
+    let mut context = device.create_context(&context_descriptor).unwrap();
+
+    let info = device.context_info(&context).unwrap();
+    assert!(!info.renderer.is_empty());
+    assert!(!info.vendor.is_empty());
+    assert_eq!(GLVersion::parse(&info.version_string).unwrap(), info.version);
+    assert!(info.version.major >= 3);
+    assert_eq!(info.api, device.gl_api());
+    assert!(!info.extensions.is_empty());
+
+    assert_eq!(GLVersion::parse("OpenGL ES 3.2 Mesa 23.0.4").unwrap(), GLVersion::new(3, 2));
+    assert_eq!(GLVersion::parse("4.6.0 NVIDIA 535.54.03").unwrap(), GLVersion::new(4, 6));
+    match GLVersion::parse("OpenGL ES") {
+        Err(Error::UnrecognizedGLVersion) => {}
+        result => panic!("Shouldn't have parsed a version without numbers: {:?}", result),
+    }
+
+    device.destroy_context(&mut context).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_context_loss() {
+    use crate::ContextResetStatus;
+
+    let connection = Connection::new().unwrap();
+    let adapter = connection.create_low_power_adapter().expect("Failed to create adapter!");
+    let mut device = match connection.create_device(&adapter) {
+        Ok(device) => device,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create device: {:?}", err),
+    };
+
+    let mut context_attributes = ContextAttributes {
+        version: GLVersion::new(3, 0),
+        flags: ContextAttributeFlags::ROBUST_ACCESS | ContextAttributeFlags::LOSE_CONTEXT_ON_RESET,
+        samples: 0,
+    };
+    let context_descriptor = match device.create_context_descriptor(&context_attributes) {
+        Ok(context_descriptor) => {
+            let attributes = device.context_descriptor_attributes(&context_descriptor);
+            assert!(attributes.flags.contains(context_attributes.flags));
+            context_descriptor
+        }
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Robust contexts aren't supported here, but simulated losses still are.
+            context_attributes.flags = ContextAttributeFlags::empty();
+            device.create_context_descriptor(&context_attributes).unwrap()
+        }
+        Err(err) => panic!("Failed to create context descriptor: {:?}", err),
+    };
+
+    let mut context = device.create_context(&context_descriptor).unwrap();
+    assert_eq!(device.context_reset_status(&context).unwrap(), ContextResetStatus::NoError);
+    device.simulate_context_loss(&mut context);
+    assert_eq!(device.context_reset_status(&context).unwrap(), ContextResetStatus::Guilty);
+
+    // Recover by replacing the context with a fresh one.
+    device.destroy_context(&mut context).unwrap();
+    let mut context = device.create_context(&context_descriptor).unwrap();
+    assert_eq!(device.context_reset_status(&context).unwrap(), ContextResetStatus::NoError);
+    device.destroy_context(&mut context).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_debug_context() {
+    let connection = Connection::new().unwrap();
+    let adapter = connection.create_low_power_adapter().expect("Failed to create adapter!");
+    let mut device = match connection.create_device(&adapter) {
+        Ok(device) => device,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create device: {:?}", err),
+    };
+
+    let context_attributes = ContextAttributes {
+        version: GLVersion::new(3, 0),
+        flags: ContextAttributeFlags::DEBUG,
+        samples: 0,
+    };
+    let context_descriptor = match device.create_context_descriptor(&context_attributes) {
+        Ok(context_descriptor) => context_descriptor,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Debug contexts aren't supported here.
+            return;
+        }
+        Err(err) => panic!("Failed to create context descriptor: {:?}", err),
+    };
+    let mut context = device.create_context(&context_descriptor).unwrap();
+    let actual_attributes =
+        device.context_descriptor_attributes(&device.context_descriptor(&context));
+    assert!(actual_attributes.flags.contains(ContextAttributeFlags::DEBUG));
+
+    let surface = make_surface(&mut device, &context);
+    device.set_surface_label(&context, &surface, "test surface").unwrap();
+    device.bind_surface_to_context(&mut context, surface).unwrap();
+    device.make_context_current(&context).unwrap();
+
+    let gl = Gl::load_with(|symbol| device.get_proc_address(&context, symbol));
+    if gl.GetObjectLabel.is_loaded() {
+        let framebuffer_object = device.context_surface_info(&context)
+                                       .unwrap()
+                                       .unwrap()
+                                       .framebuffer_object;
+        if framebuffer_object != 0 {
+            let mut label = [0u8; 64];
+            let mut length = 0;
+            unsafe {
+                gl.GetObjectLabel(gl::FRAMEBUFFER,
+                                  framebuffer_object,
+                                  label.len() as _,
+                                  &mut length,
+                                  label.as_mut_ptr() as *mut _);
+            }
+            assert_eq!(&label[..length as usize], &b"test surface FBO"[..]);
+        }
+    }
+
+    device.destroy_context(&mut context).unwrap();
 }
 
 #[cfg_attr(not(feature = "sm-test"), test)]
@@ -101,7 +374,7 @@ pub fn test_context_creation() {
     for &version in versions {
         for flag_bits in 0..(ContextAttributeFlags::all().bits() + 1) {
             let flags = ContextAttributeFlags::from_bits_truncate(flag_bits);
-            let attributes = ContextAttributes { version, flags };
+            let attributes = ContextAttributes { version, flags, samples: 0 };
             let descriptor = match device.create_context_descriptor(&attributes) {
                 Ok(descriptor) => descriptor,
                 Err(Error::UnsupportedGLProfile) | Err(Error::UnsupportedGLVersion) => {
@@ -168,6 +441,7 @@ pub fn test_newly_created_contexts_are_not_current() {
     let context_descriptor = device.create_context_descriptor(&ContextAttributes {
         version: GLVersion::new(3, 0),
         flags: ContextAttributeFlags::empty(),
+        samples: 0,
     }).unwrap();
 
     // Make no context current.
@@ -226,6 +500,7 @@ pub fn test_generic_surface_creation() {
     let descriptor = device.create_context_descriptor(&ContextAttributes {
         version: GLVersion::new(3, 0),
         flags: ContextAttributeFlags::empty(),
+        samples: 0,
     }).unwrap();
 
     let mut context = device.create_context(&descriptor).unwrap();
@@ -238,6 +513,7 @@ pub fn test_generic_surface_creation() {
     ].iter().map(|&access| {
         let surface = device.create_surface(&context, access, SurfaceType::Generic {
             size: Size2D::new(640, 480),
+            descriptor: SurfaceDescriptor::default(),
         }).unwrap();
         let info = device.surface_info(&surface);
         assert_eq!(info.size, Size2D::new(640, 480));
@@ -572,6 +848,615 @@ pub fn test_surface_texture_right_side_up() {
     }
 }
 
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_surface_data_lock() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    unsafe {
+        let surface = env.device
+                         .create_surface(&env.context,
+                                         SurfaceAccess::GPUCPU,
+                                         SurfaceType::Generic {
+                                             size: Size2D::new(640, 480),
+                                             descriptor: SurfaceDescriptor::default(),
+                                         })
+                         .unwrap();
+        let mut old_surface = env.device
+                                 .unbind_surface_from_context(&mut env.context)
+                                 .unwrap()
+                                 .unwrap();
+        match env.device.lock_surface_data(&mut old_surface) {
+            Err(Error::SurfaceDataInaccessible) => {}
+            Err(err) => panic!("Locking a GPU-only surface should fail, but got {:?}", err),
+            Ok(_) => panic!("Locking a GPU-only surface should fail!"),
+        }
+        env.device.destroy_surface(&mut env.context, &mut old_surface).unwrap();
+
+        env.device.bind_surface_to_context(&mut env.context, surface).unwrap();
+        env.device.make_context_current(&env.context).unwrap();
+        bind_context_fbo(&env.gl, &env.device, &env.context);
+        clear(&env.gl, &[0, 255, 0, 255]);
+
+        // Read the green back on the CPU, and write red into the bottom row.
+        let mut surface = env.device
+                             .unbind_surface_from_context(&mut env.context)
+                             .unwrap()
+                             .unwrap();
+        {
+            let mut data_guard = env.device.lock_surface_data(&mut surface).unwrap();
+            assert!(data_guard.stride() >= 640 * 4);
+            let data = data_guard.data();
+            assert_eq!(&data[0..4], &[0, 255, 0, 255]);
+            for pixel in data[0..(640 * 4)].chunks_mut(4) {
+                pixel.copy_from_slice(&[255, 0, 0, 255]);
+            }
+        }
+
+        env.device.bind_surface_to_context(&mut env.context, surface).unwrap();
+        env.device.make_context_current(&env.context).unwrap();
+        bind_context_fbo(&env.gl, &env.device, &env.context);
+        assert_eq!(get_pixel_from_bottom_row(&env.gl), [255, 0, 0, 255]);
+        assert_eq!(get_pixel_from_second_from_bottom_row(&env.gl), [0, 255, 0, 255]);
+
+        env.device.destroy_context(&mut env.context).unwrap();
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_surface_data_lock_in_surface_format() {
+    use crate::SurfaceFormat;
+
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    // An odd width, so that rows of one-byte pixels aren't padded to four bytes.
+    let descriptor = SurfaceDescriptor {
+        format: SurfaceFormat::R8,
+        ..SurfaceDescriptor::default()
+    };
+    let surface_type = SurfaceType::Generic { size: Size2D::new(63, 2), descriptor };
+    let mut surface = match env.device.create_surface(&env.context,
+                                                      SurfaceAccess::GPUCPU,
+                                                      surface_type) {
+        Ok(surface) => surface,
+        Err(Error::UnsupportedSurfaceFormat) => {
+            env.device.destroy_context(&mut env.context).unwrap();
+            return;
+        }
+        Err(err) => panic!("Failed to create an R8 surface: {:?}", err),
+    };
+
+    {
+        let mut data_guard = env.device.lock_surface_data(&mut surface).unwrap();
+        assert_eq!(data_guard.stride(), 63);
+        let data = data_guard.data();
+        assert_eq!(data.len(), 63 * 2);
+        for (index, byte) in data.iter_mut().enumerate() {
+            *byte = index as u8;
+        }
+    }
+    {
+        let mut data_guard = env.device.lock_surface_data(&mut surface).unwrap();
+        let data = data_guard.data();
+        for (index, &byte) in data.iter().enumerate() {
+            assert_eq!(byte, index as u8);
+        }
+    }
+
+    env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_surface_pixel_readback() {
+    use crate::{ChannelOrder, ReadbackFormat};
+    use euclid::default::{Point2D, Rect};
+
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    unsafe {
+        clear(&env.gl, &[255, 0, 0, 255]);
+        clear_bottom_row(&env.gl, &[0, 255, 0, 255]);
+
+        let mut surface = env.device
+                             .unbind_surface_from_context(&mut env.context)
+                             .unwrap()
+                             .unwrap();
+
+        let format = ReadbackFormat { channel_order: ChannelOrder::BGRA, flip_y: true };
+        match env.device.read_surface_pixels(&env.context,
+                                             &surface,
+                                             Rect::new(Point2D::new(600, 0), Size2D::new(64, 2)),
+                                             format) {
+            Err(Error::InvalidRegion) => {}
+            Err(err) => panic!("Expected an `InvalidRegion` error but got {:?}", err),
+            Ok(_) => panic!("Reading outside the surface should fail!"),
+        }
+
+        let readback = env.device
+                          .read_surface_pixels(&env.context,
+                                               &surface,
+                                               Rect::new(Point2D::new(1, 0), Size2D::new(4, 2)),
+                                               format)
+                          .unwrap();
+        assert_eq!(readback.size(), Size2D::new(4, 2));
+        while !env.device.poll_pixel_readback(&env.context, &readback).unwrap() {
+            thread::yield_now();
+        }
+        let pixels = env.device.finish_pixel_readback(&env.context, readback).unwrap();
+
+        // The rows come back top to bottom, so the green bottom row is last.
+        assert_eq!(pixels.len(), 4 * 2 * 4);
+        assert_eq!(&pixels[0..4], &[0, 0, 255, 255]);
+        assert_eq!(&pixels[16..20], &[0, 255, 0, 255]);
+
+        env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+        env.device.destroy_context(&mut env.context).unwrap();
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_fences() {
+    use std::time::Duration;
+
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    unsafe {
+        clear(&env.gl, &[0, 255, 0, 255]);
+    }
+
+    let mut fence = match env.device.create_fence(&env.context) {
+        Ok(fence) => fence,
+        Err(Error::RequiredExtensionUnavailable) | Err(Error::Unimplemented) => {
+            // Can't run this test on this backend or hardware.
+            env.device.destroy_context(&mut env.context).unwrap();
+            return;
+        }
+        Err(err) => panic!("Failed to create fence: {:?}", err),
+    };
+    assert!(env.device.wait_fence(&fence, Some(Duration::from_secs(10))).unwrap());
+    assert!(env.device.wait_fence(&fence, None).unwrap());
+    env.device.server_wait_fence(&env.context, &fence).unwrap();
+
+    match env.device.export_fence_fd(&fence) {
+        Ok(fd) => {
+            // Round-trip the sync file back into a fence.
+            let mut imported_fence = env.device.create_fence_from_fd(&env.context, fd).unwrap();
+            assert!(env.device.wait_fence(&imported_fence, None).unwrap());
+            env.device.server_wait_fence(&env.context, &imported_fence).unwrap();
+            env.device.destroy_fence(&mut imported_fence);
+        }
+        Err(Error::RequiredExtensionUnavailable) => {}
+        Err(err) => panic!("Failed to export fence: {:?}", err),
+    }
+
+    env.device.destroy_fence(&mut fence);
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_multisampled_surface() {
+    use crate::{ChannelOrder, ReadbackFormat};
+    use euclid::default::{Point2D, Rect};
+
+    let connection = Connection::new().unwrap();
+    let adapter = connection.create_low_power_adapter().expect("Failed to create adapter!");
+    let mut device = match connection.create_device(&adapter) {
+        Ok(device) => device,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create device: {:?}", err),
+    };
+
+    let context_attributes = ContextAttributes {
+        version: GLVersion::new(3, 0),
+        flags: ContextAttributeFlags::DEPTH,
+        samples: 4,
+    };
+    let context_descriptor = match device.create_context_descriptor(&context_attributes) {
+        Ok(context_descriptor) => context_descriptor,
+        Err(Error::Unimplemented) => {
+            // This backend doesn't support multisampled surfaces.
+            return;
+        }
+        Err(err) => panic!("Failed to create context descriptor: {:?}", err),
+    };
+    assert_eq!(device.context_descriptor_attributes(&context_descriptor).samples, 4);
+
+    let mut context = device.create_context(&context_descriptor).unwrap();
+    let actual_descriptor = device.context_descriptor(&context);
+    assert_eq!(device.context_descriptor_attributes(&actual_descriptor).samples, 4);
+
+    let surface = make_surface(&mut device, &context);
+    device.bind_surface_to_context(&mut context, surface).unwrap();
+    device.make_context_current(&context).unwrap();
+
+    let gl = Gl::load_with(|symbol| device.get_proc_address(&context, symbol));
+
+    unsafe {
+        let framebuffer_object = device.context_surface_info(&context)
+                                       .unwrap()
+                                       .unwrap()
+                                       .framebuffer_object;
+        gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+        gl.Viewport(0, 0, 640, 480);
+
+        let mut samples = 0;
+        gl.GetIntegerv(gl::SAMPLES, &mut samples);
+        assert!(samples > 1);
+
+        clear(&gl, &[255, 0, 0, 255]);
+        clear_bottom_row(&gl, &[0, 255, 0, 255]);
+    }
+
+    // Unbinding the surface resolves it, so the rendering shows up in readbacks.
+    let mut surface = device.unbind_surface_from_context(&mut context).unwrap().unwrap();
+    let format = ReadbackFormat { channel_order: ChannelOrder::RGBA, flip_y: false };
+    let readback = device.read_surface_pixels(&context,
+                                              &surface,
+                                              Rect::new(Point2D::new(0, 0), Size2D::new(1, 2)),
+                                              format)
+                         .unwrap();
+    let pixels = device.finish_pixel_readback(&context, readback).unwrap();
+    assert_eq!(&pixels[0..4], &[0, 255, 0, 255]);
+    assert_eq!(&pixels[4..8], &[255, 0, 0, 255]);
+
+    device.destroy_surface(&mut context, &mut surface).unwrap();
+    device.destroy_context(&mut context).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_sampleable_depth() {
+    let connection = Connection::new().unwrap();
+    let adapter = connection.create_low_power_adapter().expect("Failed to create adapter!");
+    let mut device = match connection.create_device(&adapter) {
+        Ok(device) => device,
+        Err(Error::RequiredExtensionUnavailable) => {
+            // Can't run these tests on this hardware.
+            return;
+        }
+        Err(err) => panic!("Failed to create device: {:?}", err),
+    };
+
+    let context_descriptor = device.create_context_descriptor(&ContextAttributes {
+        version: GLVersion::new(3, 0),
+        flags: ContextAttributeFlags::DEPTH,
+        samples: 0,
+    }).unwrap();
+    let mut producer_context = device.create_context(&context_descriptor).unwrap();
+    let mut consumer_context = device.create_context(&context_descriptor).unwrap();
+
+    let descriptor = SurfaceDescriptor { sampleable_depth: true, ..SurfaceDescriptor::default() };
+    let surface_type = SurfaceType::Generic { size: Size2D::new(640, 480), descriptor };
+    let surface = match device.create_surface(&producer_context,
+                                              SurfaceAccess::GPUOnly,
+                                              surface_type) {
+        Ok(surface) => surface,
+        Err(Error::Unimplemented) => {
+            // This backend doesn't support sampleable depth buffers.
+            device.destroy_context(&mut producer_context).unwrap();
+            device.destroy_context(&mut consumer_context).unwrap();
+            return;
+        }
+        Err(err) => panic!("Failed to create surface: {:?}", err),
+    };
+    device.bind_surface_to_context(&mut producer_context, surface).unwrap();
+    device.make_context_current(&producer_context).unwrap();
+
+    let gl = Gl::load_with(|symbol| device.get_proc_address(&producer_context, symbol));
+    unsafe {
+        bind_context_fbo(&gl, &device, &producer_context);
+        gl.Viewport(0, 0, 640, 480);
+        gl.ClearDepth(0.25);
+        gl.Clear(gl::DEPTH_BUFFER_BIT); check_gl(&gl);
+        gl.Flush();
+    }
+
+    let surface = device.unbind_surface_from_context(&mut producer_context).unwrap().unwrap();
+    let surface_texture = device.create_surface_texture(&mut consumer_context, surface).unwrap();
+    let depth_texture_object = device.surface_texture_depth_object(&surface_texture)
+                                     .expect("Surface texture should have a depth texture!");
+
+    device.make_context_current(&consumer_context).unwrap();
+    let gl = Gl::load_with(|symbol| device.get_proc_address(&consumer_context, symbol));
+    unsafe {
+        let mut framebuffer_object = 0;
+        gl.GenFramebuffers(1, &mut framebuffer_object);
+        gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer_object);
+        gl.FramebufferTexture2D(gl::FRAMEBUFFER,
+                                gl::DEPTH_ATTACHMENT,
+                                device.surface_gl_texture_target(),
+                                depth_texture_object,
+                                0);
+        gl.DrawBuffer(gl::NONE);
+        gl.ReadBuffer(gl::NONE);
+        assert_eq!(gl.CheckFramebufferStatus(gl::FRAMEBUFFER), gl::FRAMEBUFFER_COMPLETE);
+
+        let mut depth = 0.0f32;
+        gl.ReadPixels(0,
+                      0,
+                      1,
+                      1,
+                      gl::DEPTH_COMPONENT,
+                      gl::FLOAT,
+                      &mut depth as *mut f32 as *mut c_void); check_gl(&gl);
+        assert!(approx_eq(depth, 0.25), "Unexpected depth {}", depth);
+
+        gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl.DeleteFramebuffers(1, &framebuffer_object);
+    }
+
+    let mut surface = device.destroy_surface_texture(&mut consumer_context, surface_texture)
+                            .unwrap();
+    device.destroy_surface(&mut producer_context, &mut surface).unwrap();
+    device.destroy_context(&mut producer_context).unwrap();
+    device.destroy_context(&mut consumer_context).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_surface_formats() {
+    use crate::{ChannelOrder, ReadbackFormat, SurfaceFormat};
+    use euclid::default::{Point2D, Rect};
+
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let formats = [
+        (SurfaceFormat::RGBA8, [255, 255, 0, 255]),
+        (SurfaceFormat::BGRA8, [255, 255, 0, 255]),
+        (SurfaceFormat::RGB10_A2, [255, 255, 0, 255]),
+        (SurfaceFormat::RGBA16F, [255, 255, 0, 255]),
+        (SurfaceFormat::RGBA32F, [255, 255, 0, 255]),
+        (SurfaceFormat::R8, [255, 0, 0, 255]),
+        (SurfaceFormat::RG8, [255, 255, 0, 255]),
+        (SurfaceFormat::SRGB8_A8, [255, 255, 0, 255]),
+    ];
+    for &(format, expected_pixel) in &formats {
+        let descriptor = SurfaceDescriptor {
+            format,
+            clear_color: Some([1.0, 1.0, 0.0, 1.0]),
+            ..SurfaceDescriptor::default()
+        };
+        let surface_type = SurfaceType::Generic { size: Size2D::new(640, 480), descriptor };
+        let mut surface = match env.device.create_surface(&env.context,
+                                                          SurfaceAccess::GPUOnly,
+                                                          surface_type) {
+            Ok(surface) => surface,
+            Err(Error::UnsupportedSurfaceFormat) => continue,
+            Err(err) => panic!("Failed to create {:?} surface: {:?}", format, err),
+        };
+        assert_eq!(env.device.surface_info(&surface).format, format);
+
+        let readback_format = ReadbackFormat { channel_order: ChannelOrder::RGBA, flip_y: false };
+        let readback = env.device
+                          .read_surface_pixels(&env.context,
+                                               &surface,
+                                               Rect::new(Point2D::new(0, 0), Size2D::new(1, 1)),
+                                               readback_format)
+                          .unwrap();
+        let pixels = env.device.finish_pixel_readback(&env.context, readback).unwrap();
+        assert_eq!(&pixels[0..4], &expected_pixel, "{:?}", format);
+
+        env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+    }
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_generic_surfaces_cannot_be_presented() {
+    use euclid::default::{Point2D, Rect};
+
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let mut surface = make_surface(&mut env.device, &env.context);
+    let damage = [Rect::new(Point2D::new(0, 0), Size2D::new(16, 16))];
+    match env.device.present_surface_with_damage(&env.context, &mut surface, &damage) {
+        Err(Error::NoWidgetAttached) => {}
+        result => panic!("Presenting a generic surface should fail, got {:?}", result),
+    }
+    match env.device.surface_buffer_age(&env.context, &surface) {
+        Err(Error::NoWidgetAttached) => {}
+        result => panic!("Generic surfaces should have no buffer age, got {:?}", result),
+    }
+    match env.device.request_frame_callback(&surface) {
+        Err(Error::NoWidgetAttached) |
+        Err(Error::ConnectionRequired) |
+        Err(Error::UnsupportedOnThisPlatform) => {}
+        Err(err) => panic!("Generic surfaces should have no frame callbacks, got {:?}", err),
+        Ok(_) => panic!("Generic surfaces should have no frame callbacks!"),
+    }
+
+    env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_generic_surfaces_have_no_swap_interval() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    let mut surface = make_surface(&mut env.device, &env.context);
+    match env.device.set_swap_interval(&env.context, &surface, 0) {
+        Err(Error::NoWidgetAttached) => {}
+        result => panic!("Generic surfaces should have no swap interval, got {:?}", result),
+    }
+    match env.device.swap_interval(&env.context, &surface) {
+        Err(Error::NoWidgetAttached) => {}
+        result => panic!("Generic surfaces should have no swap interval, got {:?}", result),
+    }
+
+    env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_resize_generic_surface() {
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    bind_context_fbo(&env.gl, &env.device, &env.context);
+    clear(&env.gl, &[255, 0, 0, 255]);
+
+    // Growing the surface keeps the old pixels and clears the new ones.
+    let mut surface = env.device.unbind_surface_from_context(&mut env.context).unwrap().unwrap();
+    env.device.resize_surface(&env.context, &mut surface, Size2D::new(800, 600), true).unwrap();
+    assert_eq!(env.device.surface_info(&surface).size, Size2D::new(800, 600));
+    env.device.bind_surface_to_context(&mut env.context, surface).unwrap();
+    bind_context_fbo(&env.gl, &env.device, &env.context);
+    assert_eq!(get_pixel_from_bottom_row(&env.gl), [255, 0, 0, 255]);
+    unsafe {
+        let mut pixel: [u8; 4] = [0; 4];
+        env.gl.ReadPixels(799,
+                          599,
+                          1,
+                          1,
+                          gl::RGBA,
+                          gl::UNSIGNED_BYTE,
+                          pixel.as_mut_ptr() as *mut c_void);
+        assert_eq!(pixel, [0, 0, 0, 0]);
+    }
+
+    // Otherwise, the whole surface is cleared.
+    let mut surface = env.device.unbind_surface_from_context(&mut env.context).unwrap().unwrap();
+    env.device.resize_surface(&env.context, &mut surface, Size2D::new(320, 240), false).unwrap();
+    assert_eq!(env.device.surface_info(&surface).size, Size2D::new(320, 240));
+    env.device.bind_surface_to_context(&mut env.context, surface).unwrap();
+    bind_context_fbo(&env.gl, &env.device, &env.context);
+    assert_eq!(get_pixel_from_bottom_row(&env.gl), [0, 0, 0, 0]);
+    check_gl(&env.gl);
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_swap_chain() {
+    use crate::{SwapChain, SwapChainBuffering};
+
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    // The swap chain binds its own back buffer.
+    let mut surface = env.device.unbind_surface_from_context(&mut env.context).unwrap().unwrap();
+    env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+
+    let swap_chain: SwapChain<Device> = SwapChain::new(&mut env.device,
+                                                       &mut env.context,
+                                                       SurfaceAccess::GPUOnly,
+                                                       Size2D::new(640, 480),
+                                                       SurfaceDescriptor::default(),
+                                                       SwapChainBuffering::Double).unwrap();
+    match swap_chain.take_surface_texture(&env.device, &mut env.context) {
+        Ok(None) => {}
+        result => panic!("Nothing should have been swapped yet, got {:?}", result),
+    }
+
+    // Render a few frames, taking only the last one.
+    for color in &[[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]] {
+        bind_context_fbo(&env.gl, &env.device, &env.context);
+        clear(&env.gl, color);
+        swap_chain.swap(&mut env.device, &mut env.context).unwrap();
+    }
+
+    let surface_texture = swap_chain.take_surface_texture(&env.device, &mut env.context)
+                                    .unwrap()
+                                    .unwrap();
+    assert!(swap_chain.take_surface_texture(&env.device, &mut env.context).unwrap().is_none());
+    unsafe {
+        let mut framebuffer_object =
+            make_fbo(&env.gl,
+                     env.device.surface_gl_texture_target(),
+                     env.device.surface_texture_object(&surface_texture));
+        assert_eq!(get_pixel_from_bottom_row(&env.gl), [0, 0, 255, 255]);
+        env.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        env.gl.DeleteFramebuffers(1, &mut framebuffer_object);
+    }
+
+    // Surfaces lent to the consumer keep the swap chain alive.
+    match swap_chain.destroy(&mut env.device, &mut env.context) {
+        Err(Error::SurfaceInUse) => {}
+        result => panic!("Destroying a swap chain in use should fail, got {:?}", result),
+    }
+    swap_chain.recycle_surface_texture(&env.device, &mut env.context, surface_texture).unwrap();
+
+    // Surface textures the swap chain didn't lend out are handed back untouched.
+    let surface = make_surface(&mut env.device, &env.context);
+    let surface_texture = env.device.create_surface_texture(&mut env.context, surface).unwrap();
+    let surface_texture =
+        match swap_chain.recycle_surface_texture(&env.device, &mut env.context, surface_texture) {
+            Err((Error::IncompatibleSurfaceTexture, surface_texture)) => surface_texture,
+            Err((err, _)) => panic!("Expected `IncompatibleSurfaceTexture` but got {:?}", err),
+            Ok(()) => panic!("A surface texture that wasn't lent out shouldn't be recycled!"),
+        };
+    let mut surface = env.device.destroy_surface_texture(&mut env.context, surface_texture)
+                                .unwrap();
+    env.device.destroy_surface(&mut env.context, &mut surface).unwrap();
+
+    swap_chain.resize(&mut env.device, &mut env.context, Size2D::new(320, 240)).unwrap();
+    assert_eq!(swap_chain.size(), Size2D::new(320, 240));
+    let surface_info = env.device.context_surface_info(&env.context).unwrap().unwrap();
+    assert_eq!(surface_info.size, Size2D::new(320, 240));
+
+    swap_chain.destroy(&mut env.device, &mut env.context).unwrap();
+    assert!(env.device.context_surface_info(&env.context).unwrap().is_none());
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "sm-test"), test)]
+pub fn test_supported_colorspaces() {
+    use crate::SurfaceColorspace;
+
+    let mut env = match BasicEnvironment::new() {
+        None => return,
+        Some(env) => env,
+    };
+
+    // Backends either support no widget surfaces at all, or at least the default colorspace.
+    let colorspaces = env.device.supported_colorspaces();
+    if let Some(&first) = colorspaces.first() {
+        assert_eq!(first, SurfaceColorspace::Default);
+    }
+    for (index, colorspace) in colorspaces.iter().enumerate() {
+        assert!(!colorspaces[(index + 1)..].contains(colorspace));
+    }
+
+    env.device.destroy_context(&mut env.context).unwrap();
+}
+
 #[cfg(not(target_os = "android"))]
 #[cfg_attr(not(feature = "sm-test"), test)]
 pub fn test_depth_and_stencil() {
@@ -596,6 +1481,7 @@ pub fn test_depth_and_stencil() {
     let depth_context_descriptor = device.create_context_descriptor(&ContextAttributes {
         version: GLVersion::new(3, 0),
         flags: ContextAttributeFlags::DEPTH,
+        samples: 0,
     }).unwrap();
 
     let mut depth_context = device.create_context(&depth_context_descriptor).unwrap();
@@ -634,6 +1520,7 @@ pub fn test_depth_and_stencil() {
     let stencil_context_descriptor = device.create_context_descriptor(&ContextAttributes {
         version: GLVersion::new(3, 0),
         flags: ContextAttributeFlags::STENCIL,
+        samples: 0,
     }).unwrap();
 
     let mut stencil_context = device.create_context(&stencil_context_descriptor).unwrap();
@@ -708,7 +1595,10 @@ fn context_fbo(device: &Device, context: &Context) -> GLuint {
 fn make_surface(device: &mut Device, context: &Context) -> Surface {
     device.create_surface(&context,
                           SurfaceAccess::GPUOnly,
-                          SurfaceType::Generic { size: Size2D::new(640, 480) })
+                          SurfaceType::Generic {
+                              size: Size2D::new(640, 480),
+                              descriptor: SurfaceDescriptor::default(),
+                          })
           .unwrap()
 }
 
@@ -769,6 +1659,7 @@ impl BasicEnvironment {
         let context_descriptor = device.create_context_descriptor(&ContextAttributes {
             version: GLVersion::new(3, 0),
             flags: ContextAttributeFlags::empty(),
+            samples: 0,
         }).unwrap();
 
         let mut context = device.create_context(&context_descriptor).unwrap();